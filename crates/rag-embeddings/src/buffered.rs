@@ -0,0 +1,236 @@
+use anyhow::Result;
+use rag_indexing::tree_structrue::NodeTree;
+use tokio_util::sync::CancellationToken;
+
+use crate::{client::{EmbeddingClient, EmbeddingError}, database::{VectorRecord, VectorStore}, embedding::{leaf_record, leaf_to_vector_record}};
+
+/// 每批嵌入并写入的默认记录数
+pub const DEFAULT_BATCH_SIZE: usize = 25;
+
+/// 缓冲式入库：记录先进入缓冲区，攒满一批后统一生成 embedding 并写入向量库
+///
+/// 最后一批通常不满，**调用方必须在结束时调用 [`close`](Self::close)（或 [`flush`](Self::flush)）**，
+/// 否则缓冲区中的记录不会入库。`Drop` 时若仍有未写入的记录会打印警告。
+/// 嵌入或写入失败时缓冲区保持不变，可再次调用 `flush` 重试。
+/// 设置取消令牌后，取消时进行中的嵌入请求被丢弃，之后的 push/flush 均返回 [`EmbeddingError::Cancelled`]，
+/// 缓冲区中的记录不会被写入。
+pub struct BufferedIngestor<'a, S: VectorStore, C: EmbeddingClient> {
+    store: &'a S,
+    embedding_client: &'a C,
+    batch_size: usize,
+    buffer: Vec<VectorRecord>,
+    written: usize,
+    cancel: Option<CancellationToken>,
+}
+
+impl<'a, S: VectorStore, C: EmbeddingClient> BufferedIngestor<'a, S, C> {
+    pub fn new(store: &'a S, embedding_client: &'a C) -> Self {
+        Self {
+            store,
+            embedding_client,
+            batch_size: DEFAULT_BATCH_SIZE,
+            buffer: Vec::new(),
+            written: 0,
+            cancel: None,
+        }
+    }
+
+    pub fn with_batch_size(mut self, batch_size: usize) -> Self {
+        self.batch_size = batch_size.max(1);
+        self
+    }
+
+    pub fn with_cancellation(mut self, cancel: CancellationToken) -> Self {
+        self.cancel = Some(cancel);
+        self
+    }
+
+    fn check_cancelled(&self) -> Result<(), EmbeddingError> {
+        match &self.cancel {
+            Some(token) if token.is_cancelled() => Err(EmbeddingError::Cancelled),
+            _ => Ok(()),
+        }
+    }
+
+    /// 缓冲区中尚未写入的记录数
+    pub fn pending(&self) -> usize {
+        self.buffer.len()
+    }
+
+    /// 已写入向量库的记录数
+    pub fn written(&self) -> usize {
+        self.written
+    }
+
+    /// 加入一条记录；embedding 为空的记录在 flush 时根据 text 生成。缓冲区满时自动 flush
+    pub async fn push(&mut self, record: VectorRecord) -> Result<()> {
+        self.check_cancelled()?;
+        self.buffer.push(record);
+        if self.buffer.len() >= self.batch_size {
+            self.flush().await?;
+        }
+        Ok(())
+    }
+
+    /// 加入 NodeTree 的全部叶子；尚未嵌入的叶子在 flush 时生成 embedding
+    pub async fn push_tree(&mut self, node_tree: &NodeTree) -> Result<()> {
+        for leaf in node_tree.leaf_nodes_in_order() {
+            let record = match &leaf.embedding {
+                Some(embedding) if !embedding.is_empty() => leaf_to_vector_record(node_tree, leaf)?,
+                _ => leaf_record(node_tree, leaf, Vec::new()),
+            };
+            self.push(record).await?;
+        }
+        Ok(())
+    }
+
+    /// 立即嵌入并写入缓冲区中的全部记录
+    pub async fn flush(&mut self) -> Result<()> {
+        if self.buffer.is_empty() {
+            return Ok(());
+        }
+
+        let missing: Vec<usize> = self.buffer.iter()
+            .enumerate()
+            .filter(|(_, r)| r.embedding.is_empty())
+            .map(|(i, _)| i)
+            .collect();
+        if !missing.is_empty() {
+            let texts = missing.iter()
+                .map(|&i| self.buffer[i].text.clone().unwrap_or_default())
+                .collect();
+            let embeddings = match &self.cancel {
+                Some(token) => self.embedding_client.embed_cancellable(texts, token).await?,
+                None => self.embedding_client.embed(texts).await?,
+            };
+            // 写入失败时已生成的 embedding 保留在缓冲区中，重试无需重新嵌入
+            for (i, embedding) in missing.into_iter().zip(embeddings) {
+                self.buffer[i].embedding = embedding;
+            }
+        }
+
+        self.check_cancelled()?;
+        self.store.upsert_vectors(self.buffer.clone()).await?;
+        self.written += self.buffer.len();
+        self.buffer.clear();
+        Ok(())
+    }
+
+    /// 写入最后一批并结束，返回累计写入的记录数
+    pub async fn close(mut self) -> Result<usize> {
+        self.flush().await?;
+        Ok(self.written)
+    }
+}
+
+impl<S: VectorStore, C: EmbeddingClient> Drop for BufferedIngestor<'_, S, C> {
+    fn drop(&mut self) {
+        if !self.buffer.is_empty() && self.check_cancelled().is_ok() {
+            eprintln!(
+                "警告: BufferedIngestor 被丢弃时仍有 {} 条记录未写入，请在结束前调用 close()",
+                self.buffer.len()
+            );
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use async_trait::async_trait;
+    use crate::database::SearchResult;
+    use crate::client::{EmbeddingError, EmbeddingResult};
+    use std::sync::Mutex;
+    use std::sync::atomic::{AtomicBool, Ordering};
+
+    /// 设置 fail 后下一次调用返回错误
+    #[derive(Default)]
+    struct FlakyClient {
+        fail: AtomicBool,
+    }
+
+    #[async_trait]
+    impl EmbeddingClient for FlakyClient {
+        async fn embed(&self, texts: Vec<String>) -> EmbeddingResult<Vec<Vec<f32>>> {
+            if self.fail.swap(false, Ordering::SeqCst) {
+                return Err(EmbeddingError::Network("interrupted".to_string()));
+            }
+            Ok(texts.iter().map(|t| vec![t.chars().count() as f32]).collect())
+        }
+
+        fn dimension(&self) -> usize {
+            1
+        }
+    }
+
+    #[derive(Default)]
+    struct MemStore(Mutex<Vec<VectorRecord>>);
+
+    #[async_trait]
+    impl VectorStore for MemStore {
+        async fn add_vectors(&self, vectors: Vec<VectorRecord>) -> Result<()> { self.upsert_vectors(vectors).await }
+        async fn upsert_vectors(&self, vectors: Vec<VectorRecord>) -> Result<()> {
+            self.0.lock().unwrap().extend(vectors);
+            Ok(())
+        }
+        async fn delete_vector(&self, _ids: Vec<String>) -> Result<()> { Ok(()) }
+        async fn search(&self, _query: &[f32], _top_k: usize) -> Result<Vec<SearchResult>> { Ok(vec![]) }
+    }
+
+    fn record(id: &str, text: &str) -> VectorRecord {
+        VectorRecord {
+            id: id.to_string(),
+            embedding: vec![],
+            metadata: serde_json::json!({}),
+            text: Some(text.to_string()),
+            createat: None,
+            updateat: None,
+        }
+    }
+
+    #[tokio::test]
+    async fn test_interrupted_batch_is_kept() -> Result<()> {
+        let store = MemStore::default();
+        let client = FlakyClient::default();
+        let mut ingestor = BufferedIngestor::new(&store, &client).with_batch_size(2);
+
+        ingestor.push(record("a", "a")).await?;
+        ingestor.push(record("b", "bb")).await?;
+        ingestor.push(record("c", "ccc")).await?;
+        assert_eq!((ingestor.written(), ingestor.pending()), (2, 1));
+
+        // 中途失败：最后不满的一批仍留在缓冲区
+        client.fail.store(true, Ordering::SeqCst);
+        assert!(ingestor.flush().await.is_err());
+        assert_eq!(ingestor.pending(), 1);
+
+        assert_eq!(ingestor.close().await?, 3);
+        let stored = store.0.lock().unwrap();
+        assert_eq!(stored.iter().map(|r| r.id.as_str()).collect::<Vec<_>>(), vec!["a", "b", "c"]);
+        assert_eq!(stored[2].embedding, vec![3.0]);
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_cancelled_stops_batches() -> Result<()> {
+        let store = MemStore::default();
+        let client = FlakyClient::default();
+        let cancel = CancellationToken::new();
+        let mut ingestor = BufferedIngestor::new(&store, &client)
+            .with_batch_size(2)
+            .with_cancellation(cancel.clone());
+
+        ingestor.push(record("a", "a")).await?;
+        ingestor.push(record("b", "b")).await?;
+        ingestor.push(record("c", "c")).await?;
+        cancel.cancel();
+
+        let err = ingestor.push(record("d", "d")).await.unwrap_err();
+        assert!(matches!(err.downcast_ref(), Some(EmbeddingError::Cancelled)));
+        let err = ingestor.flush().await.unwrap_err();
+        assert!(matches!(err.downcast_ref(), Some(EmbeddingError::Cancelled)));
+        assert_eq!((ingestor.written(), ingestor.pending()), (2, 1));
+        assert_eq!(store.0.lock().unwrap().len(), 2);
+        Ok(())
+    }
+}
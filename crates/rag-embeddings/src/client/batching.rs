@@ -0,0 +1,77 @@
+use std::ops::Range;
+
+/// DashScope 单次 embedding 请求的最大输入条数
+pub const DEFAULT_MAX_BATCH_SIZE: usize = 25;
+
+/// 单次请求所有输入的 token 总数上限（保守值，可按服务配额调整）
+pub const DEFAULT_MAX_BATCH_TOKENS: usize = 32_768;
+
+/// 单次请求的条数与 token 预算，两者任一达到上限即结束当前批
+///
+/// 长分块按 token 预算提前分批，避免超出服务端的请求 token 上限；短分块按条数装满每批。
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct BatchLimits {
+    pub max_batch_size: usize,
+    pub max_batch_tokens: usize,
+}
+
+impl Default for BatchLimits {
+    fn default() -> Self {
+        Self {
+            max_batch_size: DEFAULT_MAX_BATCH_SIZE,
+            max_batch_tokens: DEFAULT_MAX_BATCH_TOKENS,
+        }
+    }
+}
+
+impl BatchLimits {
+    pub fn with_max_batch_size(mut self, max_batch_size: usize) -> Self {
+        self.max_batch_size = max_batch_size.max(1);
+        self
+    }
+
+    pub fn with_max_batch_tokens(mut self, max_batch_tokens: usize) -> Self {
+        self.max_batch_tokens = max_batch_tokens.max(1);
+        self
+    }
+
+    /// 按输入顺序切分批次，返回每批在输入中的下标范围
+    ///
+    /// `token_counts` 为每条输入的 token 数。单条输入超过 token 预算时单独成批，由服务端决定是否截断或报错。
+    pub fn split(&self, token_counts: &[usize]) -> Vec<Range<usize>> {
+        let mut batches = Vec::new();
+        let mut start = 0;
+        let mut tokens = 0;
+        for (i, &count) in token_counts.iter().enumerate() {
+            let full = i - start >= self.max_batch_size || tokens + count > self.max_batch_tokens;
+            if i > start && full {
+                batches.push(start..i);
+                start = i;
+                tokens = 0;
+            }
+            tokens += count;
+        }
+        if start < token_counts.len() {
+            batches.push(start..token_counts.len());
+        }
+        batches
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_split_batches() {
+        let limits = BatchLimits::default().with_max_batch_size(3).with_max_batch_tokens(100);
+
+        // 短输入按条数装满
+        assert_eq!(limits.split(&[1; 7]), vec![0..3, 3..6, 6..7]);
+        // 长输入按 token 预算提前结束
+        assert_eq!(limits.split(&[60, 30, 20, 90, 10]), vec![0..2, 2..3, 3..5]);
+        // 超出预算的单条输入单独成批
+        assert_eq!(limits.split(&[10, 150, 10]), vec![0..1, 1..2, 2..3]);
+        assert!(limits.split(&[]).is_empty());
+    }
+}
@@ -0,0 +1,176 @@
+use std::collections::HashMap;
+use std::num::NonZeroUsize;
+use std::sync::Mutex;
+
+use async_trait::async_trait;
+use lru::LruCache;
+
+use crate::client::{EmbeddingClient, EmbeddingError, EmbeddingResult};
+
+/// 缓存键：(模型名, 文本)
+type CacheKey = (String, String);
+
+/// 按 (模型, 文本) 缓存文档向量的 LRU 适配器，只为未命中的文本调用内部客户端
+///
+/// 重新入库少量修改过的文档时，未变化的分块直接复用缓存的向量，不再重复付费请求。
+/// 键包含 [`EmbeddingClient::model`]，切换模型不会取到旧模型的向量；同一批内重复的文本只请求一次。
+/// 只缓存 [`embed`](EmbeddingClient::embed)，查询向量（[`embed_queries`](EmbeddingClient::embed_queries)）
+/// 可能带有不同的指令前缀，直接转发给内部客户端。
+pub struct CachingEmbeddingClient<C: EmbeddingClient> {
+    inner: C,
+    cache: Mutex<LruCache<CacheKey, Vec<f32>>>,
+}
+
+impl<C: EmbeddingClient> CachingEmbeddingClient<C> {
+    /// 最多缓存 `capacity` 个向量，超出时淘汰最久未使用的
+    pub fn new(inner: C, capacity: usize) -> Self {
+        Self {
+            inner,
+            cache: Mutex::new(LruCache::new(NonZeroUsize::new(capacity).unwrap_or(NonZeroUsize::MIN))),
+        }
+    }
+
+    pub fn inner(&self) -> &C {
+        &self.inner
+    }
+
+    /// 当前缓存的向量数
+    pub fn cached(&self) -> usize {
+        self.cache.lock().unwrap().len()
+    }
+
+    fn key(&self, text: &str) -> CacheKey {
+        (self.inner.model().unwrap_or_default().to_string(), text.to_string())
+    }
+}
+
+#[async_trait]
+impl<C: EmbeddingClient> EmbeddingClient for CachingEmbeddingClient<C> {
+    async fn embed(&self, texts: Vec<String>) -> EmbeddingResult<Vec<Vec<f32>>> {
+        let mut vectors: Vec<Option<Vec<f32>>> = {
+            let mut cache = self.cache.lock().unwrap();
+            texts.iter().map(|text| cache.get(&self.key(text)).cloned()).collect()
+        };
+
+        // 未命中的文本去重后按首次出现的顺序请求
+        let mut misses: Vec<String> = Vec::new();
+        let mut positions: HashMap<&str, usize> = HashMap::new();
+        for (text, vector) in texts.iter().zip(&vectors) {
+            if vector.is_none() && !positions.contains_key(text.as_str()) {
+                positions.insert(text, misses.len());
+                misses.push(text.clone());
+            }
+        }
+        if misses.is_empty() {
+            return Ok(vectors.into_iter().flatten().collect());
+        }
+
+        let embedded = self.inner.embed(misses.clone()).await?;
+        if embedded.len() != misses.len() {
+            return Err(EmbeddingError::InvalidResponse(format!(
+                "请求了 {} 条文本，返回了 {} 个向量", misses.len(), embedded.len()
+            )));
+        }
+
+        {
+            let mut cache = self.cache.lock().unwrap();
+            for (text, vector) in misses.iter().zip(&embedded) {
+                cache.put(self.key(text), vector.clone());
+            }
+        }
+        for (text, vector) in texts.iter().zip(vectors.iter_mut()) {
+            if vector.is_none() {
+                *vector = Some(embedded[positions[text.as_str()]].clone());
+            }
+        }
+        Ok(vectors.into_iter().flatten().collect())
+    }
+
+    async fn embed_queries(&self, texts: Vec<String>) -> EmbeddingResult<Vec<Vec<f32>>> {
+        self.inner.embed_queries(texts).await
+    }
+
+    fn dimension(&self) -> usize {
+        self.inner.dimension()
+    }
+
+    fn model(&self) -> Option<&str> {
+        self.inner.model()
+    }
+
+    /// 预检总是请求内部客户端，不使用缓存
+    async fn validate(&self) -> EmbeddingResult<()> {
+        self.inner.validate().await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// 记录每次请求的文本，向量的首个分量为文本字符数
+    struct CountingClient {
+        model: String,
+        calls: Mutex<Vec<Vec<String>>>,
+    }
+
+    impl CountingClient {
+        fn new(model: &str) -> Self {
+            Self { model: model.to_string(), calls: Mutex::new(Vec::new()) }
+        }
+    }
+
+    #[async_trait]
+    impl EmbeddingClient for CountingClient {
+        async fn embed(&self, texts: Vec<String>) -> EmbeddingResult<Vec<Vec<f32>>> {
+            self.calls.lock().unwrap().push(texts.clone());
+            Ok(texts.iter().map(|t| vec![t.chars().count() as f32, self.model.len() as f32]).collect())
+        }
+
+        fn dimension(&self) -> usize {
+            2
+        }
+
+        fn model(&self) -> Option<&str> {
+            Some(&self.model)
+        }
+    }
+
+    #[tokio::test]
+    async fn test_caching_client() -> EmbeddingResult<()> {
+        let client = CachingEmbeddingClient::new(CountingClient::new("text-embedding-v1"), 2);
+        let texts = |items: &[&str]| items.iter().map(|t| t.to_string()).collect::<Vec<_>>();
+
+        let first = client.embed(texts(&["所有权", "借用"])).await?;
+        let second = client.embed(texts(&["借用", "生命周期", "借用"])).await?;
+        assert_eq!(second[0], first[1]);
+        assert_eq!(second[2], first[1]);
+        assert_eq!(second[1], vec![4.0, 17.0]);
+        assert_eq!(
+            *client.inner().calls.lock().unwrap(),
+            vec![texts(&["所有权", "借用"]), texts(&["生命周期"])]
+        );
+
+        // 全部命中时不请求内部客户端
+        client.embed(texts(&["借用"])).await?;
+        assert_eq!(client.inner().calls.lock().unwrap().len(), 2);
+
+        // 容量为 2，最久未使用的"所有权"已被淘汰
+        assert_eq!(client.cached(), 2);
+        client.embed(texts(&["所有权"])).await?;
+        assert_eq!(client.inner().calls.lock().unwrap().len(), 3);
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_cache_key_includes_model() -> EmbeddingResult<()> {
+        let v1 = CachingEmbeddingClient::new(CountingClient::new("v1"), 8);
+        let key = v1.key("所有权");
+        assert_eq!(key, ("v1".to_string(), "所有权".to_string()));
+
+        let v3 = CachingEmbeddingClient::new(CountingClient::new("text-embedding-v3"), 8);
+        v3.cache.lock().unwrap().put(key, vec![0.0, 0.0]);
+        assert_eq!(v3.embed(vec!["所有权".to_string()]).await?, vec![vec![3.0, 17.0]]);
+        Ok(())
+    }
+}
@@ -0,0 +1,218 @@
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+use anyhow::{Result, bail};
+use async_trait::async_trait;
+
+use crate::client::{EmbeddingClient, EmbeddingError, EmbeddingResult, fixed_dimension::FixedDimensionClient};
+
+/// 多个 embedding 服务之间的故障转移：按顺序尝试，返回第一个成功的结果
+///
+/// 某个服务返回可重试的错误（见 [`EmbeddingError::is_retryable`]）时，先在该服务上重试 `retries` 次，
+/// 仍失败则切换到下一个；鉴权失败不重试、直接切换；其余不可重试的错误（如取消）直接返回。每批由哪个服务生成会打印出来。
+///
+/// [`model`](EmbeddingClient::model) 返回最近一次成功请求所用服务的模型，入库时每批记录的模型即实际生成该批向量的模型；
+/// 多个任务并发共享同一客户端时，读到的可能是另一批的服务。
+///
+/// 所有服务的维度必须一致，否则写入同一张表的向量无法比较；维度不同时用 [`projected`](Self::projected)
+/// 经 [`FixedDimensionClient`] 对齐。
+pub struct FallbackEmbeddingClient {
+    providers: Vec<(String, Box<dyn EmbeddingClient>)>,
+    dimension: usize,
+    retries: usize,
+    /// 最近一次成功请求所用服务的下标
+    served: AtomicUsize,
+}
+
+impl FallbackEmbeddingClient {
+    /// `providers` 为按优先级排列的 (名称, 客户端)，名称仅用于日志
+    pub fn new(providers: Vec<(String, Box<dyn EmbeddingClient>)>) -> Result<Self> {
+        let Some(dimension) = providers.first().map(|(_, client)| client.dimension()) else {
+            bail!("FallbackEmbeddingClient 至少需要一个 embedding 服务");
+        };
+        if let Some((name, client)) = providers.iter().find(|(_, client)| client.dimension() != dimension) {
+            bail!(
+                "embedding 服务 {} 的维度 {} 与首选服务的维度 {} 不一致，请使用 FallbackEmbeddingClient::projected 对齐",
+                name,
+                client.dimension(),
+                dimension
+            );
+        }
+
+        Ok(Self { providers, dimension, retries: 0, served: AtomicUsize::new(0) })
+    }
+
+    /// 维度与 `dimension` 不同的服务经 [`FixedDimensionClient`] 截断 / 补零后使用
+    pub fn projected(providers: Vec<(String, Box<dyn EmbeddingClient>)>, dimension: usize) -> Result<Self> {
+        let providers = providers.into_iter()
+            .map(|(name, client)| {
+                let client: Box<dyn EmbeddingClient> = if client.dimension() == dimension {
+                    client
+                } else {
+                    Box::new(FixedDimensionClient::new(client, dimension))
+                };
+                (name, client)
+            })
+            .collect();
+        Self::new(providers)
+    }
+
+    /// 切换到下一个服务前，在当前服务上额外重试的次数
+    pub fn with_retries(mut self, retries: usize) -> Self {
+        self.retries = retries;
+        self
+    }
+
+    pub fn providers(&self) -> impl Iterator<Item = &str> {
+        self.providers.iter().map(|(name, _)| name.as_str())
+    }
+
+    async fn embed_with_fallback(&self, texts: Vec<String>, queries: bool) -> EmbeddingResult<Vec<Vec<f32>>> {
+        let mut last_error = None;
+        for (i, (name, client)) in self.providers.iter().enumerate() {
+            for attempt in 0..=self.retries {
+                let result = if queries {
+                    client.embed_queries(texts.clone()).await
+                } else {
+                    client.embed(texts.clone()).await
+                };
+                match result {
+                    Ok(vectors) => {
+                        println!("embedding 服务 {} 生成了 {} 个向量", name, vectors.len());
+                        self.served.store(i, Ordering::SeqCst);
+                        return Ok(vectors);
+                    }
+                    Err(e) if e.is_retryable() => {
+                        println!("embedding 服务 {} 请求失败 ({}/{}): {}", name, attempt + 1, self.retries + 1, e);
+                        last_error = Some(e);
+                    }
+                    // 鉴权失败重试无意义，直接切换到下一个服务
+                    Err(e @ EmbeddingError::Unauthorized(_)) => {
+                        println!("embedding 服务 {} 鉴权失败: {}", name, e);
+                        last_error = Some(e);
+                        break;
+                    }
+                    Err(e) => return Err(e),
+                }
+            }
+        }
+        Err(last_error.unwrap_or_else(|| EmbeddingError::Api("没有可用的 embedding 服务".to_string())))
+    }
+}
+
+#[async_trait]
+impl EmbeddingClient for FallbackEmbeddingClient {
+    async fn embed(&self, texts: Vec<String>) -> EmbeddingResult<Vec<Vec<f32>>> {
+        self.embed_with_fallback(texts, false).await
+    }
+
+    async fn embed_queries(&self, texts: Vec<String>) -> EmbeddingResult<Vec<Vec<f32>>> {
+        self.embed_with_fallback(texts, true).await
+    }
+
+    fn dimension(&self) -> usize {
+        self.dimension
+    }
+
+    fn model(&self) -> Option<&str> {
+        self.providers[self.served.load(Ordering::SeqCst)].1.model()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::Arc;
+
+    /// 前 `failures` 次调用返回 `error`，之后返回维度为 `dimension` 的全 1 向量；模型名即服务名
+    struct ScriptedClient {
+        model: String,
+        dimension: usize,
+        failures: usize,
+        error: fn() -> EmbeddingError,
+        calls: Arc<AtomicUsize>,
+    }
+
+    #[async_trait]
+    impl EmbeddingClient for ScriptedClient {
+        async fn embed(&self, texts: Vec<String>) -> EmbeddingResult<Vec<Vec<f32>>> {
+            if self.calls.fetch_add(1, Ordering::SeqCst) < self.failures {
+                return Err((self.error)());
+            }
+            Ok(texts.iter().map(|_| vec![1.0; self.dimension]).collect())
+        }
+
+        fn dimension(&self) -> usize {
+            self.dimension
+        }
+
+        fn model(&self) -> Option<&str> {
+            Some(&self.model)
+        }
+    }
+
+    fn provider(name: &str, dimension: usize, failures: usize, error: fn() -> EmbeddingError) -> ((String, Box<dyn EmbeddingClient>), Arc<AtomicUsize>) {
+        let calls = Arc::new(AtomicUsize::new(0));
+        let client = ScriptedClient { model: name.to_string(), dimension, failures, error, calls: calls.clone() };
+        ((name.to_string(), Box::new(client)), calls)
+    }
+
+    fn network() -> EmbeddingError {
+        EmbeddingError::Network("connection refused".to_string())
+    }
+
+    #[tokio::test]
+    async fn test_fallback_to_next_provider() -> Result<()> {
+        let (qwen, qwen_calls) = provider("qwen", 2, usize::MAX, network);
+        let (openai, openai_calls) = provider("openai", 2, 0, network);
+        let client = FallbackEmbeddingClient::new(vec![qwen, openai])?.with_retries(1);
+        assert_eq!(client.model(), Some("qwen"));
+
+        let vectors = client.embed(vec!["a".to_string()]).await?;
+        assert_eq!(vectors, vec![vec![1.0, 1.0]]);
+        assert_eq!(qwen_calls.load(Ordering::SeqCst), 2);
+        assert_eq!(openai_calls.load(Ordering::SeqCst), 1);
+        // 入库配置记录实际生成向量的服务的模型
+        assert_eq!(client.model(), Some("openai"));
+
+        // 全部失败时返回最后一个错误
+        let (a, _) = provider("a", 2, usize::MAX, network);
+        let (b, _) = provider("b", 2, usize::MAX, || EmbeddingError::Server("503".to_string()));
+        let client = FallbackEmbeddingClient::new(vec![a, b])?;
+        assert!(matches!(client.embed(vec!["a".to_string()]).await, Err(EmbeddingError::Server(_))));
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_non_retryable_error_stops() -> Result<()> {
+        let (primary, _) = provider("primary", 2, usize::MAX, || EmbeddingError::Cancelled);
+        let (backup, backup_calls) = provider("backup", 2, 0, network);
+        let client = FallbackEmbeddingClient::new(vec![primary, backup])?;
+
+        assert!(matches!(client.embed(vec!["a".to_string()]).await, Err(EmbeddingError::Cancelled)));
+        assert_eq!(backup_calls.load(Ordering::SeqCst), 0);
+
+        // 参数错误在其他服务上同样会失败，不切换
+        let (primary, primary_calls) = provider("primary", 2, usize::MAX, || EmbeddingError::Api("[InvalidParameter] bad input".to_string()));
+        let (backup, backup_calls) = provider("backup", 2, 0, network);
+        let client = FallbackEmbeddingClient::new(vec![primary, backup])?.with_retries(2);
+        assert!(matches!(client.embed(vec!["a".to_string()]).await, Err(EmbeddingError::Api(_))));
+        assert_eq!((primary_calls.load(Ordering::SeqCst), backup_calls.load(Ordering::SeqCst)), (1, 0));
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_dimension_check() -> Result<()> {
+        let (qwen, _) = provider("qwen", 4, 0, network);
+        let (openai, _) = provider("openai", 2, 0, network);
+        assert!(FallbackEmbeddingClient::new(vec![qwen, openai]).is_err());
+        assert!(FallbackEmbeddingClient::new(Vec::new()).is_err());
+
+        let (qwen, _) = provider("qwen", 4, usize::MAX, network);
+        let (openai, _) = provider("openai", 2, 0, network);
+        let client = FallbackEmbeddingClient::projected(vec![qwen, openai], 4)?;
+        assert_eq!(client.dimension(), 4);
+        assert_eq!(client.providers().collect::<Vec<_>>(), vec!["qwen", "openai"]);
+        assert_eq!(client.embed(vec!["a".to_string()]).await?[0].len(), 4);
+        Ok(())
+    }
+}
@@ -0,0 +1,146 @@
+use async_trait::async_trait;
+
+use crate::client::{EmbeddingClient, EmbeddingError, EmbeddingResult, l2_norm};
+
+/// 维度对齐策略
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DimensionStrategy {
+    /// 超出目标维度时截断前 N 维，不足时补零
+    TruncateOrPad,
+    /// 仅允许补零，原始维度大于目标维度时报错
+    ZeroPad,
+}
+
+/// 将内部客户端的 embedding 截断 / 补零到固定维度的适配器
+///
+/// 用于让原生维度不同的模型（如 1536 与 2560）共用同一张 pgvector 表。
+/// 截断会改变向量长度，`renormalize` 开启时（默认）变换后重新 L2 归一化；补零不改变长度。
+///
+/// **注意**：不同模型的向量空间并不对齐，截断 / 补零后跨模型的相似度只是近似值，
+/// 同一模型产生的向量之间仍可正常比较。
+pub struct FixedDimensionClient<C: EmbeddingClient> {
+    inner: C,
+    dimension: usize,
+    strategy: DimensionStrategy,
+    renormalize: bool,
+}
+
+impl<C: EmbeddingClient> FixedDimensionClient<C> {
+    pub fn new(inner: C, dimension: usize) -> Self {
+        Self {
+            inner,
+            dimension,
+            strategy: DimensionStrategy::TruncateOrPad,
+            renormalize: true,
+        }
+    }
+
+    pub fn with_strategy(mut self, strategy: DimensionStrategy) -> Self {
+        self.strategy = strategy;
+        self
+    }
+
+    /// 内部客户端不输出归一化向量时应关闭
+    pub fn with_renormalize(mut self, renormalize: bool) -> Self {
+        self.renormalize = renormalize;
+        self
+    }
+
+    pub fn inner(&self) -> &C {
+        &self.inner
+    }
+
+    fn project(&self, mut embedding: Vec<f32>) -> EmbeddingResult<Vec<f32>> {
+        if embedding.len() > self.dimension {
+            if self.strategy == DimensionStrategy::ZeroPad {
+                return Err(EmbeddingError::InvalidVector(format!(
+                    "Embedding dim {} exceeds target dim {}",
+                    embedding.len(),
+                    self.dimension
+                )));
+            }
+            embedding.truncate(self.dimension);
+
+            if self.renormalize {
+                let norm = l2_norm(&embedding);
+                if norm < 1e-8 {
+                    return Err(EmbeddingError::InvalidVector("Zero vector after truncation".to_string()));
+                }
+                embedding.iter_mut().for_each(|x| *x /= norm);
+            }
+        } else {
+            embedding.resize(self.dimension, 0.0);
+        }
+        Ok(embedding)
+    }
+}
+
+#[async_trait]
+impl<C: EmbeddingClient> EmbeddingClient for FixedDimensionClient<C> {
+    async fn embed(&self, texts: Vec<String>) -> EmbeddingResult<Vec<Vec<f32>>> {
+        self.inner.embed(texts)
+            .await?
+            .into_iter()
+            .map(|embedding| self.project(embedding))
+            .collect()
+    }
+
+    async fn embed_queries(&self, texts: Vec<String>) -> EmbeddingResult<Vec<Vec<f32>>> {
+        self.inner.embed_queries(texts)
+            .await?
+            .into_iter()
+            .map(|embedding| self.project(embedding))
+            .collect()
+    }
+
+    fn dimension(&self) -> usize {
+        self.dimension
+    }
+
+    fn model(&self) -> Option<&str> {
+        self.inner.model()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::client::{DEFAULT_NORMALIZATION_TOLERANCE, is_normalized};
+
+    struct NativeClient(Vec<f32>);
+
+    #[async_trait]
+    impl EmbeddingClient for NativeClient {
+        async fn embed(&self, texts: Vec<String>) -> EmbeddingResult<Vec<Vec<f32>>> {
+            Ok(texts.iter().map(|_| self.0.clone()).collect())
+        }
+
+        fn dimension(&self) -> usize {
+            self.0.len()
+        }
+    }
+
+    #[tokio::test]
+    async fn test_truncate_renormalizes() -> EmbeddingResult<()> {
+        let client = FixedDimensionClient::new(NativeClient(vec![0.6, 0.0, 0.8, 0.0]), 2);
+        let embeddings = client.embed(vec!["a".to_string()]).await?;
+
+        assert_eq!(client.dimension(), 2);
+        assert_eq!(embeddings[0], vec![1.0, 0.0]);
+        assert!(is_normalized(&embeddings[0], DEFAULT_NORMALIZATION_TOLERANCE));
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_zero_pad() -> EmbeddingResult<()> {
+        let client = FixedDimensionClient::new(NativeClient(vec![0.6, 0.8]), 4)
+            .with_strategy(DimensionStrategy::ZeroPad);
+        let embeddings = client.embed(vec!["a".to_string()]).await?;
+        assert_eq!(embeddings[0], vec![0.6, 0.8, 0.0, 0.0]);
+
+        let client = FixedDimensionClient::new(NativeClient(vec![0.6, 0.8]), 1)
+            .with_strategy(DimensionStrategy::ZeroPad);
+        assert!(client.embed(vec!["a".to_string()]).await.is_err());
+        Ok(())
+    }
+}
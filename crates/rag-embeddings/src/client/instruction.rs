@@ -0,0 +1,99 @@
+use async_trait::async_trait;
+
+use crate::client::{EmbeddingClient, EmbeddingResult};
+
+/// 为指令微调类 embedding 模型在输入前拼接任务指令的适配器
+///
+/// 文档（[`embed`](EmbeddingClient::embed)）与查询（[`embed_queries`](EmbeddingClient::embed_queries)）
+/// 分别使用各自的前缀，如查询前缀 "为这个句子生成表示以用于检索："，调用方无需手动拼接。
+pub struct InstructionClient<C: EmbeddingClient> {
+    inner: C,
+    document_prefix: Option<String>,
+    query_prefix: Option<String>,
+}
+
+impl<C: EmbeddingClient> InstructionClient<C> {
+    pub fn new(inner: C) -> Self {
+        Self { inner, document_prefix: None, query_prefix: None }
+    }
+
+    pub fn with_document_prefix(mut self, prefix: impl Into<String>) -> Self {
+        self.document_prefix = Some(prefix.into());
+        self
+    }
+
+    pub fn with_query_prefix(mut self, prefix: impl Into<String>) -> Self {
+        self.query_prefix = Some(prefix.into());
+        self
+    }
+
+    pub fn inner(&self) -> &C {
+        &self.inner
+    }
+}
+
+fn apply_prefix(prefix: Option<&str>, texts: Vec<String>) -> Vec<String> {
+    match prefix {
+        Some(prefix) => texts.into_iter().map(|t| format!("{}{}", prefix, t)).collect(),
+        None => texts,
+    }
+}
+
+#[async_trait]
+impl<C: EmbeddingClient> EmbeddingClient for InstructionClient<C> {
+    async fn embed(&self, texts: Vec<String>) -> EmbeddingResult<Vec<Vec<f32>>> {
+        self.inner.embed(apply_prefix(self.document_prefix.as_deref(), texts)).await
+    }
+
+    async fn embed_queries(&self, texts: Vec<String>) -> EmbeddingResult<Vec<Vec<f32>>> {
+        self.inner.embed_queries(apply_prefix(self.query_prefix.as_deref(), texts)).await
+    }
+
+    fn dimension(&self) -> usize {
+        self.inner.dimension()
+    }
+
+    fn model(&self) -> Option<&str> {
+        self.inner.model()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::Mutex;
+
+    /// 记录收到的输入文本
+    #[derive(Default)]
+    struct RecordingClient(Mutex<Vec<String>>);
+
+    #[async_trait]
+    impl EmbeddingClient for RecordingClient {
+        async fn embed(&self, texts: Vec<String>) -> EmbeddingResult<Vec<Vec<f32>>> {
+            self.0.lock().unwrap().extend(texts.iter().cloned());
+            Ok(texts.iter().map(|_| vec![1.0]).collect())
+        }
+
+        fn dimension(&self) -> usize {
+            1
+        }
+    }
+
+    #[tokio::test]
+    async fn test_instruction_prefix() -> EmbeddingResult<()> {
+        let client = InstructionClient::new(RecordingClient::default())
+            .with_query_prefix("为这个句子生成表示以用于检索：");
+
+        client.embed(vec!["文档内容".to_string()]).await?;
+        client.embed_queries(vec!["什么是所有权".to_string()]).await?;
+        assert_eq!(*client.inner().0.lock().unwrap(), vec![
+            "文档内容".to_string(),
+            "为这个句子生成表示以用于检索：什么是所有权".to_string(),
+        ]);
+
+        let client = client.with_document_prefix("passage: ");
+        client.embed(vec!["文档内容".to_string()]).await?;
+        assert_eq!(client.inner().0.lock().unwrap().last().unwrap(), "passage: 文档内容");
+        Ok(())
+    }
+}
@@ -0,0 +1,70 @@
+//! 测试用的本地 DashScope 模拟服务（基于 wiremock），客户端测试无需 API key 与网络
+
+use serde_json::{Value, json};
+use wiremock::matchers::{method, path};
+use wiremock::{Mock, MockServer, Request, Respond, ResponseTemplate};
+
+/// 测试使用的 API key，模拟服务不校验
+pub(crate) const TEST_API_KEY: &str = "test-key";
+
+/// 由文本确定性生成的（未归一化）向量：首个分量为文本字符数，其余为 1，便于核对返回顺序
+pub(crate) fn fake_embedding(text: &str, dimension: usize) -> Vec<f32> {
+    let mut embedding = vec![1.0; dimension];
+    if let Some(first) = embedding.first_mut() {
+        *first = text.chars().count() as f32;
+    }
+    embedding
+}
+
+/// 模拟 `POST /embeddings`：为请求中的每条输入返回 [`fake_embedding`]
+///
+/// `data` 按 index 倒序返回，用于检查客户端按 index 而不是按出现顺序还原结果。
+/// 请求带 `dimensions` 时按其返回，否则为 `dimension` 维。
+pub(crate) struct EmbeddingsResponder {
+    pub dimension: usize,
+}
+
+impl Respond for EmbeddingsResponder {
+    fn respond(&self, request: &Request) -> ResponseTemplate {
+        let body: Value = match serde_json::from_slice(&request.body) {
+            Ok(body) => body,
+            Err(e) => return error_response(400, "InvalidParameter", &e.to_string()),
+        };
+        let inputs = body["input"].as_array().cloned().unwrap_or_default();
+        let dimension = body["dimensions"].as_u64().map_or(self.dimension, |d| d as usize);
+        let data: Vec<Value> = inputs.iter()
+            .enumerate()
+            .rev()
+            .map(|(index, input)| json!({
+                "object": "embedding",
+                "index": index,
+                "embedding": fake_embedding(input.as_str().unwrap_or_default(), dimension),
+            }))
+            .collect();
+
+        ResponseTemplate::new(200).set_body_json(json!({
+            "object": "list",
+            "model": body["model"],
+            "data": data,
+            "usage": { "prompt_tokens": inputs.len(), "total_tokens": inputs.len() },
+        }))
+    }
+}
+
+/// DashScope 兼容模式的错误响应
+pub(crate) fn error_response(status: u16, code: &str, message: &str) -> ResponseTemplate {
+    ResponseTemplate::new(status).set_body_json(json!({
+        "error": { "code": code, "message": message, "type": code },
+    }))
+}
+
+/// 启动模拟服务并挂载返回 `dimension` 维向量的 embeddings 接口
+pub(crate) async fn embeddings_server(dimension: usize) -> MockServer {
+    let server = MockServer::start().await;
+    Mock::given(method("POST"))
+        .and(path("/embeddings"))
+        .respond_with(EmbeddingsResponder { dimension })
+        .mount(&server)
+        .await;
+    server
+}
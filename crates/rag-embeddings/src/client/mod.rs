@@ -1,26 +1,331 @@
+pub mod batching;
+pub mod caching;
+pub mod fallback;
+pub mod fixed_dimension;
+pub mod instruction;
+#[cfg(test)]
+pub(crate) mod mock_server;
+pub mod normalizing;
+pub mod openai;
 pub mod qwen;
+pub mod rate_limit;
+pub mod registry;
+pub mod retry;
 use async_trait::async_trait;
+use tokio_util::sync::CancellationToken;
 
 #[derive(Debug, thiserror::Error)]
 pub enum EmbeddingError {
     #[error("Network error: {0}")]
     Network(String),
+    /// 请求被拒绝（如参数错误），重试不会成功
     #[error("API error: {0}")]
     Api(String),
+    /// 服务端错误（5xx），服务恢复后可重试
+    #[error("Server error: {0}")]
+    Server(String),
     #[error("Invalid response: {0}")]
     InvalidResponse(String),
     #[error("Invalid vector: {0}")]
     InvalidVector(String),
+    #[error("Request cancelled")]
+    Cancelled,
+    /// API key 无效或无权访问模型
+    #[error("Unauthorized: {0}")]
+    Unauthorized(String),
+    /// 被限流或额度 / 余额不足
+    #[error("Quota exceeded: {0}")]
+    QuotaExceeded(String),
+}
+
+impl EmbeddingError {
+    /// 网络错误、限流（429）与服务端错误（5xx）可重试（或切换服务），与 HTTP 层的重试策略一致；
+    /// 参数错误等被拒绝的请求、鉴权失败、异常响应、向量非法与取消不可重试
+    pub fn is_retryable(&self) -> bool {
+        matches!(self, Self::Network(_) | Self::Server(_) | Self::QuotaExceeded(_))
+    }
 }
 
 pub type EmbeddingResult<T> = Result<T, EmbeddingError>;
 
+/// [`EmbeddingClient::validate`] 发送的探测文本
+pub const VALIDATION_PROBE: &str = "ping";
+
+/// 检查探测请求返回了一个维度为 `dimension` 的向量
+pub(crate) fn check_probe(vectors: &[Vec<f32>], dimension: usize) -> EmbeddingResult<()> {
+    match vectors {
+        [vector] if vector.len() == dimension => Ok(()),
+        [vector] => Err(EmbeddingError::InvalidResponse(format!(
+            "探测向量维度 {} 与客户端维度 {} 不一致", vector.len(), dimension
+        ))),
+        _ => Err(EmbeddingError::InvalidResponse(format!("探测请求返回了 {} 个向量", vectors.len()))),
+    }
+}
+
+/// 按响应中的 `index` 将向量还原到输入位置，`expected` 为输入条数
+///
+/// 服务端偶尔会跳过部分输入（如空字符串），按位置拼接会把向量错配到别的输入上；
+/// 有下标缺失、重复或越界时返回 [`EmbeddingError::InvalidResponse`] 并列出缺失的下标。
+pub(crate) fn align_by_index(indexed: Vec<(usize, Vec<f32>)>, expected: usize) -> EmbeddingResult<Vec<Vec<f32>>> {
+    let mut slots: Vec<Option<Vec<f32>>> = vec![None; expected];
+    for (index, embedding) in indexed {
+        match slots.get_mut(index) {
+            Some(slot @ None) => *slot = Some(embedding),
+            Some(Some(_)) => return Err(EmbeddingError::InvalidResponse(format!("响应中下标 {} 重复", index))),
+            None => return Err(EmbeddingError::InvalidResponse(format!(
+                "响应中下标 {} 超出输入条数 {}", index, expected
+            ))),
+        }
+    }
+
+    let missing: Vec<usize> = slots.iter()
+        .enumerate()
+        .filter(|(_, slot)| slot.is_none())
+        .map(|(i, _)| i)
+        .collect();
+    if !missing.is_empty() {
+        return Err(EmbeddingError::InvalidResponse(format!(
+            "期望 {} 个向量，缺少 {} 个（下标: {:?}）", expected, missing.len(), missing
+        )));
+    }
+    Ok(slots.into_iter().flatten().collect())
+}
+
+/// 归一化校验的默认容差
+pub const DEFAULT_NORMALIZATION_TOLERANCE: f32 = 1e-6;
+
+/// 计算向量的 L2 范数：sqrt(∑(x_i²))
+pub fn l2_norm(vector: &[f32]) -> f32 {
+    vector.iter()
+        .map(|&x| (x as f64).powi(2))
+        .sum::<f64>()
+        .sqrt() as f32
+}
+
+/// 检查向量是否已 L2 归一化（|‖v‖ - 1| < tolerance）
+pub fn is_normalized(vector: &[f32], tolerance: f32) -> bool {
+    !vector.is_empty() && (l2_norm(vector) - 1.0).abs() < tolerance
+}
+
+/// 将向量原位 L2 归一化，返回是否有改动
+///
+/// 已归一化（容差 [`DEFAULT_NORMALIZATION_TOLERANCE`]）的向量与零向量保持不变。
+pub fn renormalize(vector: &mut [f32]) -> bool {
+    let norm = l2_norm(vector);
+    if norm < 1e-8 || (norm - 1.0).abs() < DEFAULT_NORMALIZATION_TOLERANCE {
+        return false;
+    }
+    vector.iter_mut().for_each(|x| *x /= norm);
+    true
+}
+
+/// 向量点积：∑(a_i * b_i)，长度不同时返回 [`EmbeddingError::InvalidVector`]
+pub fn dot(a: &[f32], b: &[f32]) -> EmbeddingResult<f32> {
+    if a.len() != b.len() {
+        return Err(EmbeddingError::InvalidVector(format!("向量长度不一致: {} 与 {}", a.len(), b.len())));
+    }
+    Ok(a.iter().zip(b).map(|(&x, &y)| x as f64 * y as f64).sum::<f64>() as f32)
+}
+
+/// 余弦相似度：dot(a, b) / (‖a‖ * ‖b‖)，取值 [-1, 1]；含零向量时为 0，长度不同时报错
+///
+/// 客户端返回的向量默认已 L2 归一化，此时余弦相似度等于 [`dot`]，可直接用点积省去范数计算。
+pub fn cosine_similarity(a: &[f32], b: &[f32]) -> EmbeddingResult<f32> {
+    let dot = dot(a, b)?;
+    let norm = l2_norm(a) * l2_norm(b);
+    Ok(if norm == 0.0 { 0.0 } else { dot / norm })
+}
+
 /// 统一向量嵌入接口
 #[async_trait]
 pub trait EmbeddingClient: Send + Sync {
     /// 批量嵌入文本
     async fn embed(&self, texts: Vec<String>) -> EmbeddingResult<Vec<Vec<f32>>>;
 
+    /// 将文本作为检索查询嵌入，默认同 [`embed`](Self::embed)
+    ///
+    /// 区分文档与查询的客户端须覆盖（如 Qwen 以 `retrieval.query` 请求、[`InstructionClient`](instruction::InstructionClient) 拼接查询前缀），
+    /// 包装其他客户端的适配器须转发给内部客户端的 `embed_queries`，而不是 `embed`。
+    async fn embed_queries(&self, texts: Vec<String>) -> EmbeddingResult<Vec<Vec<f32>>> {
+        self.embed(texts).await
+    }
+
     /// 获取向量维度
     fn dimension(&self) -> usize;
-}
\ No newline at end of file
+
+    /// 模型名称，随入库配置写入记录（见 [`IngestConfig`](crate::ingest_config::IngestConfig)），
+    /// 查询时据此检查是否与入库模型一致；无法确定单一模型时为 `None`
+    fn model(&self) -> Option<&str> {
+        None
+    }
+
+    /// 启动前的预检：嵌入一条探测文本，确认 API key 有效、模型可用且返回的维度与 [`dimension`](Self::dimension) 一致
+    ///
+    /// 在解析大量文档前调用，避免处理完才发现鉴权或额度错误。
+    async fn validate(&self) -> EmbeddingResult<()> {
+        let vectors = self.embed(vec![VALIDATION_PROBE.to_string()]).await?;
+        check_probe(&vectors, self.dimension())
+    }
+
+    /// 可取消的 [`embed`](Self::embed)：token 被取消时立即丢弃进行中的请求并返回 [`EmbeddingError::Cancelled`]
+    async fn embed_cancellable(&self, texts: Vec<String>, cancel: &CancellationToken) -> EmbeddingResult<Vec<Vec<f32>>> {
+        tokio::select! {
+            biased;
+            _ = cancel.cancelled() => Err(EmbeddingError::Cancelled),
+            result = self.embed(texts) => result,
+        }
+    }
+
+    /// 批量校验归一化状态，失败时返回未通过校验的 (下标, L2 范数)
+    fn verify_batch_normalized(&self, vectors: &[Vec<f32>], tolerance: f32) -> Result<(), Vec<(usize, f32)>> {
+        let failed: Vec<(usize, f32)> = vectors.iter()
+            .enumerate()
+            .filter(|(_, v)| !is_normalized(v, tolerance))
+            .map(|(i, v)| (i, l2_norm(v)))
+            .collect();
+
+        if failed.is_empty() { Ok(()) } else { Err(failed) }
+    }
+}
+
+#[async_trait]
+impl<T: EmbeddingClient + ?Sized> EmbeddingClient for Box<T> {
+    async fn embed(&self, texts: Vec<String>) -> EmbeddingResult<Vec<Vec<f32>>> {
+        (**self).embed(texts).await
+    }
+
+    async fn embed_queries(&self, texts: Vec<String>) -> EmbeddingResult<Vec<Vec<f32>>> {
+        (**self).embed_queries(texts).await
+    }
+
+    async fn embed_cancellable(&self, texts: Vec<String>, cancel: &CancellationToken) -> EmbeddingResult<Vec<Vec<f32>>> {
+        (**self).embed_cancellable(texts, cancel).await
+    }
+
+    fn dimension(&self) -> usize {
+        (**self).dimension()
+    }
+
+    fn model(&self) -> Option<&str> {
+        (**self).model()
+    }
+
+    async fn validate(&self) -> EmbeddingResult<()> {
+        (**self).validate().await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_cosine_similarity() -> EmbeddingResult<()> {
+        let a = [0.6, 0.8];
+        assert!((cosine_similarity(&a, &a)? - 1.0).abs() < 1e-6);
+        assert!((cosine_similarity(&a, &[-0.6, -0.8])? + 1.0).abs() < 1e-6);
+        assert!(cosine_similarity(&a, &[-0.8, 0.6])?.abs() < 1e-6);
+        // 与长度无关，归一化后等于点积
+        assert!((cosine_similarity(&[3.0, 4.0], &[6.0, 8.0])? - 1.0).abs() < 1e-6);
+        assert!((cosine_similarity(&a, &[1.0, 0.0])? - dot(&a, &[1.0, 0.0])?).abs() < 1e-6);
+        assert_eq!(dot(&[1.0, 2.0, 3.0], &[4.0, 5.0, 6.0])?, 32.0);
+
+        assert_eq!(cosine_similarity(&a, &[0.0, 0.0])?, 0.0);
+        assert!(matches!(cosine_similarity(&a, &[1.0]), Err(EmbeddingError::InvalidVector(_))));
+        assert!(matches!(dot(&[], &[1.0]), Err(EmbeddingError::InvalidVector(_))));
+        Ok(())
+    }
+
+    struct FixedClient;
+
+    #[async_trait]
+    impl EmbeddingClient for FixedClient {
+        async fn embed(&self, texts: Vec<String>) -> EmbeddingResult<Vec<Vec<f32>>> {
+            Ok(texts.iter().map(|_| vec![1.0, 0.0]).collect())
+        }
+
+        fn dimension(&self) -> usize {
+            2
+        }
+    }
+
+    /// 模拟耗时很长的嵌入请求
+    struct SlowClient;
+
+    #[async_trait]
+    impl EmbeddingClient for SlowClient {
+        async fn embed(&self, texts: Vec<String>) -> EmbeddingResult<Vec<Vec<f32>>> {
+            tokio::time::sleep(std::time::Duration::from_secs(60)).await;
+            Ok(texts.iter().map(|_| vec![1.0]).collect())
+        }
+
+        fn dimension(&self) -> usize {
+            1
+        }
+    }
+
+    #[tokio::test]
+    async fn test_embed_cancellable() {
+        let cancel = CancellationToken::new();
+        let trigger = cancel.clone();
+        tokio::spawn(async move {
+            tokio::time::sleep(std::time::Duration::from_millis(10)).await;
+            trigger.cancel();
+        });
+
+        let start = std::time::Instant::now();
+        let result = SlowClient.embed_cancellable(vec!["a".to_string()], &cancel).await;
+        assert!(matches!(result, Err(EmbeddingError::Cancelled)));
+        assert!(start.elapsed() < std::time::Duration::from_secs(5));
+
+        let result = FixedClient.embed_cancellable(vec!["a".to_string()], &cancel).await;
+        assert!(matches!(result, Err(EmbeddingError::Cancelled)));
+    }
+
+    #[test]
+    fn test_verify_batch_normalized() {
+        let client = FixedClient;
+        let vectors = vec![
+            vec![0.6, 0.8],
+            vec![3.0, 4.0],
+            vec![],
+            vec![1.0, 0.0],
+        ];
+
+        let failed = client.verify_batch_normalized(&vectors, DEFAULT_NORMALIZATION_TOLERANCE).unwrap_err();
+        assert_eq!(failed.iter().map(|(i, _)| *i).collect::<Vec<_>>(), vec![1, 2]);
+        assert!((failed[0].1 - 5.0).abs() < 1e-6);
+        assert_eq!(failed[1].1, 0.0);
+
+        assert!(client.verify_batch_normalized(&vectors[..1], DEFAULT_NORMALIZATION_TOLERANCE).is_ok());
+        // 放宽容差后 [0.6, 0.79] 也可通过
+        assert!(client.verify_batch_normalized(&[vec![0.6, 0.79]], 1e-2).is_ok());
+    }
+
+    /// 返回向量维度与声明不一致的客户端
+    struct MisreportingClient;
+
+    #[async_trait]
+    impl EmbeddingClient for MisreportingClient {
+        async fn embed(&self, texts: Vec<String>) -> EmbeddingResult<Vec<Vec<f32>>> {
+            Ok(texts.iter().map(|_| vec![1.0]).collect())
+        }
+
+        fn dimension(&self) -> usize {
+            1536
+        }
+    }
+
+    #[tokio::test]
+    async fn test_validate() {
+        assert!(FixedClient.validate().await.is_ok());
+        let boxed: Box<dyn EmbeddingClient> = Box::new(FixedClient);
+        assert!(boxed.validate().await.is_ok());
+        assert!(matches!(MisreportingClient.validate().await, Err(EmbeddingError::InvalidResponse(_))));
+
+        assert!(!EmbeddingError::Unauthorized("invalid key".to_string()).is_retryable());
+        assert!(EmbeddingError::QuotaExceeded("throttled".to_string()).is_retryable());
+        assert!(EmbeddingError::Server("busy".to_string()).is_retryable());
+        assert!(!EmbeddingError::Api("[InvalidParameter] bad input".to_string()).is_retryable());
+    }
+}
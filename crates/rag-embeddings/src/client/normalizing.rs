@@ -0,0 +1,82 @@
+use async_trait::async_trait;
+use rag_indexing::normalize::TextNormalizer;
+
+use crate::client::{EmbeddingClient, EmbeddingResult};
+
+/// 在 embedding 前对文档与查询文本做 [`TextNormalizer`] 规范化的适配器
+///
+/// 全角 / 半角、空白不一致的同一内容得到相同的向量。只改变发送给内部客户端的文本：
+/// 叶子的 `text` 与 token 数保持原文，去重哈希见 [`normalize_for_dedup`](crate::dedup::normalize_for_dedup)。
+/// [`CachingEmbeddingClient`](crate::client::caching::CachingEmbeddingClient) 包在本适配器外层时以原文为缓存键，
+/// 包在内层时以规范化后的文本为键。
+pub struct NormalizingClient<C: EmbeddingClient> {
+    inner: C,
+    normalizer: TextNormalizer,
+}
+
+impl<C: EmbeddingClient> NormalizingClient<C> {
+    pub fn new(inner: C, normalizer: TextNormalizer) -> Self {
+        Self { inner, normalizer }
+    }
+
+    pub fn inner(&self) -> &C {
+        &self.inner
+    }
+
+    pub fn normalizer(&self) -> &TextNormalizer {
+        &self.normalizer
+    }
+
+    fn normalize_all(&self, texts: Vec<String>) -> Vec<String> {
+        texts.iter().map(|t| self.normalizer.normalize(t)).collect()
+    }
+}
+
+#[async_trait]
+impl<C: EmbeddingClient> EmbeddingClient for NormalizingClient<C> {
+    async fn embed(&self, texts: Vec<String>) -> EmbeddingResult<Vec<Vec<f32>>> {
+        self.inner.embed(self.normalize_all(texts)).await
+    }
+
+    async fn embed_queries(&self, texts: Vec<String>) -> EmbeddingResult<Vec<Vec<f32>>> {
+        self.inner.embed_queries(self.normalize_all(texts)).await
+    }
+
+    fn dimension(&self) -> usize {
+        self.inner.dimension()
+    }
+
+    fn model(&self) -> Option<&str> {
+        self.inner.model()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::Mutex;
+
+    #[derive(Default)]
+    struct RecordingClient(Mutex<Vec<String>>);
+
+    #[async_trait]
+    impl EmbeddingClient for RecordingClient {
+        async fn embed(&self, texts: Vec<String>) -> EmbeddingResult<Vec<Vec<f32>>> {
+            self.0.lock().unwrap().extend(texts.iter().cloned());
+            Ok(texts.iter().map(|_| vec![1.0]).collect())
+        }
+
+        fn dimension(&self) -> usize {
+            1
+        }
+    }
+
+    #[tokio::test]
+    async fn test_normalizing_client() -> EmbeddingResult<()> {
+        let client = NormalizingClient::new(RecordingClient::default(), TextNormalizer::default());
+        client.embed(vec!["Ｒｕｓｔ　所有权  ".to_string()]).await?;
+        client.embed_queries(vec![" 什么是\n所有权？".to_string()]).await?;
+        assert_eq!(*client.inner().0.lock().unwrap(), vec!["Rust 所有权", "什么是 所有权?"]);
+        Ok(())
+    }
+}
@@ -0,0 +1,174 @@
+use async_trait::async_trait;
+use reqwest::Client;
+use serde::Serialize;
+
+use crate::client::{EmbeddingClient, EmbeddingError, EmbeddingResult, align_by_index};
+
+/// OpenAI 接口地址
+pub const OPENAI_API: &str = "https://api.openai.com/v1";
+
+#[derive(Serialize)]
+struct OpenAIRequest<'a> {
+    model: &'a str,
+    input: &'a [String],
+}
+
+/// 将非 2xx 响应转换为 EmbeddingError，与 Qwen 客户端的分类一致
+///
+/// 额度不足（`insufficient_quota`）与 HTTP 429 归为 [`EmbeddingError::QuotaExceeded`]，
+/// key 无效（`invalid_api_key`）与 HTTP 401 / 403 归为 [`EmbeddingError::Unauthorized`]，
+/// 其余 5xx 归为 [`EmbeddingError::Server`]，4xx 归为 [`EmbeddingError::Api`]。
+fn api_error(status: reqwest::StatusCode, resp_text: &str) -> EmbeddingError {
+    let error = serde_json::from_str::<serde_json::Value>(resp_text)
+        .ok()
+        .and_then(|value| value.get("error").cloned());
+    let code = error.as_ref()
+        .and_then(|e| {
+            // OpenAI 的 code 可能为 null，此时退回 type 字段
+            e.get("code").and_then(|c| c.as_str()).or_else(|| e.get("type").and_then(|t| t.as_str()))
+        })
+        .unwrap_or_default()
+        .to_lowercase();
+    let message = match error.as_ref().and_then(|e| e.get("message")).and_then(|m| m.as_str()) {
+        Some(message) => format!("[{}] {}", code, message),
+        None => format!("HTTP {}: {}", status, resp_text.trim()),
+    };
+
+    if code.contains("quota") || code.contains("rate_limit") || status == reqwest::StatusCode::TOO_MANY_REQUESTS {
+        EmbeddingError::QuotaExceeded(message)
+    } else if code.contains("api_key")
+        || status == reqwest::StatusCode::UNAUTHORIZED
+        || status == reqwest::StatusCode::FORBIDDEN
+    {
+        EmbeddingError::Unauthorized(message)
+    } else if status.is_server_error() {
+        EmbeddingError::Server(message)
+    } else {
+        EmbeddingError::Api(message)
+    }
+}
+
+/// OpenAI（及兼容接口）的 embedding 客户端，返回的向量已是单位长度
+pub struct OpenAIEmbeddingClient {
+    api_key: String,
+    model: String,
+    base_url: String,
+    client: Client,
+    dimension: usize,
+}
+
+impl OpenAIEmbeddingClient {
+    pub fn new(api_key: String, model: String) -> Self {
+        let dimension = match model.as_str() {
+            "text-embedding-3-large" => 3072,
+            _ => 1536, // text-embedding-3-small / text-embedding-ada-002
+        };
+
+        Self {
+            api_key,
+            model,
+            base_url: OPENAI_API.to_string(),
+            client: Client::new(),
+            dimension,
+        }
+    }
+
+    /// 使用 OpenAI 兼容的其他服务地址
+    pub fn with_base_url(mut self, base_url: impl Into<String>) -> Self {
+        self.base_url = base_url.into();
+        self
+    }
+
+    /// 使用外部构建的 HTTP 客户端，可与其他客户端共享连接池
+    pub fn with_http_client(mut self, client: Client) -> Self {
+        self.client = client;
+        self
+    }
+}
+
+#[async_trait]
+impl EmbeddingClient for OpenAIEmbeddingClient {
+    async fn embed(&self, texts: Vec<String>) -> EmbeddingResult<Vec<Vec<f32>>> {
+        if texts.is_empty() {
+            return Err(EmbeddingError::Api("Input texts cannot be empty".to_string()));
+        }
+
+        let resp = self.client
+            .post(format!("{}/embeddings", self.base_url))
+            .bearer_auth(&self.api_key)
+            .json(&OpenAIRequest { model: &self.model, input: &texts })
+            .send()
+            .await
+            .map_err(|e| EmbeddingError::Network(e.to_string()))?;
+
+        let status = resp.status();
+        let resp_text = resp.text().await.map_err(|e| EmbeddingError::Network(e.to_string()))?;
+        if !status.is_success() {
+            return Err(api_error(status, &resp_text));
+        }
+
+        let value: serde_json::Value = serde_json::from_str(&resp_text)
+            .map_err(|e| EmbeddingError::InvalidResponse(e.to_string()))?;
+        let data = value.get("data")
+            .and_then(|d| d.as_array())
+            .ok_or_else(|| EmbeddingError::InvalidResponse("无法从响应中提取 embedding 数据".to_string()))?;
+
+        let embeds: Vec<(usize, Vec<f32>)> = data.iter()
+            .filter_map(|item| {
+                let index = item.get("index")?.as_u64()? as usize;
+                let embedding = item.get("embedding")?.as_array()?
+                    .iter()
+                    .filter_map(|v| v.as_f64().map(|f| f as f32))
+                    .collect();
+                Some((index, embedding))
+            })
+            .collect();
+        align_by_index(embeds, texts.len())
+    }
+
+    fn dimension(&self) -> usize {
+        self.dimension
+    }
+
+    fn model(&self) -> Option<&str> {
+        Some(&self.model)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use reqwest::StatusCode;
+    use wiremock::matchers::method;
+    use wiremock::{Mock, MockServer, ResponseTemplate};
+
+    fn openai_error(code: &str, message: &str) -> String {
+        serde_json::json!({ "error": { "message": message, "type": "invalid_request_error", "code": code } }).to_string()
+    }
+
+    #[test]
+    fn test_api_error() {
+        let error = api_error(StatusCode::UNAUTHORIZED, &openai_error("invalid_api_key", "Incorrect API key provided"));
+        assert!(matches!(error, EmbeddingError::Unauthorized(msg) if msg == "[invalid_api_key] Incorrect API key provided"));
+        assert!(matches!(api_error(StatusCode::TOO_MANY_REQUESTS, &openai_error("insufficient_quota", "quota")), EmbeddingError::QuotaExceeded(_)));
+        assert!(matches!(api_error(StatusCode::TOO_MANY_REQUESTS, "Too Many Requests"), EmbeddingError::QuotaExceeded(_)));
+        let null_code = r#"{"error":{"message":"quota","type":"insufficient_quota","code":null}}"#;
+        assert!(matches!(api_error(StatusCode::BAD_REQUEST, null_code), EmbeddingError::QuotaExceeded(_)));
+        assert!(matches!(api_error(StatusCode::SERVICE_UNAVAILABLE, "upstream down"), EmbeddingError::Server(msg) if msg.contains("upstream down")));
+        assert!(matches!(api_error(StatusCode::BAD_REQUEST, &openai_error("invalid_value", "bad input")), EmbeddingError::Api(_)));
+    }
+
+    #[tokio::test]
+    async fn test_validate_reports_unauthorized() {
+        let server = MockServer::start().await;
+        Mock::given(method("POST"))
+            .respond_with(ResponseTemplate::new(401).set_body_string(openai_error("invalid_api_key", "Incorrect API key provided")))
+            .mount(&server)
+            .await;
+
+        let client = OpenAIEmbeddingClient::new("bad-key".to_string(), "text-embedding-3-small".to_string())
+            .with_base_url(server.uri());
+        let result = client.validate().await;
+        assert!(matches!(result, Err(ref e @ EmbeddingError::Unauthorized(_)) if !e.is_retryable()));
+    }
+}
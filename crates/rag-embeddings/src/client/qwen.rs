@@ -1,7 +1,19 @@
-use crate::client::{EmbeddingClient, EmbeddingError, EmbeddingResult};
+use crate::client::{DEFAULT_NORMALIZATION_TOLERANCE, EmbeddingClient, EmbeddingError, EmbeddingResult, VALIDATION_PROBE, align_by_index, check_probe, is_normalized};
+use crate::client::batching::BatchLimits;
+use crate::client::rate_limit::RateLimiter;
+use crate::client::retry::{RetryPolicy, is_retryable_status};
 use async_trait::async_trait;
+use rag_indexing::tiktoken::count_tokens;
 use reqwest::Client;
 use serde::{Deserialize, Serialize};
+use std::io::Write;
+use std::sync::Arc;
+use std::time::Duration;
+
+use flate2::{Compression, write::GzEncoder};
+
+mod batch;
+pub use batch::{BatchJob, BatchStatus};
 
 #[derive(Serialize)]
 struct QwenRequest {
@@ -9,6 +21,8 @@ struct QwenRequest {
     input: Vec<String>,
     #[serde(skip_serializing_if = "Option::is_none")]
     task: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    dimensions: Option<usize>,
 }
 
 #[derive(Deserialize, Debug)]
@@ -22,14 +36,75 @@ struct ErrorResponse {
     error: DashScopeError,
 }
 
+/// 请求体小于该字节数时即使开启压缩也直接发送
+pub const COMPRESSION_MIN_BYTES: usize = 8 * 1024;
+
+/// gzip 压缩请求体
+fn gzip(body: &[u8]) -> std::io::Result<Vec<u8>> {
+    let mut encoder = GzEncoder::new(Vec::new(), Compression::fast());
+    encoder.write_all(body)?;
+    encoder.finish()
+}
+
+/// DashScope OpenAI 兼容接口地址
+pub const QWEN_COMPATIBLE_API: &str = "https://dashscope.aliyuncs.com/compatible-mode/v1";
+
+/// 嵌入待检索文档时的 task
+pub const DOCUMENT_TASK: &str = "retrieval.document";
+
+/// 嵌入检索查询时的 task，[`EmbeddingClient::embed_queries`] 总是使用
+pub const QUERY_TASK: &str = "retrieval.query";
+
+/// 将非 2xx 响应转换为 EmbeddingError，优先解析 DashScope 的错误结构
+///
+/// 限流与额度不足（如 `Throttling.*`、`Arrearage`、`insufficient_quota`、HTTP 429）归为 [`EmbeddingError::QuotaExceeded`]，
+/// API key 无效或无权访问（如 `InvalidApiKey`、`AccessDenied.*`、HTTP 401 / 403）归为 [`EmbeddingError::Unauthorized`]。
+/// 其余 5xx 归为 [`EmbeddingError::Server`]，4xx 归为 [`EmbeddingError::Api`]。
+fn api_error(status: reqwest::StatusCode, resp_text: &str) -> EmbeddingError {
+    let (code, message) = match serde_json::from_str::<ErrorResponse>(resp_text) {
+        Ok(err_resp) => {
+            let msg = err_resp.error.message.unwrap_or("Unknown error".to_string());
+            let code = err_resp.error.code.unwrap_or_default();
+            let message = format!("[{}] {}", code, msg);
+            (code.to_lowercase(), message)
+        }
+        Err(_) => (String::new(), format!("HTTP {}: {}", status, resp_text.trim())),
+    };
+
+    if code.starts_with("throttling") || code.contains("quota") || code == "arrearage" {
+        EmbeddingError::QuotaExceeded(message)
+    } else if code.contains("apikey") || code.contains("api_key") || code.starts_with("accessdenied") {
+        EmbeddingError::Unauthorized(message)
+    } else if status == reqwest::StatusCode::TOO_MANY_REQUESTS {
+        EmbeddingError::QuotaExceeded(message)
+    } else if status == reqwest::StatusCode::UNAUTHORIZED || status == reqwest::StatusCode::FORBIDDEN {
+        EmbeddingError::Unauthorized(message)
+    } else if status.is_server_error() {
+        EmbeddingError::Server(message)
+    } else {
+        EmbeddingError::Api(message)
+    }
+}
+
 pub struct QwenEmbeddingClient {
     api_key: String,
     model: String,
     task: Option<String>,
+    base_url: String,
     client: Client,
     dimension: usize,
     /// 是否启用归一化
     normalize: bool,
+    /// 共享限流器（可选）
+    rate_limiter: Option<Arc<RateLimiter>>,
+    /// 是否 gzip 压缩请求体
+    compress_requests: bool,
+    /// 单次请求的条数与 token 预算
+    batch_limits: BatchLimits,
+    /// 请求的输出维度（`dimensions` 参数），未设置时为模型默认维度
+    dimensions: Option<usize>,
+    /// 网络错误、限流与 5xx 时的重试策略（可选）
+    retry: Option<RetryPolicy>,
 }
 
 impl QwenEmbeddingClient {
@@ -45,14 +120,81 @@ impl QwenEmbeddingClient {
             api_key,
             model,
             task,
+            base_url: QWEN_COMPATIBLE_API.to_string(),
             client: Client::new(),
             dimension,
             normalize: true, // 启用归一化
+            rate_limiter: None,
+            compress_requests: false,
+            batch_limits: BatchLimits::default(),
+            dimensions: None,
+            retry: None,
         }
     }
 
+    /// 使用其他服务地址（如专属域名或本地模拟服务），默认 [`QWEN_COMPATIBLE_API`]
+    pub fn with_base_url(mut self, base_url: impl Into<String>) -> Self {
+        self.base_url = base_url.into();
+        self
+    }
+
+    /// 使用外部构建的 HTTP 客户端（如配置了代理、TLS 或超时），可与 LLM 等其他客户端共享连接池
+    pub fn with_http_client(mut self, client: Client) -> Self {
+        self.client = client;
+        self
+    }
+
+    /// 单次请求的最大输入条数，默认 [`DEFAULT_MAX_BATCH_SIZE`](crate::client::batching::DEFAULT_MAX_BATCH_SIZE)
+    pub fn with_max_batch_size(mut self, max_batch_size: usize) -> Self {
+        self.batch_limits = self.batch_limits.with_max_batch_size(max_batch_size);
+        self
+    }
+
+    /// 单次请求所有输入的 token 总数上限，默认 [`DEFAULT_MAX_BATCH_TOKENS`](crate::client::batching::DEFAULT_MAX_BATCH_TOKENS)
+    pub fn with_max_batch_tokens(mut self, max_batch_tokens: usize) -> Self {
+        self.batch_limits = self.batch_limits.with_max_batch_tokens(max_batch_tokens);
+        self
+    }
+
+    /// 开启后超过 [`COMPRESSION_MIN_BYTES`] 的请求体以 `Content-Encoding: gzip` 发送，默认关闭
+    ///
+    /// 大批量嵌入时可显著减少上传量；需服务端支持 gzip 请求体，开启前请先确认接口可正常返回。
+    pub fn with_request_compression(mut self, enabled: bool) -> Self {
+        self.compress_requests = enabled;
+        self
+    }
+
+    /// 使用共享限流器，每次请求前按请求数与 token 数等待额度
+    pub fn with_rate_limiter(mut self, rate_limiter: Arc<RateLimiter>) -> Self {
+        self.rate_limiter = Some(rate_limiter);
+        self
+    }
+
+    /// 请求降维后的向量（如 `text-embedding-v3` 支持 1024、768、512 等），减少存储并加快检索
+    ///
+    /// 请求中携带 `dimensions` 参数，[`dimension`](EmbeddingClient::dimension) 返回该值；
+    /// 返回的向量长度与之不符时报 [`EmbeddingError::InvalidResponse`]。模型是否支持该维度由服务端校验。
+    pub fn with_dimensions(mut self, dimensions: usize) -> Self {
+        self.dimensions = Some(dimensions);
+        self.dimension = dimensions;
+        self
+    }
+
+    /// 网络错误、HTTP 429 与 5xx 时按指数退避（带随机抖动）重试最多 `max_retries` 次，见 [`RetryPolicy`]
+    ///
+    /// 其余 4xx（如 API key 无效）不重试；重试耗尽后返回最后一次的错误。默认不重试。
+    pub fn with_retry(mut self, max_retries: u32, base_delay: Duration) -> Self {
+        self.retry = Some(RetryPolicy::new(max_retries, base_delay));
+        self
+    }
+
     pub fn for_text(api_key: String, model: String) -> Self {
-        Self::new(api_key, model, Some("retrieval.document".to_string()))
+        Self::new(api_key, model, Some(DOCUMENT_TASK.to_string()))
+    }
+
+    /// 用于嵌入检索查询；`for_text` 创建的客户端调用 [`embed_queries`](EmbeddingClient::embed_queries) 时同样以查询方式嵌入
+    pub fn for_query(api_key: String, model: String) -> Self {
+        Self::new(api_key, model, Some(QUERY_TASK.to_string()))
     }
     
     /// L2 归一化单个 embedding 向量
@@ -95,50 +237,74 @@ impl QwenEmbeddingClient {
     }
 
     /// 验证向量的归一化状态
-    /// 检查 L2 范数是否接近 1.0（容差 1e-6）
-    pub fn is_normalized(&self, embedding: &Vec<f32>) -> bool {
-        if embedding.is_empty() {
-            return false;
-        }
+    /// 检查 L2 范数是否接近 1.0（误差小于 tolerance）
+    pub fn is_normalized(&self, embedding: &[f32], tolerance: f32) -> bool {
+        is_normalized(embedding, tolerance)
+    }
 
-        let norm: f64 = embedding.iter()
-            .map(|&x| (x as f64).powi(2))
-            .sum::<f64>()
-            .sqrt();
+    /// 从响应 JSON 中提取 embedding（兼容 OpenAI 格式与达摩院原生格式），并逐个归一化
+    ///
+    /// `expected` 为请求的输入条数；按响应中的下标（`index` / `text_index`）还原输入顺序，见 [`align_by_index`]
+    fn parse_embeddings(&self, value: &serde_json::Value, expected: usize) -> EmbeddingResult<Vec<Vec<f32>>> {
+        // 根据实际响应结构提取 embeddings
+        let (items, index_key) = if let Some(items) = value.get("data").and_then(|d| d.as_array()) {
+            // OpenAI 兼容格式
+            (items, "index")
+        } else if let Some(items) = value.get("output")
+            .and_then(|o| o.get("embeddings"))
+            .and_then(|e| e.as_array())
+        {
+            // 达摩院原生格式
+            (items, "text_index")
+        } else {
+            return Err(EmbeddingError::InvalidResponse(
+                "无法从响应中提取 embedding 数据".to_string()
+            ));
+        };
 
-        let tolerance = 1e-6;
-        (norm - 1.0).abs() < tolerance
-    }
+        let mut embeds: Vec<(usize, Vec<f32>)> = Vec::new();
+        for (position, item) in items.iter().enumerate() {
+            let Some(embedding_array) = item.get("embedding").and_then(|e| e.as_array()) else {
+                continue;
+            };
+            // 没有下标时按出现顺序
+            let index = item.get(index_key).and_then(|i| i.as_u64()).map_or(position, |i| i as usize);
+            let mut embedding: Vec<f32> = embedding_array
+                .iter()
+                .filter_map(|v| v.as_f64().map(|f| f as f32))
+                .collect();
+            if let Some(dimensions) = self.dimensions
+                && embedding.len() != dimensions
+            {
+                return Err(EmbeddingError::InvalidResponse(format!(
+                    "请求 {} 维向量，下标 {} 返回了 {} 维", dimensions, index, embedding.len()
+                )));
+            }
 
-    /// 获取客户端配置信息
-    pub fn info(&self) -> String {
-        format!(
-            "QwenEmbeddingClient: model={}, dimension={}, normalize={}",
-            self.model, self.dimension, self.normalize
-        )
-    }
-}
+            // 立即归一化单个向量
+            self.normalize_embedding(&mut embedding)?;
 
-#[async_trait]
-impl EmbeddingClient for QwenEmbeddingClient {
-    async fn embed(&self, texts: Vec<String>) -> EmbeddingResult<Vec<Vec<f32>>> {
-        if texts.is_empty() {
-            return Err(EmbeddingError::Api("Input texts cannot be empty".to_string()));
+            embeds.push((index, embedding));
         }
+        align_by_index(embeds, expected)
+    }
 
-        let request = QwenRequest {
-            model: self.model.clone(),
-            input: texts.clone(),
-            task: self.task.clone(),
-        };
-
-        const QWEN_EMBEDDING_API: &str = "https://dashscope.aliyuncs.com/compatible-mode/v1/embeddings";
+    /// 发送一次请求，返回状态码与响应文本；只有网络错误时返回 `Err`
+    async fn send_request(&self, body: &[u8], compressed: bool, tokens: usize) -> EmbeddingResult<(reqwest::StatusCode, String)> {
+        if let Some(limiter) = &self.rate_limiter {
+            limiter.acquire(tokens).await;
+        }
 
-        let resp = self.client
-            .post(QWEN_EMBEDDING_API)
+        let mut builder = self.client
+            .post(format!("{}/embeddings", self.base_url))
             .header("Authorization", format!("Bearer {}", self.api_key))
-            .header("Content-Type", "application/json")
-            .json(&request)
+            .header("Content-Type", "application/json");
+        if compressed {
+            builder = builder.header("Content-Encoding", "gzip");
+        }
+
+        let resp = builder
+            .body(body.to_vec())
             .send()
             .await
             .map_err(|e| {
@@ -151,17 +317,69 @@ impl EmbeddingClient for QwenEmbeddingClient {
             println!("读取响应文本错误: {}", e);
             EmbeddingError::Network(e.to_string())
         })?;
+        Ok((status, resp_text))
+    }
 
-        if !status.is_success() {
-            println!("API 返回错误状态");
-            if let Ok(err_resp) = serde_json::from_str::<ErrorResponse>(&resp_text) {
-                let msg = err_resp.error.message.unwrap_or("Unknown error".to_string());
-                let code = err_resp.error.code.unwrap_or_default();
-                return Err(EmbeddingError::Api(format!("[{}] {}", code, msg)));
-            } else {
-                return Err(EmbeddingError::Api(format!("HTTP {}: {}", status, resp_text.trim())));
-            }
+    /// 构造请求体；同步请求与批量任务的每一行共用，两条路径生成的向量一致
+    fn build_request(&self, input: Vec<String>, task: Option<&str>) -> QwenRequest {
+        QwenRequest {
+            model: self.model.clone(),
+            input,
+            task: task.map(str::to_string),
+            dimensions: self.dimensions,
+        }
+    }
+
+    /// 按 [`BatchLimits`] 分批顺序请求，结果按输入顺序拼接
+    async fn embed_with_task(&self, texts: Vec<String>, task: Option<&str>) -> EmbeddingResult<Vec<Vec<f32>>> {
+        if texts.is_empty() {
+            return Err(EmbeddingError::Api("Input texts cannot be empty".to_string()));
+        }
+
+        let token_counts: Vec<usize> = texts.iter().map(|t| count_tokens(t, "qwen")).collect();
+        let batches = self.batch_limits.split(&token_counts);
+        if batches.len() == 1 {
+            return self.embed_batch(texts, token_counts.iter().sum(), task).await;
+        }
+
+        let mut vectors = Vec::with_capacity(texts.len());
+        for range in batches {
+            let tokens = token_counts[range.clone()].iter().sum();
+            vectors.extend(self.embed_batch(texts[range].to_vec(), tokens, task).await?);
         }
+        Ok(vectors)
+    }
+
+    /// 发送单个请求（按重试策略重试），`tokens` 为本批输入的 token 总数（用于限流）
+    async fn embed_batch(&self, texts: Vec<String>, tokens: usize, task: Option<&str>) -> EmbeddingResult<Vec<Vec<f32>>> {
+        let request = self.build_request(texts.clone(), task);
+
+        let mut body = serde_json::to_vec(&request)
+            .map_err(|e| EmbeddingError::InvalidResponse(e.to_string()))?;
+        let compressed = self.compress_requests && body.len() >= COMPRESSION_MIN_BYTES;
+        if compressed {
+            body = gzip(&body).map_err(|e| EmbeddingError::Network(e.to_string()))?;
+        }
+
+        let max_retries = self.retry.map_or(0, |retry| retry.max_retries);
+        let mut attempt = 0;
+        let resp_text = loop {
+            let (error, retryable) = match self.send_request(&body, compressed, tokens).await {
+                Ok((status, resp_text)) if status.is_success() => break resp_text,
+                Ok((status, resp_text)) => {
+                    println!("API 返回错误状态");
+                    (api_error(status, &resp_text), is_retryable_status(status))
+                }
+                Err(e) => (e, true),
+            };
+            let Some(retry) = self.retry.filter(|_| retryable && attempt < max_retries) else {
+                return Err(error);
+            };
+            let delay = retry.delay(attempt);
+            attempt += 1;
+            println!("embedding 请求失败，{:?} 后重试 ({}/{}): {}", delay, attempt, max_retries, error);
+            tokio::time::sleep(delay).await;
+        };
 
         // 使用 Value 来动态解析
         let value: serde_json::Value = serde_json::from_str(&resp_text)
@@ -172,62 +390,17 @@ impl EmbeddingClient for QwenEmbeddingClient {
 
         // println!("解析后的 JSON: {:#}", value);
 
-        // 根据实际响应结构提取 embeddings
-        let mut vectors: Vec<Vec<f32>> = if let Some(embeddings) = value.get("data").and_then(|d| d.as_array()) {
-            // OpenAI 兼容格式
-            let mut embeds: Vec<(usize, Vec<f32>)> = Vec::new();
-            for item in embeddings {
-                if let (Some(index), Some(embedding_array)) = (
-                    item.get("index").and_then(|i| i.as_u64()),
-                    item.get("embedding").and_then(|e| e.as_array()),
-                ) {
-                    let mut embedding: Vec<f32> = embedding_array
-                        .iter()
-                        .filter_map(|v| v.as_f64().map(|f| f as f32))
-                        .collect();
-                    
-                    // 立即归一化单个向量
-                    self.normalize_embedding(&mut embedding)?;
-                    
-                    embeds.push((index as usize, embedding));
-                }
-            }
-            embeds.sort_by_key(|(index, _)| *index);
-            embeds.into_iter().map(|(_, embedding)| embedding).collect()
-        } else if let Some(embeddings) = value.get("output")
-            .and_then(|o| o.get("embeddings"))
-            .and_then(|e| e.as_array()) 
-        {
-            // 达摩院原生格式
-            let mut embeds: Vec<Vec<f32>> = Vec::new();
-            for item in embeddings {
-                if let Some(embedding_array) = item.get("embedding").and_then(|e| e.as_array()) {
-                    let mut embedding: Vec<f32> = embedding_array
-                        .iter()
-                        .filter_map(|v| v.as_f64().map(|f| f as f32))
-                        .collect();
-                    
-                    // 立即归一化单个向量
-                    self.normalize_embedding(&mut embedding)?;
-                    
-                    embeds.push(embedding);
-                }
-            }
-            embeds
-        } else {
-            return Err(EmbeddingError::InvalidResponse(
-                "无法从响应中提取 embedding 数据".to_string()
-            ));
-        };
+        let mut vectors = self.parse_embeddings(&value, texts.len())?;
 
         // 确保所有向量都已归一化（冗余检查）
         self.normalize_vectors(&mut vectors)?;
 
         // 验证归一化结果
-        for (i, embedding) in vectors.iter().enumerate() {
-            if !self.is_normalized(embedding) {
-                println!("警告: 向量 {} 归一化失败，L2 范数: {:.6}", 
-                    i, embedding.iter().map(|&x| x as f64 * x as f64).sum::<f64>().sqrt());
+        if self.normalize
+            && let Err(failed) = self.verify_batch_normalized(&vectors, DEFAULT_NORMALIZATION_TOLERANCE)
+        {
+            for (i, norm) in failed {
+                println!("警告: 向量 {} 归一化失败，L2 范数: {:.6}", i, norm);
             }
         }
 
@@ -236,59 +409,314 @@ impl EmbeddingClient for QwenEmbeddingClient {
         Ok(vectors)
     }
 
+    /// 获取客户端配置信息
+    pub fn info(&self) -> String {
+        format!(
+            "QwenEmbeddingClient: model={}, dimension={}, normalize={}",
+            self.model, self.dimension, self.normalize
+        )
+    }
+}
+
+#[async_trait]
+impl EmbeddingClient for QwenEmbeddingClient {
+    /// 按 [`BatchLimits`] 分批顺序请求，结果按输入顺序拼接；使用客户端配置的 task
+    async fn embed(&self, texts: Vec<String>) -> EmbeddingResult<Vec<Vec<f32>>> {
+        self.embed_with_task(texts, self.task.as_deref()).await
+    }
+
+    /// 不论客户端配置的 task，总是以 [`QUERY_TASK`] 请求
+    async fn embed_queries(&self, texts: Vec<String>) -> EmbeddingResult<Vec<Vec<f32>>> {
+        self.embed_with_task(texts, Some(QUERY_TASK)).await
+    }
+
     fn dimension(&self) -> usize {
         self.dimension
     }
+
+    fn model(&self) -> Option<&str> {
+        Some(&self.model)
+    }
+
+    /// 同默认实现，鉴权与额度错误附带模型名与处理建议
+    async fn validate(&self) -> EmbeddingResult<()> {
+        let vectors = self.embed(vec![VALIDATION_PROBE.to_string()]).await.map_err(|e| match e {
+            EmbeddingError::Unauthorized(msg) => EmbeddingError::Unauthorized(format!(
+                "DashScope API key 无效或无权访问模型 {}，请检查 DASHSCOPE_API_KEY: {}", self.model, msg
+            )),
+            EmbeddingError::QuotaExceeded(msg) => EmbeddingError::QuotaExceeded(format!(
+                "DashScope 调用被限流或额度不足（模型 {}），请稍后重试或检查账户余额: {}", self.model, msg
+            )),
+            other => other,
+        })?;
+        check_probe(&vectors, self.dimension)
+    }
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
-    use tokio;
-    use dotenv::dotenv;
+    use crate::client::mock_server::{EmbeddingsResponder, TEST_API_KEY, embeddings_server, error_response, fake_embedding};
     use anyhow::Result;
+    use wiremock::matchers::method;
+    use wiremock::{Mock, MockServer};
+
+    #[test]
+    fn test_gzip_request_body() -> Result<()> {
+        use std::io::Read;
+
+        let request = QwenRequest {
+            model: "text-embedding-v1".to_string(),
+            input: vec!["大语言模型".repeat(1000)],
+            task: None,
+            dimensions: None,
+        };
+        let body = serde_json::to_vec(&request)?;
+        assert!(body.len() >= COMPRESSION_MIN_BYTES);
+
+        let compressed = gzip(&body)?;
+        assert!(compressed.len() < body.len() / 10);
+
+        let mut decoded = Vec::new();
+        flate2::read::GzDecoder::new(compressed.as_slice()).read_to_end(&mut decoded)?;
+        assert_eq!(decoded, body);
+        Ok(())
+    }
+
+    #[test]
+    fn test_api_error_kinds() {
+        use reqwest::StatusCode;
+
+        let body = |code: &str| format!(r#"{{"error": {{"code": "{}", "message": "msg"}}}}"#, code);
+        assert!(matches!(api_error(StatusCode::UNAUTHORIZED, &body("InvalidApiKey")), EmbeddingError::Unauthorized(_)));
+        assert!(matches!(api_error(StatusCode::BAD_REQUEST, &body("invalid_api_key")), EmbeddingError::Unauthorized(_)));
+        assert!(matches!(api_error(StatusCode::FORBIDDEN, &body("AccessDenied.Unpurchased")), EmbeddingError::Unauthorized(_)));
+        assert!(matches!(api_error(StatusCode::TOO_MANY_REQUESTS, &body("Throttling.RateQuota")), EmbeddingError::QuotaExceeded(_)));
+        assert!(matches!(api_error(StatusCode::BAD_REQUEST, &body("Arrearage")), EmbeddingError::QuotaExceeded(_)));
+        assert!(matches!(api_error(StatusCode::TOO_MANY_REQUESTS, "too many requests"), EmbeddingError::QuotaExceeded(_)));
+        assert!(matches!(api_error(StatusCode::UNAUTHORIZED, "unauthorized"), EmbeddingError::Unauthorized(_)));
+
+        match api_error(StatusCode::BAD_REQUEST, &body("InvalidParameter")) {
+            EmbeddingError::Api(msg) => assert_eq!(msg, "[InvalidParameter] msg"),
+            other => panic!("unexpected error: {:?}", other),
+        }
+    }
+
+    fn mock_client(server: &MockServer) -> QwenEmbeddingClient {
+        QwenEmbeddingClient::for_text(TEST_API_KEY.to_string(), "text-embedding-v1".to_string())
+            .with_base_url(server.uri())
+    }
+
+    /// 客户端返回的向量即 `text` 的 [`fake_embedding`] 归一化后的结果
+    fn assert_matches_fake(embedding: &[f32], text: &str) {
+        let mut expected = fake_embedding(text, embedding.len());
+        crate::client::renormalize(&mut expected);
+        assert!(embedding.iter().zip(&expected).all(|(a, b)| (a - b).abs() < 1e-6), "向量与文本 {:?} 不对应", text);
+    }
 
     #[tokio::test]
     async fn test_embed() -> Result<()> {
-        dotenv().ok();
-        let api_key = std::env::var("DASHSCOPE_API_KEY")
-            .expect("请设置环境变量 DASHSCOPE_API_KEY 或在 .env 文件中配置");
-        
-        let client = QwenEmbeddingClient::for_text(api_key, "text-embedding-v1".to_string());
+        let server = embeddings_server(1536).await;
+        let client = mock_client(&server);
         let texts = vec!["Hello, world!".to_string(), "Rust is awesome!".to_string()];
-        
+
         println!("客户端信息: {}", client.info());
-        
+
         let embeddings = client.embed(texts.clone()).await?;
-        
-        println!("生成了 {} 个 embedding，向量维度: {}", embeddings.len(), embeddings[0].len());
-        
-        // 验证每个向量的维度
-        for (i, embedding) in embeddings.iter().enumerate() {
+        assert_eq!(embeddings.len(), texts.len());
+
+        for (i, (embedding, text)) in embeddings.iter().zip(&texts).enumerate() {
             assert_eq!(embedding.len(), client.dimension(), "向量 {} 维度不匹配", i);
-            
-            // 验证归一化
-            let is_norm = client.is_normalized(embedding);
-            let norm = embedding.iter().map(|&x| x as f64 * x as f64).sum::<f64>().sqrt();
-            
-            println!("向量 {}: 维度={}, 归一化={}, L2范数={:.8}", 
-                i, embedding.len(), is_norm, norm);
-            
-            assert!(is_norm, "向量 {} 未正确归一化", i);
-            assert!((norm - 1.0).abs() < 1e-6, "向量 {} L2 范数没有在正确的范围", i);
+            assert!(client.is_normalized(embedding, DEFAULT_NORMALIZATION_TOLERANCE), "向量 {} 未正确归一化", i);
+
+            // 服务端倒序返回，结果仍按输入顺序
+            assert_matches_fake(embedding, text);
         }
-        
-        println!("✅ 所有测试通过！");
+
+        let requests = server.received_requests().await.unwrap_or_default();
+        assert_eq!(requests.len(), 1);
+        assert_eq!(requests[0].headers["authorization"], format!("Bearer {}", TEST_API_KEY).as_str());
+        let body: serde_json::Value = requests[0].body_json()?;
+        assert_eq!(body["model"], "text-embedding-v1");
+        assert_eq!(body["task"], "retrieval.document");
+        assert_eq!(body["input"], serde_json::json!(texts));
         Ok(())
     }
 
+    #[tokio::test]
+    async fn test_embed_splits_batches() -> Result<()> {
+        let server = embeddings_server(4).await;
+        let client = mock_client(&server).with_max_batch_size(25);
+        let texts: Vec<String> = (1..=60).map(|i| "字".repeat(i)).collect();
+
+        let embeddings = client.embed(texts.clone()).await?;
+        assert_eq!(embeddings.len(), texts.len());
+        let requests = server.received_requests().await.unwrap_or_default();
+        let batch_sizes = requests.iter()
+            .map(|request| Ok(request.body_json::<serde_json::Value>()?["input"].as_array().map_or(0, Vec::len)))
+            .collect::<Result<Vec<_>>>()?;
+        assert_eq!(batch_sizes, vec![25, 25, 10]);
+        // 每批内服务端倒序返回，拼接结果仍按输入顺序
+        for (embedding, text) in embeddings.iter().zip(&texts) {
+            assert_matches_fake(embedding, text);
+        }
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_embed_api_errors() {
+        let server = MockServer::start().await;
+        Mock::given(method("POST"))
+            .respond_with(error_response(401, "InvalidApiKey", "Invalid API-key provided."))
+            .up_to_n_times(1)
+            .mount(&server)
+            .await;
+        Mock::given(method("POST"))
+            .respond_with(error_response(429, "Throttling.RateQuota", "Requests rate limit exceeded"))
+            .mount(&server)
+            .await;
+
+        let client = mock_client(&server);
+        let result = client.validate().await;
+        assert!(matches!(result, Err(EmbeddingError::Unauthorized(msg)) if msg.contains("text-embedding-v1")));
+        let result = client.embed(vec!["a".to_string()]).await;
+        assert!(matches!(result, Err(EmbeddingError::QuotaExceeded(_))));
+    }
+
+    #[tokio::test]
+    async fn test_embed_retries_transient_errors() -> Result<()> {
+        let server = MockServer::start().await;
+        Mock::given(method("POST"))
+            .respond_with(error_response(503, "ServiceUnavailable", "Service is busy"))
+            .up_to_n_times(1)
+            .mount(&server)
+            .await;
+
+        // 不重试时第一次失败即返回
+        let result = mock_client(&server).embed(vec!["a".to_string()]).await;
+        assert!(matches!(result, Err(EmbeddingError::Server(msg)) if msg.contains("ServiceUnavailable")));
+
+        server.reset().await;
+        Mock::given(method("POST"))
+            .respond_with(error_response(503, "ServiceUnavailable", "Service is busy"))
+            .up_to_n_times(2)
+            .mount(&server)
+            .await;
+        Mock::given(method("POST"))
+            .respond_with(EmbeddingsResponder { dimension: 4 })
+            .mount(&server)
+            .await;
+        let client = mock_client(&server).with_retry(3, Duration::from_millis(1));
+        let embeddings = client.embed(vec!["a".to_string()]).await?;
+        assert_matches_fake(&embeddings[0], "a");
+        assert_eq!(server.received_requests().await.unwrap_or_default().len(), 3);
+
+        // 重试耗尽后返回最后一次的错误
+        server.reset().await;
+        Mock::given(method("POST"))
+            .respond_with(error_response(429, "Throttling.RateQuota", "Requests rate limit exceeded"))
+            .mount(&server)
+            .await;
+        let result = mock_client(&server).with_retry(2, Duration::from_millis(1)).embed(vec!["a".to_string()]).await;
+        assert!(matches!(result, Err(EmbeddingError::QuotaExceeded(_))));
+        assert_eq!(server.received_requests().await.unwrap_or_default().len(), 3);
+
+        // 鉴权失败不重试
+        server.reset().await;
+        Mock::given(method("POST"))
+            .respond_with(error_response(401, "InvalidApiKey", "Invalid API-key provided."))
+            .mount(&server)
+            .await;
+        let result = mock_client(&server).with_retry(2, Duration::from_millis(1)).embed(vec!["a".to_string()]).await;
+        assert!(matches!(result, Err(EmbeddingError::Unauthorized(_))));
+        assert_eq!(server.received_requests().await.unwrap_or_default().len(), 1);
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_embed_queries_uses_query_task() -> Result<()> {
+        let server = embeddings_server(4).await;
+        let client = mock_client(&server);
+        client.embed(vec!["所有权".to_string()]).await?;
+        client.embed_queries(vec!["什么是所有权".to_string()]).await?;
+        let untasked = QwenEmbeddingClient::new(TEST_API_KEY.to_string(), "text-embedding-v1".to_string(), None)
+            .with_base_url(server.uri());
+        untasked.embed(vec!["所有权".to_string()]).await?;
+        untasked.embed_queries(vec!["什么是所有权".to_string()]).await?;
+
+        let bodies: Vec<serde_json::Value> = server.received_requests().await.unwrap_or_default()
+            .iter()
+            .map(|request| request.body_json())
+            .collect::<Result<_, _>>()?;
+        assert_eq!(bodies[0]["task"], DOCUMENT_TASK);
+        assert_eq!(bodies[1]["task"], QUERY_TASK);
+        assert_eq!(bodies[1]["input"], serde_json::json!(["什么是所有权"]));
+        assert!(bodies[2].get("task").is_none());
+        assert_eq!(bodies[3]["task"], QUERY_TASK);
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_embed_reduced_dimensions() -> Result<()> {
+        let server = embeddings_server(2560).await;
+        let client = QwenEmbeddingClient::for_text(TEST_API_KEY.to_string(), "text-embedding-v3".to_string())
+            .with_base_url(server.uri())
+            .with_dimensions(1024);
+        assert_eq!(client.dimension(), 1024);
+
+        let embeddings = client.embed(vec!["所有权".to_string()]).await?;
+        assert_eq!(embeddings[0].len(), 1024);
+        assert_matches_fake(&embeddings[0], "所有权");
+        let body: serde_json::Value = server.received_requests().await.unwrap_or_default()[0].body_json()?;
+        assert_eq!(body["dimensions"], 1024);
+
+        // 未设置时不发送 dimensions
+        let client = QwenEmbeddingClient::for_text(TEST_API_KEY.to_string(), "text-embedding-v3".to_string())
+            .with_base_url(server.uri());
+        assert_eq!(client.embed(vec!["所有权".to_string()]).await?[0].len(), 2560);
+        let body: serde_json::Value = server.received_requests().await.unwrap_or_default()[1].body_json()?;
+        assert!(body.get("dimensions").is_none());
+
+        // 服务端忽略了 dimensions 参数
+        let server = MockServer::start().await;
+        Mock::given(method("POST"))
+            .respond_with(wiremock::ResponseTemplate::new(200).set_body_json(serde_json::json!({
+                "data": [{ "index": 0, "embedding": fake_embedding("a", 2560) }],
+            })))
+            .mount(&server)
+            .await;
+        let result = mock_client(&server).with_dimensions(1024).embed(vec!["a".to_string()]).await;
+        assert!(matches!(&result, Err(EmbeddingError::InvalidResponse(msg)) if msg.contains("1024")), "{:?}", result);
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_embed_gapped_response() {
+        // 服务端跳过了第 2 条（空字符串）输入，只返回下标 0 与 2
+        let server = MockServer::start().await;
+        Mock::given(method("POST"))
+            .respond_with(wiremock::ResponseTemplate::new(200).set_body_json(serde_json::json!({
+                "data": [
+                    { "index": 2, "embedding": fake_embedding("c", 4) },
+                    { "index": 0, "embedding": fake_embedding("a", 4) },
+                ],
+            })))
+            .mount(&server)
+            .await;
+
+        let client = mock_client(&server);
+        let result = client.embed(vec!["a".to_string(), String::new(), "c".to_string()]).await;
+        assert!(matches!(&result, Err(EmbeddingError::InvalidResponse(msg)) if msg.contains("[1]")), "{:?}", result);
+
+        assert!(matches!(align_by_index(vec![(0, vec![1.0]), (0, vec![1.0])], 2), Err(EmbeddingError::InvalidResponse(_))));
+        assert!(matches!(align_by_index(vec![(3, vec![1.0])], 1), Err(EmbeddingError::InvalidResponse(_))));
+        assert_eq!(align_by_index(vec![(1, vec![2.0]), (0, vec![1.0])], 2).unwrap(), vec![vec![1.0], vec![2.0]]);
+    }
+
     #[tokio::test]
     async fn test_empty_input() {
-        dotenv().ok();
-        let api_key = std::env::var("DASHSCOPE_API_KEY")
-            .expect("请设置环境变量 DASHSCOPE_API_KEY 或在 .env 文件中配置");
-        let client = QwenEmbeddingClient::for_text(api_key, "text-embedding-v1".to_string());
-        
+        let client = QwenEmbeddingClient::for_text(TEST_API_KEY.to_string(), "text-embedding-v1".to_string());
+
         let result = client.embed(vec![]).await;
         assert!(result.is_err());
         if let Err(EmbeddingError::Api(msg)) = result {
@@ -300,17 +728,14 @@ mod tests {
 
     #[tokio::test]
     async fn test_zero_vector_normalization() {
-        dotenv().ok();
-        let api_key = std::env::var("DASHSCOPE_API_KEY")
-            .expect("请设置环境变量 DASHSCOPE_API_KEY 或在 .env 文件中配置");
-        let client = QwenEmbeddingClient::for_text(api_key, "text-embedding-v1".to_string());
-        
+        let client = QwenEmbeddingClient::for_text(TEST_API_KEY.to_string(), "text-embedding-v1".to_string());
+
         let mut zero_vector = vec![0.0f32; 1536];
         let result = client.normalize_embedding(&mut zero_vector);
-        
+
         assert!(result.is_err());
         if let Err(EmbeddingError::InvalidVector(msg)) = result {
             assert!(msg.contains("Zero vector"));
         }
     }
-}
\ No newline at end of file
+}
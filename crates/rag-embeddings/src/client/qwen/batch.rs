@@ -0,0 +1,316 @@
+//! DashScope 批量（异步）embedding 任务
+//!
+//! 基于 OpenAI 兼容的 Batch 接口：上传 JSONL 输入文件 → 创建任务 → 轮询状态 → 下载结果文件。
+//! 费用低于同步调用，适合数万条文本的离线入库。`BatchJob` 可序列化，长时间运行的入库流程
+//! 可以保存任务信息，在中断后重新轮询并下载结果，而不必保持一个 HTTP 请求。
+
+use std::time::Duration;
+
+use reqwest::multipart::{Form, Part};
+use serde::{Deserialize, Serialize};
+
+use super::{QwenEmbeddingClient, QwenRequest, api_error};
+use crate::client::{EmbeddingError, EmbeddingResult};
+
+/// 批量任务状态（与 OpenAI Batch 接口一致）
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum BatchStatus {
+    Validating,
+    InProgress,
+    Finalizing,
+    Completed,
+    Failed,
+    Expired,
+    Cancelling,
+    Cancelled,
+}
+
+impl BatchStatus {
+    /// 是否为终止状态（不会再变化）
+    pub fn is_terminal(&self) -> bool {
+        matches!(self, Self::Completed | Self::Failed | Self::Expired | Self::Cancelled)
+    }
+}
+
+/// 已提交的批量任务，可持久化用于断点续跑
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct BatchJob {
+    pub id: String,
+    pub status: BatchStatus,
+    pub input_file_id: String,
+    pub output_file_id: Option<String>,
+    pub error_file_id: Option<String>,
+    /// 提交的文本条数
+    pub total: usize,
+}
+
+#[derive(Deserialize)]
+struct FileObject {
+    id: String,
+}
+
+#[derive(Deserialize)]
+struct BatchObject {
+    id: String,
+    status: BatchStatus,
+    input_file_id: String,
+    output_file_id: Option<String>,
+    error_file_id: Option<String>,
+}
+
+#[derive(Serialize)]
+struct BatchLine {
+    custom_id: String,
+    method: &'static str,
+    url: &'static str,
+    body: QwenRequest,
+}
+
+impl QwenEmbeddingClient {
+    /// 提交批量 embedding 任务：上传输入文件并创建任务
+    pub async fn submit_batch_job(&self, texts: &[String]) -> EmbeddingResult<BatchJob> {
+        if texts.is_empty() {
+            return Err(EmbeddingError::Api("Input texts cannot be empty".to_string()));
+        }
+
+        let input = self.build_batch_input(texts)?;
+        let form = Form::new()
+            .text("purpose", "batch")
+            .part(
+                "file",
+                Part::bytes(input.into_bytes())
+                    .file_name("embedding_batch.jsonl")
+                    .mime_str("application/jsonl")
+                    .map_err(|e| EmbeddingError::Network(e.to_string()))?,
+            );
+
+        let resp = self.client
+            .post(format!("{}/files", self.base_url))
+            .header("Authorization", format!("Bearer {}", self.api_key))
+            .multipart(form)
+            .send()
+            .await
+            .map_err(|e| EmbeddingError::Network(e.to_string()))?;
+        let file: FileObject = Self::read_json(resp).await?;
+
+        let resp = self.client
+            .post(format!("{}/batches", self.base_url))
+            .header("Authorization", format!("Bearer {}", self.api_key))
+            .json(&serde_json::json!({
+                "input_file_id": file.id,
+                "endpoint": "/v1/embeddings",
+                "completion_window": "24h",
+            }))
+            .send()
+            .await
+            .map_err(|e| EmbeddingError::Network(e.to_string()))?;
+        let batch: BatchObject = Self::read_json(resp).await?;
+
+        println!("📤 已提交批量 embedding 任务 {}（{} 条文本）", batch.id, texts.len());
+        Ok(Self::to_job(batch, texts.len()))
+    }
+
+    /// 查询任务最新状态
+    pub async fn poll_batch_job(&self, job: &BatchJob) -> EmbeddingResult<BatchJob> {
+        let resp = self.client
+            .get(format!("{}/batches/{}", self.base_url, job.id))
+            .header("Authorization", format!("Bearer {}", self.api_key))
+            .send()
+            .await
+            .map_err(|e| EmbeddingError::Network(e.to_string()))?;
+        let batch: BatchObject = Self::read_json(resp).await?;
+
+        Ok(Self::to_job(batch, job.total))
+    }
+
+    /// 按 `poll_interval` 轮询，直到任务进入终止状态
+    pub async fn wait_batch_job(&self, job: &BatchJob, poll_interval: Duration) -> EmbeddingResult<BatchJob> {
+        let mut job = job.clone();
+        while !job.status.is_terminal() {
+            tokio::time::sleep(poll_interval).await;
+            job = self.poll_batch_job(&job).await?;
+        }
+        Ok(job)
+    }
+
+    /// 下载已完成任务的结果，按输入顺序返回归一化后的向量
+    pub async fn fetch_batch_results(&self, job: &BatchJob) -> EmbeddingResult<Vec<Vec<f32>>> {
+        if job.status != BatchStatus::Completed {
+            return Err(EmbeddingError::Api(format!(
+                "批量任务 {} 未成功完成，当前状态: {:?}", job.id, job.status
+            )));
+        }
+        let output_file_id = job.output_file_id.as_deref().ok_or_else(|| {
+            EmbeddingError::InvalidResponse(format!("批量任务 {} 没有结果文件", job.id))
+        })?;
+
+        let resp = self.client
+            .get(format!("{}/files/{}/content", self.base_url, output_file_id))
+            .header("Authorization", format!("Bearer {}", self.api_key))
+            .send()
+            .await
+            .map_err(|e| EmbeddingError::Network(e.to_string()))?;
+        let status = resp.status();
+        let content = resp.text().await.map_err(|e| EmbeddingError::Network(e.to_string()))?;
+        if !status.is_success() {
+            return Err(api_error(status, &content));
+        }
+
+        self.parse_batch_output(&content, job)
+    }
+
+    /// 批量异步 embedding：提交任务、轮询直到完成并下载结果
+    ///
+    /// 语义与 `EmbeddingClient::embed` 一致（同序、已归一化），但适合数万条文本；
+    /// 需要断点续跑时请分别调用 `submit_batch_job` / `wait_batch_job` / `fetch_batch_results`
+    pub async fn embed_batch_async(&self, texts: Vec<String>, poll_interval: Duration) -> EmbeddingResult<Vec<Vec<f32>>> {
+        let job = self.submit_batch_job(&texts).await?;
+        let job = self.wait_batch_job(&job, poll_interval).await?;
+        self.fetch_batch_results(&job).await
+    }
+
+    /// 每条文本一行请求，custom_id 为其在输入中的下标；请求体与同步接口相同（含 `task`、`dimensions`）
+    fn build_batch_input(&self, texts: &[String]) -> EmbeddingResult<String> {
+        let mut input = String::new();
+        for (i, text) in texts.iter().enumerate() {
+            let line = BatchLine {
+                custom_id: i.to_string(),
+                method: "POST",
+                url: "/v1/embeddings",
+                body: self.build_request(vec![text.clone()], self.task.as_deref()),
+            };
+            let json = serde_json::to_string(&line)
+                .map_err(|e| EmbeddingError::InvalidResponse(e.to_string()))?;
+            input.push_str(&json);
+            input.push('\n');
+        }
+        Ok(input)
+    }
+
+    /// 解析结果文件（JSONL），按 custom_id 还原输入顺序；任何一条缺失或失败都会报错
+    fn parse_batch_output(&self, content: &str, job: &BatchJob) -> EmbeddingResult<Vec<Vec<f32>>> {
+        let mut vectors: Vec<Option<Vec<f32>>> = vec![None; job.total];
+
+        for line in content.lines().filter(|l| !l.trim().is_empty()) {
+            let value: serde_json::Value = serde_json::from_str(line)
+                .map_err(|e| EmbeddingError::InvalidResponse(e.to_string()))?;
+
+            let index = value.get("custom_id")
+                .and_then(|id| id.as_str())
+                .and_then(|id| id.parse::<usize>().ok())
+                .filter(|&i| i < job.total)
+                .ok_or_else(|| EmbeddingError::InvalidResponse(format!("无效的 custom_id: {}", line)))?;
+
+            let body = value.get("response")
+                .and_then(|r| r.get("body"))
+                .ok_or_else(|| EmbeddingError::Api(format!(
+                    "第 {} 条请求失败: {}", index, value.get("error").unwrap_or(&serde_json::Value::Null)
+                )))?;
+
+            vectors[index] = self.parse_embeddings(body, 1)?.into_iter().next();
+        }
+
+        let missing: Vec<usize> = vectors.iter()
+            .enumerate()
+            .filter(|(_, v)| v.is_none())
+            .map(|(i, _)| i)
+            .collect();
+        if !missing.is_empty() {
+            return Err(EmbeddingError::InvalidResponse(format!(
+                "批量任务 {} 缺少 {} 条结果（下标: {:?}），错误文件: {:?}",
+                job.id, missing.len(), missing, job.error_file_id
+            )));
+        }
+
+        Ok(vectors.into_iter().flatten().collect())
+    }
+
+    async fn read_json<T: serde::de::DeserializeOwned>(resp: reqwest::Response) -> EmbeddingResult<T> {
+        let status = resp.status();
+        let text = resp.text().await.map_err(|e| EmbeddingError::Network(e.to_string()))?;
+        if !status.is_success() {
+            return Err(api_error(status, &text));
+        }
+        serde_json::from_str(&text).map_err(|e| EmbeddingError::InvalidResponse(format!("{}: {}", e, text)))
+    }
+
+    fn to_job(batch: BatchObject, total: usize) -> BatchJob {
+        BatchJob {
+            id: batch.id,
+            status: batch.status,
+            input_file_id: batch.input_file_id,
+            output_file_id: batch.output_file_id,
+            error_file_id: batch.error_file_id,
+            total,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn job(total: usize) -> BatchJob {
+        BatchJob {
+            id: "batch_test".to_string(),
+            status: BatchStatus::Completed,
+            input_file_id: "file-in".to_string(),
+            output_file_id: Some("file-out".to_string()),
+            error_file_id: None,
+            total,
+        }
+    }
+
+    fn output_line(custom_id: usize, embedding: &[f32]) -> String {
+        serde_json::json!({
+            "custom_id": custom_id.to_string(),
+            "response": {
+                "status_code": 200,
+                "body": { "data": [{ "index": 0, "embedding": embedding }] }
+            },
+            "error": null
+        }).to_string()
+    }
+
+    #[test]
+    fn test_build_batch_input() -> anyhow::Result<()> {
+        let client = QwenEmbeddingClient::for_text("test-key".to_string(), "text-embedding-v3".to_string())
+            .with_dimensions(1024);
+        let input = client.build_batch_input(&["你好".to_string(), "world".to_string()])?;
+
+        let lines: Vec<serde_json::Value> = input.lines()
+            .map(serde_json::from_str)
+            .collect::<Result<_, _>>()?;
+        assert_eq!(lines.len(), 2);
+        assert_eq!(lines[1]["custom_id"], "1");
+        assert_eq!(lines[1]["url"], "/v1/embeddings");
+        assert_eq!(lines[0]["body"]["model"], "text-embedding-v3");
+        assert_eq!(lines[0]["body"]["input"], serde_json::json!(["你好"]));
+        // 与同步请求一致
+        assert_eq!(lines[0]["body"]["task"], "retrieval.document");
+        assert_eq!(lines[0]["body"]["dimensions"], 1024);
+        Ok(())
+    }
+
+    #[test]
+    fn test_parse_batch_output_restores_order() {
+        let client = QwenEmbeddingClient::for_text("test-key".to_string(), "text-embedding-v1".to_string());
+        // 结果文件中的顺序与输入顺序无关
+        let content = [output_line(1, &[0.0, 2.0]), output_line(0, &[3.0, 0.0])].join("\n");
+
+        let vectors = client.parse_batch_output(&content, &job(2)).unwrap();
+        assert_eq!(vectors, vec![vec![1.0, 0.0], vec![0.0, 1.0]]);
+    }
+
+    #[test]
+    fn test_parse_batch_output_reports_missing() {
+        let client = QwenEmbeddingClient::for_text("test-key".to_string(), "text-embedding-v1".to_string());
+        let content = output_line(0, &[1.0, 0.0]);
+
+        match client.parse_batch_output(&content, &job(3)) {
+            Err(EmbeddingError::InvalidResponse(msg)) => assert!(msg.contains("[1, 2]"), "{}", msg),
+            other => panic!("Expected missing results error, got {:?}", other),
+        }
+    }
+}
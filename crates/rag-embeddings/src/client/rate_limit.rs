@@ -0,0 +1,148 @@
+use std::time::{Duration, Instant};
+
+use tokio::sync::Mutex;
+
+/// 令牌桶限流器，同时限制每分钟请求数（RPM）与每分钟 token 数（TPM）
+///
+/// 通过 `Arc<RateLimiter>` 在多个客户端 / 并发任务之间共享，避免各自重试时集中触发 429。
+/// 每次 `acquire` 立即预占额度（允许透支），再在锁外等待到额度恢复，因此按调用顺序排队。
+/// RPM 或 TPM 为 0 表示不限制该项。
+pub struct RateLimiter {
+    requests_per_minute: u32,
+    tokens_per_minute: u32,
+    state: Mutex<Bucket>,
+}
+
+struct Bucket {
+    requests: f64,
+    tokens: f64,
+    last_refill: Instant,
+}
+
+impl RateLimiter {
+    pub fn new(requests_per_minute: u32, tokens_per_minute: u32) -> Self {
+        Self {
+            requests_per_minute,
+            tokens_per_minute,
+            state: Mutex::new(Bucket {
+                requests: requests_per_minute as f64,
+                tokens: tokens_per_minute as f64,
+                last_refill: Instant::now(),
+            }),
+        }
+    }
+
+    /// 等待直到可以发送一个消耗 `tokens` 个 token 的请求
+    pub async fn acquire(&self, tokens: usize) {
+        let wait = {
+            let mut bucket = self.state.lock().await;
+            self.reserve(&mut bucket, Instant::now(), tokens)
+        };
+        if !wait.is_zero() {
+            tokio::time::sleep(wait).await;
+        }
+    }
+
+    /// 按经过的时间补充额度后扣除本次请求，返回需要等待的时长
+    fn reserve(&self, bucket: &mut Bucket, now: Instant, tokens: usize) -> Duration {
+        let elapsed = now.saturating_duration_since(bucket.last_refill).as_secs_f64();
+        bucket.last_refill = now;
+
+        let request_wait = take(&mut bucket.requests, self.requests_per_minute, elapsed, 1.0);
+        let token_wait = take(&mut bucket.tokens, self.tokens_per_minute, elapsed, tokens as f64);
+        Duration::from_secs_f64(request_wait.max(token_wait))
+    }
+}
+
+/// 按经过的秒数补充一项额度并扣除 `amount`，返回额度恢复前需要等待的秒数；`per_minute` 为 0 时不限制
+fn take(available: &mut f64, per_minute: u32, elapsed: f64, amount: f64) -> f64 {
+    if per_minute == 0 {
+        return 0.0;
+    }
+    let per_minute = per_minute as f64;
+    let rate = per_minute / 60.0;
+    *available = (*available + elapsed * rate).min(per_minute);
+    // 单次请求超过整分钟额度时按满额计算，否则永远无法发出
+    *available -= amount.min(per_minute);
+    if *available < 0.0 { -*available / rate } else { 0.0 }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::Arc;
+
+    fn bucket(limiter: &RateLimiter, now: Instant) -> Bucket {
+        Bucket {
+            requests: limiter.requests_per_minute as f64,
+            tokens: limiter.tokens_per_minute as f64,
+            last_refill: now,
+        }
+    }
+
+    #[test]
+    fn test_request_quota() {
+        let limiter = RateLimiter::new(2, 1_000_000);
+        let now = Instant::now();
+        let mut b = bucket(&limiter, now);
+
+        assert_eq!(limiter.reserve(&mut b, now, 1), Duration::ZERO);
+        assert_eq!(limiter.reserve(&mut b, now, 1), Duration::ZERO);
+        // 每 30 秒恢复一个请求额度
+        assert_eq!(limiter.reserve(&mut b, now, 1).as_secs(), 30);
+        assert_eq!(limiter.reserve(&mut b, now + Duration::from_secs(30), 1).as_secs(), 30);
+    }
+
+    #[test]
+    fn test_token_quota() {
+        let limiter = RateLimiter::new(1000, 60);
+        let now = Instant::now();
+        let mut b = bucket(&limiter, now);
+
+        assert_eq!(limiter.reserve(&mut b, now, 60), Duration::ZERO);
+        assert_eq!(limiter.reserve(&mut b, now, 30).as_secs(), 30);
+        // 超过整分钟额度的请求按满额计
+        let mut b = bucket(&limiter, now);
+        assert_eq!(limiter.reserve(&mut b, now, 500), Duration::ZERO);
+        assert_eq!(limiter.reserve(&mut b, now, 6).as_secs(), 6);
+    }
+
+    #[test]
+    fn test_zero_limit_is_unlimited() {
+        let now = Instant::now();
+        let limiter = RateLimiter::new(0, 60);
+        let mut b = bucket(&limiter, now);
+        for _ in 0..10 {
+            assert_eq!(limiter.reserve(&mut b, now, 1), Duration::ZERO);
+        }
+        assert_eq!(limiter.reserve(&mut b, now, 60).as_secs(), 10);
+
+        let limiter = RateLimiter::new(1, 0);
+        let mut b = bucket(&limiter, now);
+        assert_eq!(limiter.reserve(&mut b, now, 1_000_000), Duration::ZERO);
+        assert_eq!(limiter.reserve(&mut b, now, 1).as_secs(), 60);
+    }
+
+    #[tokio::test]
+    async fn test_shared_limiter_throttles_concurrent_callers() {
+        // 每分钟 6000 个请求 = 每 10ms 一个，初始突发额度被前几次占满
+        let limiter = Arc::new(RateLimiter::new(6000, 1_000_000));
+        {
+            let mut b = limiter.state.lock().await;
+            b.requests = 0.0;
+            b.last_refill = Instant::now();
+        }
+
+        let start = Instant::now();
+        let handles: Vec<_> = (0..5)
+            .map(|_| {
+                let limiter = limiter.clone();
+                tokio::spawn(async move { limiter.acquire(1).await })
+            })
+            .collect();
+        for handle in handles {
+            handle.await.unwrap();
+        }
+        assert!(start.elapsed() >= Duration::from_millis(45));
+    }
+}
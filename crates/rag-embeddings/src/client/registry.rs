@@ -0,0 +1,221 @@
+use anyhow::{Context, Result, bail};
+use rag_indexing::normalize::TextNormalizer;
+use serde::Deserialize;
+
+use crate::client::{
+    EmbeddingClient,
+    fallback::FallbackEmbeddingClient,
+    fixed_dimension::FixedDimensionClient,
+    instruction::InstructionClient,
+    normalizing::NormalizingClient,
+    openai::OpenAIEmbeddingClient,
+    qwen::QwenEmbeddingClient,
+};
+
+/// embedding 服务配置，通常从配置文件反序列化
+///
+/// ```toml
+/// provider = "qwen"
+/// model = "text-embedding-v3"
+/// task = "retrieval.document"
+/// dimension = 1536
+/// api_key_env = "DASHSCOPE_API_KEY"
+/// query_instruction = "为这个句子生成表示以用于检索："
+///
+/// [normalizer]
+/// lowercase = false
+/// ```
+#[derive(Debug, Clone, Deserialize)]
+pub struct ProviderConfig {
+    /// "qwen" 或 "openai"
+    pub provider: String,
+    pub model: String,
+    /// 仅 qwen 使用，嵌入文档时的 task，如 "retrieval.document"；查询总是以 "retrieval.query" 嵌入
+    #[serde(default)]
+    pub task: Option<String>,
+    /// 目标维度，与模型原生维度不同时：qwen 通过 `dimensions` 参数请求降维向量
+    /// （见 [`QwenEmbeddingClient::with_dimensions`]），openai 经 [`FixedDimensionClient`] 截断 / 补零
+    #[serde(default)]
+    pub dimension: Option<usize>,
+    /// 读取 API key 的环境变量名，默认按 provider 选择
+    #[serde(default)]
+    pub api_key_env: Option<String>,
+    /// OpenAI 兼容服务地址，未配置时使用各服务的默认地址
+    #[serde(default)]
+    pub base_url: Option<String>,
+    /// 文档文本的指令前缀，见 [`InstructionClient`]
+    #[serde(default)]
+    pub document_instruction: Option<String>,
+    /// 查询文本的指令前缀
+    #[serde(default)]
+    pub query_instruction: Option<String>,
+    /// embedding 前的文本规范化，见 [`NormalizingClient`]；未配置时原样发送
+    ///
+    /// 只作用于发送给 embedding 服务的文本，不改变入库的叶子文本、token 数与去重哈希。
+    #[serde(default)]
+    pub normalizer: Option<TextNormalizer>,
+}
+
+impl ProviderConfig {
+    fn api_key(&self, default_env: &str) -> Result<String> {
+        let env = self.api_key_env.as_deref().unwrap_or(default_env);
+        std::env::var(env).with_context(|| format!("请设置环境变量 {}", env))
+    }
+}
+
+/// 按配置构建 embedding 客户端
+pub fn build_embedding_client(config: &ProviderConfig) -> Result<Box<dyn EmbeddingClient>> {
+    let mut client: Box<dyn EmbeddingClient> = match config.provider.trim().to_lowercase().as_str() {
+        "qwen" | "dashscope" => {
+            let mut client = QwenEmbeddingClient::new(
+                config.api_key("DASHSCOPE_API_KEY")?,
+                config.model.clone(),
+                config.task.clone(),
+            );
+            // 由服务端直接返回目标维度，截断会破坏向量的归一化与语义
+            if let Some(dimension) = config.dimension
+                && dimension != client.dimension()
+            {
+                client = client.with_dimensions(dimension);
+            }
+            match &config.base_url {
+                Some(base_url) => Box::new(client.with_base_url(base_url.clone())),
+                None => Box::new(client),
+            }
+        }
+        "openai" => {
+            let client = OpenAIEmbeddingClient::new(config.api_key("OPENAI_API_KEY")?, config.model.clone());
+            match &config.base_url {
+                Some(base_url) => Box::new(client.with_base_url(base_url.clone())),
+                None => Box::new(client),
+            }
+        }
+        other => bail!("未知的 embedding provider: {}", other),
+    };
+
+    if config.document_instruction.is_some() || config.query_instruction.is_some() {
+        let mut instructed = InstructionClient::new(client);
+        if let Some(prefix) = &config.document_instruction {
+            instructed = instructed.with_document_prefix(prefix.clone());
+        }
+        if let Some(prefix) = &config.query_instruction {
+            instructed = instructed.with_query_prefix(prefix.clone());
+        }
+        client = Box::new(instructed);
+    }
+
+    // 先规范化原文，再拼接指令前缀
+    if let Some(normalizer) = config.normalizer {
+        client = Box::new(NormalizingClient::new(client, normalizer));
+    }
+
+    match config.dimension {
+        Some(dimension) if dimension != client.dimension() => {
+            Ok(Box::new(FixedDimensionClient::new(client, dimension)))
+        }
+        _ => Ok(client),
+    }
+}
+
+/// 按优先级构建多个 embedding 服务并组合为 [`FallbackEmbeddingClient`]，只有一个配置时直接返回该客户端
+///
+/// 各服务维度必须一致，不一致时可在配置中设置 `dimension` 对齐
+pub fn build_fallback_client(configs: &[ProviderConfig]) -> Result<Box<dyn EmbeddingClient>> {
+    let mut providers = configs.iter()
+        .map(|config| Ok((format!("{}/{}", config.provider, config.model), build_embedding_client(config)?)))
+        .collect::<Result<Vec<_>>>()?;
+    if providers.len() == 1 {
+        return Ok(providers.remove(0).1);
+    }
+    Ok(Box::new(FallbackEmbeddingClient::new(providers)?))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn config(provider: &str, model: &str, dimension: Option<usize>) -> ProviderConfig {
+        ProviderConfig {
+            provider: provider.to_string(),
+            model: model.to_string(),
+            task: None,
+            dimension,
+            api_key_env: Some("PATH".to_string()), // 任意已存在的环境变量
+            base_url: None,
+            document_instruction: None,
+            query_instruction: None,
+            normalizer: None,
+        }
+    }
+
+    #[test]
+    fn test_build_embedding_client() -> Result<()> {
+        assert_eq!(build_embedding_client(&config("qwen", "text-embedding-v3", None))?.dimension(), 2560);
+        assert_eq!(build_embedding_client(&config("OpenAI", "text-embedding-3-large", None))?.dimension(), 3072);
+        assert_eq!(build_embedding_client(&config("qwen", "text-embedding-v3", Some(1536)))?.dimension(), 1536);
+        assert!(build_embedding_client(&config("unknown", "m", None)).is_err());
+
+        let mut missing_key = config("qwen", "text-embedding-v1", None);
+        missing_key.api_key_env = Some("RAG_RS_TEST_MISSING_KEY".to_string());
+        assert!(build_embedding_client(&missing_key).is_err());
+        Ok(())
+    }
+
+    /// 各层适配器都把 `embed_queries` 转发到 Qwen 的查询 task，而不是退化为 `embed`
+    #[tokio::test]
+    async fn test_wrappers_forward_queries() -> Result<()> {
+        let server = crate::client::mock_server::embeddings_server(1536).await;
+        let client = build_embedding_client(&ProviderConfig {
+            task: Some("retrieval.document".to_string()),
+            base_url: Some(server.uri()),
+            query_instruction: Some("query: ".to_string()),
+            normalizer: Some(TextNormalizer::default()),
+            ..config("qwen", "text-embedding-v1", Some(8))
+        })?;
+        let client = crate::client::caching::CachingEmbeddingClient::new(client, 8);
+
+        client.embed(vec!["所有权".to_string()]).await?;
+        assert_eq!(client.embed_queries(vec!["  所有权 ".to_string()]).await?[0].len(), 8);
+
+        let bodies: Vec<serde_json::Value> = server.received_requests().await.unwrap_or_default()
+            .iter()
+            .map(|request| request.body_json())
+            .collect::<Result<_, _>>()?;
+        assert_eq!(bodies[0]["task"], "retrieval.document");
+        assert_eq!(bodies[0]["dimensions"], 8);
+        assert_eq!(bodies[0]["input"], serde_json::json!(["所有权"]));
+        assert_eq!(bodies[1]["task"], "retrieval.query");
+        assert_eq!(bodies[1]["input"], serde_json::json!(["query: 所有权"]));
+        Ok(())
+    }
+
+    #[test]
+    fn test_build_fallback_client() -> Result<()> {
+        let primary = config("qwen", "text-embedding-v1", None);
+        assert_eq!(build_fallback_client(std::slice::from_ref(&primary))?.dimension(), 1536);
+
+        let backup = config("openai", "text-embedding-3-large", None);
+        assert!(build_fallback_client(&[primary.clone(), backup.clone()]).is_err());
+        let backup = ProviderConfig { dimension: Some(1536), ..backup };
+        assert_eq!(build_fallback_client(&[primary, backup])?.dimension(), 1536);
+        assert!(build_fallback_client(&[]).is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn test_deserialize_config() -> Result<()> {
+        let config: ProviderConfig = serde_json::from_str(r#"{"provider": "qwen", "model": "text-embedding-v1"}"#)?;
+        assert_eq!(config.task, None);
+        assert_eq!(config.dimension, None);
+        assert_eq!(config.query_instruction, None);
+        assert_eq!(config.normalizer, None);
+
+        let config: ProviderConfig = serde_json::from_str(
+            r#"{"provider": "qwen", "model": "text-embedding-v3", "query_instruction": "query: ", "normalizer": {"lowercase": true}}"#
+        )?;
+        assert_eq!(config.query_instruction.as_deref(), Some("query: "));
+        assert_eq!(config.normalizer, Some(TextNormalizer::default().with_lowercase(true)));
+        assert_eq!(build_embedding_client(&ProviderConfig { api_key_env: Some("PATH".to_string()), ..config })?.dimension(), 2560);
+        Ok(())
+    }
+}
@@ -0,0 +1,59 @@
+use std::collections::hash_map::RandomState;
+use std::hash::{BuildHasher, Hasher};
+use std::time::Duration;
+
+use reqwest::StatusCode;
+
+/// 请求失败后的重试策略：第 n 次重试前等待 `base_delay * 2^n`，并乘以 [0.5, 1) 的随机抖动
+///
+/// 抖动避免多个并发任务在同一时刻重试、再次触发限流。
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct RetryPolicy {
+    /// 首次请求之外的最大重试次数
+    pub max_retries: u32,
+    pub base_delay: Duration,
+}
+
+impl RetryPolicy {
+    pub fn new(max_retries: u32, base_delay: Duration) -> Self {
+        Self { max_retries, base_delay }
+    }
+
+    /// 第 `attempt` 次重试（从 0 开始）前的等待时间
+    pub fn delay(&self, attempt: u32) -> Duration {
+        let backoff = self.base_delay.saturating_mul(1 << attempt.min(16));
+        backoff.mul_f64(0.5 + jitter() / 2.0)
+    }
+}
+
+/// 限流（429）与服务端错误（5xx）可重试，其余 4xx（如 key 无效、参数错误）重试也不会成功
+pub(crate) fn is_retryable_status(status: StatusCode) -> bool {
+    status == StatusCode::TOO_MANY_REQUESTS || status.is_server_error()
+}
+
+/// [0, 1) 的随机数，取自标准库随机初始化的哈希种子，不引入额外依赖
+fn jitter() -> f64 {
+    let bits = RandomState::new().build_hasher().finish();
+    (bits >> 11) as f64 / (1u64 << 53) as f64
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_backoff_delay() {
+        let policy = RetryPolicy::new(3, Duration::from_millis(100));
+        for attempt in 0..4 {
+            let delay = policy.delay(attempt);
+            let backoff = Duration::from_millis(100 * (1 << attempt));
+            assert!(delay >= backoff / 2 && delay < backoff, "attempt {}: {:?}", attempt, delay);
+        }
+        assert!(policy.delay(u32::MAX) > Duration::ZERO);
+
+        assert!(is_retryable_status(StatusCode::TOO_MANY_REQUESTS));
+        assert!(is_retryable_status(StatusCode::SERVICE_UNAVAILABLE));
+        assert!(!is_retryable_status(StatusCode::UNAUTHORIZED));
+        assert!(!is_retryable_status(StatusCode::BAD_REQUEST));
+    }
+}
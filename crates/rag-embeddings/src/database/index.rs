@@ -0,0 +1,89 @@
+use crate::database::{DistanceMetric, VectorPrecision};
+
+/// pgvector 的 HNSW / IVFFlat 索引最多支持的 `vector` 维度
+pub const MAX_INDEX_DIMENSIONS: usize = 2000;
+
+/// HNSW 每个节点的默认连接数（与 pgvector 默认值一致）
+pub const DEFAULT_HNSW_M: u32 = 16;
+
+/// HNSW 建索引时候选列表的默认大小（与 pgvector 默认值一致）
+pub const DEFAULT_HNSW_EF_CONSTRUCTION: u32 = 64;
+
+/// embedding 列上的近似最近邻索引（见 `StoreOptions::index_type`）
+///
+/// HNSW 在建表时创建；IVFFlat 需在写入数据后调用 `PgVectorStore::create_index` 创建。
+///
+/// 没有索引时每次检索都是全表扫描，数千条记录以上会明显变慢。
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum IndexType {
+    /// HNSW：查询快、召回高，建索引较慢、占用内存较多；空表上也可直接创建
+    Hnsw { m: u32, ef_construction: u32 },
+    /// IVFFlat：建索引快，`lists` 一般取 行数 / 1000；聚类中心取自已有数据，建表时不创建，须写入数据后手动创建
+    IvfFlat { lists: u32 },
+    /// 不建向量索引，检索为精确的全表扫描
+    None,
+}
+
+impl Default for IndexType {
+    fn default() -> Self {
+        Self::Hnsw { m: DEFAULT_HNSW_M, ef_construction: DEFAULT_HNSW_EF_CONSTRUCTION }
+    }
+}
+
+impl IndexType {
+    /// 索引名，不同索引类型使用不同的名字，切换类型时旧索引需手动删除
+    pub fn index_name(&self, table_name: &str) -> Option<String> {
+        let kind = match self {
+            Self::Hnsw { .. } => "hnsw",
+            Self::IvfFlat { .. } => "ivfflat",
+            Self::None => return None,
+        };
+        Some(format!("{}_embedding_{}_idx", table_name.replace('.', "_"), kind))
+    }
+
+    /// 是否可在空表上创建；IVFFlat 在空表上建的聚类中心没有意义，召回会很差
+    pub fn builds_on_empty_table(&self) -> bool {
+        !matches!(self, Self::IvfFlat { .. })
+    }
+
+    /// 创建索引的 SQL，操作符类与检索使用的距离度量、列的存储精度一致；[`IndexType::None`] 时为 `None`
+    pub(crate) fn create_sql(&self, table_name: &str, metric: DistanceMetric, precision: VectorPrecision) -> Option<String> {
+        let (method, options) = match self {
+            Self::Hnsw { m, ef_construction } => ("hnsw", format!("m = {}, ef_construction = {}", m, ef_construction)),
+            Self::IvfFlat { lists } => ("ivfflat", format!("lists = {}", lists)),
+            Self::None => return None,
+        };
+        Some(format!(
+            "CREATE INDEX IF NOT EXISTS {} ON {} USING {} (embedding {}) WITH ({})",
+            self.index_name(table_name)?,
+            table_name,
+            method,
+            metric.opclass(precision),
+            options
+        ))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_create_sql() {
+        assert_eq!(
+            IndexType::default().create_sql("vectors", DistanceMetric::Cosine, VectorPrecision::Full).unwrap(),
+            "CREATE INDEX IF NOT EXISTS vectors_embedding_hnsw_idx ON vectors USING hnsw (embedding vector_cosine_ops) WITH (m = 16, ef_construction = 64)"
+        );
+        assert_eq!(
+            IndexType::IvfFlat { lists: 100 }.create_sql("rag.vectors", DistanceMetric::L2, VectorPrecision::Full).unwrap(),
+            "CREATE INDEX IF NOT EXISTS rag_vectors_embedding_ivfflat_idx ON rag.vectors USING ivfflat (embedding vector_l2_ops) WITH (lists = 100)"
+        );
+        assert!(IndexType::Hnsw { m: 8, ef_construction: 32 }
+            .create_sql("vectors", DistanceMetric::InnerProduct, VectorPrecision::Half)
+            .unwrap()
+            .contains("(embedding halfvec_ip_ops) WITH (m = 8, ef_construction = 32)"));
+        assert!(IndexType::default().builds_on_empty_table());
+        assert!(!IndexType::IvfFlat { lists: 100 }.builds_on_empty_table());
+        assert_eq!(IndexType::None.create_sql("vectors", DistanceMetric::Cosine, VectorPrecision::Full), None);
+    }
+}
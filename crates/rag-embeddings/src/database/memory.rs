@@ -0,0 +1,270 @@
+use std::sync::RwLock;
+
+use anyhow::Result;
+use async_trait::async_trait;
+use chrono::Utc;
+use serde_json::Value as JsonValue;
+
+use super::{DistanceMetric, ManagedVectorStore, SearchQuery, SearchResult, VectorRecord, VectorStore};
+use super::query::{check_delete_filter, json_contains};
+use crate::dedup::cosine;
+
+/// 纯内存的向量库：记录保存在 `Vec` 中，检索时在 Rust 侧暴力计算余弦相似度
+///
+/// 不需要数据库，适合单元测试检索逻辑或小规模临时数据；维度校验与 [`PgVectorStore`](super::pgvector::PgVectorStore) 一致：
+/// insert 维度不符时报错，upsert 跳过。
+pub struct InMemoryVectorStore {
+    dimensions: usize,
+    records: RwLock<Vec<VectorRecord>>,
+}
+
+impl InMemoryVectorStore {
+    pub fn new(dimensions: usize) -> Self {
+        Self {
+            dimensions,
+            records: RwLock::new(Vec::new()),
+        }
+    }
+
+    pub fn dimensions(&self) -> usize {
+        self.dimensions
+    }
+
+    /// 所有记录的副本，按写入顺序
+    pub fn records(&self) -> Vec<VectorRecord> {
+        self.records.read().unwrap().clone()
+    }
+
+    fn write_metadata(&self, id: &str, update: impl FnOnce(&mut JsonValue)) -> Result<()> {
+        let mut records = self.records.write().unwrap();
+        let Some(record) = records.iter_mut().find(|r| r.id == id) else {
+            anyhow::bail!("Vector {} not found", id);
+        };
+        update(&mut record.metadata);
+        record.updateat = Some(Utc::now());
+        Ok(())
+    }
+}
+
+#[async_trait]
+impl VectorStore for InMemoryVectorStore {
+    async fn add_vectors(&self, vectors: Vec<VectorRecord>) -> Result<()> {
+        let mut records = self.records.write().unwrap();
+        // 先整体校验，失败时不写入任何记录（与数据库事务一致）
+        for (i, vec) in vectors.iter().enumerate() {
+            if vec.embedding.len() != self.dimensions {
+                anyhow::bail!(
+                    "Embedding dim mismatch: expected {}, got {}",
+                    self.dimensions,
+                    vec.embedding.len()
+                );
+            }
+            if records.iter().chain(&vectors[..i]).any(|r| r.id == vec.id) {
+                anyhow::bail!("Vector {} already exists", vec.id);
+            }
+        }
+
+        let now = Utc::now();
+        records.extend(vectors.into_iter().map(|mut vec| {
+            vec.createat.get_or_insert(now);
+            vec.updateat.get_or_insert(now);
+            vec
+        }));
+        Ok(())
+    }
+
+    async fn upsert_vectors(&self, vectors: Vec<VectorRecord>) -> Result<()> {
+        let mut records = self.records.write().unwrap();
+        let now = Utc::now();
+        for mut vec in vectors {
+            if vec.embedding.len() != self.dimensions {
+                continue;
+            }
+            vec.updateat.get_or_insert(now);
+            match records.iter_mut().find(|r| r.id == vec.id) {
+                Some(existing) => {
+                    // 与 ON CONFLICT DO UPDATE 一致，保留原有的创建时间
+                    vec.createat = existing.createat;
+                    *existing = vec;
+                }
+                None => {
+                    vec.createat.get_or_insert(now);
+                    records.push(vec);
+                }
+            }
+        }
+        Ok(())
+    }
+
+    async fn delete_vector(&self, ids: Vec<String>) -> Result<()> {
+        self.records.write().unwrap().retain(|r| !ids.contains(&r.id));
+        Ok(())
+    }
+
+    async fn search(&self, query: &[f32], top_k: usize) -> Result<Vec<SearchResult>> {
+        self.search_with(&SearchQuery::new(query.to_vec()).top_k(top_k)).await
+    }
+
+    /// 暴力扫描全部记录，过滤条件与相似度排序都在内存中完成
+    async fn search_with(&self, query: &SearchQuery) -> Result<Vec<SearchResult>> {
+        if query.vector.len() != self.dimensions {
+            anyhow::bail!(
+                "Query dim mismatch: expected {}, got {}",
+                self.dimensions,
+                query.vector.len()
+            );
+        }
+
+        let records = self.records.read().unwrap();
+        let mut hits: Vec<(VectorRecord, f32)> = records.iter()
+            .map(|record| (record, DistanceMetric::Cosine.normalize(1.0 - cosine(&record.embedding, &query.vector))))
+            .filter(|(record, score)| query.matches(record, *score))
+            .map(|(record, score)| (record.clone(), score))
+            .collect();
+        hits.sort_by(|a, b| b.1.total_cmp(&a.1));
+        hits.truncate(query.top_k);
+        if !query.include_embeddings {
+            hits.iter_mut().for_each(|(record, _)| record.embedding = Vec::new());
+        }
+
+        Ok(SearchResult::ranked(hits))
+    }
+}
+
+#[async_trait]
+impl ManagedVectorStore for InMemoryVectorStore {
+    async fn delete_by_metadata(&self, filter: JsonValue) -> Result<u64> {
+        check_delete_filter(&filter)?;
+        let mut records = self.records.write().unwrap();
+        let before = records.len();
+        records.retain(|r| !json_contains(&r.metadata, &filter));
+        Ok((before - records.len()) as u64)
+    }
+
+    async fn update_metadata(&self, id: &str, metadata: JsonValue) -> Result<()> {
+        self.write_metadata(id, |existing| *existing = metadata)
+    }
+
+    async fn merge_metadata(&self, id: &str, patch: JsonValue) -> Result<()> {
+        let JsonValue::Object(patch) = patch else {
+            anyhow::bail!("Metadata patch must be a JSON object");
+        };
+        self.write_metadata(id, |existing| {
+            if !existing.is_object() {
+                *existing = JsonValue::Object(Default::default());
+            }
+            if let JsonValue::Object(existing) = existing {
+                existing.extend(patch);
+            }
+        })
+    }
+
+    async fn count(&self) -> Result<usize> {
+        Ok(self.records.read().unwrap().len())
+    }
+
+    async fn get_by_id(&self, id: &str) -> Result<Option<VectorRecord>> {
+        Ok(self.records.read().unwrap().iter().find(|r| r.id == id).cloned())
+    }
+
+    async fn list(&self, limit: usize, offset: usize) -> Result<Vec<VectorRecord>> {
+        let records = self.records.read().unwrap();
+        let mut sorted: Vec<&VectorRecord> = records.iter().collect();
+        sorted.sort_by(|a, b| (a.createat, &a.id).cmp(&(b.createat, &b.id)));
+        Ok(sorted.into_iter().skip(offset).take(limit).cloned().collect())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn record(id: &str, embedding: Vec<f32>, document_id: &str) -> VectorRecord {
+        VectorRecord {
+            id: id.to_string(),
+            embedding,
+            metadata: serde_json::json!({ "document_id": document_id }),
+            text: Some(format!("text {}", id)),
+            createat: None,
+            updateat: None,
+        }
+    }
+
+    #[tokio::test]
+    async fn test_in_memory_store() -> Result<()> {
+        let store = InMemoryVectorStore::new(3);
+        store.add_vectors(vec![
+            record("a", vec![1.0, 0.0, 0.0], "doc-001"),
+            record("b", vec![0.0, 1.0, 0.0], "doc-001"),
+            record("c", vec![0.8, 0.6, 0.0], "doc-002"),
+        ]).await?;
+        assert!(store.add_vectors(vec![record("d", vec![1.0], "doc-001")]).await.is_err());
+        assert!(store.add_vectors(vec![record("a", vec![1.0, 0.0, 0.0], "doc-001")]).await.is_err());
+        assert_eq!(store.count().await?, 3);
+
+        let hits = store.search(&[1.0, 0.0, 0.0], 2).await?;
+        assert_eq!(hits.iter().map(|h| h.record.id.as_str()).collect::<Vec<_>>(), vec!["a", "c"]);
+        assert!((hits[0].score - 1.0).abs() < 1e-6);
+        assert!((hits[1].score - 0.9).abs() < 1e-6);
+        assert_eq!(hits[1].rank, 2);
+        assert!(hits[0].record.embedding.is_empty());
+        assert_eq!(store.search_full(&[1.0, 0.0, 0.0], 1).await?[0].record.embedding, vec![1.0, 0.0, 0.0]);
+        assert!(store.search(&[1.0, 0.0], 2).await.is_err());
+
+        let filtered = store.search_with(&SearchQuery::new(vec![1.0, 0.0, 0.0]).top_k(2).filter_document("doc-001")).await?;
+        assert_eq!(filtered.iter().map(|h| h.record.id.as_str()).collect::<Vec<_>>(), vec!["a", "b"]);
+
+        let created = store.get_by_id("b").await?.unwrap().createat;
+        store.upsert_vectors(vec![record("b", vec![1.0, 0.0, 0.0], "doc-003"), record("e", vec![1.0], "doc-003")]).await?;
+        store.merge_metadata("b", serde_json::json!({ "tag": "x" })).await?;
+        let b = store.get_by_id("b").await?.unwrap();
+        assert_eq!(b.metadata, serde_json::json!({ "document_id": "doc-003", "tag": "x" }));
+        assert_eq!(b.createat, created);
+        assert!(store.get_by_id("e").await?.is_none());
+        assert!(store.update_metadata("missing", serde_json::json!({})).await.is_err());
+
+        store.delete_vector(vec!["a".to_string(), "b".to_string()]).await?;
+        assert!(store.get_by_id("a").await?.is_none());
+        assert_eq!(store.search(&[1.0, 0.0, 0.0], 10).await?.len(), 1);
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_list_pages() -> Result<()> {
+        let store = InMemoryVectorStore::new(3);
+        store.add_vectors((1..=5).rev().map(|n| record(&format!("r{}", n), vec![1.0, 0.0, 0.0], "doc-001")).collect()).await?;
+
+        let mut ids = Vec::new();
+        let mut offset = 0;
+        loop {
+            let page = store.list(2, offset).await?;
+            assert!(page.len() <= 2);
+            ids.extend(page.iter().map(|r| r.id.clone()));
+            if page.len() < 2 {
+                break;
+            }
+            offset += page.len();
+        }
+        // 同一批写入的创建时间相同，按 id 排序
+        assert_eq!(ids, vec!["r1", "r2", "r3", "r4", "r5"]);
+        assert_eq!(store.list(2, 0).await?[0].embedding, vec![1.0, 0.0, 0.0]);
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_delete_by_metadata() -> Result<()> {
+        let store = InMemoryVectorStore::new(3);
+        store.add_vectors(vec![
+            record("a1", vec![1.0, 0.0, 0.0], "doc-a"),
+            record("a2", vec![0.0, 1.0, 0.0], "doc-a"),
+            record("b1", vec![0.0, 0.0, 1.0], "doc-b"),
+        ]).await?;
+
+        assert!(store.delete_by_metadata(serde_json::json!({})).await.is_err());
+        assert!(store.delete_by_metadata(serde_json::json!(["doc-a"])).await.is_err());
+        assert_eq!(store.delete_by_metadata(serde_json::json!({ "document_id": "doc-a" })).await?, 2);
+        assert_eq!(store.records().iter().map(|r| r.id.as_str()).collect::<Vec<_>>(), vec!["b1"]);
+        assert_eq!(store.delete_by_metadata(serde_json::json!({ "document_id": "doc-a" })).await?, 0);
+        Ok(())
+    }
+}
@@ -0,0 +1,79 @@
+use crate::database::SearchResult;
+use crate::dedup::cosine;
+
+/// 按最大边际相关性（MMR）从候选中贪心选出 `top_k` 条，名次按选中顺序重新编号
+///
+/// 每一步选择 `lambda * sim(query, d) - (1 - lambda) * max sim(d, 已选)` 最大的候选，
+/// 相似度均为 embedding 间的余弦相似度。`lambda` 取值 [0, 1]：1 等同按相关性排序，越小越偏向多样性。
+/// 候选须携带 embedding（见 [`VectorStore::search_full`](super::VectorStore::search_full)），
+/// 缺少 embedding 的候选相关性与多样性都按 0 计算。结果的 `score` 保持检索时的相似度。
+pub fn mmr_select(query: &[f32], candidates: Vec<SearchResult>, top_k: usize, lambda: f32) -> Vec<SearchResult> {
+    let lambda = lambda.clamp(0.0, 1.0);
+    let relevance: Vec<f32> = candidates.iter()
+        .map(|hit| cosine(query, &hit.record.embedding))
+        .collect();
+
+    let mut remaining: Vec<usize> = (0..candidates.len()).collect();
+    let mut selected: Vec<usize> = Vec::new();
+    while selected.len() < top_k && !remaining.is_empty() {
+        let mmr = |i: usize| {
+            let redundancy = selected.iter()
+                .map(|&j| cosine(&candidates[i].record.embedding, &candidates[j].record.embedding))
+                .fold(0.0, f32::max);
+            lambda * relevance[i] - (1.0 - lambda) * redundancy
+        };
+        // 分数相同时保留检索顺序靠前的候选
+        let (pos, _) = remaining.iter()
+            .enumerate()
+            .map(|(pos, &i)| (pos, mmr(i)))
+            .fold((0, f32::NEG_INFINITY), |best, (pos, score)| if score > best.1 { (pos, score) } else { best });
+        selected.push(remaining.remove(pos));
+    }
+
+    let mut candidates: Vec<Option<SearchResult>> = candidates.into_iter().map(Some).collect();
+    SearchResult::ranked(selected.into_iter().filter_map(|i| {
+        let hit = candidates[i].take()?;
+        Some((hit.record, hit.score))
+    }))
+}
+
+#[cfg(test)]
+mod tests {
+    use anyhow::Result;
+
+    use crate::database::{InMemoryVectorStore, VectorRecord, VectorStore};
+
+    fn record(id: &str, embedding: Vec<f32>) -> VectorRecord {
+        VectorRecord {
+            id: id.to_string(),
+            embedding,
+            metadata: serde_json::json!({}),
+            text: None,
+            createat: None,
+            updateat: None,
+        }
+    }
+
+    #[tokio::test]
+    async fn test_search_mmr() -> Result<()> {
+        let store = InMemoryVectorStore::new(2);
+        store.add_vectors(vec![
+            record("a", vec![1.0, 0.0]),
+            record("a-dup", vec![0.99, 0.01]),
+            record("b", vec![0.7, 0.7]),
+        ]).await?;
+        let ids = |hits: &[crate::database::SearchResult]| hits.iter().map(|h| h.record.id.clone()).collect::<Vec<_>>();
+
+        // lambda = 1 退化为按相关性排序，选中近重复的分块
+        let hits = store.search_mmr(&[1.0, 0.0], 2, 3, 1.0).await?;
+        assert_eq!(ids(&hits), vec!["a", "a-dup"]);
+
+        // lambda 较小时多样性优先，跳过近重复的分块
+        let hits = store.search_mmr(&[1.0, 0.0], 2, 3, 0.3).await?;
+        assert_eq!(ids(&hits), vec!["a", "b"]);
+        assert_eq!(hits[1].rank, 2);
+        assert!(hits[1].score < hits[0].score);
+        assert_eq!(store.search_mmr(&[1.0, 0.0], 5, 3, 0.3).await?.len(), 3);
+        Ok(())
+    }
+}
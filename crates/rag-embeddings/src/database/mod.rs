@@ -1,4 +1,23 @@
+pub mod index;
+pub mod memory;
+pub mod mmr;
+pub mod precision;
 pub mod pgvector;
+pub mod query;
+pub mod score;
+#[cfg(feature = "sqlite")]
+pub mod sqlite;
+pub mod text_search;
+
+pub use index::IndexType;
+pub use memory::InMemoryVectorStore;
+pub use mmr::mmr_select;
+pub use precision::VectorPrecision;
+pub use query::SearchQuery;
+pub use score::DistanceMetric;
+pub use text_search::TextSearchStore;
+
+use std::fmt;
 
 use sqlx::FromRow;
 use anyhow::Result;
@@ -6,6 +25,7 @@ use chrono::{DateTime, Utc};
 use async_trait::async_trait;
 use serde_json::Value as JsonValue;
 
+use rag_indexing::tree_structrue::markdown_bulid::{SNIPPET_MAX_CHARS, snippet};
 use serde::{Deserialize, Serialize};
 
 #[derive(Debug, Clone, Serialize, Deserialize, FromRow)]
@@ -18,8 +38,70 @@ pub struct VectorRecord {
     pub updateat: Option<DateTime<Utc>>,
 }
 
+impl VectorRecord {
+    /// 可在编辑器中打开的来源引用，如 `docs/guide.md:120-145`（单行时为 `docs/guide.md:120`）
+    ///
+    /// 优先使用 `metadata.file_path`，没有时退回 `file_name`；缺少行号时只返回文件，都没有时返回 `None`。
+    pub fn source_reference(&self) -> Option<String> {
+        let meta_str = |key: &str| self.metadata.get(key).and_then(|v| v.as_str());
+        let meta_line = |key: &str| self.metadata.get(key).and_then(|v| v.as_u64());
+
+        let file = meta_str("file_path").or_else(|| meta_str("file_name"))?;
+        Some(match (meta_line("line_start"), meta_line("line_end")) {
+            (Some(start), Some(end)) if end > start => format!("{}:{}-{}", file, start, end),
+            (Some(start), _) => format!("{}:{}", file, start),
+            _ => file.to_string(),
+        })
+    }
+}
+
+/// 一条检索结果
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SearchResult {
+    /// 名次，从 1 开始
+    pub rank: usize,
+    /// 相似度，见 [`VectorStore::search`]
+    pub score: f32,
+    pub record: VectorRecord,
+}
+
+impl SearchResult {
+    pub fn new(rank: usize, score: f32, record: VectorRecord) -> Self {
+        Self { rank, score, record }
+    }
+
+    /// 按给定顺序为 (记录, 相似度) 依次编号
+    pub fn ranked(hits: impl IntoIterator<Item = (VectorRecord, f32)>) -> Vec<Self> {
+        hits.into_iter()
+            .enumerate()
+            .map(|(i, (record, score))| Self::new(i + 1, score, record))
+            .collect()
+    }
+
+    /// 按相似度降序重排（稳定排序）并重新编号，分数被修改或结果被合并后调用
+    pub fn rerank(results: &mut [Self]) {
+        results.sort_by(|a, b| b.score.total_cmp(&a.score));
+        for (i, result) in results.iter_mut().enumerate() {
+            result.rank = i + 1;
+        }
+    }
+}
+
+impl fmt::Display for SearchResult {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "#{} ({:.3}) {} — {}",
+            self.rank,
+            self.score,
+            self.record.id,
+            snippet(self.record.text.as_deref().unwrap_or_default(), SNIPPET_MAX_CHARS)
+        )
+    }
+}
+
 #[async_trait]
-pub trait VectorStore {
+pub trait VectorStore: Send + Sync {
     
     async fn add_vectors(&self, vectors: Vec<VectorRecord>) -> Result<()>;
 
@@ -27,6 +109,132 @@ pub trait VectorStore {
 
     async fn delete_vector(&self, ids: Vec<String>) -> Result<()>;
 
-    async fn search(&self) -> Result<Vec<VectorRecord>>;
+    /// 检索与 `query` 最相似的 `top_k` 条记录，按相似度降序，名次从 1 开始
+    ///
+    /// 相似度为经 [`DistanceMetric::normalize`] 归一化的 [0, 1] 值，1 表示完全相同。
+    /// 是否携带 embedding 取决于实现，内置的数据库存储默认不携带，需要向量时用 [`search_full`](Self::search_full)
+    async fn search(&self, query: &[f32], top_k: usize) -> Result<Vec<SearchResult>>;
+
+    /// 只返回文本与 metadata 的检索，结果的 `embedding` 总是为空
+    ///
+    /// 数据库存储不会读取 embedding 列；需要向量（如 MMR）时用 [`search_full`](Self::search_full)，
+    /// 或按 id 另行读取（如 [`PgVectorStore::get_by_ids`](pgvector::PgVectorStore::get_by_ids)）。
+    async fn search_lite(&self, query: &[f32], top_k: usize) -> Result<Vec<SearchResult>> {
+        let mut hits = self.search_with(&SearchQuery::new(query.to_vec()).top_k(top_k)).await?;
+        // 不支持按需读取的存储也保证不返回向量
+        hits.iter_mut().for_each(|hit| hit.record.embedding = Vec::new());
+        Ok(hits)
+    }
 
-}
\ No newline at end of file
+    /// 结果携带完整 embedding 的检索，每条结果多传输 `dimensions` 个 f32
+    async fn search_full(&self, query: &[f32], top_k: usize) -> Result<Vec<SearchResult>> {
+        self.search_with(&SearchQuery::new(query.to_vec()).top_k(top_k).with_embeddings()).await
+    }
+
+    /// 最大边际相关性（MMR）检索：先按相似度取 `fetch_k` 条候选（含 embedding），再贪心选出 `top_k` 条，
+    /// 在与查询的相关性和与已选结果的差异之间权衡，避免返回同一段落的近重复分块，见 [`mmr_select`]
+    async fn search_mmr(&self, query: &[f32], top_k: usize, fetch_k: usize, lambda: f32) -> Result<Vec<SearchResult>> {
+        let candidates = self.search_full(query, fetch_k.max(top_k)).await?;
+        Ok(mmr_select(query, candidates, top_k, lambda))
+    }
+
+    /// 按 [`SearchQuery`] 检索；默认实现先取 `top_k` 条再在内存中过滤，支持 SQL 的存储应下推过滤条件
+    async fn search_with(&self, query: &SearchQuery) -> Result<Vec<SearchResult>> {
+        let hits = self.search(&query.vector, query.top_k).await?;
+        Ok(SearchResult::ranked(
+            hits.into_iter()
+                .filter(|hit| query.matches(&hit.record, hit.score))
+                .map(|hit| (hit.record, hit.score)),
+        ))
+    }
+
+}
+
+/// 支持按 metadata 删除、修改与遍历记录的向量库，内置存储（内存、pgvector、SQLite）均已实现
+///
+/// 与 [`VectorStore`] 分开，只用于检索的存储（如测试替身）无需实现这些方法。
+#[async_trait]
+pub trait ManagedVectorStore: VectorStore {
+    /// 删除 metadata 包含 `filter` 的全部记录（JSONB `@>` 语义），返回删除的行数
+    ///
+    /// `filter` 须为非空 JSON 对象，如 `{"document_id": "doc-001"}`；空对象会匹配所有记录，因此直接报错。
+    async fn delete_by_metadata(&self, filter: JsonValue) -> Result<u64>;
+
+    /// 整体替换记录的 metadata，不修改 embedding
+    async fn update_metadata(&self, id: &str, metadata: JsonValue) -> Result<()>;
+
+    /// 将 `patch` 中的键合并进记录的 metadata（同名键覆盖），不修改 embedding
+    async fn merge_metadata(&self, id: &str, patch: JsonValue) -> Result<()>;
+
+    /// 记录总数
+    async fn count(&self) -> Result<usize>;
+
+    /// 按 id 读取单条完整记录（含 embedding），不存在时为 `None`
+    async fn get_by_id(&self, id: &str) -> Result<Option<VectorRecord>>;
+
+    /// 分页列出完整记录（含 embedding），按 `(createat, id)` 排序保证翻页结果确定
+    ///
+    /// 用于审计、导出等需要遍历全部记录的场景；以 `offset` 递增 `limit` 反复调用，返回不足 `limit` 条时结束。
+    async fn list(&self, limit: usize, offset: usize) -> Result<Vec<VectorRecord>>;
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn record(id: &str, text: &str) -> VectorRecord {
+        VectorRecord {
+            id: id.to_string(),
+            embedding: vec![],
+            metadata: serde_json::json!({}),
+            text: Some(text.to_string()),
+            createat: None,
+            updateat: None,
+        }
+    }
+
+    #[test]
+    fn test_search_result() {
+        let mut results = SearchResult::ranked(vec![(record("a", "第一条"), 0.5), (record("b", "第二条\n结果"), 0.9)]);
+        assert_eq!(results[1].rank, 2);
+
+        SearchResult::rerank(&mut results);
+        assert_eq!(results.iter().map(|r| (r.rank, r.record.id.as_str())).collect::<Vec<_>>(), vec![(1, "b"), (2, "a")]);
+        assert_eq!(results[0].to_string(), "#1 (0.900) b — 第二条 结果");
+    }
+
+    /// 总是返回带 embedding 的记录，不支持按需读取
+    struct FullStore;
+
+    #[async_trait]
+    impl VectorStore for FullStore {
+        async fn add_vectors(&self, _vectors: Vec<VectorRecord>) -> Result<()> { Ok(()) }
+        async fn upsert_vectors(&self, _vectors: Vec<VectorRecord>) -> Result<()> { Ok(()) }
+        async fn delete_vector(&self, _ids: Vec<String>) -> Result<()> { Ok(()) }
+        async fn search(&self, query: &[f32], _top_k: usize) -> Result<Vec<SearchResult>> {
+            Ok(SearchResult::ranked(vec![(VectorRecord { embedding: query.to_vec(), ..record("a", "") }, 1.0)]))
+        }
+    }
+
+    #[tokio::test]
+    async fn test_search_lite() -> Result<()> {
+        assert!(FullStore.search_lite(&[1.0, 0.0], 1).await?[0].record.embedding.is_empty());
+        assert_eq!(FullStore.search_full(&[1.0, 0.0], 1).await?[0].record.embedding, vec![1.0, 0.0]);
+        Ok(())
+    }
+
+    #[test]
+    fn test_source_reference() {
+        let with = |metadata: JsonValue| VectorRecord { metadata, ..record("a", "") };
+        assert_eq!(
+            with(serde_json::json!({ "file_path": "docs/guide.md", "file_name": "guide.md", "line_start": 120, "line_end": 145 })).source_reference().as_deref(),
+            Some("docs/guide.md:120-145")
+        );
+        assert_eq!(
+            with(serde_json::json!({ "file_name": "guide.md", "line_start": 7, "line_end": 7 })).source_reference().as_deref(),
+            Some("guide.md:7")
+        );
+        assert_eq!(with(serde_json::json!({ "file_name": "guide.md", "line_start": null })).source_reference().as_deref(), Some("guide.md"));
+        assert_eq!(with(serde_json::json!({})).source_reference(), None);
+    }
+}
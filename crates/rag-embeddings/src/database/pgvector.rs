@@ -1,26 +1,234 @@
+use std::time::Duration;
+
+use std::collections::HashMap;
+
 use anyhow::{Context, Result};
 use async_trait::async_trait;
 use chrono::Utc;
-use sqlx::PgPool;
+use serde_json::Value as JsonValue;
+use sqlx::{FromRow, PgPool, Postgres, QueryBuilder, Transaction, postgres::PgPoolOptions};
 use uuid::Uuid;
 
-use crate::database::{VectorRecord, VectorStore};
+use crate::client::{DEFAULT_NORMALIZATION_TOLERANCE, EmbeddingClient};
+use crate::database::{DistanceMetric, IndexType, ManagedVectorStore, SearchQuery, SearchResult, TextSearchStore, VectorPrecision, VectorRecord, VectorStore};
+use crate::database::query::{SqlParam, check_delete_filter};
+use crate::dedup::chunk_content_hash;
+
+#[derive(FromRow)]
+struct ScoredRecord {
+    #[sqlx(flatten)]
+    record: VectorRecord,
+    score: f32,
+}
+
+#[derive(FromRow)]
+struct HybridRecord {
+    #[sqlx(flatten)]
+    record: VectorRecord,
+    vector_score: f32,
+    text_score: f32,
+}
+
+/// 全文检索使用的文本搜索配置：`simple` 不做词干化与停用词过滤，SKU、型号等按原样匹配
+pub const TEXT_SEARCH_CONFIG: &str = "simple";
+
+/// `add_vectors` 每条多行 INSERT 语句的默认记录数
+pub const DEFAULT_INSERT_BATCH_SIZE: usize = 1000;
+
+/// 每条记录绑定 7 个参数，Postgres 单条语句最多 65535 个参数
+const MAX_INSERT_BATCH_SIZE: usize = u16::MAX as usize / 7;
+
+/// 校验已有表的向量维度与请求的维度一致
+fn check_dimension(table_name: &str, existing: Option<i32>, requested: usize) -> Result<()> {
+    match existing {
+        Some(existing) if existing as usize != requested => anyhow::bail!(
+            "table {} exists with VECTOR({}), requested {}; use PgVectorStore::migrate_dimension to recreate the column",
+            table_name,
+            existing,
+            requested
+        ),
+        _ => Ok(()),
+    }
+}
+
+/// 连接池默认的最大连接数
+pub const DEFAULT_MAX_CONNECTIONS: u32 = 5;
+
+/// 连接池配置
+#[derive(Debug, Clone)]
+pub struct PoolConfig {
+    pub max_connections: u32,
+    /// 获取连接的超时时间
+    pub acquire_timeout: Duration,
+    /// 空闲连接的回收时间，None 表示不回收
+    pub idle_timeout: Option<Duration>,
+}
+
+impl Default for PoolConfig {
+    fn default() -> Self {
+        Self {
+            max_connections: DEFAULT_MAX_CONNECTIONS,
+            acquire_timeout: Duration::from_secs(30),
+            idle_timeout: Some(Duration::from_secs(600)),
+        }
+    }
+}
+
+impl PoolConfig {
+    pub fn with_max_connections(mut self, max_connections: u32) -> Self {
+        self.max_connections = max_connections;
+        self
+    }
+
+    pub fn with_acquire_timeout(mut self, acquire_timeout: Duration) -> Self {
+        self.acquire_timeout = acquire_timeout;
+        self
+    }
+
+    pub fn with_idle_timeout(mut self, idle_timeout: Option<Duration>) -> Self {
+        self.idle_timeout = idle_timeout;
+        self
+    }
+}
+
+/// 建表与检索选项，未指定的项使用各类型的默认值
+///
+/// ```ignore
+/// let options = StoreOptions::default()
+///     .with_metric(DistanceMetric::InnerProduct)
+///     .with_precision(VectorPrecision::Half);
+/// let store = PgVectorStore::new_with_options(pool, "vectors", 1536, options).await?;
+/// ```
+#[derive(Debug, Clone, Copy, Default)]
+pub struct StoreOptions {
+    /// 检索使用的距离度量，同一张表应始终使用同一度量，否则索引不会被使用
+    pub metric: DistanceMetric,
+    /// embedding 列上的向量索引（已存在时跳过）；IVFFlat 不在建表时创建，见 [`PgVectorStore::create_index`]
+    pub index_type: IndexType,
+    /// embedding 列的存储精度
+    pub precision: VectorPrecision,
+}
+
+impl StoreOptions {
+    pub fn with_metric(mut self, metric: DistanceMetric) -> Self {
+        self.metric = metric;
+        self
+    }
+
+    pub fn with_index_type(mut self, index_type: IndexType) -> Self {
+        self.index_type = index_type;
+        self
+    }
+
+    pub fn with_precision(mut self, precision: VectorPrecision) -> Self {
+        self.precision = precision;
+        self
+    }
+}
 
 pub struct PgVectorStore {
     pool: PgPool,
     table_name: String,
     dimensions: usize,
+    metric: DistanceMetric,
+    index_type: IndexType,
+    precision: VectorPrecision,
+    insert_batch_size: usize,
 }
 
 impl PgVectorStore {
+    /// 以默认的 [`StoreOptions`] 初始化表
     pub async fn new(pool: PgPool, table_name: &str, dimensions: usize) -> Result<Self> {
-        let store = Self {
+        Self::new_with_options(pool, table_name, dimensions, StoreOptions::default()).await
+    }
+
+    /// 初始化表（embedding 列按 `precision` 声明为 `vector(N)` 或 `halfvec(N)`），
+    /// 并在 embedding 列上按 `metric` 的操作符类创建 `index_type` 指定的向量索引（已存在时跳过）
+    ///
+    /// 检索按 `metric` 排序，返回的分数见 [`DistanceMetric`]。同一张表应始终使用同一度量，否则索引不会被使用。
+    pub async fn new_with_options(pool: PgPool, table_name: &str, dimensions: usize, options: StoreOptions) -> Result<Self> {
+        let store = Self::unchecked(pool, table_name, dimensions, options);
+        store.init_table().await?;
+        Ok(store)
+    }
+
+    /// 构造 store，不检查也不初始化表
+    fn unchecked(pool: PgPool, table_name: &str, dimensions: usize, options: StoreOptions) -> Self {
+        Self {
             pool,
             table_name: table_name.to_string(),
             dimensions,
-        };
-        store.init_table().await?;
-        Ok(store)
+            metric: options.metric,
+            index_type: options.index_type,
+            precision: options.precision,
+            insert_batch_size: DEFAULT_INSERT_BATCH_SIZE,
+        }
+    }
+
+    /// 按连接池配置创建连接池，并按 `options` 初始化表
+    pub async fn connect(
+        database_url: &str,
+        table_name: &str,
+        dimensions: usize,
+        options: StoreOptions,
+        config: PoolConfig,
+    ) -> Result<Self> {
+        let pool = PgPoolOptions::new()
+            .max_connections(config.max_connections)
+            .acquire_timeout(config.acquire_timeout)
+            .idle_timeout(config.idle_timeout)
+            .connect(database_url)
+            .await
+            .with_context(|| format!("Failed to connect to {}", database_url))?;
+        Self::new_with_options(pool, table_name, dimensions, options).await
+    }
+
+    /// 以默认的距离度量、向量索引、存储精度与连接池配置（仅指定最大连接数）连接数据库并初始化表
+    ///
+    /// 需要自定义度量、索引、精度或超时时用 [`connect`](Self::connect)，已有连接池时用 [`new`](Self::new)。
+    pub async fn open(database_url: &str, table_name: &str, dimensions: usize, max_connections: u32) -> Result<Self> {
+        let config = PoolConfig::default().with_max_connections(max_connections);
+        Self::connect(database_url, table_name, dimensions, StoreOptions::default(), config).await
+    }
+
+    /// 共享底层连接池（如供其他表的 store 复用）
+    pub fn pool(&self) -> &PgPool {
+        &self.pool
+    }
+
+    /// 绑定查询用的嵌入客户端，得到支持 `search_by_text` 的向量库
+    pub fn with_embedding_client<C: EmbeddingClient>(self, embedding_client: C) -> TextSearchStore<Self, C> {
+        TextSearchStore::new(self, embedding_client)
+    }
+
+    pub fn table_name(&self) -> &str {
+        &self.table_name
+    }
+
+    pub fn dimensions(&self) -> usize {
+        self.dimensions
+    }
+
+    pub fn metric(&self) -> DistanceMetric {
+        self.metric
+    }
+
+    pub fn index_type(&self) -> IndexType {
+        self.index_type
+    }
+
+    pub fn precision(&self) -> VectorPrecision {
+        self.precision
+    }
+
+    /// 设置插入时每条多行 INSERT 语句的记录数，受 Postgres 参数个数限制最大约 9000
+    pub fn with_insert_batch_size(mut self, insert_batch_size: usize) -> Self {
+        self.insert_batch_size = insert_batch_size.clamp(1, MAX_INSERT_BATCH_SIZE);
+        self
+    }
+
+    pub fn insert_batch_size(&self) -> usize {
+        self.insert_batch_size
     }
 
     async fn init_table(&self) -> Result<()> {
@@ -34,13 +242,15 @@ impl PgVectorStore {
             r#"
             CREATE TABLE IF NOT EXISTS {} (
                 id UUID PRIMARY KEY,
-                embedding VECTOR({}),
+                embedding {}({}),
                 metadata JSONB DEFAULT '{{}}'::jsonb,
                 text TEXT,
+                content_hash TEXT,
                 createat TIMESTAMPTZ DEFAULT NOW(),
                 updateat TIMESTAMPTZ DEFAULT NOW()
             );"#,
             self.table_name,
+            self.precision.column_type(),
             self.dimensions,
         );
         
@@ -48,18 +258,500 @@ impl PgVectorStore {
             .execute(&self.pool)
             .await
             .context("Failed to init vector table")?;
-        
+
+        // 旧表补充 content_hash 列（规范化文本的 sha256），用于跨文档分块去重
+        sqlx::query(&format!(
+            "ALTER TABLE {} ADD COLUMN IF NOT EXISTS content_hash TEXT",
+            self.table_name
+        ))
+        .execute(&self.pool)
+        .await?;
+        sqlx::query(&format!(
+            "CREATE INDEX IF NOT EXISTS {}_content_hash_idx ON {} (content_hash)",
+            self.table_name.replace('.', "_"),
+            self.table_name
+        ))
+        .execute(&self.pool)
+        .await
+        .context("Failed to create content_hash index")?;
+
+        // 由 text 自动生成的全文索引列，供 search_hybrid 使用；旧表补充后已有记录也会被填充
+        sqlx::query(&format!(
+            "ALTER TABLE {} ADD COLUMN IF NOT EXISTS text_search TSVECTOR GENERATED ALWAYS AS (to_tsvector('{}', coalesce(text, ''))) STORED",
+            self.table_name,
+            TEXT_SEARCH_CONFIG
+        ))
+        .execute(&self.pool)
+        .await
+        .context("Failed to add text_search column")?;
+        sqlx::query(&format!(
+            "CREATE INDEX IF NOT EXISTS {}_text_search_idx ON {} USING gin (text_search)",
+            self.table_name.replace('.', "_"),
+            self.table_name
+        ))
+        .execute(&self.pool)
+        .await
+        .context("Failed to create text_search index")?;
+
+        // 按文档内顺序取相邻分块（见 neighbors）
+        sqlx::query(&format!(
+            "CREATE INDEX IF NOT EXISTS {}_document_order_idx ON {} ((metadata->>'document_id'), ((metadata->>'order')::bigint))",
+            self.table_name.replace('.', "_"),
+            self.table_name
+        ))
+        .execute(&self.pool)
+        .await
+        .context("Failed to create document order index")?;
+
+        // 表已存在时 CREATE TABLE IF NOT EXISTS 不会修改列定义，需显式校验维度与精度
+        let declared = self.declared_column().await?;
+        if let Some((column_type, _)) = &declared
+            && column_type != self.precision.column_type()
+        {
+            anyhow::bail!(
+                "table {} exists with {} embeddings, requested {}; use PgVectorStore::migrate_dimension to recreate the column",
+                self.table_name,
+                column_type,
+                self.precision.column_type()
+            );
+        }
+        check_dimension(&self.table_name, declared.map(|(_, dimension)| dimension), self.dimensions)?;
+        if self.index_type.builds_on_empty_table() {
+            self.create_vector_index().await?;
+        }
         Ok(())
     }
 
-}
+    /// embedding 列与查询向量（`$1`）的原始距离表达式
+    fn distance_sql(&self) -> String {
+        format!("embedding {} $1::{}", self.metric.operator(), self.precision.column_type())
+    }
 
-#[async_trait]
-impl VectorStore for PgVectorStore {
-    async fn add_vectors(&self, vectors: Vec<VectorRecord>) -> Result<()> {
+    /// 创建配置的向量索引（已存在时跳过），用于 IVFFlat 等需要在写入数据后建立的索引
+    ///
+    /// IVFFlat 的聚类中心取自表中已有数据，空表时报错；数据量变化较大后可删除索引再调用以重建。
+    pub async fn create_index(&self) -> Result<()> {
+        if !self.index_type.builds_on_empty_table() && self.count().await? == 0 {
+            anyhow::bail!(
+                "table {} is empty; insert vectors before creating the {:?} index",
+                self.table_name,
+                self.index_type
+            );
+        }
+        self.create_vector_index().await
+    }
+
+    /// 创建近似最近邻索引，操作符类与检索的距离度量一致
+    async fn create_vector_index(&self) -> Result<()> {
+        let Some(sql) = self.index_type.create_sql(&self.table_name, self.metric, self.precision) else {
+            return Ok(());
+        };
+        let max_dimensions = self.precision.max_index_dimensions();
+        if self.dimensions > max_dimensions {
+            println!(
+                "警告: {} 维超过 pgvector {} 索引支持的 {} 维，表 {} 不建向量索引，检索为全表扫描",
+                self.dimensions, self.precision.column_type(), max_dimensions, self.table_name
+            );
+            return Ok(());
+        }
+        sqlx::query(&sql)
+            .execute(&self.pool)
+            .await
+            .context("Failed to create vector index")?;
+        Ok(())
+    }
+
+    /// 查询表中 embedding 列声明的类型名与维度（vector / halfvec 类型的 atttypmod 即维度），表不存在时为 `None`
+    async fn declared_column(&self) -> Result<Option<(String, i32)>> {
+        let column: Option<(String, i32)> = sqlx::query_as(
+            r#"SELECT t.typname::text, a.atttypmod FROM pg_attribute a
+               JOIN pg_type t ON t.oid = a.atttypid
+               WHERE a.attrelid = to_regclass($1) AND a.attname = 'embedding' AND NOT a.attisdropped"#,
+        )
+        .bind(&self.table_name)
+        .fetch_optional(&self.pool)
+        .await?;
+
+        Ok(column.filter(|(_, typmod)| *typmod > 0))
+    }
+
+    /// 将已存在的表迁移到新的向量维度或存储精度
+    ///
+    /// 旧维度的向量无法转换，迁移会**删除表中全部记录**后修改列类型，之后需要重新入库。
+    pub async fn migrate_dimension(pool: PgPool, table_name: &str, dimensions: usize, options: StoreOptions) -> Result<Self> {
+        let store = Self::unchecked(pool, table_name, dimensions, options);
+
+        if let Some((column_type, existing)) = store.declared_column().await?
+            && (existing as usize != dimensions || column_type != store.precision.column_type())
+        {
+            let mut tx = store.pool.begin().await?;
+            sqlx::query(&format!("DELETE FROM {}", store.table_name))
+                .execute(&mut *tx)
+                .await?;
+            sqlx::query(&format!(
+                "ALTER TABLE {} ALTER COLUMN embedding TYPE {}({})",
+                store.table_name, store.precision.column_type(), dimensions
+            ))
+            .execute(&mut *tx)
+            .await
+            .context("Failed to migrate embedding column")?;
+            tx.commit().await?;
+        }
+
+        store.init_table().await?;
+        Ok(store)
+    }
+
+    /// 查询文档入库时记录的源文件哈希（`metadata.file_hash`，与分块内容哈希 `content_hash` 列无关）
+    pub async fn document_file_hash(&self, document_id: &str) -> Result<Option<String>> {
+        // 旧版本写在 metadata.content_hash 中
+        let hash: Option<Option<String>> = sqlx::query_scalar(&format!(
+            r#"SELECT COALESCE(metadata->>'file_hash', metadata->>'content_hash') FROM "{}"
+               WHERE metadata->>'document_id' = $1
+               LIMIT 1"#,
+            self.table_name
+        ))
+        .bind(document_id)
+        .fetch_optional(&self.pool)
+        .await?;
+
+        Ok(hash.flatten())
+    }
+
+    /// 按分块内容哈希查找已入库的记录，返回 content_hash -> id
+    pub async fn find_by_content_hashes(&self, hashes: &[String]) -> Result<HashMap<String, String>> {
+        if hashes.is_empty() {
+            return Ok(HashMap::new());
+        }
+
+        let rows: Vec<(String, Uuid)> = sqlx::query_as(&format!(
+            r#"SELECT DISTINCT ON (content_hash) content_hash, id FROM "{}"
+               WHERE content_hash = ANY($1)
+               ORDER BY content_hash, createat"#,
+            self.table_name
+        ))
+        .bind(hashes)
+        .fetch_all(&self.pool)
+        .await?;
+
+        Ok(rows.into_iter().map(|(hash, id)| (hash, id.to_string())).collect())
+    }
+
+    /// 在共享分块的 `metadata.document_ids` 中登记引用它的文档（已登记则不变）
+    pub async fn add_document_reference(&self, id: &str, document_id: &str) -> Result<()> {
+        let uuid = Uuid::parse_str(id).context(format!("Invalid UUID: {}", id))?;
+        sqlx::query(&format!(
+            r#"UPDATE "{0}"
+               SET metadata = jsonb_set(
+                     metadata,
+                     '{{document_ids}}',
+                     COALESCE(metadata->'document_ids', jsonb_build_array(metadata->'document_id')) || to_jsonb($2::text)
+                   ),
+                   updateat = NOW()
+               WHERE id = $1
+                 AND NOT COALESCE(metadata->'document_ids', jsonb_build_array(metadata->'document_id')) @> to_jsonb($2::text)"#,
+            self.table_name
+        ))
+        .bind(uuid)
+        .bind(document_id)
+        .execute(&self.pool)
+        .await?;
+
+        Ok(())
+    }
+
+    /// 以 `value_expr`（$1 为传入的 JSON）更新单条记录的 metadata 与 updateat
+    async fn write_metadata(&self, id: &str, metadata: JsonValue, value_expr: &str) -> Result<()> {
+        let uuid = Uuid::parse_str(id).context(format!("Invalid UUID: {}", id))?;
+        let result = sqlx::query(&format!(
+            r#"UPDATE "{}" SET metadata = {}, updateat = NOW() WHERE id = $2"#,
+            self.table_name,
+            value_expr
+        ))
+        .bind(metadata)
+        .bind(uuid)
+        .execute(&self.pool)
+        .await?;
+
+        if result.rows_affected() == 0 {
+            anyhow::bail!("Vector {} not found", id);
+        }
+        Ok(())
+    }
+
+    /// 列出库中每个文档的分块数 (document_id, count)，按分块数降序
+    ///
+    /// 用于查看语料构成，分块数为 0 或明显偏少的文档可能入库失败。没有 document_id 的记录不计入。
+    pub async fn document_summary(&self) -> Result<Vec<(String, u64)>> {
+        let rows: Vec<(String, i64)> = sqlx::query_as(&format!(
+            r#"SELECT metadata->>'document_id' AS document_id, COUNT(*) AS chunks
+               FROM "{}"
+               WHERE metadata ? 'document_id'
+               GROUP BY 1
+               ORDER BY 2 DESC, 1"#,
+            self.table_name
+        ))
+        .fetch_all(&self.pool)
+        .await?;
+
+        Ok(rows.into_iter().map(|(id, count)| (id, count as u64)).collect())
+    }
+
+    /// 取文档中阅读顺序（`metadata.order`）落在 `[order - window, order + window]` 内的分块，按顺序升序
+    ///
+    /// 结果包含 `order` 处的分块本身，用于不加载 NodeTree 时按命中扩展上下文。
+    /// 仅匹配 `metadata.document_id`，跨文档共享的分块按其首次入库的文档计算。
+    pub async fn neighbors(&self, document_id: &str, order: i64, window: usize) -> Result<Vec<VectorRecord>> {
+        let window = window as i64;
+        let records: Vec<VectorRecord> = sqlx::query_as(&format!(
+            r#"SELECT id::text, embedding::real[] AS embedding, metadata, text, createat, updateat
+               FROM "{}"
+               WHERE metadata->>'document_id' = $1
+                 AND (metadata->>'order')::bigint BETWEEN $2 AND $3
+               ORDER BY (metadata->>'order')::bigint"#,
+            self.table_name
+        ))
+        .bind(document_id)
+        .bind(order.saturating_sub(window))
+        .bind(order.saturating_add(window))
+        .fetch_all(&self.pool)
+        .await?;
+
+        Ok(records)
+    }
+
+    /// 按 id 读取完整记录（含 embedding），不存在的 id 被忽略，结果顺序与 `ids` 无关
+    ///
+    /// 用于为 [`VectorStore::search_lite`] 的结果按需补全向量。
+    pub async fn get_by_ids(&self, ids: &[String]) -> Result<Vec<VectorRecord>> {
+        if ids.is_empty() {
+            return Ok(Vec::new());
+        }
+
+        let uuids = ids.iter()
+            .map(|id| Uuid::parse_str(id).context(format!("Invalid UUID: {}", id)))
+            .collect::<Result<Vec<_>>>()?;
+        let records = sqlx::query_as::<_, VectorRecord>(&format!(
+            r#"SELECT id::text, embedding::real[] AS embedding, metadata, text, createat, updateat
+               FROM "{}"
+               WHERE id = ANY($1)"#,
+            self.table_name
+        ))
+        .bind(uuids)
+        .fetch_all(&self.pool)
+        .await?;
+
+        Ok(records)
+    }
+
+    /// 按 metadata 过滤的检索：`filter` 为 JSON 对象，如 `{"document_id": "doc-001", "is_image": false}`，
+    /// 以参数化的 `metadata @> $filter` 与距离排序组合，见 [`SearchQuery::filter_contains`]
+    pub async fn search_filtered(&self, query: &[f32], top_k: usize, filter: JsonValue) -> Result<Vec<SearchResult>> {
+        if !filter.is_object() {
+            anyhow::bail!("Search filter must be a JSON object");
+        }
+        self.search_with(&SearchQuery::new(query.to_vec()).top_k(top_k).filter_contains(filter)).await
+    }
+
+    /// 混合检索：融合向量相似度与全文检索（`ts_rank_cd`）的得分，适合 SKU、型号等需要精确关键词匹配的查询
+    ///
+    /// 向量检索与全文检索各取 `top_k` 条候选，对两路候选的并集计算两种得分后按
+    /// `alpha * 向量得分 + (1 - alpha) * 全文得分` 降序取 `top_k` 条，结果的 `score` 为融合后的得分：
+    /// - 向量得分为经 [`DistanceMetric::normalize`] 归一化的 [0, 1] 相似度；
+    /// - 全文得分为 `ts_rank_cd` 除以候选中的最大值，关键词匹配最好的候选为 1，未匹配的为 0。
+    ///
+    /// `query_text` 按 [`TEXT_SEARCH_CONFIG`] 分词后以 OR 连接，命中任一词即参与排序、命中越多得分越高。
+    /// `alpha` 取值 [0, 1]：1 等同纯向量检索，0 等同纯全文检索。结果不含 embedding。
+    pub async fn search_hybrid(&self, query_text: &str, query_vec: &[f32], top_k: usize, alpha: f32) -> Result<Vec<SearchResult>> {
+        if query_vec.len() != self.dimensions {
+            anyhow::bail!(
+                "Query dim mismatch: expected {}, got {}",
+                self.dimensions,
+                query_vec.len()
+            );
+        }
+        let alpha = alpha.clamp(0.0, 1.0);
+
+        let distance_expr = self.distance_sql();
+        let sql = format!(
+            r#"WITH q AS (
+                   SELECT NULLIF(replace(plainto_tsquery('{config}', $2)::text, '&', '|'), '')::tsquery AS query
+               ),
+               vector_hits AS (
+                   SELECT id FROM "{table}" ORDER BY {distance} LIMIT $3
+               ),
+               text_hits AS (
+                   SELECT id FROM "{table}", q
+                   WHERE text_search @@ q.query
+                   ORDER BY ts_rank_cd(text_search, q.query) DESC
+                   LIMIT $3
+               )
+               SELECT id::text, ARRAY[]::real[] AS embedding, metadata, text, createat, updateat,
+                      ({score})::real AS vector_score,
+                      COALESCE(ts_rank_cd(text_search, q.query), 0)::real AS text_score
+               FROM "{table}", q
+               WHERE id IN (SELECT id FROM vector_hits UNION SELECT id FROM text_hits)"#,
+            config = TEXT_SEARCH_CONFIG,
+            table = self.table_name,
+            distance = distance_expr,
+            score = self.metric.score_sql(&distance_expr),
+        );
+
+        let rows = sqlx::query_as::<_, HybridRecord>(&sql)
+            .bind(query_vec)
+            .bind(query_text)
+            .bind(top_k as i64)
+            .fetch_all(&self.pool)
+            .await?;
+
+        let max_text_score = rows.iter().map(|row| row.text_score).fold(0.0, f32::max);
+        let mut hits: Vec<(VectorRecord, f32)> = rows.into_iter()
+            .map(|row| {
+                let text_score = if max_text_score > 0.0 { row.text_score / max_text_score } else { 0.0 };
+                (row.record, alpha * row.vector_score + (1.0 - alpha) * text_score)
+            })
+            .collect();
+        hits.sort_by(|a, b| b.1.total_cmp(&a.1));
+        hits.truncate(top_k);
+
+        Ok(SearchResult::ranked(hits))
+    }
+
+    /// 按文档分组检索：返回最佳分块最相似的 `max_docs` 个文档，每个文档附带其最相似的 `top_k_per_doc` 个分块
+    ///
+    /// 文档按最佳分块的相似度降序，文档内的分块按相似度降序、`rank` 从 1 开始编号。
+    /// 用窗口函数（`ROW_NUMBER() OVER (PARTITION BY document_id ...)`）在一次查询中完成分组，
+    /// 需计算全表距离，不走向量索引。没有 `metadata.document_id` 的记录不参与；结果不含 embedding。
+    pub async fn search_grouped(&self, query: &[f32], top_k_per_doc: usize, max_docs: usize) -> Result<Vec<(String, Vec<SearchResult>)>> {
+        if query.len() != self.dimensions {
+            anyhow::bail!(
+                "Query dim mismatch: expected {}, got {}",
+                self.dimensions,
+                query.len()
+            );
+        }
+
+        let distance_expr = self.distance_sql();
+        let score_expr = self.metric.score_sql(&distance_expr);
+        let rows: Vec<ScoredRecord> = sqlx::query_as(&format!(
+            r#"WITH ranked AS (
+                   SELECT id, metadata, text, createat, updateat,
+                          metadata->>'document_id' AS document_id,
+                          {distance} AS distance,
+                          {}::real AS score,
+                          ROW_NUMBER() OVER (
+                              PARTITION BY metadata->>'document_id'
+                              ORDER BY {distance}
+                          ) AS chunk_rank
+                   FROM "{}"
+                   WHERE metadata ? 'document_id'
+               ),
+               top_docs AS (
+                   SELECT document_id, distance AS best
+                   FROM ranked
+                   WHERE chunk_rank = 1
+                   ORDER BY distance
+                   LIMIT $3
+               )
+               SELECT r.id::text, ARRAY[]::real[] AS embedding, r.metadata, r.text, r.createat, r.updateat, r.score
+               FROM ranked r
+               JOIN top_docs d ON d.document_id = r.document_id
+               WHERE r.chunk_rank <= $2
+               ORDER BY d.best, d.document_id, r.chunk_rank"#,
+            score_expr,
+            self.table_name,
+            distance = distance_expr
+        ))
+        .bind(query)
+        .bind(top_k_per_doc as i64)
+        .bind(max_docs as i64)
+        .fetch_all(&self.pool)
+        .await?;
+
+        // 同一文档的分块在结果中相邻
+        let mut groups: Vec<(String, Vec<(VectorRecord, f32)>)> = Vec::new();
+        for row in rows {
+            let document_id = row.record.metadata["document_id"].as_str().unwrap_or_default().to_string();
+            match groups.last_mut() {
+                Some((id, hits)) if *id == document_id => hits.push((row.record, row.score)),
+                _ => groups.push((document_id, vec![(row.record, row.score)])),
+            }
+        }
+        Ok(groups.into_iter().map(|(id, hits)| (id, SearchResult::ranked(hits))).collect())
+    }
+
+    /// 删除文档的全部记录，返回删除的行数
+    ///
+    /// 跨文档去重后仍被其他文档引用的共享分块不删除，只从其 `metadata.document_ids` 中移除该文档。
+    pub async fn delete_document(&self, document_id: &str) -> Result<u64> {
         let mut tx = self.pool.begin().await?;
+        let deleted = self.release_document(&mut tx, document_id, &JsonValue::Object(Default::default())).await?;
+        tx.commit().await?;
+        Ok(deleted)
+    }
 
-        for vec in vectors {
+    /// 解除文档对其记录（metadata 还须包含 `filter`，`{}` 表示不限）的引用，返回删除的行数
+    ///
+    /// `metadata.document_ids` 中还有其他文档的共享分块只移除该文档，`document_id` 为该文档时改为剩余的第一个文档；
+    /// 其余属于该文档的记录直接删除。
+    async fn release_document(&self, tx: &mut Transaction<'_, Postgres>, document_id: &str, filter: &JsonValue) -> Result<u64> {
+        sqlx::query(&format!(
+            r#"UPDATE "{}"
+               SET metadata = CASE
+                     WHEN metadata->>'document_id' = $1
+                       THEN jsonb_set(metadata, '{{document_id}}', ((metadata->'document_ids') - $1)->0)
+                     ELSE metadata
+                   END || jsonb_build_object('document_ids', (metadata->'document_ids') - $1),
+                   updateat = NOW()
+               WHERE metadata->'document_ids' ? $1
+                 AND jsonb_array_length((metadata->'document_ids') - $1) > 0
+                 AND metadata @> $2::jsonb"#,
+            self.table_name
+        ))
+        .bind(document_id)
+        .bind(filter)
+        .execute(&mut **tx)
+        .await?;
+
+        let result = sqlx::query(&format!(
+            r#"DELETE FROM "{}" WHERE metadata->>'document_id' = $1 AND metadata @> $2::jsonb"#,
+            self.table_name
+        ))
+        .bind(document_id)
+        .bind(filter)
+        .execute(&mut **tx)
+        .await?;
+
+        Ok(result.rows_affected())
+    }
+
+    /// 将表中所有未归一化的 embedding 原位 L2 归一化，返回更新的行数
+    ///
+    /// 用于把其他流程写入的未归一化向量迁移到本 crate 假定的归一化约定。在数据库内完成，
+    /// 不传输向量；已归一化的向量与零向量不改动。需要 pgvector >= 0.7（`l2_normalize`）。
+    pub async fn renormalize_all(&self) -> Result<u64> {
+        let result = sqlx::query(&format!(
+            r#"UPDATE "{}"
+               SET embedding = l2_normalize(embedding), updateat = NOW()
+               WHERE {norm}(embedding) > 1e-8
+                 AND abs({norm}(embedding) - 1) >= $1"#,
+            self.table_name,
+            norm = self.precision.norm_function()
+        ))
+        .bind(DEFAULT_NORMALIZATION_TOLERANCE as f64)
+        .execute(&self.pool)
+        .await
+        .context("Failed to renormalize embeddings")?;
+
+        Ok(result.rows_affected())
+    }
+
+    /// 在事务中插入记录，按 `insert_batch_size` 分批执行多行 INSERT
+    ///
+    /// 写入前逐条校验 id 与维度，任一条不符时不执行任何语句；id 已存在时报错，由调用方回滚事务。
+    async fn insert_records(&self, tx: &mut Transaction<'_, Postgres>, records: &[VectorRecord]) -> Result<()> {
+        let mut rows = Vec::with_capacity(records.len());
+        for vec in records {
             let id = Uuid::parse_str(&vec.id)
                 .context(format!("Invalid UUID: {}", vec.id))?;
             if vec.embedding.len() != self.dimensions {
@@ -69,25 +761,68 @@ impl VectorStore for PgVectorStore {
                     vec.embedding.len()
                 );
             }
-            let now = Utc::now();
-            let createat = vec.createat.unwrap_or(now);
-            let updateat = vec.updateat.unwrap_or(now);
+            rows.push((id, vec));
+        }
 
-            sqlx::query(&format!(
-                r#"INSERT INTO "{}" (id, embedding, metadata, text, createat, updateat) 
-                   VALUES ($1, $2, $3, $4, $5, $6)"#,
+        let now = Utc::now();
+        for batch in rows.chunks(self.insert_batch_size) {
+            let mut query = QueryBuilder::<Postgres>::new(format!(
+                r#"INSERT INTO "{}" (id, embedding, metadata, text, content_hash, createat, updateat) "#,
                 self.table_name
-            ))
-            .bind(id)
-            .bind(&vec.embedding)
-            .bind(&vec.metadata)
-            .bind(&vec.text)
-            .bind(createat)
-            .bind(updateat)
-            .execute(&mut *tx)
-            .await?;
+            ));
+            query.push_values(batch, |mut row, (id, vec)| {
+                row.push_bind(*id)
+                    .push_bind(&vec.embedding)
+                    .push_bind(&vec.metadata)
+                    .push_bind(&vec.text)
+                    .push_bind(vec.text.as_deref().map(chunk_content_hash))
+                    .push_bind(vec.createat.unwrap_or(now))
+                    .push_bind(vec.updateat.unwrap_or(now));
+            });
+            query.build().execute(&mut **tx).await?;
         }
+        Ok(())
+    }
+
+    /// 原子地替换文档：在同一事务中删除文档的全部旧记录并插入 `records`
+    ///
+    /// 与 [`delete_document`](Self::delete_document) 相同，仍被其他文档引用的共享分块只解除该文档的引用。
+    ///
+    /// 任一步失败（如维度不符、id 冲突）时整体回滚，旧记录保持不变，检索不会看到删除一半或空的文档。
+    /// `records` 须已带 embedding，且 `metadata.document_id` 均为 `document_id`，否则不执行任何操作直接报错。
+    pub async fn reindex_document(&self, document_id: &str, records: Vec<VectorRecord>) -> Result<ReindexSummary> {
+        if let Some(record) = records.iter()
+            .find(|r| r.metadata.get("document_id").and_then(|v| v.as_str()) != Some(document_id))
+        {
+            anyhow::bail!("Record {} does not belong to document {}", record.id, document_id);
+        }
+
+        let mut tx = self.pool.begin().await?;
+        let deleted = self.release_document(&mut tx, document_id, &JsonValue::Object(Default::default())).await?;
 
+        self.insert_records(&mut tx, &records)
+            .await
+            .with_context(|| format!("Failed to reindex document {}", document_id))?;
+        tx.commit().await?;
+
+        Ok(ReindexSummary { deleted, inserted: records.len() as u64 })
+    }
+}
+
+/// [`PgVectorStore::reindex_document`] 的结果
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct ReindexSummary {
+    /// 删除的旧记录数
+    pub deleted: u64,
+    /// 插入的新记录数
+    pub inserted: u64,
+}
+
+#[async_trait]
+impl VectorStore for PgVectorStore {
+    async fn add_vectors(&self, vectors: Vec<VectorRecord>) -> Result<()> {
+        let mut tx = self.pool.begin().await?;
+        self.insert_records(&mut tx, &vectors).await?;
         tx.commit().await?;
         Ok(())
     }
@@ -105,12 +840,13 @@ impl VectorStore for PgVectorStore {
             let updateat = vec.updateat.unwrap_or(now);
 
             sqlx::query(&format!(
-                r#"INSERT INTO "{}" (id, embedding, metadata, text, createat, updateat)
-                   VALUES ($1, $2, $3, $4, $5, $6)
+                r#"INSERT INTO "{}" (id, embedding, metadata, text, content_hash, createat, updateat)
+                   VALUES ($1, $2, $3, $4, $5, $6, $7)
                    ON CONFLICT (id) DO UPDATE SET
                      embedding = EXCLUDED.embedding,
                      metadata = EXCLUDED.metadata,
                      text = EXCLUDED.text,
+                     content_hash = EXCLUDED.content_hash,
                      updateat = EXCLUDED.updateat"#,
                 self.table_name
             ))
@@ -118,6 +854,7 @@ impl VectorStore for PgVectorStore {
             .bind(&vec.embedding)
             .bind(&vec.metadata)
             .bind(&vec.text)
+            .bind(vec.text.as_deref().map(chunk_content_hash))
             .bind(createat)
             .bind(updateat)
             .execute(&mut *tx)
@@ -150,24 +887,140 @@ impl VectorStore for PgVectorStore {
         Ok(())
     }
 
-    async fn search(&self) -> Result<Vec<VectorRecord>> {
-        let rows = sqlx::query_as::<_, VectorRecord>(&format!(
-            r#"SELECT id::text, embedding, metadata, text, createat, updateat 
-               FROM "{}""#,
+    async fn search(&self, query: &[f32], top_k: usize) -> Result<Vec<SearchResult>> {
+        self.search_with(&SearchQuery::new(query.to_vec()).top_k(top_k)).await
+    }
+
+    async fn search_with(&self, query: &SearchQuery) -> Result<Vec<SearchResult>> {
+        if query.vector.len() != self.dimensions {
+            anyhow::bail!(
+                "Query dim mismatch: expected {}, got {}",
+                self.dimensions,
+                query.vector.len()
+            );
+        }
+
+        // 原始距离按度量归一化为 [0, 1] 的相似度
+        let distance_expr = self.distance_sql();
+        let score_expr = self.metric.score_sql(&distance_expr);
+        let (where_clause, params) = query.where_clause(&score_expr, 3);
+        // 不需要向量时不读取 embedding 列，避免每条结果传输 dimensions 个 f32
+        let embedding_expr = if query.include_embeddings { "embedding::real[]" } else { "ARRAY[]::real[]" };
+        let sql = format!(
+            r#"SELECT id::text, {} AS embedding, metadata, text, createat, updateat,
+                      {}::real AS score
+               FROM "{}"
+               WHERE {}
+               ORDER BY {}
+               LIMIT $2"#,
+            embedding_expr,
+            score_expr,
+            self.table_name,
+            where_clause,
+            distance_expr
+        );
+
+        let mut q = sqlx::query_as::<_, ScoredRecord>(&sql)
+            .bind(&query.vector)
+            .bind(query.top_k as i64);
+        for param in params {
+            q = match param {
+                SqlParam::Text(value) => q.bind(value),
+                SqlParam::Float(value) => q.bind(value as f64),
+                SqlParam::Json(value) => q.bind(value),
+            };
+        }
+        let rows = q.fetch_all(&self.pool).await?;
+
+        Ok(SearchResult::ranked(rows.into_iter().map(|row| (row.record, row.score))))
+    }
+}
+
+#[async_trait]
+impl ManagedVectorStore for PgVectorStore {
+    /// 含 `document_id` 的过滤条件按 [`delete_document`](PgVectorStore::delete_document) 的方式处理：
+    /// 仍被其他文档引用的共享分块只解除该文档的引用。按 `document_ids` 过滤会绕过引用关系，直接报错。
+    async fn delete_by_metadata(&self, filter: JsonValue) -> Result<u64> {
+        check_delete_filter(&filter)?;
+        let mut rest = filter.as_object().cloned().unwrap_or_default();
+        if rest.contains_key("document_ids") {
+            anyhow::bail!("Delete filter must not contain document_ids; use delete_document to release shared chunks");
+        }
+        match rest.remove("document_id") {
+            Some(JsonValue::String(document_id)) => {
+                let mut tx = self.pool.begin().await?;
+                let deleted = self.release_document(&mut tx, &document_id, &JsonValue::Object(rest)).await?;
+                tx.commit().await?;
+                return Ok(deleted);
+            }
+            Some(other) => anyhow::bail!("Delete filter document_id must be a string, got {}", other),
+            None => {}
+        }
+
+        let result = sqlx::query(&format!(
+            r#"DELETE FROM "{}" WHERE metadata @> $1::jsonb"#,
             self.table_name
         ))
+        .bind(filter)
+        .execute(&self.pool)
+        .await?;
+
+        Ok(result.rows_affected())
+    }
+
+    async fn update_metadata(&self, id: &str, metadata: JsonValue) -> Result<()> {
+        self.write_metadata(id, metadata, "$1").await
+    }
+
+    async fn merge_metadata(&self, id: &str, patch: JsonValue) -> Result<()> {
+        if !patch.is_object() {
+            anyhow::bail!("Metadata patch must be a JSON object");
+        }
+        self.write_metadata(id, patch, "metadata || $1").await
+    }
+
+    async fn count(&self) -> Result<usize> {
+        let count: i64 = sqlx::query_scalar(&format!(r#"SELECT COUNT(*) FROM "{}""#, self.table_name))
+            .fetch_one(&self.pool)
+            .await?;
+        Ok(count as usize)
+    }
+
+    async fn list(&self, limit: usize, offset: usize) -> Result<Vec<VectorRecord>> {
+        let records = sqlx::query_as::<_, VectorRecord>(&format!(
+            r#"SELECT id::text, embedding::real[] AS embedding, metadata, text, createat, updateat
+               FROM "{}"
+               ORDER BY createat, id
+               LIMIT $1 OFFSET $2"#,
+            self.table_name
+        ))
+        .bind(limit as i64)
+        .bind(offset as i64)
         .fetch_all(&self.pool)
         .await?;
 
-        Ok(rows)
+        Ok(records)
     }
-}
 
+    async fn get_by_id(&self, id: &str) -> Result<Option<VectorRecord>> {
+        let uuid = Uuid::parse_str(id).context(format!("Invalid UUID: {}", id))?;
+        let record = sqlx::query_as::<_, VectorRecord>(&format!(
+            r#"SELECT id::text, embedding::real[] AS embedding, metadata, text, createat, updateat
+               FROM "{}"
+               WHERE id = $1"#,
+            self.table_name
+        ))
+        .bind(uuid)
+        .fetch_optional(&self.pool)
+        .await?;
+
+        Ok(record)
+    }
+}
 
 #[cfg(test)]
 mod tests {
     use super::*;
-    use sqlx::postgres::PgPoolOptions;
     #[tokio::test]
     async fn test_add_vector() { 
         let pool = PgPoolOptions::new()
@@ -176,7 +1029,7 @@ mod tests {
             .await
             .expect("Failed to connect");
 
-        let store = PgVectorStore::new(pool,"test1",3)
+        let store = PgVectorStore::new_with_options(pool, "test1", 3, StoreOptions::default().with_index_type(IndexType::None))
             .await
             .expect("Failed to create PgvectorStore");
 
@@ -202,11 +1055,610 @@ mod tests {
             .await
             .expect("failed to connect");
 
-        let store = PgVectorStore::new(pool,"test1",3)
+        let store = PgVectorStore::new_with_options(pool, "test1", 3, StoreOptions::default().with_index_type(IndexType::None))
             .await
             .expect("Faile to create Pgstore");
 
         let maybe = store.delete_vector(vec!["00000000-0000-0000-0000-000000000001".to_string()]).await.unwrap();
         println!("maybe: {:?}",maybe);
     }
-}
\ No newline at end of file
+
+    #[test]
+    fn test_check_dimension() {
+        assert!(check_dimension("vectors", Some(1536), 1536).is_ok());
+        assert!(check_dimension("vectors", None, 2560).is_ok());
+        let err = check_dimension("vectors", Some(1536), 2560).unwrap_err();
+        assert!(err.to_string().contains("table vectors exists with VECTOR(1536), requested 2560"));
+    }
+
+    #[tokio::test]
+    async fn test_vector_index() -> Result<()> {
+        let index_def = |store: &PgVectorStore, name: String| {
+            let pool = store.pool().clone();
+            async move {
+                sqlx::query_scalar::<_, String>("SELECT indexdef FROM pg_indexes WHERE indexname = $1")
+                    .bind(name)
+                    .fetch_optional(&pool)
+                    .await
+            }
+        };
+
+        let store = PgVectorStore::open("postgres:///rag_db", "test_vector_index", 3, DEFAULT_MAX_CONNECTIONS).await?;
+        let name = store.index_type().index_name(store.table_name()).unwrap();
+        let def = index_def(&store, name).await?.expect("HNSW 索引应在建表时创建");
+        assert!(def.contains("USING hnsw (embedding vector_cosine_ops)"), "{}", def);
+
+        let store = PgVectorStore::new_with_options(store.pool().clone(), "test_vector_index_none", 3, StoreOptions::default().with_index_type(IndexType::None)).await?;
+        let hnsw_name = IndexType::default().index_name(store.table_name()).unwrap();
+        assert_eq!(index_def(&store, hnsw_name).await?, None);
+
+        // IVFFlat 不在建表时创建，空表上 create_index 报错，写入数据后才创建
+        let ivfflat = IndexType::IvfFlat { lists: 1 };
+        sqlx::query("DROP TABLE IF EXISTS test_vector_index_ivfflat").execute(store.pool()).await?;
+        let store = PgVectorStore::new_with_options(store.pool().clone(), "test_vector_index_ivfflat", 3, StoreOptions::default().with_index_type(ivfflat)).await?;
+        let name = ivfflat.index_name(store.table_name()).unwrap();
+        assert_eq!(index_def(&store, name.clone()).await?, None);
+        assert!(store.create_index().await.is_err());
+        store.add_vectors(vec![VectorRecord {
+            id: "00000000-0000-0000-0000-000000000801".to_string(),
+            embedding: vec![1.0, 0.0, 0.0],
+            metadata: serde_json::json!({}),
+            text: None,
+            createat: None,
+            updateat: None,
+        }]).await?;
+        store.create_index().await?;
+        let def = index_def(&store, name).await?.expect("写入数据后应创建 IVFFlat 索引");
+        assert!(def.contains("USING ivfflat (embedding vector_cosine_ops)"), "{}", def);
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_distance_metrics() -> Result<()> {
+        let record = |n: u32, embedding: Vec<f32>| VectorRecord {
+            id: format!("00000000-0000-0000-0000-0000000006{:02}", n),
+            embedding,
+            metadata: serde_json::json!({ "document_id": "doc-metric" }),
+            text: Some(format!("chunk {}", n)),
+            createat: None,
+            updateat: None,
+        };
+        // 未归一化的向量：方向相同但很长 / 很近但方向略偏 / 方向偏 45° 但内积最大
+        let records = vec![
+            record(1, vec![10.0, 0.0]),
+            record(2, vec![1.0, 0.1]),
+            record(3, vec![20.0, 20.0]),
+        ];
+
+        for (metric, first) in [
+            (DistanceMetric::Cosine, "chunk 1"),
+            (DistanceMetric::L2, "chunk 2"),
+            (DistanceMetric::InnerProduct, "chunk 3"),
+        ] {
+            let table = format!("test_metric_{}", metric.opclass(VectorPrecision::Full));
+            let store = PgVectorStore::connect("postgres:///rag_db", &table, 2, StoreOptions::default().with_metric(metric), PoolConfig::default()).await?;
+            store.upsert_vectors(records.clone()).await?;
+
+            let hits = store.search(&[1.0, 0.0], 3).await?;
+            assert_eq!(hits[0].record.text.as_deref(), Some(first), "{:?}", metric);
+            assert!(hits.iter().all(|h| (0.0..=1.0).contains(&h.score)));
+            store.delete_document("doc-metric").await?;
+        }
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_vector_precision() -> Result<()> {
+        let record = |n: u32, embedding: Vec<f32>| VectorRecord {
+            id: format!("00000000-0000-0000-0000-0000000006{:02}", n),
+            embedding,
+            metadata: serde_json::json!({ "document_id": "doc-precision" }),
+            text: Some(format!("chunk {}", n)),
+            createat: None,
+            updateat: None,
+        };
+
+        for precision in [VectorPrecision::Full, VectorPrecision::Half] {
+            let table = format!("test_precision_{}", precision.column_type());
+            let store = PgVectorStore::connect("postgres:///rag_db", &table, 2, StoreOptions::default().with_precision(precision), PoolConfig::default()).await?;
+            assert_eq!(store.declared_column().await?, Some((precision.column_type().to_string(), 2)));
+            store.add_vectors(vec![record(1, vec![0.0, 1.0]), record(2, vec![1.0, 0.0])]).await?;
+            store.upsert_vectors(vec![record(3, vec![0.8, 0.6])]).await?;
+
+            let hits = store.search_full(&[1.0, 0.0], 2).await?;
+            assert_eq!(hits.iter().filter_map(|h| h.record.text.as_deref()).collect::<Vec<_>>(), vec!["chunk 2", "chunk 3"], "{:?}", precision);
+            assert!((hits[1].record.embedding[0] - 0.8).abs() < 1e-3);
+            store.delete_document("doc-precision").await?;
+        }
+
+        // 已存在的表精度不同时报错
+        let pool = PgPool::connect("postgres:///rag_db").await?;
+        assert!(PgVectorStore::new_with_options(pool, "test_precision_vector", 2, StoreOptions::default().with_precision(VectorPrecision::Half)).await.is_err());
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_search_hybrid() -> Result<()> {
+        let store = PgVectorStore::open("postgres:///rag_db", "test_search_hybrid", 2, DEFAULT_MAX_CONNECTIONS).await?;
+        let record = |n: u32, text: &str, embedding: Vec<f32>| VectorRecord {
+            id: format!("00000000-0000-0000-0000-0000000007{:02}", n),
+            embedding,
+            metadata: serde_json::json!({ "document_id": "doc-hybrid" }),
+            text: Some(text.to_string()),
+            createat: None,
+            updateat: None,
+        };
+        store.upsert_vectors(vec![
+            record(1, "XK-2048 保修期 两年", vec![0.6, 0.8]),
+            // 向量上更接近查询，但型号不对
+            record(2, "XK-2049 保修期 一年", vec![1.0, 0.0]),
+            record(3, "退货 流程", vec![0.0, 1.0]),
+        ]).await?;
+
+        let texts = |hits: &[SearchResult]| hits.iter().filter_map(|h| h.record.text.clone()).collect::<Vec<_>>();
+        let hits = store.search_hybrid("XK-2048 保修期", &[1.0, 0.0], 2, 0.3).await?;
+        assert_eq!(texts(&hits)[0], "XK-2048 保修期 两年");
+        assert!(hits[0].score > hits[1].score);
+
+        // alpha = 1 等同纯向量检索
+        let hits = store.search_hybrid("XK-2048 保修期", &[1.0, 0.0], 2, 1.0).await?;
+        assert_eq!(texts(&hits)[0], "XK-2049 保修期 一年");
+
+        // 没有可检索的词时只按向量得分排序
+        assert_eq!(store.search_hybrid("", &[0.0, 1.0], 1, 0.5).await?[0].record.text.as_deref(), Some("退货 流程"));
+
+        store.delete_document("doc-hybrid").await?;
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_migrate_dimension() -> Result<()> {
+        let store = PgVectorStore::open("postgres:///rag_db", "test_migrate", 3, DEFAULT_MAX_CONNECTIONS).await?;
+        let pool = store.pool().clone();
+        assert!(PgVectorStore::new(pool.clone(), "test_migrate", 4).await.is_err());
+
+        let store = PgVectorStore::migrate_dimension(pool.clone(), "test_migrate", 4, StoreOptions::default()).await?;
+        assert_eq!(store.declared_column().await?, Some(("vector".to_string(), 4)));
+        PgVectorStore::new(pool, "test_migrate", 4).await?;
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_add_vectors_batched() -> Result<()> {
+        let store = PgVectorStore::connect("postgres:///rag_db", "test_bulk_insert", 3, StoreOptions::default().with_index_type(IndexType::None), PoolConfig::default()).await?;
+        let records = |offset: u32| (0..10_000u32)
+            .map(|n| VectorRecord {
+                id: Uuid::from_u128((offset + n) as u128).to_string(),
+                embedding: vec![1.0, n as f32, 0.0],
+                metadata: serde_json::json!({ "document_id": "doc-bulk" }),
+                text: Some(format!("chunk {}", n)),
+                createat: None,
+                updateat: None,
+            })
+            .collect::<Vec<_>>();
+        sqlx::query(r#"TRUNCATE "test_bulk_insert""#).execute(store.pool()).await?;
+
+        // 每批 1 条即逐行 INSERT
+        let store = store.with_insert_batch_size(1);
+        store.add_vectors(records(0)).await?;
+        let store = store.with_insert_batch_size(DEFAULT_INSERT_BATCH_SIZE);
+        store.add_vectors(records(10_000)).await?;
+        assert_eq!(store.count().await?, 20_000);
+
+        // 任一记录失败时整批回滚
+        let mut invalid = records(20_000);
+        invalid[9_999].embedding = vec![1.0];
+        assert!(store.add_vectors(invalid).await.is_err());
+        assert!(store.add_vectors(records(10_000)).await.is_err());
+        assert_eq!(store.count().await?, 20_000);
+
+        store.delete_document("doc-bulk").await?;
+        Ok(())
+    }
+
+    /// 批量插入相对逐行插入的耗时对比，依赖机器负载，需显式运行：
+    /// `cargo test -p rag-embeddings test_add_vectors_speed -- --ignored --nocapture`
+    #[tokio::test]
+    #[ignore]
+    async fn test_add_vectors_speed() -> Result<()> {
+        let store = PgVectorStore::connect("postgres:///rag_db", "test_bulk_insert_speed", 3, StoreOptions::default().with_index_type(IndexType::None), PoolConfig::default()).await?;
+        let records = |offset: u32| (0..10_000u32)
+            .map(|n| VectorRecord {
+                id: Uuid::from_u128((offset + n) as u128).to_string(),
+                embedding: vec![1.0, n as f32, 0.0],
+                metadata: serde_json::json!({ "document_id": "doc-bulk-speed" }),
+                text: Some(format!("chunk {}", n)),
+                createat: None,
+                updateat: None,
+            })
+            .collect::<Vec<_>>();
+        sqlx::query(r#"TRUNCATE "test_bulk_insert_speed""#).execute(store.pool()).await?;
+
+        let start = std::time::Instant::now();
+        let store = store.with_insert_batch_size(1);
+        store.add_vectors(records(0)).await?;
+        let row_by_row = start.elapsed();
+
+        let start = std::time::Instant::now();
+        let store = store.with_insert_batch_size(DEFAULT_INSERT_BATCH_SIZE);
+        store.add_vectors(records(10_000)).await?;
+        let batched = start.elapsed();
+        println!("逐行插入 {:?}，批量插入 {:?}", row_by_row, batched);
+        assert!(batched * 2 < row_by_row);
+
+        store.delete_document("doc-bulk-speed").await?;
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_delete_by_metadata() -> Result<()> {
+        let store = PgVectorStore::open("postgres:///rag_db", "test_delete_metadata", 2, DEFAULT_MAX_CONNECTIONS).await?;
+        let record = |n: u32, document_id: &str| VectorRecord {
+            id: format!("00000000-0000-0000-0000-0000000005{:02}", n),
+            embedding: vec![1.0, 0.0],
+            metadata: serde_json::json!({ "document_id": document_id, "order": n }),
+            text: Some(format!("chunk {}", n)),
+            createat: None,
+            updateat: None,
+        };
+        store.upsert_vectors(vec![record(1, "doc-a"), record(2, "doc-a"), record(3, "doc-b")]).await?;
+
+        assert!(store.delete_by_metadata(serde_json::json!({})).await.is_err());
+        assert!(store.delete_by_metadata(serde_json::json!("doc-a")).await.is_err());
+        assert_eq!(store.delete_by_metadata(serde_json::json!({ "document_id": "doc-a" })).await?, 2);
+
+        let hits = store.search(&[1.0, 0.0], 10).await?;
+        assert_eq!(hits.iter().filter_map(|h| h.record.text.as_deref()).collect::<Vec<_>>(), vec!["chunk 3"]);
+
+        store.delete_by_metadata(serde_json::json!({ "document_id": "doc-b" })).await?;
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_delete_by_metadata_shared_chunk() -> Result<()> {
+        let store = PgVectorStore::open("postgres:///rag_db", "test_delete_metadata_shared", 2, DEFAULT_MAX_CONNECTIONS).await?;
+        sqlx::query(r#"TRUNCATE "test_delete_metadata_shared""#).execute(store.pool()).await?;
+        let record = |n: u32, document_id: &str| VectorRecord {
+            id: format!("00000000-0000-0000-0000-0000000006{:02}", n),
+            embedding: vec![1.0, 0.0],
+            metadata: serde_json::json!({ "document_id": document_id, "document_ids": [document_id], "is_image": n == 2 }),
+            text: Some(format!("chunk {}", n)),
+            createat: None,
+            updateat: None,
+        };
+        store.upsert_vectors(vec![record(1, "doc-b"), record(2, "doc-a"), record(3, "doc-a")]).await?;
+        // doc-b 的分块 1 同时被 doc-a 引用
+        store.add_document_reference(&record(1, "").id, "doc-a").await?;
+
+        assert!(store.delete_by_metadata(serde_json::json!({ "document_ids": ["doc-a"] })).await.is_err());
+        assert!(store.delete_by_metadata(serde_json::json!({ "document_id": 1 })).await.is_err());
+
+        // 额外条件只作用于匹配的记录
+        assert_eq!(store.delete_by_metadata(serde_json::json!({ "document_id": "doc-a", "is_image": true })).await?, 1);
+        assert_eq!(store.delete_by_metadata(serde_json::json!({ "document_id": "doc-a" })).await?, 1);
+        let shared = store.get_by_id(&record(1, "").id).await?.unwrap();
+        assert_eq!(shared.metadata["document_id"], "doc-b");
+        assert_eq!(shared.metadata["document_ids"], serde_json::json!(["doc-b"]));
+
+        assert_eq!(store.delete_by_metadata(serde_json::json!({ "document_id": "doc-b" })).await?, 1);
+        assert_eq!(store.count().await?, 0);
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_list_pages() -> Result<()> {
+        let store = PgVectorStore::open("postgres:///rag_db", "test_list", 2, DEFAULT_MAX_CONNECTIONS).await?;
+        sqlx::query(r#"TRUNCATE "test_list""#).execute(store.pool()).await?;
+        let ids: Vec<String> = (1..=5).map(|n| format!("00000000-0000-0000-0000-0000000008{:02}", n)).collect();
+        store.add_vectors(ids.iter().rev().map(|id| VectorRecord {
+            id: id.clone(),
+            embedding: vec![1.0, 0.0],
+            metadata: serde_json::json!({ "document_id": "doc-list" }),
+            text: Some(id.clone()),
+            createat: None,
+            updateat: None,
+        }).collect()).await?;
+
+        let mut listed = Vec::new();
+        let mut offset = 0;
+        loop {
+            let page = store.list(2, offset).await?;
+            assert!(page.len() <= 2);
+            listed.extend(page.iter().map(|r| r.id.clone()));
+            if page.len() < 2 {
+                break;
+            }
+            offset += page.len();
+        }
+        // 同一批写入的创建时间相同，按 id 排序
+        assert_eq!(listed, ids);
+        assert_eq!(store.list(1, 0).await?[0].embedding, vec![1.0, 0.0]);
+
+        store.delete_document("doc-list").await?;
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_count_and_get_by_id() -> Result<()> {
+        let store = PgVectorStore::open("postgres:///rag_db", "test_count", 3, DEFAULT_MAX_CONNECTIONS).await?;
+        sqlx::query(r#"TRUNCATE "test_count""#).execute(store.pool()).await?;
+        let id = "00000000-0000-0000-0000-000000000003".to_string();
+        store.upsert_vectors(vec![VectorRecord {
+            id: id.clone(),
+            embedding: vec![0.0, 1.0, 0.0],
+            metadata: serde_json::json!({ "document_id": "doc-001" }),
+            text: Some("text".to_string()),
+            createat: None,
+            updateat: None,
+        }]).await?;
+        assert_eq!(store.count().await?, 1);
+
+        let record = store.get_by_id(&id).await?.expect("记录应存在");
+        assert_eq!(record.id, id);
+        assert_eq!(record.embedding, vec![0.0, 1.0, 0.0]);
+        assert_eq!(record.text.as_deref(), Some("text"));
+        assert!(store.get_by_id("00000000-0000-0000-0000-0000000000ff").await?.is_none());
+        assert!(store.get_by_id("not-a-uuid").await.is_err());
+
+        store.delete_vector(vec![id]).await?;
+        assert_eq!(store.count().await?, 0);
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_update_metadata() -> Result<()> {
+        let store = PgVectorStore::open("postgres:///rag_db", "test_metadata", 3, DEFAULT_MAX_CONNECTIONS).await?;
+        let id = "00000000-0000-0000-0000-000000000002".to_string();
+        store.upsert_vectors(vec![VectorRecord {
+            id: id.clone(),
+            embedding: vec![1.0, 0.0, 0.0],
+            metadata: serde_json::json!({ "file_name": "wrong.md", "document_id": "doc-001" }),
+            text: Some("text".to_string()),
+            createat: None,
+            updateat: None,
+        }]).await?;
+
+        store.merge_metadata(&id, serde_json::json!({ "file_name": "right.md", "tags": ["a"] })).await?;
+        let hits = store.search_full(&[1.0, 0.0, 0.0], 1).await?;
+        assert_eq!(hits[0].record.metadata, serde_json::json!({ "file_name": "right.md", "document_id": "doc-001", "tags": ["a"] }));
+        assert_eq!(hits[0].record.embedding, vec![1.0, 0.0, 0.0]);
+        let hits = store.search_lite(&[1.0, 0.0, 0.0], 1).await?;
+        assert!(hits[0].record.embedding.is_empty());
+        assert_eq!(store.get_by_ids(std::slice::from_ref(&id)).await?[0].embedding, vec![1.0, 0.0, 0.0]);
+
+        store.update_metadata(&id, serde_json::json!({ "document_id": "doc-002" })).await?;
+        let hits = store.search(&[1.0, 0.0, 0.0], 1).await?;
+        assert_eq!(hits[0].record.metadata, serde_json::json!({ "document_id": "doc-002" }));
+
+        assert!(store.update_metadata("00000000-0000-0000-0000-0000000000ff", serde_json::json!({})).await.is_err());
+        store.delete_vector(vec![id]).await
+    }
+
+    #[tokio::test]
+    async fn test_document_summary() -> Result<()> {
+        let store = PgVectorStore::open("postgres:///rag_db", "test_document_summary", 3, DEFAULT_MAX_CONNECTIONS).await?;
+        let record = |n: u32, document_id: &str| VectorRecord {
+            id: format!("00000000-0000-0000-0000-0000000001{:02}", n),
+            embedding: vec![1.0, 0.0, 0.0],
+            metadata: serde_json::json!({ "document_id": document_id }),
+            text: Some(format!("chunk {}", n)),
+            createat: None,
+            updateat: None,
+        };
+        store.upsert_vectors(vec![record(1, "doc-a"), record(2, "doc-b"), record(3, "doc-b"), record(4, "doc-c")]).await?;
+
+        let summary = store.document_summary().await?;
+        assert_eq!(summary, vec![
+            ("doc-b".to_string(), 2),
+            ("doc-a".to_string(), 1),
+            ("doc-c".to_string(), 1),
+        ]);
+
+        for document_id in ["doc-a", "doc-b", "doc-c"] {
+            store.delete_document(document_id).await?;
+        }
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_reindex_document() -> Result<()> {
+        let store = PgVectorStore::open("postgres:///rag_db", "test_reindex_document", 3, DEFAULT_MAX_CONNECTIONS).await?;
+        let record = |n: u32, embedding: Vec<f32>| VectorRecord {
+            id: format!("00000000-0000-0000-0000-0000000003{:02}", n),
+            embedding,
+            metadata: serde_json::json!({ "document_id": "doc-reindex" }),
+            text: Some(format!("chunk {}", n)),
+            createat: None,
+            updateat: None,
+        };
+        store.upsert_vectors(vec![record(1, vec![1.0, 0.0, 0.0]), record(2, vec![0.0, 1.0, 0.0])]).await?;
+
+        let summary = store.reindex_document("doc-reindex", vec![record(3, vec![0.0, 0.0, 1.0])]).await?;
+        assert_eq!(summary, ReindexSummary { deleted: 2, inserted: 1 });
+
+        // 第二条维度不符，整体回滚，旧记录仍在
+        assert!(store.reindex_document("doc-reindex", vec![record(4, vec![1.0, 0.0, 0.0]), record(5, vec![1.0])]).await.is_err());
+        assert_eq!(store.document_summary().await?.into_iter().find(|(id, _)| id == "doc-reindex"), Some(("doc-reindex".to_string(), 1)));
+        assert_eq!(store.get_by_ids(&[record(3, vec![]).id]).await?.len(), 1);
+
+        let other = VectorRecord { metadata: serde_json::json!({ "document_id": "other" }), ..record(6, vec![1.0, 0.0, 0.0]) };
+        assert!(store.reindex_document("doc-reindex", vec![other]).await.is_err());
+
+        store.delete_document("doc-reindex").await?;
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_delete_shared_chunk() -> Result<()> {
+        let store = PgVectorStore::open("postgres:///rag_db", "test_delete_shared_chunk", 3, DEFAULT_MAX_CONNECTIONS).await?;
+        let record = |n: u32, document_id: &str| VectorRecord {
+            id: format!("00000000-0000-0000-0000-0000000004{:02}", n),
+            embedding: vec![1.0, 0.0, 0.0],
+            metadata: serde_json::json!({ "document_id": document_id, "document_ids": [document_id] }),
+            text: Some(format!("chunk {}", n)),
+            createat: None,
+            updateat: None,
+        };
+        let shared = record(1, "doc-a").id;
+        store.upsert_vectors(vec![record(1, "doc-a"), record(2, "doc-a"), record(3, "doc-b")]).await?;
+        store.add_document_reference(&shared, "doc-b").await?;
+
+        // doc-b 通过 document_ids 命中共享分块
+        let hits = store.search_with(&SearchQuery::new(vec![1.0, 0.0, 0.0]).top_k(10).filter_document("doc-b")).await?;
+        assert_eq!(hits.len(), 2);
+
+        // 删除 doc-a 只删其独有分块，共享分块改归 doc-b
+        assert_eq!(store.delete_document("doc-a").await?, 1);
+        let kept = store.get_by_ids(std::slice::from_ref(&shared)).await?;
+        assert_eq!(kept[0].metadata["document_id"], "doc-b");
+        assert_eq!(kept[0].metadata["document_ids"], serde_json::json!(["doc-b"]));
+
+        assert_eq!(store.delete_document("doc-b").await?, 2);
+        assert!(store.get_by_ids(&[shared]).await?.is_empty());
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_renormalize_all() -> Result<()> {
+        let store = PgVectorStore::open("postgres:///rag_db", "test_renormalize_all", 2, DEFAULT_MAX_CONNECTIONS).await?;
+        let record = |n: u32, embedding: Vec<f32>| VectorRecord {
+            id: format!("00000000-0000-0000-0000-0000000002{:02}", n),
+            embedding,
+            metadata: serde_json::json!({ "document_id": "doc-renormalize" }),
+            text: Some(format!("chunk {}", n)),
+            createat: None,
+            updateat: None,
+        };
+        store.upsert_vectors(vec![record(1, vec![3.0, 4.0]), record(2, vec![0.0, 1.0])]).await?;
+
+        assert_eq!(store.renormalize_all().await?, 1);
+        assert_eq!(store.renormalize_all().await?, 0);
+        let hits = store.search_full(&[0.6, 0.8], 1).await?;
+        assert!(hits[0].record.embedding.iter().zip([0.6, 0.8]).all(|(a, b)| (a - b).abs() < 1e-6));
+
+        store.delete_document("doc-renormalize").await?;
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_search_nearest_neighbors() -> Result<()> {
+        let store = PgVectorStore::open("postgres:///rag_db", "test_search", 2, DEFAULT_MAX_CONNECTIONS).await?;
+        let record = |n: u32, embedding: Vec<f32>| VectorRecord {
+            id: format!("00000000-0000-0000-0000-0000000004{:02}", n),
+            embedding,
+            metadata: serde_json::json!({ "document_id": "doc-search" }),
+            text: Some(format!("chunk {}", n)),
+            createat: None,
+            updateat: None,
+        };
+        store.upsert_vectors(vec![
+            record(1, vec![0.0, 1.0]),
+            record(2, vec![1.0, 0.0]),
+            record(3, vec![-1.0, 0.0]),
+            record(4, vec![0.8, 0.6]),
+        ]).await?;
+
+        let hits = store.search(&[1.0, 0.0], 3).await?;
+        let texts: Vec<&str> = hits.iter().filter_map(|h| h.record.text.as_deref()).collect();
+        assert_eq!(texts, vec!["chunk 2", "chunk 4", "chunk 1"]);
+        assert_eq!(hits.iter().map(|h| h.rank).collect::<Vec<_>>(), vec![1, 2, 3]);
+        assert!((hits[0].score - 1.0).abs() < 1e-6);
+        assert!(hits.windows(2).all(|pair| pair[0].score >= pair[1].score));
+        assert!(store.search(&[1.0, 0.0, 0.0], 3).await.is_err());
+
+        store.delete_document("doc-search").await?;
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_search_filtered() -> Result<()> {
+        let store = PgVectorStore::open("postgres:///rag_db", "test_search_filtered", 2, DEFAULT_MAX_CONNECTIONS).await?;
+        let record = |n: u32, document_id: &str, is_image: bool| VectorRecord {
+            id: format!("00000000-0000-0000-0000-0000000005{:02}", n),
+            embedding: vec![1.0, n as f32 / 10.0],
+            metadata: serde_json::json!({ "document_id": document_id, "is_image": is_image }),
+            text: Some(format!("{} chunk {}", document_id, n)),
+            createat: None,
+            updateat: None,
+        };
+        store.upsert_vectors(vec![
+            record(1, "doc-a", false),
+            record(2, "doc-b", false),
+            record(3, "doc-a", true),
+            record(4, "doc-b", false),
+        ]).await?;
+
+        let hits = store.search_filtered(&[1.0, 0.0], 10, serde_json::json!({ "document_id": "doc-a" })).await?;
+        assert_eq!(hits.iter().filter_map(|h| h.record.text.as_deref()).collect::<Vec<_>>(), vec!["doc-a chunk 1", "doc-a chunk 3"]);
+        let hits = store.search_filtered(&[1.0, 0.0], 10, serde_json::json!({ "document_id": "doc-a", "is_image": false })).await?;
+        assert_eq!(hits.len(), 1);
+        // 过滤值作为参数绑定，不会拼接进 SQL
+        let hits = store.search_filtered(&[1.0, 0.0], 10, serde_json::json!({ "document_id": "doc-a' OR '1'='1" })).await?;
+        assert!(hits.is_empty());
+        assert!(store.search_filtered(&[1.0, 0.0], 10, serde_json::json!("doc-a")).await.is_err());
+
+        store.delete_document("doc-a").await?;
+        store.delete_document("doc-b").await?;
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_search_grouped() -> Result<()> {
+        let store = PgVectorStore::open("postgres:///rag_db", "test_search_grouped", 2, DEFAULT_MAX_CONNECTIONS).await?;
+        let record = |n: u32, document_id: &str, embedding: Vec<f32>| VectorRecord {
+            id: format!("00000000-0000-0000-0000-0000000003{:02}", n),
+            embedding,
+            metadata: serde_json::json!({ "document_id": document_id }),
+            text: Some(format!("{} chunk {}", document_id, n)),
+            createat: None,
+            updateat: None,
+        };
+        store.upsert_vectors(vec![
+            record(1, "doc-a", vec![1.0, 0.0]),
+            record(2, "doc-a", vec![0.8, 0.6]),
+            record(3, "doc-a", vec![0.6, 0.8]),
+            record(4, "doc-b", vec![0.0, 1.0]),
+            record(5, "doc-c", vec![0.96, 0.28]),
+            record(6, "doc-c", vec![-1.0, 0.0]),
+        ]).await?;
+
+        let groups = store.search_grouped(&[1.0, 0.0], 2, 2).await?;
+        let summary: Vec<(String, Vec<String>)> = groups.iter()
+            .map(|(id, hits)| (id.clone(), hits.iter().filter_map(|h| h.record.text.clone()).collect()))
+            .collect();
+        assert_eq!(summary, vec![
+            ("doc-a".to_string(), vec!["doc-a chunk 1".to_string(), "doc-a chunk 2".to_string()]),
+            ("doc-c".to_string(), vec!["doc-c chunk 5".to_string(), "doc-c chunk 6".to_string()]),
+        ]);
+        assert_eq!(groups[0].1.iter().map(|h| h.rank).collect::<Vec<_>>(), vec![1, 2]);
+        assert!(groups[0].1[0].score > groups[1].1[0].score);
+
+        for document_id in ["doc-a", "doc-b", "doc-c"] {
+            store.delete_document(document_id).await?;
+        }
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_neighbors() -> Result<()> {
+        let store = PgVectorStore::open("postgres:///rag_db", "test_neighbors", 3, DEFAULT_MAX_CONNECTIONS).await?;
+        let record = |order: i64, document_id: &str| VectorRecord {
+            id: format!("00000000-0000-0000-0000-0000000002{:02}", order + if document_id == "doc-a" { 0 } else { 50 }),
+            embedding: vec![1.0, 0.0, 0.0],
+            metadata: serde_json::json!({ "document_id": document_id, "order": order }),
+            text: Some(format!("{} chunk {}", document_id, order)),
+            createat: None,
+            updateat: None,
+        };
+        store.upsert_vectors((0..6).map(|i| record(i, "doc-a")).chain([record(3, "doc-b")]).collect()).await?;
+
+        let texts = |records: Vec<VectorRecord>| records.into_iter().filter_map(|r| r.text).collect::<Vec<_>>();
+        assert_eq!(texts(store.neighbors("doc-a", 3, 1).await?), vec!["doc-a chunk 2", "doc-a chunk 3", "doc-a chunk 4"]);
+        assert_eq!(texts(store.neighbors("doc-a", 0, 2).await?), vec!["doc-a chunk 0", "doc-a chunk 1", "doc-a chunk 2"]);
+        assert_eq!(texts(store.neighbors("doc-b", 3, 0).await?), vec!["doc-b chunk 3"]);
+
+        store.delete_document("doc-a").await?;
+        store.delete_document("doc-b").await?;
+        Ok(())
+    }
+}
@@ -0,0 +1,41 @@
+/// pgvector 的 HNSW / IVFFlat 索引最多支持的 `halfvec` 维度
+pub const MAX_HALFVEC_INDEX_DIMENSIONS: usize = 4000;
+
+/// embedding 列的存储精度，建表时指定（见 `StoreOptions::precision`）
+///
+/// `Half` 以 `halfvec`（float16）存储，磁盘与内存占用减半，可建索引的维度上限也从 2000 提高到 4000
+/// （如 2560 维的 `text-embedding-v3`）；精度损失对检索排序的影响通常可以忽略。需要 pgvector >= 0.7。
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum VectorPrecision {
+    /// `vector(N)`，float32
+    #[default]
+    Full,
+    /// `halfvec(N)`，float16
+    Half,
+}
+
+impl VectorPrecision {
+    /// embedding 列的 SQL 类型名，也用于查询向量的类型转换
+    pub fn column_type(self) -> &'static str {
+        match self {
+            VectorPrecision::Full => "vector",
+            VectorPrecision::Half => "halfvec",
+        }
+    }
+
+    /// 该精度下向量索引支持的最大维度
+    pub fn max_index_dimensions(self) -> usize {
+        match self {
+            VectorPrecision::Full => crate::database::index::MAX_INDEX_DIMENSIONS,
+            VectorPrecision::Half => MAX_HALFVEC_INDEX_DIMENSIONS,
+        }
+    }
+
+    /// 计算向量 L2 范数的 SQL 函数
+    pub(crate) fn norm_function(self) -> &'static str {
+        match self {
+            VectorPrecision::Full => "vector_norm",
+            VectorPrecision::Half => "l2_norm",
+        }
+    }
+}
@@ -0,0 +1,267 @@
+use crate::database::VectorRecord;
+
+/// 类型化的检索请求：查询向量 + 元数据过滤 + 分数阈值
+///
+/// ```ignore
+/// let query = SearchQuery::new(query_vec)
+///     .top_k(10)
+///     .filter_document("doc-001")
+///     .exclude_images()
+///     .min_score(0.3);
+/// let hits = store.search_with(&query).await?;
+/// ```
+#[derive(Debug, Clone)]
+pub struct SearchQuery {
+    pub vector: Vec<f32>,
+    pub top_k: usize,
+    pub document_ids: Vec<String>,
+    pub file_name: Option<String>,
+    pub exclude_images: bool,
+    /// metadata 中字符串字段的等值过滤，如 ("language", "zh")
+    pub metadata_equals: Vec<(String, String)>,
+    /// metadata 须包含的 JSON 对象（JSONB `@>`），如 `{"document_id": "doc-001", "is_image": false}`
+    pub metadata_contains: Option<serde_json::Value>,
+    /// 归一化相似度（[0, 1]，见 [`DistanceMetric`](crate::database::DistanceMetric)）的下限
+    pub min_score: Option<f32>,
+    /// 结果是否携带 embedding（默认不携带，记录的 `embedding` 为空），MMR 等需要向量时显式开启
+    pub include_embeddings: bool,
+}
+
+/// SQL 绑定参数
+#[derive(Debug, Clone, PartialEq)]
+pub(crate) enum SqlParam {
+    Text(String),
+    Float(f32),
+    Json(serde_json::Value),
+}
+
+/// 默认返回条数
+pub const DEFAULT_TOP_K: usize = 5;
+
+impl SearchQuery {
+    pub fn new(vector: Vec<f32>) -> Self {
+        Self {
+            vector,
+            top_k: DEFAULT_TOP_K,
+            document_ids: Vec::new(),
+            file_name: None,
+            exclude_images: false,
+            metadata_equals: Vec::new(),
+            metadata_contains: None,
+            min_score: None,
+            include_embeddings: false,
+        }
+    }
+
+    pub fn top_k(mut self, top_k: usize) -> Self {
+        self.top_k = top_k;
+        self
+    }
+
+    /// 限定文档，多次调用时命中任一文档即可；跨文档共享的分块按 `metadata.document_ids` 匹配
+    pub fn filter_document(mut self, document_id: impl Into<String>) -> Self {
+        self.document_ids.push(document_id.into());
+        self
+    }
+
+    pub fn filter_file_name(mut self, file_name: impl Into<String>) -> Self {
+        self.file_name = Some(file_name.into());
+        self
+    }
+
+    pub fn exclude_images(mut self) -> Self {
+        self.exclude_images = true;
+        self
+    }
+
+    pub fn filter_metadata(mut self, key: impl Into<String>, value: impl Into<String>) -> Self {
+        self.metadata_equals.push((key.into(), value.into()));
+        self
+    }
+
+    /// metadata 须包含 `filter` 中的全部键值（值可为字符串、数字、布尔或嵌套对象），多次调用时合并
+    pub fn filter_contains(mut self, filter: serde_json::Value) -> Self {
+        match (&mut self.metadata_contains, filter) {
+            (Some(serde_json::Value::Object(existing)), serde_json::Value::Object(filter)) => existing.extend(filter),
+            (contains, filter) => *contains = Some(filter),
+        }
+        self
+    }
+
+    /// 丢弃相似度低于阈值的结果；阈值作用于 [0, 1] 的归一化相似度，与距离度量无关
+    pub fn min_score(mut self, min_score: f32) -> Self {
+        self.min_score = Some(min_score);
+        self
+    }
+
+    /// 结果携带完整 embedding；每条结果多传输 `dimensions` 个 f32，只在确实需要向量时使用
+    pub fn with_embeddings(mut self) -> Self {
+        self.include_embeddings = true;
+        self
+    }
+
+    /// 判断记录是否满足过滤条件（供不支持 SQL 的存储在内存中过滤）
+    pub fn matches(&self, record: &VectorRecord, score: f32) -> bool {
+        let meta_str = |key: &str| record.metadata.get(key).and_then(|v| v.as_str());
+
+        if !self.document_ids.is_empty() && !self.matches_document(record) {
+            return false;
+        }
+        if let Some(file_name) = &self.file_name
+            && meta_str("file_name") != Some(file_name.as_str())
+        {
+            return false;
+        }
+        if self.exclude_images && record.metadata.get("is_image").and_then(|v| v.as_bool()) == Some(true) {
+            return false;
+        }
+        if self.metadata_equals.iter().any(|(key, value)| meta_str(key) != Some(value.as_str())) {
+            return false;
+        }
+        if let Some(filter) = &self.metadata_contains
+            && !json_contains(&record.metadata, filter)
+        {
+            return false;
+        }
+        self.min_score.is_none_or(|min| score >= min)
+    }
+
+    /// 记录的 `document_id` 或跨文档共享分块的 `document_ids` 命中任一限定文档
+    fn matches_document(&self, record: &VectorRecord) -> bool {
+        let wanted = |id: &str| self.document_ids.iter().any(|d| d == id);
+        record.metadata.get("document_id").and_then(|v| v.as_str()).is_some_and(wanted)
+            || record.metadata.get("document_ids")
+                .and_then(|v| v.as_array())
+                .is_some_and(|ids| ids.iter().filter_map(|v| v.as_str()).any(wanted))
+    }
+
+    /// 编译为 WHERE 子句（不含 `WHERE` 关键字），参数从 `$first_param` 开始编号
+    ///
+    /// `score_expr` 为计算相似度的 SQL 表达式，用于 `min_score` 阈值。
+    pub(crate) fn where_clause(&self, score_expr: &str, first_param: usize) -> (String, Vec<SqlParam>) {
+        let mut clauses = Vec::new();
+        let mut params = Vec::new();
+        let next = |param: SqlParam, params: &mut Vec<SqlParam>| {
+            params.push(param);
+            format!("${}", first_param + params.len() - 1)
+        };
+
+        if !self.document_ids.is_empty() {
+            let placeholders: Vec<String> = self.document_ids.iter()
+                .map(|id| next(SqlParam::Text(id.clone()), &mut params))
+                .collect();
+            clauses.push(format!(
+                "(metadata->>'document_id' IN ({0}) OR metadata->'document_ids' ?| ARRAY[{0}])",
+                placeholders.join(", ")
+            ));
+        }
+        if let Some(file_name) = &self.file_name {
+            let p = next(SqlParam::Text(file_name.clone()), &mut params);
+            clauses.push(format!("metadata->>'file_name' = {}", p));
+        }
+        if self.exclude_images {
+            clauses.push("(metadata->>'is_image')::boolean IS NOT TRUE".to_string());
+        }
+        for (key, value) in &self.metadata_equals {
+            let k = next(SqlParam::Text(key.clone()), &mut params);
+            let v = next(SqlParam::Text(value.clone()), &mut params);
+            clauses.push(format!("metadata->>{} = {}", k, v));
+        }
+        if let Some(filter) = &self.metadata_contains {
+            let p = next(SqlParam::Json(filter.clone()), &mut params);
+            clauses.push(format!("metadata @> {}::jsonb", p));
+        }
+        if let Some(min_score) = self.min_score {
+            let p = next(SqlParam::Float(min_score), &mut params);
+            clauses.push(format!("{} >= {}", score_expr, p));
+        }
+
+        if clauses.is_empty() {
+            ("TRUE".to_string(), params)
+        } else {
+            (clauses.join(" AND "), params)
+        }
+    }
+}
+
+/// 校验按 metadata 删除的过滤条件：必须是非空的 JSON 对象，避免 `{}` 匹配并清空整张表
+pub(crate) fn check_delete_filter(filter: &serde_json::Value) -> anyhow::Result<()> {
+    match filter.as_object() {
+        Some(filter) if !filter.is_empty() => Ok(()),
+        Some(_) => anyhow::bail!("Delete filter must not be empty"),
+        None => anyhow::bail!("Delete filter must be a JSON object"),
+    }
+}
+
+/// JSONB `@>` 的内存实现：对象逐键包含，数组中每个元素都能在 `value` 中找到包含它的元素，其余按值相等
+pub(crate) fn json_contains(value: &serde_json::Value, filter: &serde_json::Value) -> bool {
+    use serde_json::Value;
+    match (value, filter) {
+        (Value::Object(value), Value::Object(filter)) => filter.iter()
+            .all(|(key, expected)| value.get(key).is_some_and(|actual| json_contains(actual, expected))),
+        (Value::Array(value), Value::Array(filter)) => filter.iter()
+            .all(|expected| value.iter().any(|actual| json_contains(actual, expected))),
+        _ => value == filter,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_where_clause() {
+        let query = SearchQuery::new(vec![1.0])
+            .top_k(10)
+            .filter_document("doc-001")
+            .filter_document("doc-002")
+            .exclude_images()
+            .filter_metadata("language", "zh")
+            .min_score(0.3);
+
+        let (sql, params) = query.where_clause("score_expr", 3);
+        assert_eq!(sql, "(metadata->>'document_id' IN ($3, $4) OR metadata->'document_ids' ?| ARRAY[$3, $4]) \
+            AND (metadata->>'is_image')::boolean IS NOT TRUE AND metadata->>$5 = $6 AND score_expr >= $7");
+        assert_eq!(params, vec![
+            SqlParam::Text("doc-001".to_string()),
+            SqlParam::Text("doc-002".to_string()),
+            SqlParam::Text("language".to_string()),
+            SqlParam::Text("zh".to_string()),
+            SqlParam::Float(0.3),
+        ]);
+        assert_eq!(SearchQuery::new(vec![1.0]).where_clause("s", 2), ("TRUE".to_string(), vec![]));
+
+        let query = SearchQuery::new(vec![1.0])
+            .filter_contains(serde_json::json!({ "document_id": "doc-001" }))
+            .filter_contains(serde_json::json!({ "is_image": false }));
+        let (sql, params) = query.where_clause("s", 3);
+        assert_eq!(sql, "metadata @> $3::jsonb");
+        assert_eq!(params, vec![SqlParam::Json(serde_json::json!({ "document_id": "doc-001", "is_image": false }))]);
+    }
+
+    #[test]
+    fn test_matches() {
+        let record = VectorRecord {
+            id: "1".to_string(),
+            embedding: vec![],
+            metadata: serde_json::json!({ "document_id": "doc-001", "is_image": true, "file_name": "a.md" }),
+            text: None,
+            createat: None,
+            updateat: None,
+        };
+
+        assert!(SearchQuery::new(vec![]).filter_document("doc-001").filter_file_name("a.md").matches(&record, 0.5));
+        assert!(!SearchQuery::new(vec![]).filter_document("doc-002").matches(&record, 0.5));
+        assert!(!SearchQuery::new(vec![]).exclude_images().matches(&record, 0.5));
+        assert!(!SearchQuery::new(vec![]).min_score(0.6).matches(&record, 0.5));
+        assert!(!SearchQuery::new(vec![]).filter_metadata("language", "zh").matches(&record, 0.5));
+        assert!(SearchQuery::new(vec![]).filter_contains(serde_json::json!({ "document_id": "doc-001", "is_image": true })).matches(&record, 0.5));
+        assert!(!SearchQuery::new(vec![]).filter_contains(serde_json::json!({ "is_image": false })).matches(&record, 0.5));
+        let shared = VectorRecord {
+            metadata: serde_json::json!({ "document_id": "doc-001", "document_ids": ["doc-001", "doc-002"] }),
+            ..record
+        };
+        assert!(SearchQuery::new(vec![]).filter_document("doc-002").matches(&shared, 0.5));
+        assert!(!SearchQuery::new(vec![]).filter_document("doc-003").matches(&shared, 0.5));
+    }
+}
@@ -0,0 +1,87 @@
+use crate::database::VectorPrecision;
+
+/// 向量距离度量，建表时指定（见 `StoreOptions::metric`），决定检索使用的运算符与索引的操作符类
+///
+/// 各度量的原始距离含义不同，检索返回的分数统一经 [`normalize`](Self::normalize)
+/// 映射为 [0, 1] 的相似度（1 表示完全相同），`min_score` 等阈值与度量无关。
+/// 嵌入客户端默认输出 L2 归一化的向量，此时三种度量的排序一致；存储未归一化的原始向量时再按需选择 L2 或内积。
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum DistanceMetric {
+    /// 余弦距离 d ∈ [0, 2]（pgvector `<=>`），similarity = 1 - d / 2
+    #[default]
+    Cosine,
+    /// 欧氏距离 d ≥ 0（pgvector `<->`），similarity = 1 / (1 + d)
+    L2,
+    /// 负内积 d = -⟨a, b⟩（pgvector `<#>`），similarity = clamp((1 - d) / 2, 0, 1)；
+    /// 单位向量下与余弦相似度的映射一致，非归一化向量超出范围的部分被截断
+    InnerProduct,
+}
+
+impl DistanceMetric {
+    /// 将原始距离映射为 [0, 1] 的相似度
+    pub fn normalize(self, distance: f32) -> f32 {
+        let similarity = match self {
+            DistanceMetric::Cosine => 1.0 - distance / 2.0,
+            DistanceMetric::L2 => 1.0 / (1.0 + distance.max(0.0)),
+            DistanceMetric::InnerProduct => (1.0 - distance) / 2.0,
+        };
+        similarity.clamp(0.0, 1.0)
+    }
+
+    /// pgvector 的距离运算符
+    pub fn operator(self) -> &'static str {
+        match self {
+            DistanceMetric::Cosine => "<=>",
+            DistanceMetric::L2 => "<->",
+            DistanceMetric::InnerProduct => "<#>",
+        }
+    }
+
+    /// 该度量在 pgvector 索引中对应的操作符类，如 `vector_cosine_ops`、`halfvec_l2_ops`
+    pub(crate) fn opclass(self, precision: VectorPrecision) -> String {
+        let ops = match self {
+            DistanceMetric::Cosine => "cosine_ops",
+            DistanceMetric::L2 => "l2_ops",
+            DistanceMetric::InnerProduct => "ip_ops",
+        };
+        format!("{}_{}", precision.column_type(), ops)
+    }
+
+    /// 与 [`normalize`](Self::normalize) 等价的 SQL 表达式，`distance_expr` 为原始距离
+    pub(crate) fn score_sql(self, distance_expr: &str) -> String {
+        match self {
+            DistanceMetric::Cosine => format!("GREATEST(0, LEAST(1, 1 - ({}) / 2))", distance_expr),
+            DistanceMetric::L2 => format!("(1 / (1 + GREATEST(0, {})))", distance_expr),
+            DistanceMetric::InnerProduct => format!("GREATEST(0, LEAST(1, (1 - ({})) / 2))", distance_expr),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_normalize() {
+        // 相同向量
+        assert_eq!(DistanceMetric::Cosine.normalize(0.0), 1.0);
+        assert_eq!(DistanceMetric::L2.normalize(0.0), 1.0);
+        assert_eq!(DistanceMetric::InnerProduct.normalize(-1.0), 1.0);
+
+        // 正交的单位向量在余弦与内积下得分一致
+        assert_eq!(DistanceMetric::Cosine.normalize(1.0), 0.5);
+        assert_eq!(DistanceMetric::InnerProduct.normalize(0.0), 0.5);
+
+        // 相反向量
+        assert_eq!(DistanceMetric::Cosine.normalize(2.0), 0.0);
+        assert_eq!(DistanceMetric::InnerProduct.normalize(1.0), 0.0);
+
+        assert_eq!(DistanceMetric::L2.normalize(1.0), 0.5);
+        assert!(DistanceMetric::L2.normalize(100.0) < 0.01);
+        assert_eq!(DistanceMetric::InnerProduct.normalize(-5.0), 1.0);
+
+        assert_eq!(DistanceMetric::L2.operator(), "<->");
+        assert_eq!(DistanceMetric::InnerProduct.opclass(VectorPrecision::Full), "vector_ip_ops");
+        assert_eq!(DistanceMetric::Cosine.opclass(VectorPrecision::Half), "halfvec_cosine_ops");
+    }
+}
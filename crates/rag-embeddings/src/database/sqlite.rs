@@ -0,0 +1,516 @@
+use std::path::Path;
+use std::sync::{Arc, Mutex, Once};
+
+use anyhow::{Context, Result, anyhow};
+use async_trait::async_trait;
+use chrono::{DateTime, Utc};
+use rusqlite::{Connection, OptionalExtension, params};
+use serde_json::Value as JsonValue;
+
+use crate::client::renormalize;
+use crate::database::{DistanceMetric, ManagedVectorStore, SearchQuery, SearchResult, VectorRecord, VectorStore};
+use crate::database::query::{check_delete_filter, json_contains};
+
+static REGISTER_SQLITE_VEC: Once = Once::new();
+
+/// 为之后打开的所有连接注册 sqlite-vec 扩展
+fn register_sqlite_vec() {
+    REGISTER_SQLITE_VEC.call_once(|| unsafe {
+        #[allow(clippy::missing_transmute_annotations)]
+        rusqlite::ffi::sqlite3_auto_extension(Some(std::mem::transmute(
+            sqlite_vec::sqlite3_vec_init as *const (),
+        )));
+    });
+}
+
+/// 向量以 f32 小端字节序存储，与 sqlite-vec 的 float32 向量格式一致
+fn to_blob(embedding: &[f32]) -> Vec<u8> {
+    embedding.iter().flat_map(|x| x.to_le_bytes()).collect()
+}
+
+fn from_blob(blob: &[u8]) -> Vec<f32> {
+    blob.chunks_exact(4)
+        .map(|b| f32::from_le_bytes([b[0], b[1], b[2], b[3]]))
+        .collect()
+}
+
+fn parse_time(value: Option<String>) -> Option<DateTime<Utc>> {
+    value.and_then(|v| DateTime::parse_from_rfc3339(&v).ok()).map(|t| t.with_timezone(&Utc))
+}
+
+/// 基于 sqlite + sqlite-vec 的本地向量库（`sqlite` feature）
+///
+/// 表结构与 [`PgVectorStore`](crate::database::pgvector::PgVectorStore) 对应：
+/// id / embedding / metadata(JSON) / text / createat / updateat。
+/// 检索使用 sqlite-vec 的 `vec_distance_cosine` 暴力扫描，适合桌面端、CLI 等中小规模数据。
+pub struct SqliteVectorStore {
+    conn: Arc<Mutex<Connection>>,
+    table_name: String,
+    dimensions: usize,
+    normalize_on_load: bool,
+}
+
+impl SqliteVectorStore {
+    /// 打开（或创建）数据库文件并初始化表
+    pub async fn open(path: impl AsRef<Path>, table_name: &str, dimensions: usize) -> Result<Self> {
+        register_sqlite_vec();
+        let conn = Connection::open(path.as_ref())
+            .with_context(|| format!("Failed to open sqlite database {}", path.as_ref().display()))?;
+        Self::with_connection(conn, table_name, dimensions).await
+    }
+
+    /// 使用内存数据库，进程退出后数据丢失
+    pub async fn open_in_memory(table_name: &str, dimensions: usize) -> Result<Self> {
+        register_sqlite_vec();
+        Self::with_connection(Connection::open_in_memory()?, table_name, dimensions).await
+    }
+
+    async fn with_connection(conn: Connection, table_name: &str, dimensions: usize) -> Result<Self> {
+        let store = Self {
+            conn: Arc::new(Mutex::new(conn)),
+            table_name: table_name.to_string(),
+            dimensions,
+            normalize_on_load: false,
+        };
+        store.init_table().await?;
+        Ok(store)
+    }
+
+    /// 写入前将 embedding L2 归一化，用于导入其他流程生成的未归一化向量；已入库的向量见 [`renormalize_all`](Self::renormalize_all)
+    pub fn with_normalize_on_load(mut self, normalize: bool) -> Self {
+        self.normalize_on_load = normalize;
+        self
+    }
+
+    async fn init_table(&self) -> Result<()> {
+        let sql = format!(
+            r#"
+            CREATE TABLE IF NOT EXISTS "{}" (
+                id TEXT PRIMARY KEY,
+                embedding BLOB NOT NULL,
+                metadata TEXT NOT NULL DEFAULT '{{}}',
+                text TEXT,
+                createat TEXT,
+                updateat TEXT
+            );"#,
+            self.table_name
+        );
+        self.run(move |conn| {
+            conn.query_row("SELECT vec_version()", [], |row| row.get::<_, String>(0))
+                .context("sqlite-vec extension not loaded")?;
+            conn.execute_batch(&sql).context("Failed to init vector table")?;
+            Ok(())
+        })
+        .await
+    }
+
+    /// 在阻塞线程池中使用连接
+    async fn run<T, F>(&self, f: F) -> Result<T>
+    where
+        T: Send + 'static,
+        F: FnOnce(&mut Connection) -> Result<T> + Send + 'static,
+    {
+        let conn = self.conn.clone();
+        tokio::task::spawn_blocking(move || {
+            let mut conn = conn.lock().map_err(|_| anyhow!("sqlite connection poisoned"))?;
+            f(&mut conn)
+        })
+        .await?
+    }
+
+    async fn write_vectors(&self, vectors: Vec<VectorRecord>, upsert: bool) -> Result<()> {
+        let dimensions = self.dimensions;
+        let normalize = self.normalize_on_load;
+        let sql = if upsert {
+            format!(
+                r#"INSERT INTO "{}" (id, embedding, metadata, text, createat, updateat)
+                   VALUES (?1, ?2, ?3, ?4, ?5, ?6)
+                   ON CONFLICT (id) DO UPDATE SET
+                     embedding = excluded.embedding,
+                     metadata = excluded.metadata,
+                     text = excluded.text,
+                     updateat = excluded.updateat"#,
+                self.table_name
+            )
+        } else {
+            format!(
+                r#"INSERT INTO "{}" (id, embedding, metadata, text, createat, updateat)
+                   VALUES (?1, ?2, ?3, ?4, ?5, ?6)"#,
+                self.table_name
+            )
+        };
+
+        self.run(move |conn| {
+            let tx = conn.transaction()?;
+            {
+                let mut stmt = tx.prepare(&sql)?;
+                for mut vec in vectors {
+                    if vec.embedding.len() != dimensions {
+                        // 与 PgVectorStore 保持一致：insert 报错，upsert 跳过
+                        if upsert {
+                            continue;
+                        }
+                        anyhow::bail!(
+                            "Embedding dim mismatch: expected {}, got {}",
+                            dimensions,
+                            vec.embedding.len()
+                        );
+                    }
+                    if normalize {
+                        renormalize(&mut vec.embedding);
+                    }
+                    let now = Utc::now();
+                    stmt.execute(params![
+                        vec.id,
+                        to_blob(&vec.embedding),
+                        vec.metadata.to_string(),
+                        vec.text,
+                        vec.createat.unwrap_or(now).to_rfc3339(),
+                        vec.updateat.unwrap_or(now).to_rfc3339(),
+                    ])?;
+                }
+            }
+            tx.commit()?;
+            Ok(())
+        })
+        .await
+    }
+
+    async fn write_metadata(&self, id: &str, metadata: JsonValue, value_expr: &'static str) -> Result<()> {
+        let sql = format!(
+            r#"UPDATE "{}" SET metadata = {}, updateat = ?2 WHERE id = ?3"#,
+            self.table_name, value_expr
+        );
+        let id = id.to_string();
+        self.run(move |conn| {
+            let updated = conn.execute(&sql, params![metadata.to_string(), Utc::now().to_rfc3339(), id])?;
+            if updated == 0 {
+                anyhow::bail!("Vector {} not found", id);
+            }
+            Ok(())
+        })
+        .await
+    }
+
+    /// 将表中所有未归一化的 embedding 原位 L2 归一化，返回更新的行数
+    ///
+    /// 用于把其他流程写入的未归一化向量迁移到本 crate 假定的归一化约定，已归一化的向量与零向量不改动。
+    pub async fn renormalize_all(&self) -> Result<u64> {
+        let select = format!(r#"SELECT id, embedding FROM "{}""#, self.table_name);
+        let update = format!(r#"UPDATE "{}" SET embedding = ?1, updateat = ?2 WHERE id = ?3"#, self.table_name);
+        self.run(move |conn| {
+            let tx = conn.transaction()?;
+            let mut updated = 0;
+            {
+                let rows = tx.prepare(&select)?
+                    .query_map([], |row| Ok((row.get::<_, String>(0)?, row.get::<_, Vec<u8>>(1)?)))?
+                    .collect::<rusqlite::Result<Vec<_>>>()?;
+                let mut stmt = tx.prepare(&update)?;
+                let now = Utc::now().to_rfc3339();
+                for (id, blob) in rows {
+                    let mut embedding = from_blob(&blob);
+                    if renormalize(&mut embedding) {
+                        stmt.execute(params![to_blob(&embedding), now, id])?;
+                        updated += 1;
+                    }
+                }
+            }
+            tx.commit()?;
+            Ok(updated)
+        })
+        .await
+    }
+
+    /// 按 id 读取单条记录
+    pub async fn get(&self, id: &str) -> Result<Option<VectorRecord>> {
+        let sql = format!(
+            r#"SELECT id, embedding, metadata, text, createat, updateat FROM "{}" WHERE id = ?1"#,
+            self.table_name
+        );
+        let id = id.to_string();
+        self.run(move |conn| {
+            let row = conn.query_row(&sql, params![id], |row| {
+                Ok((
+                    row.get::<_, String>(0)?,
+                    row.get::<_, Vec<u8>>(1)?,
+                    row.get::<_, String>(2)?,
+                    row.get::<_, Option<String>>(3)?,
+                    row.get::<_, Option<String>>(4)?,
+                    row.get::<_, Option<String>>(5)?,
+                ))
+            })
+            .optional()?;
+
+            row.map(|(id, embedding, metadata, text, createat, updateat)| {
+                Ok(VectorRecord {
+                    id,
+                    embedding: from_blob(&embedding),
+                    metadata: serde_json::from_str(&metadata)?,
+                    text,
+                    createat: parse_time(createat),
+                    updateat: parse_time(updateat),
+                })
+            })
+            .transpose()
+        })
+        .await
+    }
+}
+
+#[async_trait]
+impl VectorStore for SqliteVectorStore {
+    async fn add_vectors(&self, vectors: Vec<VectorRecord>) -> Result<()> {
+        self.write_vectors(vectors, false).await
+    }
+
+    async fn upsert_vectors(&self, vectors: Vec<VectorRecord>) -> Result<()> {
+        self.write_vectors(vectors, true).await
+    }
+
+    async fn delete_vector(&self, ids: Vec<String>) -> Result<()> {
+        if ids.is_empty() {
+            return Ok(());
+        }
+
+        let placeholders = (1..=ids.len()).map(|i| format!("?{}", i)).collect::<Vec<_>>();
+        let sql = format!(
+            r#"DELETE FROM "{}" WHERE id IN ({})"#,
+            self.table_name,
+            placeholders.join(", ")
+        );
+        self.run(move |conn| {
+            conn.execute(&sql, rusqlite::params_from_iter(ids))?;
+            Ok(())
+        })
+        .await
+    }
+
+    async fn search(&self, query: &[f32], top_k: usize) -> Result<Vec<SearchResult>> {
+        self.search_with(&SearchQuery::new(query.to_vec()).top_k(top_k)).await
+    }
+
+    /// 暴力扫描全表，过滤条件在内存中应用后再截取 top_k
+    async fn search_with(&self, query: &SearchQuery) -> Result<Vec<SearchResult>> {
+        if query.vector.len() != self.dimensions {
+            anyhow::bail!(
+                "Query dim mismatch: expected {}, got {}",
+                self.dimensions,
+                query.vector.len()
+            );
+        }
+
+        // 余弦距离取值 [0, 2]，在 Rust 侧归一化为 [0, 1] 的相似度
+        let sql = format!(
+            r#"SELECT id, embedding, metadata, text, createat, updateat,
+                      vec_distance_cosine(embedding, ?1) AS distance
+               FROM "{}"
+               ORDER BY distance"#,
+            self.table_name
+        );
+        let query = query.clone();
+        self.run(move |conn| {
+            let mut stmt = conn.prepare(&sql)?;
+            let rows = stmt.query_map(params![to_blob(&query.vector)], |row| {
+                Ok((
+                    row.get::<_, String>(0)?,
+                    row.get::<_, Vec<u8>>(1)?,
+                    row.get::<_, String>(2)?,
+                    row.get::<_, Option<String>>(3)?,
+                    row.get::<_, Option<String>>(4)?,
+                    row.get::<_, Option<String>>(5)?,
+                    row.get::<_, f64>(6)?,
+                ))
+            })?;
+
+            let mut hits = Vec::new();
+            for row in rows {
+                let (id, embedding, metadata, text, createat, updateat, distance) = row?;
+                let score = DistanceMetric::Cosine.normalize(distance as f32);
+                let record = VectorRecord {
+                    id,
+                    embedding: if query.include_embeddings { from_blob(&embedding) } else { Vec::new() },
+                    metadata: serde_json::from_str(&metadata)?,
+                    text,
+                    createat: parse_time(createat),
+                    updateat: parse_time(updateat),
+                };
+                if query.matches(&record, score) {
+                    hits.push((record, score));
+                    if hits.len() == query.top_k {
+                        break;
+                    }
+                }
+            }
+            Ok(SearchResult::ranked(hits))
+        })
+        .await
+    }
+}
+
+#[async_trait]
+impl ManagedVectorStore for SqliteVectorStore {
+    /// SQLite 的 JSON 函数没有包含判断，在内存中按 [`json_contains`] 匹配后删除
+    async fn delete_by_metadata(&self, filter: JsonValue) -> Result<u64> {
+        check_delete_filter(&filter)?;
+        let select = format!(r#"SELECT id, metadata FROM "{}""#, self.table_name);
+        let delete = format!(r#"DELETE FROM "{}" WHERE id = ?1"#, self.table_name);
+        self.run(move |conn| {
+            let tx = conn.transaction()?;
+            let mut deleted = 0;
+            {
+                let rows = tx.prepare(&select)?
+                    .query_map([], |row| Ok((row.get::<_, String>(0)?, row.get::<_, String>(1)?)))?
+                    .collect::<rusqlite::Result<Vec<_>>>()?;
+                let mut stmt = tx.prepare(&delete)?;
+                for (id, metadata) in rows {
+                    if json_contains(&serde_json::from_str(&metadata)?, &filter) {
+                        deleted += stmt.execute(params![id])? as u64;
+                    }
+                }
+            }
+            tx.commit()?;
+            Ok(deleted)
+        })
+        .await
+    }
+
+    async fn update_metadata(&self, id: &str, metadata: JsonValue) -> Result<()> {
+        self.write_metadata(id, metadata, "json(?1)").await
+    }
+
+    async fn merge_metadata(&self, id: &str, patch: JsonValue) -> Result<()> {
+        if !patch.is_object() {
+            anyhow::bail!("Metadata patch must be a JSON object");
+        }
+        self.write_metadata(id, patch, "json_patch(metadata, ?1)").await
+    }
+
+    async fn count(&self) -> Result<usize> {
+        let sql = format!(r#"SELECT COUNT(*) FROM "{}""#, self.table_name);
+        self.run(move |conn| {
+            let count: i64 = conn.query_row(&sql, [], |row| row.get(0))?;
+            Ok(count as usize)
+        })
+        .await
+    }
+
+    async fn get_by_id(&self, id: &str) -> Result<Option<VectorRecord>> {
+        self.get(id).await
+    }
+
+    async fn list(&self, limit: usize, offset: usize) -> Result<Vec<VectorRecord>> {
+        let sql = format!(
+            r#"SELECT id, embedding, metadata, text, createat, updateat FROM "{}"
+               ORDER BY createat, id
+               LIMIT ?1 OFFSET ?2"#,
+            self.table_name
+        );
+        self.run(move |conn| {
+            let mut stmt = conn.prepare(&sql)?;
+            let rows = stmt.query_map(params![limit as i64, offset as i64], |row| {
+                Ok((
+                    row.get::<_, String>(0)?,
+                    row.get::<_, Vec<u8>>(1)?,
+                    row.get::<_, String>(2)?,
+                    row.get::<_, Option<String>>(3)?,
+                    row.get::<_, Option<String>>(4)?,
+                    row.get::<_, Option<String>>(5)?,
+                ))
+            })?;
+
+            let mut records = Vec::new();
+            for row in rows {
+                let (id, embedding, metadata, text, createat, updateat) = row?;
+                records.push(VectorRecord {
+                    id,
+                    embedding: from_blob(&embedding),
+                    metadata: serde_json::from_str(&metadata)?,
+                    text,
+                    createat: parse_time(createat),
+                    updateat: parse_time(updateat),
+                });
+            }
+            Ok(records)
+        })
+        .await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn record(id: &str, embedding: Vec<f32>, document_id: &str) -> VectorRecord {
+        VectorRecord {
+            id: id.to_string(),
+            embedding,
+            metadata: serde_json::json!({ "document_id": document_id }),
+            text: Some(format!("text {}", id)),
+            createat: None,
+            updateat: None,
+        }
+    }
+
+    #[tokio::test]
+    async fn test_sqlite_store() -> Result<()> {
+        let store = SqliteVectorStore::open_in_memory("vectors", 3).await?;
+        store.add_vectors(vec![
+            record("a", vec![1.0, 0.0, 0.0], "doc-001"),
+            record("b", vec![0.0, 1.0, 0.0], "doc-001"),
+            record("c", vec![0.8, 0.6, 0.0], "doc-002"),
+        ]).await?;
+        assert!(store.add_vectors(vec![record("d", vec![1.0], "doc-001")]).await.is_err());
+        assert_eq!(store.count().await?, 3);
+        let pages = [store.list(2, 0).await?, store.list(2, 2).await?, store.list(2, 4).await?];
+        assert_eq!(pages.iter().map(Vec::len).collect::<Vec<_>>(), vec![2, 1, 0]);
+        assert_eq!(pages.concat().iter().map(|r| r.id.as_str()).collect::<Vec<_>>(), vec!["a", "b", "c"]);
+
+        let hits = store.search(&[1.0, 0.0, 0.0], 2).await?;
+        assert_eq!(hits.iter().map(|h| h.record.id.as_str()).collect::<Vec<_>>(), vec!["a", "c"]);
+        assert!((hits[0].score - 1.0).abs() < 1e-6);
+        assert!((hits[1].score - 0.9).abs() < 1e-6);
+        assert_eq!(hits[1].rank, 2);
+        assert!(hits[0].record.embedding.is_empty());
+        assert!(hits[0].record.createat.is_some());
+        assert_eq!(store.search_full(&[1.0, 0.0, 0.0], 1).await?[0].record.embedding, vec![1.0, 0.0, 0.0]);
+
+        let filtered = store.search_with(&SearchQuery::new(vec![1.0, 0.0, 0.0]).top_k(2).filter_document("doc-001")).await?;
+        assert_eq!(filtered.iter().map(|h| h.record.id.as_str()).collect::<Vec<_>>(), vec!["a", "b"]);
+
+        store.upsert_vectors(vec![record("b", vec![1.0, 0.0, 0.0], "doc-003")]).await?;
+        store.merge_metadata("b", serde_json::json!({ "tag": "x" })).await?;
+        let b = store.get("b").await?.unwrap();
+        assert_eq!(b.metadata, serde_json::json!({ "document_id": "doc-003", "tag": "x" }));
+        assert!(store.update_metadata("missing", serde_json::json!({})).await.is_err());
+
+        store.delete_vector(vec!["a".to_string(), "b".to_string()]).await?;
+        assert!(store.get_by_id("a").await?.is_none());
+        assert_eq!(store.count().await?, 1);
+        assert_eq!(store.search(&[1.0, 0.0, 0.0], 10).await?.len(), 1);
+
+        assert!(store.delete_by_metadata(serde_json::json!({})).await.is_err());
+        assert_eq!(store.delete_by_metadata(serde_json::json!({ "document_id": "doc-002" })).await?, 1);
+        assert_eq!(store.count().await?, 0);
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_renormalize() -> Result<()> {
+        let store = SqliteVectorStore::open_in_memory("vectors", 2).await?;
+        store.add_vectors(vec![
+            record("a", vec![3.0, 4.0], "doc-001"),
+            record("b", vec![0.0, 1.0], "doc-001"),
+            record("c", vec![0.0, 0.0], "doc-001"),
+        ]).await?;
+        assert_eq!(store.renormalize_all().await?, 1);
+        assert_eq!(store.get("a").await?.unwrap().embedding, vec![0.6, 0.8]);
+        assert_eq!(store.get("c").await?.unwrap().embedding, vec![0.0, 0.0]);
+        assert_eq!(store.renormalize_all().await?, 0);
+
+        let store = SqliteVectorStore::open_in_memory("vectors", 2).await?.with_normalize_on_load(true);
+        store.upsert_vectors(vec![record("a", vec![0.0, 2.0], "doc-001")]).await?;
+        assert_eq!(store.get("a").await?.unwrap().embedding, vec![0.0, 1.0]);
+        assert_eq!(store.renormalize_all().await?, 0);
+        Ok(())
+    }
+}
@@ -0,0 +1,92 @@
+use anyhow::{Result, anyhow};
+
+use crate::{client::EmbeddingClient, database::{SearchResult, VectorStore}, ingest_config::check_query_model};
+
+/// 绑定了嵌入客户端的向量库，支持直接以文本检索
+///
+/// 查询通过 [`EmbeddingClient::embed_queries`] 嵌入（Qwen 总是以 `retrieval.query` 请求），
+/// 可与入库共用同一个客户端；客户端维度须与向量库一致。
+pub struct TextSearchStore<S: VectorStore, C: EmbeddingClient> {
+    store: S,
+    embedding_client: C,
+}
+
+impl<S: VectorStore, C: EmbeddingClient> TextSearchStore<S, C> {
+    pub fn new(store: S, embedding_client: C) -> Self {
+        Self { store, embedding_client }
+    }
+
+    pub fn store(&self) -> &S {
+        &self.store
+    }
+
+    pub fn embedding_client(&self) -> &C {
+        &self.embedding_client
+    }
+
+    /// 以查询方式（[`EmbeddingClient::embed_queries`]）嵌入文本
+    pub async fn embed_query(&self, query: &str) -> Result<Vec<f32>> {
+        self.embedding_client
+            .embed_queries(vec![query.to_string()])
+            .await?
+            .into_iter()
+            .next()
+            .ok_or_else(|| anyhow!("查询向量为空"))
+    }
+
+    /// 以查询方式嵌入文本后检索最相似的 `top_k` 条记录
+    ///
+    /// 命中记录的入库模型与查询模型不一致时打印警告，见 [`check_query_model`]。
+    pub async fn search_by_text(&self, query: &str, top_k: usize) -> Result<Vec<SearchResult>> {
+        let embedding = self.embed_query(query).await?;
+        let hits = self.store.search(&embedding, top_k).await?;
+        check_query_model(&hits, &self.embedding_client);
+        Ok(hits)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use async_trait::async_trait;
+    use crate::client::EmbeddingResult;
+    use crate::database::VectorRecord;
+    use std::sync::Mutex;
+
+    struct EchoClient;
+
+    #[async_trait]
+    impl EmbeddingClient for EchoClient {
+        async fn embed(&self, texts: Vec<String>) -> EmbeddingResult<Vec<Vec<f32>>> {
+            Ok(texts.iter().map(|t| vec![t.chars().count() as f32]).collect())
+        }
+
+        fn dimension(&self) -> usize {
+            1
+        }
+    }
+
+    /// 记录收到的查询向量
+    #[derive(Default)]
+    struct RecordingStore(Mutex<Vec<Vec<f32>>>);
+
+    #[async_trait]
+    impl VectorStore for RecordingStore {
+        async fn add_vectors(&self, _vectors: Vec<VectorRecord>) -> Result<()> { Ok(()) }
+        async fn upsert_vectors(&self, _vectors: Vec<VectorRecord>) -> Result<()> { Ok(()) }
+        async fn delete_vector(&self, _ids: Vec<String>) -> Result<()> { Ok(()) }
+
+        async fn search(&self, query: &[f32], _top_k: usize) -> Result<Vec<SearchResult>> {
+            self.0.lock().unwrap().push(query.to_vec());
+            Ok(vec![])
+        }
+    }
+
+    #[tokio::test]
+    async fn test_search_by_text_embeds_query() -> Result<()> {
+        let store = TextSearchStore::new(RecordingStore::default(), EchoClient);
+        store.search_by_text("所有权", 5).await?;
+        assert_eq!(*store.store().0.lock().unwrap(), vec![vec![3.0]]);
+        Ok(())
+    }
+}
@@ -0,0 +1,169 @@
+use std::collections::HashMap;
+
+use rag_indexing::normalize::TextNormalizer;
+use rag_indexing::tree_structrue::{LeafNode, NodeId, NodeTree};
+
+use crate::client::cosine_similarity;
+use crate::ingest::content_hash;
+
+/// 近重复判定的默认余弦相似度阈值
+pub const DEFAULT_DEDUP_THRESHOLD: f32 = 0.98;
+
+/// 入库时的文档内去重配置
+#[derive(Debug, Clone)]
+pub struct DedupConfig {
+    /// embedding 余弦相似度不低于该值时视为近重复
+    pub similarity_threshold: f32,
+    /// 跨文档去重：规范化文本哈希已存在于库中的叶子不再入库，只在已有记录的 `metadata.document_ids` 中登记来源文档
+    pub cross_document: bool,
+}
+
+impl Default for DedupConfig {
+    fn default() -> Self {
+        Self { similarity_threshold: DEFAULT_DEDUP_THRESHOLD, cross_document: false }
+    }
+}
+
+impl DedupConfig {
+    pub fn with_similarity_threshold(mut self, threshold: f32) -> Self {
+        self.similarity_threshold = threshold;
+        self
+    }
+
+    pub fn with_cross_document(mut self, enabled: bool) -> Self {
+        self.cross_document = enabled;
+        self
+    }
+}
+
+/// 被跳过的重复叶子及其保留的叶子
+#[derive(Debug, Clone, PartialEq)]
+pub struct Duplicate {
+    pub duplicate: NodeId,
+    pub kept: NodeId,
+    /// 文本规范化后相同时为 1.0
+    pub similarity: f32,
+}
+
+/// 去重用的文本规范化：[`TextNormalizer`] 的 NFKC 与小写，并去除全部空白
+///
+/// 与 embedding 时的规范化配置（[`ProviderConfig::normalizer`](crate::client::registry::ProviderConfig::normalizer)）无关，
+/// 保证 `content_hash` 在不同配置下稳定。
+pub fn normalize_for_dedup(text: &str) -> String {
+    TextNormalizer::default()
+        .with_collapse_whitespace(false)
+        .with_lowercase(true)
+        .normalize(text)
+        .chars()
+        .filter(|c| !c.is_whitespace())
+        .collect()
+}
+
+/// 分块内容哈希：规范化文本的 sha256，对应向量表的 `content_hash` 列
+pub fn chunk_content_hash(text: &str) -> String {
+    content_hash(&normalize_for_dedup(text))
+}
+
+/// 余弦相似度，长度不同或含零向量时为 0
+pub(crate) fn cosine(a: &[f32], b: &[f32]) -> f32 {
+    cosine_similarity(a, b).unwrap_or(0.0)
+}
+
+/// 按文档顺序查找近重复叶子：与之前保留的叶子规范化文本相同，
+/// 或（双方都有 embedding 时）余弦相似度不低于阈值，则判为重复
+pub fn find_duplicates(node_tree: &NodeTree, config: &DedupConfig) -> Vec<Duplicate> {
+    let mut finder = DuplicateFinder::new(config);
+    node_tree.leaf_nodes_in_order()
+        .into_iter()
+        .filter_map(|leaf| finder.check(leaf))
+        .collect()
+}
+
+/// [`find_duplicates`] 的增量版本：按文档顺序逐个送入叶子，保留已判定叶子的状态
+///
+/// 分批入库时每批只需检查新叶子，不必对整棵树重新判定。叶子须按文档顺序且每个只送入一次。
+pub struct DuplicateFinder<'a> {
+    config: &'a DedupConfig,
+    by_text: HashMap<String, NodeId>,
+    kept: Vec<(NodeId, Vec<f32>)>,
+}
+
+impl<'a> DuplicateFinder<'a> {
+    pub fn new(config: &'a DedupConfig) -> Self {
+        Self { config, by_text: HashMap::new(), kept: Vec::new() }
+    }
+
+    /// 判定 `leaf` 是否与之前送入的保留叶子重复；不重复时将其记为保留叶子
+    pub fn check(&mut self, leaf: &LeafNode) -> Option<Duplicate> {
+        let normalized = normalize_for_dedup(&leaf.text);
+        if let Some(&kept_id) = self.by_text.get(&normalized) {
+            return Some(Duplicate { duplicate: leaf.id, kept: kept_id, similarity: 1.0 });
+        }
+
+        if let Some(embedding) = leaf.embedding.as_deref() {
+            let best = self.kept.iter()
+                .map(|(id, other)| (*id, cosine(embedding, other)))
+                .max_by(|a, b| a.1.total_cmp(&b.1));
+            if let Some((kept_id, similarity)) = best
+                && similarity >= self.config.similarity_threshold
+            {
+                return Some(Duplicate { duplicate: leaf.id, kept: kept_id, similarity });
+            }
+            self.kept.push((leaf.id, embedding.to_vec()));
+        }
+        self.by_text.insert(normalized, leaf.id);
+        None
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rag_indexing::tree_structrue::markdown_bulid::MarkdownParser;
+
+    const DOC: &str = "# 报告\n\n## 第一节\n\n本报告仅供内部参考。\n\n第一节正文。\n\n## 第二节\n\n本报告 仅供内部参考。\n\n第二节正文。\n";
+
+    fn leaf_id(tree: &NodeTree, text: &str) -> NodeId {
+        tree.leaf_nodes().find(|l| l.text == text).unwrap().id
+    }
+
+    #[test]
+    fn test_text_duplicates() -> anyhow::Result<()> {
+        let tree = MarkdownParser::new("doc-001".to_string(), None).parse(DOC)?;
+        let duplicates = find_duplicates(&tree, &DedupConfig::default());
+
+        assert_eq!(duplicates, vec![Duplicate {
+            duplicate: leaf_id(&tree, "本报告 仅供内部参考。"),
+            kept: leaf_id(&tree, "本报告仅供内部参考。"),
+            similarity: 1.0,
+        }]);
+        Ok(())
+    }
+
+    #[test]
+    fn test_chunk_content_hash() {
+        assert_eq!(chunk_content_hash("本报告仅供内部参考。"), chunk_content_hash("本报告 仅供\n内部参考。"));
+        assert_eq!(chunk_content_hash("Disclaimer"), chunk_content_hash("disclaimer"));
+        assert_eq!(chunk_content_hash("ＡＰＩ　Ｋｅｙ！"), chunk_content_hash("api key!"));
+        assert_ne!(chunk_content_hash("第一节正文。"), chunk_content_hash("第二节正文。"));
+    }
+
+    #[test]
+    fn test_embedding_threshold() -> anyhow::Result<()> {
+        let mut tree = MarkdownParser::new("doc-001".to_string(), None).parse(DOC)?;
+        let first = leaf_id(&tree, "第一节正文。");
+        let second = leaf_id(&tree, "第二节正文。");
+        for leaf in tree.leaf_nodes_in_order().iter().map(|l| l.id).collect::<Vec<_>>() {
+            tree.set_leaf_embedding(leaf, vec![0.0, 0.0, 1.0])?;
+        }
+        tree.set_leaf_embedding(first, vec![1.0, 0.0, 0.0])?;
+        tree.set_leaf_embedding(second, vec![0.99, 0.14, 0.0])?;
+
+        let duplicates = find_duplicates(&tree, &DedupConfig::default().with_similarity_threshold(0.95));
+        assert!(duplicates.iter().any(|d| d.duplicate == second && d.kept == first));
+
+        let duplicates = find_duplicates(&tree, &DedupConfig::default().with_similarity_threshold(0.999));
+        assert!(duplicates.iter().all(|d| d.duplicate != second));
+        Ok(())
+    }
+}
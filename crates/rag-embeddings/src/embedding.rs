@@ -1,10 +1,25 @@
-use anyhow::Result;
-use rag_indexing::tree_structrue::{LeafNode, NodeTree};
+use anyhow::{Result, anyhow, bail};
+use rag_indexing::tree_structrue::{LeafNode, Node, NodeId, NodeTree};
+use uuid::Uuid;
 
-use crate::{client::{EmbeddingClient, qwen::QwenEmbeddingClient}, database::{VectorRecord, VectorStore, pgvector::PgVectorStore}};
+use std::collections::{HashMap, HashSet};
+
+use tokio_util::sync::CancellationToken;
+
+use crate::{buffered::DEFAULT_BATCH_SIZE, client::{DEFAULT_NORMALIZATION_TOLERANCE, EmbeddingClient, EmbeddingError, l2_norm, qwen::QwenEmbeddingClient}, database::{InMemoryVectorStore, ManagedVectorStore, VectorRecord, VectorStore, pgvector::PgVectorStore}, dedup::{DedupConfig, Duplicate, DuplicateFinder, chunk_content_hash, find_duplicates}, ingest_config::{INGEST_CONFIG_KEY, IngestConfig}};
 
 // 叶子节点转为向量数据库中的记录 
-pub fn leaf_to_vector_record(node_tree: &NodeTree, leaf: &LeafNode) -> VectorRecord {
+///
+/// 叶子尚未生成 embedding（或 embedding 为空）时返回错误，不会产生零长度向量的记录
+pub fn leaf_to_vector_record(node_tree: &NodeTree, leaf: &LeafNode) -> Result<VectorRecord> {
+    match &leaf.embedding {
+        Some(embedding) if !embedding.is_empty() => Ok(leaf_record(node_tree, leaf, embedding.clone())),
+        _ => bail!("leaf {} has no embedding", leaf.id),
+    }
+}
+
+/// 按给定 embedding 构造叶子的记录，不检查 embedding 是否为空
+pub(crate) fn leaf_record(node_tree: &NodeTree, leaf: &LeafNode, embedding: Vec<f32>) -> VectorRecord {
     let hierarchy = &leaf.metadata.hierarchy;
     let parent_titles: Vec<String> = node_tree.get_ancestors(leaf.id)
         .into_iter()
@@ -13,32 +28,69 @@ pub fn leaf_to_vector_record(node_tree: &NodeTree, leaf: &LeafNode) -> VectorRec
 
     VectorRecord {
         id: leaf.id.to_string(),
-        embedding: leaf.embedding.clone().unwrap_or_default(), // embedding 已自动 L2 归一化
+        embedding, // embedding 已自动 L2 归一化
         text: Some(leaf.text.clone()),
         metadata: serde_json::json!({
             "document_id": leaf.metadata.document_id,
             "node_id": leaf.id.to_string(),
-            "chunk_index": leaf.metadata.hierarchy.last().and_then(|s| s.split('_').nth(1)).and_then(|s| s.parse::<i32>().ok()),
+            "chunk_index": leaf.metadata.chunk_index,
+            "order": leaf.metadata.order,
             "chunk_size": leaf.metadata.chunk_size,
+            "token_count": leaf.metadata.chunk_size,
+            "token_model": node_tree.token_model(),
+            "char_len": leaf.metadata.char_len,
             "file_name": leaf.metadata.file_name,
+            "file_path": leaf.metadata.file_path,
+            "line_start": leaf.metadata.line_start,
+            "line_end": leaf.metadata.line_end,
+            "document_title": node_tree.document_title(),
+            "document_author": node_tree.document_author(),
             "hierarchy": hierarchy,
             "parent_titles": parent_titles,
+            "type": leaf.block_type(),
             "is_image": leaf.metadata.image_path.is_some(),
             "image_alt": leaf.metadata.image_alt,
+            "image_title": leaf.metadata.image_title,
             "image_path": leaf.metadata.image_path,
+            "window": leaf.metadata.window,
+            "oversized": leaf.metadata.oversized,
+            "slug": leaf.metadata.slug,
+            "slug_path": leaf.metadata.slug_path,
         }),
         createat: None,
         updateat: None,
     }
 }
 
+/// [`save_node_tree`] 的入库结果
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct SaveSummary {
+    /// 本次新生成 embedding 并已入库的叶子数
+    pub embedded: usize,
+    /// 写入向量库的记录数，包括调用前已有 embedding 的叶子
+    pub stored: usize,
+    /// 所在批次嵌入或写入失败、未能入库的叶子数
+    pub failed: usize,
+    /// 失败批次的错误信息
+    pub error: Option<String>,
+    /// 被跳过的文档内重复叶子
+    pub duplicates: Vec<Duplicate>,
+}
+
+impl SaveSummary {
+    /// 所有待入库的叶子都已写入
+    pub fn is_complete(&self) -> bool {
+        self.failed == 0
+    }
+}
+
 /// 将 NodeTree 的叶子节点转换为向量表示并存储到数据库
 /// 
 /// # 流程
-/// 1. 遍历所有叶子节点，收集未生成 embedding 的文本
-/// 2. 使用 QwenEmbeddingClient 生成 embedding 向量（**自动 L2 归一化**）
+/// 1. 按文档顺序收集待入库的叶子，每 [`DEFAULT_BATCH_SIZE`] 个一批
+/// 2. 为本批中未生成 embedding 的叶子调用 QwenEmbeddingClient 生成向量（**自动 L2 归一化**）
 /// 3. 将归一化后的向量存储到对应叶子节点
-/// 4. 转换为 VectorRecord 格式并存储到 pgvector 数据库
+/// 4. 转换为 VectorRecord 格式并 upsert 到 pgvector 数据库，再处理下一批
 /// 
 /// # 注意事项
 /// - 所有生成的 embedding 向量都会自动进行 L2 归一化（单位长度）
@@ -46,85 +98,353 @@ pub fn leaf_to_vector_record(node_tree: &NodeTree, leaf: &LeafNode) -> VectorRec
 /// - 向量维度：text-embedding-v1/v2=1536, text-embedding-v3=2560
 /// 
 /// # 错误处理
-/// - 如果 API 调用失败，会返回详细的错误信息
+/// - 某一批嵌入或写入失败时停止处理，之前的批次已写入数据库、向量也保留在树中，
+///   重新调用时不会再次生成；失败与未处理的叶子计入 [`SaveSummary::failed`]
 /// - 零向量无法归一化，会抛出 InvalidVector 错误
 pub async fn save_node_tree(
     node_tree: &mut NodeTree,
-    store: PgVectorStore,
-    embedding_client: QwenEmbeddingClient,
-) -> Result<()> {
-    
-    let mut texts = Vec::new();
-    let mut leaf_ids = Vec::new();
-
-    for leaf in node_tree.leaf_nodes() { 
-        if leaf.embedding.is_none() {
-            texts.push(leaf.text.clone());
-            leaf_ids.push(leaf.id);
-        }
-    }
-
-    if !texts.is_empty() {
-        let embeddings = embedding_client.embed(texts).await?;        
-        // 验证每个向量的归一化状态
-        for (i, embedding) in embeddings.iter().enumerate() {
-            let norm = embedding.iter().map(|&x| x as f64 * x as f64).sum::<f64>().sqrt();
-            let is_normalized = (norm - 1.0).abs() < 1e-6;
-            
-            if i < 3 { // 只打印前3个向量的详细信息
-                println!("  向量 {}: L2范数={:.8}, 归一化={}, 范围[{:.4} ~ {:.4}]", 
-                    i, norm, is_normalized, 
-                    embedding.iter().fold(f32::INFINITY, |a, &b| a.min(b)),
-                    embedding.iter().fold(f32::NEG_INFINITY, |a, &b| a.max(b))
-                );
+    store: &PgVectorStore,
+    embedding_client: &QwenEmbeddingClient,
+) -> Result<SaveSummary> {
+    save_node_tree_with(node_tree, store, embedding_client, None, None).await
+}
+
+/// 同 [`save_node_tree`]，可选地在文档内去除近重复叶子
+///
+/// 开启去重时，规范化文本相同的叶子不再生成 embedding；embedding 生成后再按余弦相似度阈值判定一次。
+/// 重复叶子不会入库，其 node_id 与 hierarchy 记录在保留记录的 `metadata.duplicates` 中。
+/// 开启 [`DedupConfig::cross_document`] 时，内容哈希已在库中的叶子直接复用已有记录，
+/// 当前文档登记到该记录的 `metadata.document_ids`；新入库记录的 `document_ids` 初始为当前文档。
+/// 传入 `cancel` 时可中途取消：进行中的嵌入请求被丢弃，已完成的批次保留在库中，返回 [`EmbeddingError::Cancelled`]。
+pub async fn save_node_tree_with(
+    node_tree: &mut NodeTree,
+    store: &PgVectorStore,
+    embedding_client: &QwenEmbeddingClient,
+    dedup: Option<&DedupConfig>,
+    cancel: Option<&CancellationToken>,
+) -> Result<SaveSummary> {
+    // 文本完全重复的叶子无需生成 embedding
+    let skipped: HashSet<_> = dedup
+        .map(|config| find_duplicates(node_tree, config).into_iter().map(|d| d.duplicate).collect())
+        .unwrap_or_default();
+
+    let mut leaf_ids: Vec<NodeId> = node_tree
+        .leaf_nodes_in_order()
+        .into_iter()
+        .filter(|leaf| !skipped.contains(&leaf.id))
+        .map(|leaf| leaf.id)
+        .collect();
+
+    // 跨文档去重：库中已有相同内容的分块只登记引用，不再生成 embedding
+    if dedup.is_some_and(|config| config.cross_document) {
+        if cancel.is_some_and(|token| token.is_cancelled()) {
+            return Err(EmbeddingError::Cancelled.into());
+        }
+        let pending: Vec<&LeafNode> = leaf_ids.iter()
+            .filter_map(|id| node_tree.nodes.get(id)?.as_leaf())
+            .filter(|leaf| leaf.embedding.is_none())
+            .collect();
+        let hashes: Vec<String> = pending.iter().map(|leaf| chunk_content_hash(&leaf.text)).collect();
+        let existing = store.find_by_content_hashes(&hashes).await?;
+        let mut shared = HashSet::new();
+        for (leaf, hash) in pending.iter().zip(&hashes) {
+            if let Some(record_id) = existing.get(hash) {
+                store.add_document_reference(record_id, &leaf.metadata.document_id).await?;
+                shared.insert(leaf.id);
             }
-            
-            assert!(is_normalized, "向量 {} 未正确归一化，L2范数: {:.8}", i, norm);
         }
-
-        for (i, embedding) in embeddings.clone().into_iter().enumerate() {
-            node_tree.set_leaf_embedding(leaf_ids[i], embedding)?;
+        leaf_ids.retain(|id| !shared.contains(id));
+        if !shared.is_empty() {
+            println!("{} 个叶子与已入库分块内容相同，仅登记文档引用", shared.len());
         }
-        
-        println!("已将 {} 个归一化向量存储到 NodeTree", embeddings.len());
-    } else {
+    }
+
+    let summary = BatchWriter::new(store, embedding_client, dedup, cancel)
+        .write(node_tree, &leaf_ids)
+        .await?;
+    if summary.embedded == 0 && summary.is_complete() {
         println!("所有叶子节点已有 embedding，无需重新生成");
     }
+    if !summary.duplicates.is_empty() {
+        println!("跳过 {} 个近重复叶子", summary.duplicates.len());
+    }
+    Ok(summary)
+}
+
+/// 为 NodeTree 的全部叶子生成 embedding 并构造记录，不写入向量库
+///
+/// 批次、归一化校验与记录的 metadata 与 [`save_node_tree`] 一致；任一批失败时返回错误。
+/// 由调用方一次性写入（如 [`PgVectorStore::reindex_document`]），避免文档只入库一半。
+/// `dimensions` 为目标表的向量维度，embedding 维度不符时报错。
+pub async fn embed_node_tree<C: EmbeddingClient>(
+    node_tree: &mut NodeTree,
+    embedding_client: &C,
+    dimensions: usize,
+) -> Result<Vec<VectorRecord>> {
+    let leaf_ids: Vec<NodeId> = node_tree.leaf_nodes_in_order().into_iter().map(|leaf| leaf.id).collect();
+    let staging = InMemoryVectorStore::new(dimensions);
+    let summary = BatchWriter::new(&staging, embedding_client, None, None)
+        .write(node_tree, &leaf_ids)
+        .await?;
+    if !summary.is_complete() {
+        bail!("{} 个叶子未能生成 embedding: {}", summary.failed, summary.error.unwrap_or_default());
+    }
+
+    // 内存库跳过维度不符的记录
+    let records = staging.records();
+    if records.len() != leaf_ids.len() {
+        bail!("{} 个叶子的 embedding 维度与 {} 不符", leaf_ids.len() - records.len(), dimensions);
+    }
+    Ok(records)
+}
+
+/// 按批嵌入叶子并写入向量库，每批完成即落库
+struct BatchWriter<'a, S, C> {
+    store: &'a S,
+    embedding_client: &'a C,
+    dedup: Option<&'a DedupConfig>,
+    cancel: Option<&'a CancellationToken>,
+    batch_size: usize,
+    /// 已写入的叶子 -> 写入时 `metadata.duplicates` 的条数
+    stored: HashMap<NodeId, usize>,
+    /// 开启去重时，已按文档顺序判定过的叶子的去重状态
+    finder: Option<DuplicateFinder<'a>>,
+    /// 树中全部叶子（文档顺序），`checked` 之前的已送入 `finder`
+    order: Vec<NodeId>,
+    checked: usize,
+    duplicates: Vec<Duplicate>,
+    /// 保留叶子 -> 其重复叶子的引用，写入 `metadata.duplicates`
+    references: HashMap<NodeId, Vec<serde_json::Value>>,
+}
+
+impl<'a, S: ManagedVectorStore, C: EmbeddingClient> BatchWriter<'a, S, C> {
+    fn new(store: &'a S, embedding_client: &'a C, dedup: Option<&'a DedupConfig>, cancel: Option<&'a CancellationToken>) -> Self {
+        Self {
+            store,
+            embedding_client,
+            dedup,
+            cancel,
+            batch_size: DEFAULT_BATCH_SIZE,
+            stored: HashMap::new(),
+            finder: dedup.map(DuplicateFinder::new),
+            order: Vec::new(),
+            checked: 0,
+            duplicates: Vec::new(),
+            references: HashMap::new(),
+        }
+    }
+
+    /// 按文档顺序判定尚未检查的叶子，直到 `until`（含）或树的末尾
+    ///
+    /// 每个叶子只判定一次，调用时 `until` 及之前的待入库叶子须已有 embedding。
+    fn check_duplicates(&mut self, node_tree: &NodeTree, until: Option<NodeId>) {
+        let Some(finder) = self.finder.as_mut() else { return };
+        while let Some(&id) = self.order.get(self.checked) {
+            self.checked += 1;
+            if let Some(duplicate) = node_tree.nodes.get(&id).and_then(|n| n.as_leaf()).and_then(|leaf| finder.check(leaf)) {
+                let hierarchy = node_tree.nodes.get(&duplicate.duplicate).map(|n| n.metadata().hierarchy.clone());
+                self.references.entry(duplicate.kept).or_default().push(serde_json::json!({
+                    "node_id": duplicate.duplicate.to_string(),
+                    "hierarchy": hierarchy,
+                    "similarity": duplicate.similarity,
+                }));
+                self.duplicates.push(duplicate);
+            }
+            if Some(id) == until {
+                break;
+            }
+        }
+    }
+
+    fn check_cancelled(&self) -> Result<(), EmbeddingError> {
+        match self.cancel {
+            Some(token) if token.is_cancelled() => Err(EmbeddingError::Cancelled),
+            _ => Ok(()),
+        }
+    }
+
+    /// 依次写入 `leaf_ids`（须为文档顺序）；某批失败时停止，取消时返回错误
+    async fn write(mut self, node_tree: &mut NodeTree, leaf_ids: &[NodeId]) -> Result<SaveSummary> {
+        let mut summary = SaveSummary::default();
+        self.order = node_tree.leaf_nodes_in_order().iter().map(|leaf| leaf.id).collect();
+        let batches: Vec<&[NodeId]> = leaf_ids.chunks(self.batch_size.max(1)).collect();
+        for (i, batch) in batches.iter().enumerate() {
+            // 最后一批之后的叶子（如只按文本判定的重复叶子）随最后一批一起判定
+            let until = if i + 1 == batches.len() { None } else { batch.last().copied() };
+            match self.write_batch(node_tree, batch, until).await {
+                Ok(embedded) => summary.embedded += embedded,
+                Err(e) if matches!(e.downcast_ref(), Some(EmbeddingError::Cancelled)) => return Err(e),
+                Err(e) => {
+                    summary.failed = batches[i..].iter().map(|batch| batch.len()).sum();
+                    println!("第 {}/{} 批入库失败，{} 个叶子未入库: {:#}", i + 1, batches.len(), summary.failed, e);
+                    summary.error = Some(format!("{:#}", e));
+                    break;
+                }
+            }
+        }
+
+        // 失败后未处理的叶子没有 embedding，只按文本判定
+        self.check_duplicates(node_tree, None);
+        summary.stored = self.stored.len();
+        summary.duplicates = self.duplicates;
+        Ok(summary)
+    }
+
+    /// 嵌入并写入一批叶子，返回新生成 embedding 的叶子数；去重判定推进到 `until`（见 [`Self::check_duplicates`]）
+    async fn write_batch(&mut self, node_tree: &mut NodeTree, batch: &[NodeId], until: Option<NodeId>) -> Result<usize> {
+        self.check_cancelled()?;
+        let missing: Vec<(NodeId, String)> = batch.iter()
+            .filter_map(|id| node_tree.nodes.get(id)?.as_leaf())
+            .filter(|leaf| leaf.embedding.is_none())
+            .map(|leaf| (leaf.id, leaf.text.clone()))
+            .collect();
+
+        if !missing.is_empty() {
+            let texts: Vec<String> = missing.iter().map(|(_, text)| text.clone()).collect();
+            let embeddings = match self.cancel {
+                Some(token) => self.embedding_client.embed_cancellable(texts, token).await?,
+                None => self.embedding_client.embed(texts).await?,
+            };
+            if embeddings.len() != missing.len() {
+                bail!("请求 {} 个 embedding，返回了 {} 个", missing.len(), embeddings.len());
+            }
+
+            // 验证每个向量的归一化状态
+            self.embedding_client
+                .verify_batch_normalized(&embeddings, DEFAULT_NORMALIZATION_TOLERANCE)
+                .map_err(|failed| anyhow!("{} 个向量未正确归一化 (下标, L2范数): {:?}", failed.len(), failed))?;
+
+            for ((leaf_id, _), embedding) in missing.iter().zip(embeddings) {
+                node_tree.set_leaf_embedding(*leaf_id, embedding)?;
+            }
+            println!("已将 {} 个归一化向量存储到 NodeTree", missing.len());
+        }
+
+        // 之前的叶子都已有 embedding，只需按文档顺序判定本批新增的叶子
+        self.check_duplicates(node_tree, until);
+        let duplicate_ids: HashSet<_> = self.duplicates.iter().map(|d| d.duplicate).collect();
+
+        let cross_document = self.dedup.is_some_and(|config| config.cross_document);
+        let ingest_config = IngestConfig::new(node_tree, self.embedding_client).to_value();
+        let mut records = Vec::new();
+        for leaf in batch.iter().filter(|id| !duplicate_ids.contains(id)).filter_map(|id| node_tree.nodes.get(id)?.as_leaf()) {
+            let mut record = leaf_to_vector_record(node_tree, leaf)?;
+            record.metadata[INGEST_CONFIG_KEY] = ingest_config.clone();
+            if let Some(refs) = self.references.get(&leaf.id) {
+                record.metadata["duplicates"] = serde_json::Value::Array(refs.clone());
+            }
+            if cross_document {
+                record.metadata["document_ids"] = serde_json::json!([leaf.metadata.document_id]);
+            }
+            records.push((leaf.id, record));
+        }
+
+        // 验证存储的向量也是归一化的
+        let vectors: Vec<Vec<f32>> = records.iter().map(|(_, r)| r.embedding.clone()).collect();
+        self.embedding_client
+            .verify_batch_normalized(&vectors, DEFAULT_NORMALIZATION_TOLERANCE)
+            .map_err(|failed| anyhow!("{} 个待存储向量未正确归一化 (下标, L2范数): {:?}", failed.len(), failed))?;
+
+        self.check_cancelled()?;
+        let (written, records): (Vec<NodeId>, Vec<VectorRecord>) = records.into_iter().unzip();
+        self.store.upsert_vectors(records).await?;
+        for leaf_id in written {
+            self.stored.insert(leaf_id, self.references.get(&leaf_id).map_or(0, Vec::len));
+        }
+
+        // 之前批次写入的记录有了新的重复叶子，更新其 duplicates 列表
+        for (kept, refs) in &self.references {
+            if self.stored.get(kept).is_some_and(|&count| count != refs.len()) {
+                self.store.merge_metadata(&kept.to_string(), serde_json::json!({ "duplicates": refs })).await?;
+                self.stored.insert(*kept, refs.len());
+            }
+        }
 
-    // match serde_json::to_string_pretty(node_tree) {
-    //     Ok(json) => {
-    //         println!("\n{} NODE TREE STRUCTURE (JSON) {}\n", "=".repeat(20), "=".repeat(20));
-    //         println!("{}", json);
-    //         println!("\n{}", "=".repeat(62));
-    //     }
-    //     Err(e) => eprintln!("序列化失败: {}", e),
-    // }
-
-    let records: Vec<VectorRecord> = node_tree
-        .leaf_nodes()
-        .filter(|leaf| leaf.embedding.is_some())
-        .map(|leaf| {
-            let record = leaf_to_vector_record(node_tree, leaf);
-            // 验证存储的向量也是归一化的
-            let norm = record.embedding.iter().map(|&x| x as f64 * x as f64).sum::<f64>().sqrt();
-            assert!((norm - 1.0).abs() < 1e-6, "存储的向量未正确归一化，L2范数: {:.8}", norm);
-            record
-        })
+        Ok(missing.len())
+    }
+}
+
+/// 由 `document_id` 派生文档级记录 id 时使用的 UUID v5 命名空间
+const DOCUMENT_NAMESPACE: Uuid = Uuid::from_u128(0x5c0ef3a1_7b42_5d19_8e6a_2f4b9c1d7e30);
+
+/// 文档级 embedding：所有已生成 embedding 的叶子向量的均值，再做 L2 归一化
+///
+/// 重复叶子没有 embedding，不参与平均；维度与第一个叶子不同的向量被忽略。
+/// 没有可用的叶子 embedding 或均值为零向量时返回 `None`。
+pub fn document_embedding(node_tree: &NodeTree) -> Option<Vec<f32>> {
+    let mut embeddings = node_tree.leaf_nodes_in_order()
+        .into_iter()
+        .filter_map(|leaf| leaf.embedding.as_deref())
+        .filter(|embedding| !embedding.is_empty());
+    let first = embeddings.next()?;
+    let mut sum = first.to_vec();
+    for embedding in embeddings.filter(|e| e.len() == first.len()) {
+        sum.iter_mut().zip(embedding).for_each(|(s, x)| *s += x);
+    }
+
+    // 均值与和方向相同，直接对和归一化
+    let norm = l2_norm(&sum);
+    if norm == 0.0 {
+        return None;
+    }
+    Some(sum.into_iter().map(|x| x / norm).collect())
+}
+
+/// 文档级 embedding 的记录：id 由 `document_id` 派生（重复构建时覆盖），`metadata.type` 为 `"document"`
+pub fn document_record(node_tree: &NodeTree, embedding: Vec<f32>) -> VectorRecord {
+    let (document_id, file_name) = match node_tree.nodes.get(&node_tree.root) {
+        Some(Node::Root(root)) => (root.document_id.clone(), root.metadata.file_name.clone()),
+        _ => (String::new(), None),
+    };
+
+    VectorRecord {
+        id: Uuid::new_v5(&DOCUMENT_NAMESPACE, document_id.as_bytes()).to_string(),
+        embedding,
+        text: node_tree.document_title().map(str::to_string),
+        metadata: serde_json::json!({
+            "document_id": document_id,
+            "file_name": file_name,
+            "document_title": node_tree.document_title(),
+            "document_author": node_tree.document_author(),
+            "leaf_count": node_tree.leaf_nodes().filter(|leaf| leaf.embedding.is_some()).count(),
+            "type": "document",
+        }),
+        createat: None,
+        updateat: None,
+    }
+}
+
+/// 为每棵已入库的树计算文档级 embedding 并 upsert 到 `store`，返回写入的文档数
+///
+/// 用于两阶段检索：先检索文档向量确定范围，再在命中文档内检索叶子，
+/// 见 `rag_retrieval::Retriever::with_document_store`。`store` 应为单独的表
+/// （如 `PgVectorStore::new(pool, "document_vectors", dim)`），避免文档记录出现在叶子检索结果中。
+/// 叶子 embedding 须已生成（如经 [`save_node_tree`]），没有 embedding 的树被跳过。
+pub async fn build_document_embeddings<S: VectorStore>(trees: &[NodeTree], store: &S) -> Result<usize> {
+    let records: Vec<VectorRecord> = trees.iter()
+        .filter_map(|tree| Some(document_record(tree, document_embedding(tree)?)))
         .collect();
+    let skipped = trees.len() - records.len();
+    if skipped > 0 {
+        println!("{} 个文档没有叶子 embedding，跳过文档向量", skipped);
+    }
 
-    store.upsert_vectors(records).await?;
-    
-    Ok(())
+    let count = records.len();
+    if count > 0 {
+        store.upsert_vectors(records).await?;
+    }
+    Ok(count)
 }
 
 #[cfg(test)]
 mod tests {
     use anyhow::Result;
-    use rag_indexing::tree_structrue::markdown_bulid::MarkdownParser;
-    use sqlx::PgPool;
+    use rag_indexing::tree_structrue::markdown_bulid::{DEFAULT_TOKEN_MODEL, MarkdownParser};
+    use async_trait::async_trait;
     use dotenv::dotenv;
+    use std::sync::Mutex;
 
-    use crate::{client::qwen::QwenEmbeddingClient, database::pgvector::PgVectorStore, embedding::save_node_tree};
+    use crate::{client::{EmbeddingClient, EmbeddingResult, qwen::QwenEmbeddingClient}, database::{InMemoryVectorStore, ManagedVectorStore, SearchResult, VectorRecord, VectorStore, pgvector::{DEFAULT_MAX_CONNECTIONS, PgVectorStore}}, dedup::{DedupConfig, find_duplicates}, embedding::{BatchWriter, build_document_embeddings, document_embedding, embed_node_tree, document_record, leaf_to_vector_record, save_node_tree}, ingest_config::IngestConfig};
 
     const TEST: &str = r#"
 # ChatGPT出现以来中美大模型发展报告
@@ -152,10 +472,175 @@ ChatGPT的出现并非偶然，而是人工智能发展到一定阶段的必然
         let parser = MarkdownParser::new("doc-001".to_string(),Some("test.md".to_string()));
         let mut tree = parser.parse(TEST)?;
 
-        let pool = PgPool::connect("postgres:///rag_db").await?;
-        let store = PgVectorStore::new(pool, "vectors", 1536).await?;
-        save_node_tree(&mut tree, store, embedding_client).await?;
+        let store = PgVectorStore::open("postgres:///rag_db", "vectors", 1536, DEFAULT_MAX_CONNECTIONS).await?;
+        save_node_tree(&mut tree, &store, &embedding_client).await?;
+        Ok(())
+    }
+
+    #[test]
+    fn test_leaf_without_embedding() -> Result<()> {
+        let parser = MarkdownParser::new("doc-001".to_string(), None);
+        let mut tree = parser.parse("# 标题\n\n正文段落。")?;
+        let id = tree.leaf_nodes().next().unwrap().id;
+
+        let err = leaf_to_vector_record(&tree, tree.leaf_nodes().next().unwrap()).unwrap_err();
+        assert_eq!(err.to_string(), format!("leaf {} has no embedding", id));
+
+        tree.set_leaf_embedding(id, Vec::new())?;
+        assert!(leaf_to_vector_record(&tree, tree.leaf_nodes().next().unwrap()).is_err());
+
+        tree.set_leaf_embedding(id, vec![1.0, 0.0])?;
+        let record = leaf_to_vector_record(&tree, tree.leaf_nodes().next().unwrap())?;
+        assert_eq!(record.embedding, vec![1.0, 0.0]);
+        assert_eq!(record.metadata["document_id"], "doc-001");
+        assert_eq!(record.metadata["token_count"], record.metadata["chunk_size"]);
+        assert_eq!(record.metadata["token_model"], DEFAULT_TOKEN_MODEL);
+        Ok(())
+    }
+
+    struct UnitClient;
+
+    #[async_trait]
+    impl EmbeddingClient for UnitClient {
+        async fn embed(&self, texts: Vec<String>) -> EmbeddingResult<Vec<Vec<f32>>> {
+            Ok(texts.iter().map(|_| vec![1.0, 0.0]).collect())
+        }
+
+        fn dimension(&self) -> usize {
+            2
+        }
+    }
+
+    /// 记录每次 upsert 与 metadata 合并，第 `fail_on` 次（从 1 开始）upsert 返回错误，其余操作转发给内存存储
+    struct MemStore {
+        fail_on: Option<usize>,
+        upserts: Mutex<Vec<Vec<VectorRecord>>>,
+        patches: Mutex<Vec<(String, serde_json::Value)>>,
+        inner: InMemoryVectorStore,
+    }
+
+    impl Default for MemStore {
+        fn default() -> Self {
+            Self { fail_on: None, upserts: Mutex::default(), patches: Mutex::default(), inner: InMemoryVectorStore::new(2) }
+        }
+    }
+
+    #[async_trait]
+    impl VectorStore for MemStore {
+        async fn add_vectors(&self, vectors: Vec<VectorRecord>) -> Result<()> { self.upsert_vectors(vectors).await }
+        async fn upsert_vectors(&self, vectors: Vec<VectorRecord>) -> Result<()> {
+            {
+                let mut upserts = self.upserts.lock().unwrap();
+                if self.fail_on == Some(upserts.len() + 1) {
+                    anyhow::bail!("connection reset");
+                }
+                upserts.push(vectors.clone());
+            }
+            self.inner.upsert_vectors(vectors).await
+        }
+        async fn delete_vector(&self, ids: Vec<String>) -> Result<()> { self.inner.delete_vector(ids).await }
+        async fn search(&self, query: &[f32], top_k: usize) -> Result<Vec<SearchResult>> { self.inner.search(query, top_k).await }
+    }
+
+    #[async_trait]
+    impl ManagedVectorStore for MemStore {
+        async fn delete_by_metadata(&self, filter: serde_json::Value) -> Result<u64> { self.inner.delete_by_metadata(filter).await }
+        async fn update_metadata(&self, id: &str, metadata: serde_json::Value) -> Result<()> { self.inner.update_metadata(id, metadata).await }
+        async fn merge_metadata(&self, id: &str, patch: serde_json::Value) -> Result<()> {
+            self.patches.lock().unwrap().push((id.to_string(), patch.clone()));
+            self.inner.merge_metadata(id, patch).await
+        }
+        async fn count(&self) -> Result<usize> { self.inner.count().await }
+        async fn get_by_id(&self, id: &str) -> Result<Option<VectorRecord>> { self.inner.get_by_id(id).await }
+        async fn list(&self, limit: usize, offset: usize) -> Result<Vec<VectorRecord>> { self.inner.list(limit, offset).await }
+    }
+
+    #[tokio::test]
+    async fn test_batches_survive_failure() -> Result<()> {
+        let parser = MarkdownParser::new("doc-001".to_string(), None);
+        let mut tree = parser.parse("# 标题\n\n第一段。\n\n第二段。\n\n第三段。\n\n第四段。\n\n第五段。")?;
+        let leaf_ids: Vec<_> = tree.leaf_nodes_in_order().iter().map(|leaf| leaf.id).collect();
+        assert_eq!(leaf_ids.len(), 5);
+
+        // 第二批写入失败：第一批已落库，其余叶子计为失败
+        let store = MemStore { fail_on: Some(2), ..Default::default() };
+        let mut writer = BatchWriter::new(&store, &UnitClient, None, None);
+        writer.batch_size = 2;
+        let summary = writer.write(&mut tree, &leaf_ids).await?;
+        assert_eq!((summary.embedded, summary.stored, summary.failed), (2, 2, 3));
+        assert!(!summary.is_complete());
+        assert_eq!(summary.error.as_deref(), Some("connection reset"));
+        assert_eq!(store.upserts.lock().unwrap()[0].len(), 2);
+        let config = IngestConfig::from_metadata(&store.upserts.lock().unwrap()[0][0].metadata).unwrap();
+        assert_eq!((config.dimension, config.normalized), (2, true));
+
+        // 重新写入时已生成的 embedding 不再请求
+        let store = MemStore::default();
+        let mut writer = BatchWriter::new(&store, &UnitClient, None, None);
+        writer.batch_size = 2;
+        let summary = writer.write(&mut tree, &leaf_ids).await?;
+        assert_eq!((summary.embedded, summary.stored, summary.failed), (1, 5, 0));
+        assert!(summary.is_complete());
+
+        // 去重时，后面批次的重复叶子追加到之前批次已写入记录的 duplicates 中
+        let mut tree = parser.parse("# 标题\n\n第一段。\n\n第二段。\n\n第三段。")?;
+        let leaf_ids: Vec<_> = tree.leaf_nodes_in_order().iter().map(|leaf| leaf.id).collect();
+        let store = MemStore::default();
+        let dedup = DedupConfig::default();
+        let mut writer = BatchWriter::new(&store, &UnitClient, Some(&dedup), None);
+        writer.batch_size = 2;
+        let summary = writer.write(&mut tree, &leaf_ids).await?;
+        assert_eq!((summary.embedded, summary.stored, summary.duplicates.len()), (3, 1, 2));
+        assert_eq!(summary.duplicates, find_duplicates(&tree, &dedup));
+        let kept = store.get_by_id(&leaf_ids[0].to_string()).await?.unwrap();
+        assert_eq!(kept.metadata["duplicates"].as_array().unwrap().len(), 2);
+        let upserts = store.upserts.lock().unwrap();
+        assert_eq!(upserts[0][0].metadata["duplicates"].as_array().unwrap().len(), 1);
+        assert!(upserts[1].is_empty());
+        let patches = store.patches.lock().unwrap();
+        assert_eq!(patches.len(), 1);
+        assert_eq!(patches[0].0, leaf_ids[0].to_string());
+        assert_eq!(patches[0].1["duplicates"].as_array().unwrap().len(), 2);
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_embed_node_tree() -> Result<()> {
+        let parser = MarkdownParser::new("doc-001".to_string(), None);
+        let mut tree = parser.parse("# 标题\n\n第一段。\n\n第二段。")?;
+        let records = embed_node_tree(&mut tree, &UnitClient, 2).await?;
+        assert_eq!(records.len(), 2);
+        assert!(records.iter().all(|r| r.metadata["document_id"] == "doc-001" && r.embedding == vec![1.0, 0.0]));
+        assert!(IngestConfig::from_metadata(&records[0].metadata).is_some());
+
+        let mut tree = parser.parse("# 标题\n\n第一段。")?;
+        assert!(embed_node_tree(&mut tree, &UnitClient, 3).await.is_err());
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_build_document_embeddings() -> Result<()> {
+        let parser = MarkdownParser::new("doc-001".to_string(), Some("a.md".to_string()));
+        let mut tree = parser.parse("# 标题\n\n第一段。\n\n第二段。")?;
+        let empty = parser.parse("# 空文档\n\n尚未嵌入。")?;
+        assert_eq!(document_embedding(&empty), None);
+
+        let ids: Vec<_> = tree.leaf_nodes_in_order().iter().map(|leaf| leaf.id).collect();
+        tree.set_leaf_embedding(ids[0], vec![1.0, 0.0])?;
+        tree.set_leaf_embedding(ids[1], vec![0.0, 1.0])?;
+        let embedding = document_embedding(&tree).unwrap();
+        assert!((embedding[0] - std::f32::consts::FRAC_1_SQRT_2).abs() < 1e-6);
+        assert!((embedding[1] - std::f32::consts::FRAC_1_SQRT_2).abs() < 1e-6);
+
+        let store = MemStore::default();
+        assert_eq!(build_document_embeddings(&[tree.clone(), empty], &store).await?, 1);
+        let upserts = store.upserts.lock().unwrap();
+        let record = &upserts[0][0];
+        assert_eq!(record.id, document_record(&tree, Vec::new()).id);
+        assert_eq!(record.text.as_deref(), Some("标题"));
+        assert_eq!(record.metadata["type"], "document");
+        assert_eq!(record.metadata["document_id"], "doc-001");
+        assert_eq!(record.metadata["leaf_count"], 2);
         Ok(())
     }
-    
-}
\ No newline at end of file
+}
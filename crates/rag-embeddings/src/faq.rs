@@ -0,0 +1,121 @@
+use anyhow::Result;
+use rag_indexing::faq::FAQChunk;
+use uuid::Uuid;
+
+use crate::{buffered::BufferedIngestor, client::EmbeddingClient, database::{VectorRecord, VectorStore}};
+
+/// 由 FAQ 分块 id 派生记录 id 时使用的 UUID v5 命名空间
+const FAQ_NAMESPACE: Uuid = Uuid::from_u128(0xd89bfeb4_e26e_5669_9e9a_497e4a5e60e9);
+
+/// 由 `chunk_id` 派生的稳定 UUID（v5）：pgvector 的 id 须为 UUID，同一分块重复入库时覆盖原记录
+pub fn faq_record_id(chunk_id: &str) -> Uuid {
+    Uuid::new_v5(&FAQ_NAMESPACE, chunk_id.as_bytes())
+}
+
+/// FAQ 分块转为向量数据库中的记录
+///
+/// `faq_id` 作为 `document_id`，`[category, title]` 作为 `hierarchy`，与文档叶子走同一检索与展示路径。
+/// 返回记录的 embedding 为空，由 [`save_faq_chunks`] 生成后写入。
+pub fn faqchunk_to_vector_record(chunk: &FAQChunk) -> VectorRecord {
+    VectorRecord {
+        id: faq_record_id(&chunk.chunk_id).to_string(),
+        embedding: Vec::new(),
+        text: Some(chunk.content.clone()),
+        metadata: serde_json::json!({
+            "document_id": chunk.faq_id,
+            "chunk_id": chunk.chunk_id,
+            "faq_id": chunk.faq_id,
+            "category": chunk.category,
+            "title": chunk.title,
+            "tags": chunk.tags,
+            "chunk_size": chunk.token_count,
+            "hierarchy": [chunk.category, chunk.title],
+            "type": "faq",
+        }),
+        createat: None,
+        updateat: None,
+    }
+}
+
+/// 为 FAQ 分块生成 embedding 并按批 upsert 到向量库，返回写入的记录数
+pub async fn save_faq_chunks<S: VectorStore, C: EmbeddingClient>(
+    chunks: &[FAQChunk],
+    store: &S,
+    embedding_client: &C,
+) -> Result<usize> {
+    let mut ingestor = BufferedIngestor::new(store, embedding_client);
+    for chunk in chunks {
+        ingestor.push(faqchunk_to_vector_record(chunk)).await?;
+    }
+    ingestor.close().await
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use async_trait::async_trait;
+    use crate::client::EmbeddingResult;
+    use crate::database::SearchResult;
+    use std::sync::Mutex;
+
+    struct LengthClient;
+
+    #[async_trait]
+    impl EmbeddingClient for LengthClient {
+        async fn embed(&self, texts: Vec<String>) -> EmbeddingResult<Vec<Vec<f32>>> {
+            Ok(texts.iter().map(|t| vec![t.chars().count() as f32]).collect())
+        }
+
+        fn dimension(&self) -> usize {
+            1
+        }
+    }
+
+    #[derive(Default)]
+    struct MemStore(Mutex<Vec<VectorRecord>>);
+
+    #[async_trait]
+    impl VectorStore for MemStore {
+        async fn add_vectors(&self, vectors: Vec<VectorRecord>) -> Result<()> { self.upsert_vectors(vectors).await }
+        async fn upsert_vectors(&self, vectors: Vec<VectorRecord>) -> Result<()> {
+            self.0.lock().unwrap().extend(vectors);
+            Ok(())
+        }
+        async fn delete_vector(&self, _ids: Vec<String>) -> Result<()> { Ok(()) }
+        async fn search(&self, _query: &[f32], _top_k: usize) -> Result<Vec<SearchResult>> { Ok(vec![]) }
+    }
+
+    fn chunk(chunk_id: &str, content: &str) -> FAQChunk {
+        FAQChunk {
+            chunk_id: chunk_id.to_string(),
+            faq_id: "faq-退货-001".to_string(),
+            category: "退货".to_string(),
+            title: "如何申请退货？".to_string(),
+            content: content.to_string(),
+            tags: vec!["售后".to_string()],
+            token_count: 12,
+        }
+    }
+
+    #[tokio::test]
+    async fn test_save_faq_chunks() -> Result<()> {
+        let chunks = vec![
+            chunk("faq-退货-001-chunk-1", "Q: 如何申请退货？\nA: 在订单页提交申请。"),
+            chunk("faq-退货-001-chunk-2", "A: 审核通过后寄回商品。"),
+        ];
+
+        let record = faqchunk_to_vector_record(&chunks[0]);
+        assert_eq!(record.id, faqchunk_to_vector_record(&chunks[0]).id);
+        assert_ne!(record.id, faqchunk_to_vector_record(&chunks[1]).id);
+        assert!(Uuid::parse_str(&record.id).is_ok());
+        assert_eq!(record.metadata["hierarchy"], serde_json::json!(["退货", "如何申请退货？"]));
+        assert_eq!(record.metadata["tags"], serde_json::json!(["售后"]));
+
+        let store = MemStore::default();
+        assert_eq!(save_faq_chunks(&chunks, &store, &LengthClient).await?, 2);
+        let stored = store.0.lock().unwrap();
+        assert_eq!(stored[0].id, record.id);
+        assert_eq!(stored[1].embedding, vec![chunks[1].content.chars().count() as f32]);
+        Ok(())
+    }
+}
@@ -0,0 +1,323 @@
+use std::collections::HashMap;
+use std::fmt;
+use std::path::{Path, PathBuf};
+
+use anyhow::{Context, Result};
+use rag_indexing::loader::{load_parallel, loader_for};
+use rag_indexing::tree_structrue::NodeTree;
+use sha2::{Digest, Sha256};
+
+use crate::{
+    client::qwen::QwenEmbeddingClient,
+    database::pgvector::PgVectorStore,
+    embedding::embed_node_tree,
+    manifest::{IngestManifest, IngestStatus},
+};
+
+/// 记录源文件 sha256 的 metadata 键，用于判断文件是否变化（与分块内容哈希 `content_hash` 列无关）
+pub const FILE_HASH_KEY: &str = "file_hash";
+
+/// 默认匹配目录下所有层级的 markdown 文件
+pub const DEFAULT_MARKDOWN_GLOB: &str = "**/*.md";
+
+/// 默认匹配目录下所有层级的文件，由 [`collect_documents`] 过滤出有加载器的类型
+pub const DEFAULT_DOCUMENT_GLOB: &str = "**/*";
+
+/// 单个文件的入库状态
+#[derive(Debug, Clone, PartialEq)]
+pub enum FileStatus {
+    /// 已解析并写入 `leaves` 个叶子节点
+    Ingested { leaves: usize },
+    /// 内容哈希与库中一致，跳过
+    Unchanged,
+    /// 与本批次中另一个文件内容完全相同，跳过；该文档原有的向量被删除，内容只由 `of` 提供
+    Duplicate { of: String },
+    /// 入库失败（不影响其他文件）
+    Failed(String),
+}
+
+#[derive(Debug, Clone)]
+pub struct FileReport {
+    pub path: PathBuf,
+    pub document_id: String,
+    pub status: FileStatus,
+}
+
+/// 目录入库结果，逐文件记录成功/失败
+#[derive(Debug, Clone, Default)]
+pub struct IngestReport {
+    pub files: Vec<FileReport>,
+}
+
+impl IngestReport {
+    pub fn ingested(&self) -> usize {
+        self.count(|s| matches!(s, FileStatus::Ingested { .. }))
+    }
+
+    pub fn skipped(&self) -> usize {
+        self.count(|s| matches!(s, FileStatus::Unchanged | FileStatus::Duplicate { .. }))
+    }
+
+    pub fn failed(&self) -> usize {
+        self.count(|s| matches!(s, FileStatus::Failed(_)))
+    }
+
+    fn count(&self, pred: impl Fn(&FileStatus) -> bool) -> usize {
+        self.files.iter().filter(|f| pred(&f.status)).count()
+    }
+}
+
+impl fmt::Display for IngestReport {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for file in &self.files {
+            match &file.status {
+                FileStatus::Ingested { leaves } => writeln!(f, "✅ {} ({} 个叶子节点)", file.document_id, leaves)?,
+                FileStatus::Unchanged => writeln!(f, "⏭️  {} (内容未变化)", file.document_id)?,
+                FileStatus::Duplicate { of } => writeln!(f, "⏭️  {} (与 {} 内容相同)", file.document_id, of)?,
+                FileStatus::Failed(err) => writeln!(f, "❌ {}: {}", file.document_id, err)?,
+            }
+        }
+        write!(
+            f,
+            "共 {} 个文件: 入库 {}, 跳过 {}, 失败 {}",
+            self.files.len(),
+            self.ingested(),
+            self.skipped(),
+            self.failed()
+        )
+    }
+}
+
+/// 计算文本内容的 sha256 哈希（十六进制）
+pub fn content_hash(content: &str) -> String {
+    format!("{:x}", Sha256::digest(content.as_bytes()))
+}
+
+/// 递归收集目录下匹配 `pattern` 的 markdown 文件，按路径排序并去重
+pub fn collect_markdown_files(dir: &Path, pattern: &str) -> Result<Vec<PathBuf>> {
+    let dir_str = dir.to_str()
+        .with_context(|| format!("目录路径不是合法的 UTF-8: {:?}", dir))?;
+    let full_pattern = format!("{}/{}", glob::Pattern::escape(dir_str.trim_end_matches('/')), pattern);
+
+    let mut files = Vec::new();
+    for entry in glob::glob(&full_pattern).with_context(|| format!("无效的 glob 模式: {}", pattern))? {
+        let path = entry?;
+        if path.is_file() && path.extension().is_some_and(|ext| ext == "md") {
+            files.push(path);
+        }
+    }
+    files.sort();
+    files.dedup();
+    Ok(files)
+}
+
+/// 递归收集目录下匹配 `pattern` 且有对应加载器（见 [`loader_for`]）的文件，按路径排序并去重
+pub fn collect_documents(dir: &Path, pattern: &str) -> Result<Vec<PathBuf>> {
+    let dir_str = dir.to_str()
+        .with_context(|| format!("目录路径不是合法的 UTF-8: {:?}", dir))?;
+    let full_pattern = format!("{}/{}", glob::Pattern::escape(dir_str.trim_end_matches('/')), pattern);
+
+    let mut files = Vec::new();
+    for entry in glob::glob(&full_pattern).with_context(|| format!("无效的 glob 模式: {}", pattern))? {
+        let path = entry?;
+        if path.is_file() && loader_for(&path).is_some() {
+            files.push(path);
+        }
+    }
+    files.sort();
+    files.dedup();
+    Ok(files)
+}
+
+/// 以相对 `dir` 的路径作为 document_id（统一使用 `/` 分隔）
+pub fn document_id_for(dir: &Path, path: &Path) -> String {
+    let relative = path.strip_prefix(dir).unwrap_or(path);
+    relative
+        .components()
+        .map(|c| c.as_os_str().to_string_lossy())
+        .collect::<Vec<_>>()
+        .join("/")
+}
+
+/// 递归入库目录下的所有文档（markdown、纯文本等，按扩展名选择加载器）
+///
+/// # 流程
+/// 1. 按 glob 模式收集有加载器的文件，以相对路径作为 `document_id`、文件名作为 `file_name`
+/// 2. 计算文件哈希：与库中记录一致则跳过；本批次内内容相同的文件只入库第一个，其余文件的旧向量被删除
+/// 3. 需要入库的文件在 rayon 线程池中并行解析（见 [`load_parallel`]）
+/// 4. 逐个文件生成全部 embedding 后，在一个事务中替换旧向量（见 [`PgVectorStore::reindex_document`]）
+///
+/// 单个文件失败只会记录在报告中，不会中断整个批次；解析或嵌入失败的文件保留库中原有记录
+pub async fn ingest_directory(
+    dir: &Path,
+    pattern: &str,
+    store: &PgVectorStore,
+    embedding_client: &QwenEmbeddingClient,
+) -> Result<IngestReport> {
+    ingest_files(dir, pattern, store, embedding_client, None).await
+}
+
+/// 可中断、可恢复的 [`ingest_directory`]：逐文档在 `manifest_path`（JSON）中记录内容哈希与入库状态
+///
+/// 每个文档状态变化后立即写回清单。重新运行时，清单中已完成且内容未变化的文档直接跳过（不查询数据库），
+/// 失败或中断时未完成的文档重新入库。清单文件不存在时从头开始并创建。
+pub async fn resume_ingestion(
+    manifest_path: &Path,
+    dir: &Path,
+    pattern: &str,
+    store: &PgVectorStore,
+    embedding_client: &QwenEmbeddingClient,
+) -> Result<IngestReport> {
+    let mut manifest = IngestManifest::load(manifest_path)?;
+    if !manifest.documents.is_empty() {
+        println!(
+            "读取入库清单 {}: 已完成 {}, 失败 {}, 未完成 {}",
+            manifest_path.display(),
+            manifest.count(IngestStatus::Stored),
+            manifest.count(IngestStatus::Failed),
+            manifest.count(IngestStatus::Pending) + manifest.count(IngestStatus::Embedded)
+        );
+    }
+    ingest_files(dir, pattern, store, embedding_client, Some(&mut manifest)).await
+}
+
+async fn ingest_files(
+    dir: &Path,
+    pattern: &str,
+    store: &PgVectorStore,
+    embedding_client: &QwenEmbeddingClient,
+    mut manifest: Option<&mut IngestManifest>,
+) -> Result<IngestReport> {
+    let files = collect_documents(dir, pattern)?;
+    let mut report = IngestReport::default();
+    let mut seen_hashes: HashMap<String, String> = HashMap::new();
+    // 需要解析入库的文件：(report 下标, 内容哈希)
+    let mut pending: Vec<(usize, String)> = Vec::new();
+
+    for path in files {
+        let document_id = document_id_for(dir, &path);
+        let status = match std::fs::read_to_string(&path) {
+            Ok(content) => {
+                let hash = content_hash(&content);
+                if let Some(original) = seen_hashes.get(&hash) {
+                    // 文件改为与另一文件相同的内容时，旧内容的向量不能继续被检索到
+                    match store.delete_document(&document_id).await {
+                        Ok(_) => FileStatus::Duplicate { of: original.clone() },
+                        Err(e) => FileStatus::Failed(format!("{:#}", e)),
+                    }
+                } else if manifest.as_deref().is_some_and(|m| m.is_completed(&document_id, &hash)) {
+                    seen_hashes.insert(hash, document_id.clone());
+                    FileStatus::Unchanged
+                } else {
+                    seen_hashes.insert(hash.clone(), document_id.clone());
+                    match store.document_file_hash(&document_id).await {
+                        Ok(existing) if existing.as_deref() == Some(hash.as_str()) => FileStatus::Unchanged,
+                        Ok(_) => {
+                            if let Some(manifest) = manifest.as_deref_mut() {
+                                manifest.mark(&document_id, &hash, IngestStatus::Pending, None);
+                            }
+                            pending.push((report.files.len(), hash));
+                            // 占位，入库后更新
+                            FileStatus::Ingested { leaves: 0 }
+                        }
+                        Err(e) => FileStatus::Failed(format!("{:#}", e)),
+                    }
+                }
+            }
+            Err(e) => FileStatus::Failed(format!("读取文件失败: {}", e)),
+        };
+
+        report.files.push(FileReport { path, document_id, status });
+    }
+    if let Some(manifest) = manifest.as_deref() {
+        manifest.save()?;
+    }
+
+    // 解析是 CPU 密集型，放到阻塞线程中并行执行
+    let to_parse: Vec<(PathBuf, String)> = pending.iter()
+        .map(|(i, _)| (report.files[*i].path.clone(), report.files[*i].document_id.clone()))
+        .collect();
+    let trees = tokio::task::spawn_blocking(move || load_parallel(&to_parse))
+        .await
+        .context("并行解析任务异常退出")?;
+
+    for ((i, hash), tree) in pending.into_iter().zip(trees) {
+        let file = &mut report.files[i];
+        file.status = match tree {
+            Ok(tree) => store_document(tree, &file.document_id, &hash, store, embedding_client, manifest.as_deref_mut())
+                .await
+                .unwrap_or_else(|e| FileStatus::Failed(format!("{:#}", e))),
+            Err(e) => FileStatus::Failed(format!("{:#}", e)),
+        };
+        if let Some(manifest) = manifest.as_deref_mut() {
+            match &file.status {
+                FileStatus::Failed(error) => manifest.mark(&file.document_id, &hash, IngestStatus::Failed, Some(error.clone())),
+                _ => manifest.mark(&file.document_id, &hash, IngestStatus::Stored, None),
+            }
+            manifest.save()?;
+        }
+    }
+
+    Ok(report)
+}
+
+/// 替换文档的向量：先为新解析的树生成全部 embedding，再在一个事务中删除旧记录并写入带文件哈希的新记录
+///
+/// 嵌入失败时库中保留旧记录，不会出现文档被清空的中间状态。
+async fn store_document(
+    mut tree: NodeTree,
+    document_id: &str,
+    hash: &str,
+    store: &PgVectorStore,
+    embedding_client: &QwenEmbeddingClient,
+    manifest: Option<&mut IngestManifest>,
+) -> Result<FileStatus> {
+    let mut records = embed_node_tree(&mut tree, embedding_client, store.dimensions()).await?;
+    for record in &mut records {
+        record.metadata[FILE_HASH_KEY] = serde_json::json!(hash);
+    }
+    if let Some(manifest) = manifest {
+        manifest.mark(document_id, hash, IngestStatus::Embedded, None);
+        manifest.save()?;
+    }
+    store.reindex_document(document_id, records).await?;
+
+    Ok(FileStatus::Ingested { leaves: tree.leaf_nodes().count() })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs;
+
+    #[test]
+    fn test_collect_markdown_files() -> Result<()> {
+        let dir = std::env::temp_dir().join(format!("rag-ingest-{}", std::process::id()));
+        fs::create_dir_all(dir.join("sub/deeper"))?;
+        fs::write(dir.join("a.md"), "# A")?;
+        fs::write(dir.join("sub/b.md"), "# B")?;
+        fs::write(dir.join("sub/deeper/c.md"), "# C")?;
+        fs::write(dir.join("sub/notes.txt"), "not markdown")?;
+
+        let files = collect_markdown_files(&dir, DEFAULT_MARKDOWN_GLOB)?;
+        let ids: Vec<String> = files.iter().map(|p| document_id_for(&dir, p)).collect();
+        assert_eq!(ids, vec!["a.md", "sub/b.md", "sub/deeper/c.md"]);
+
+        let only_sub = collect_markdown_files(&dir, "sub/*.md")?;
+        assert_eq!(only_sub.len(), 1);
+
+        fs::write(dir.join("sub/image.png"), "")?;
+        let documents = collect_documents(&dir, DEFAULT_DOCUMENT_GLOB)?;
+        let ids: Vec<String> = documents.iter().map(|p| document_id_for(&dir, p)).collect();
+        assert_eq!(ids, vec!["a.md", "sub/b.md", "sub/deeper/c.md", "sub/notes.txt"]);
+
+        fs::remove_dir_all(&dir)?;
+        Ok(())
+    }
+
+    #[test]
+    fn test_content_hash_stable() {
+        assert_eq!(content_hash("# 标题"), content_hash("# 标题"));
+        assert_ne!(content_hash("# 标题"), content_hash("# 标题 "));
+    }
+}
@@ -0,0 +1,126 @@
+use std::collections::BTreeSet;
+
+use rag_indexing::tree_structrue::NodeTree;
+use serde::{Deserialize, Serialize};
+
+use crate::{client::EmbeddingClient, database::SearchResult};
+
+/// 入库配置在记录 metadata 中的键
+pub const INGEST_CONFIG_KEY: &str = "ingest_config";
+
+/// 文档入库时的分块与嵌入配置，随每条叶子记录写入 `metadata.ingest_config`
+///
+/// 语料在不同时期用不同的分块器或模型入库后，检索结果会变得不可预期；
+/// 记录下每个文档的处理方式，查询时可据此发现模型不一致，见 [`check_query_model`]。
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct IngestConfig {
+    /// 分块方式，见 [`NodeTree::chunker`]
+    pub chunker: Option<String>,
+    /// 分块的 token 上限
+    pub max_tokens: Option<usize>,
+    /// 计算 token 数所用的模型
+    pub token_model: Option<String>,
+    /// embedding 模型，见 [`EmbeddingClient::model`]
+    pub embedding_model: Option<String>,
+    pub dimension: usize,
+    /// 写入的向量是否已 L2 归一化
+    pub normalized: bool,
+}
+
+impl IngestConfig {
+    /// 由树记录的分块方式与嵌入客户端构造；入库前会校验向量均已归一化，`normalized` 为 `true`
+    pub fn new<C: EmbeddingClient + ?Sized>(node_tree: &NodeTree, embedding_client: &C) -> Self {
+        Self {
+            chunker: node_tree.chunker().map(str::to_string),
+            max_tokens: node_tree.max_tokens(),
+            token_model: node_tree.token_model().map(str::to_string),
+            embedding_model: embedding_client.model().map(str::to_string),
+            dimension: embedding_client.dimension(),
+            normalized: true,
+        }
+    }
+
+    /// 从记录的 metadata 中读取，没有记录（如旧数据）或格式不符时为 `None`
+    pub fn from_metadata(metadata: &serde_json::Value) -> Option<Self> {
+        serde_json::from_value(metadata.get(INGEST_CONFIG_KEY)?.clone()).ok()
+    }
+
+    pub fn to_value(&self) -> serde_json::Value {
+        serde_json::to_value(self).unwrap_or_default()
+    }
+}
+
+/// 检索结果中入库模型与查询模型 `query_model` 不同的记录所用的模型（去重、排序）
+///
+/// 没有入库配置或未记录模型的记录不参与比较。
+pub fn mismatched_models(hits: &[SearchResult], query_model: &str) -> Vec<String> {
+    hits.iter()
+        .filter_map(|hit| IngestConfig::from_metadata(&hit.record.metadata)?.embedding_model)
+        .filter(|model| model != query_model)
+        .collect::<BTreeSet<_>>()
+        .into_iter()
+        .collect()
+}
+
+/// 查询所用模型与命中记录的入库模型不一致时打印警告，返回不一致的入库模型
+///
+/// 不同模型的向量之间的相似度没有意义，这种不一致通常不会报错，只会让检索结果变差。
+pub fn check_query_model<C: EmbeddingClient + ?Sized>(hits: &[SearchResult], embedding_client: &C) -> Vec<String> {
+    let Some(query_model) = embedding_client.model() else {
+        return Vec::new();
+    };
+    let mismatched = mismatched_models(hits, query_model);
+    if !mismatched.is_empty() {
+        println!(
+            "警告: 查询使用模型 {} 嵌入，但命中的记录由 {} 入库，相似度可能不可信",
+            query_model,
+            mismatched.join(", ")
+        );
+    }
+    mismatched
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::client::qwen::QwenEmbeddingClient;
+    use crate::database::VectorRecord;
+    use rag_indexing::tree_structrue::markdown_bulid::{DEFAULT_TOKEN_MODEL, MARKDOWN_CHUNKER, MarkdownParser};
+
+    fn hit(id: &str, config: Option<&IngestConfig>) -> (VectorRecord, f32) {
+        let mut metadata = serde_json::json!({ "document_id": id });
+        if let Some(config) = config {
+            metadata[INGEST_CONFIG_KEY] = config.to_value();
+        }
+        let record = VectorRecord {
+            id: id.to_string(),
+            embedding: vec![1.0],
+            metadata,
+            text: None,
+            createat: None,
+            updateat: None,
+        };
+        (record, 1.0)
+    }
+
+    #[test]
+    fn test_ingest_config() -> anyhow::Result<()> {
+        let tree = MarkdownParser::new("doc-001".to_string(), None).parse("# 标题\n\n正文。")?;
+        let client = QwenEmbeddingClient::for_text("key".to_string(), "text-embedding-v1".to_string());
+        let config = IngestConfig::new(&tree, &client);
+        assert_eq!(config.chunker.as_deref(), Some(MARKDOWN_CHUNKER));
+        assert_eq!(config.max_tokens, None);
+        assert_eq!(config.token_model.as_deref(), Some(DEFAULT_TOKEN_MODEL));
+        assert_eq!(config.embedding_model.as_deref(), Some("text-embedding-v1"));
+        assert_eq!(config.dimension, 1536);
+
+        let v3 = IngestConfig { embedding_model: Some("text-embedding-v3".to_string()), ..config.clone() };
+        let hits = SearchResult::ranked(vec![hit("a", Some(&config)), hit("b", Some(&v3)), hit("c", None)]);
+        assert_eq!(IngestConfig::from_metadata(&hits[0].record.metadata), Some(config));
+        assert_eq!(IngestConfig::from_metadata(&hits[2].record.metadata), None);
+
+        assert_eq!(check_query_model(&hits, &client), vec!["text-embedding-v3"]);
+        assert!(mismatched_models(&hits[..1], "text-embedding-v1").is_empty());
+        Ok(())
+    }
+}
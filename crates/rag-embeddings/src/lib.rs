@@ -1,3 +1,10 @@
+pub mod buffered;
 pub mod client;
 pub mod database;
-pub mod embedding;
\ No newline at end of file
+pub mod dedup;
+pub mod embedding;
+pub mod faq;
+pub mod ingest;
+pub mod ingest_config;
+pub mod manifest;
+pub mod util;
\ No newline at end of file
@@ -1,11 +1,59 @@
-use sqlx::postgres::PgPoolOptions;
-use anyhow::Result;
+use anyhow::{Context, Result};
+use dotenv::dotenv;
+use std::path::PathBuf;
 
+use rag_embeddings::{
+    client::{EmbeddingClient, qwen::QwenEmbeddingClient},
+    database::pgvector::{DEFAULT_MAX_CONNECTIONS, PgVectorStore},
+    ingest::{DEFAULT_DOCUMENT_GLOB, ingest_directory, resume_ingestion},
+};
+
+/// 用法: rag-embeddings [--dir <目录> [--glob <模式>] [--table <表名>] [--model <模型>] [--manifest <清单文件>]]
+///
+/// 目录下的 markdown 与纯文本文件按扩展名选择加载器统一入库，其余文件忽略。
+/// 指定 `--manifest` 时记录逐文档的入库状态，中断后以相同参数重新运行即可从断点继续
+#[tokio::main]
+async fn main() -> Result<()> {
+    let mut dir: Option<PathBuf> = None;
+    let mut pattern = DEFAULT_DOCUMENT_GLOB.to_string();
+    let mut table = "vectors".to_string();
+    let mut model = "text-embedding-v1".to_string();
+    let mut manifest: Option<PathBuf> = None;
+
+    let mut args = std::env::args().skip(1);
+    while let Some(arg) = args.next() {
+        let mut value = || args.next().with_context(|| format!("参数 {} 缺少取值", arg));
+        match arg.as_str() {
+            "--dir" => dir = Some(PathBuf::from(value()?)),
+            "--glob" => pattern = value()?,
+            "--table" => table = value()?,
+            "--model" => model = value()?,
+            "--manifest" => manifest = Some(PathBuf::from(value()?)),
+            other => anyhow::bail!("未知参数: {}", other),
+        }
+    }
+
+    dotenv().ok();
+    let database_url = std::env::var("DATABASE_URL").unwrap_or("postgres:///rag_db".to_string());
+
+    let Some(dir) = dir else {
+        // 未指定目录时仅连接数据库并确保表存在（按 text-embedding-v1 的 1536 维建表）
+        let store = PgVectorStore::open(&database_url, &table, 1536, DEFAULT_MAX_CONNECTIONS).await?;
+        println!("connected to database, table {} ready ({} dims)", store.table_name(), store.dimensions());
+        return Ok(());
+    };
+
+    let api_key = std::env::var("DASHSCOPE_API_KEY")
+        .context("请设置环境变量 DASHSCOPE_API_KEY 或在 .env 文件中配置")?;
+
+    let embedding_client = QwenEmbeddingClient::for_text(api_key, model);
+    let store = PgVectorStore::open(&database_url, &table, embedding_client.dimension(), DEFAULT_MAX_CONNECTIONS).await?;
+
+    let report = match manifest {
+        Some(manifest) => resume_ingestion(&manifest, &dir, &pattern, &store, &embedding_client).await?,
+        None => ingest_directory(&dir, &pattern, &store, &embedding_client).await?,
+    };
+    println!("{}", report);
 
-fn main() -> Result<()> {
-    let _ = PgPoolOptions::new()
-        .max_connections(5)
-        .connect("postgres:///rag_db");
-    println!("connected to database");
     Ok(())
-}
\ No newline at end of file
+}
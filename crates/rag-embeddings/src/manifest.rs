@@ -0,0 +1,129 @@
+use std::collections::BTreeMap;
+use std::path::{Path, PathBuf};
+
+use anyhow::{Context, Result};
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+
+/// 文档在一次（可中断的）入库过程中的状态
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum IngestStatus {
+    /// 已登记，尚未写入向量
+    Pending,
+    /// 向量已写入，内容哈希尚未记录
+    Embedded,
+    /// 入库完成
+    Stored,
+    /// 入库失败，下次运行时重试
+    Failed,
+}
+
+/// 清单中单个文档的记录
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ManifestEntry {
+    pub content_hash: String,
+    pub status: IngestStatus,
+    /// 最近一次失败的原因
+    #[serde(default)]
+    pub error: Option<String>,
+    pub updated_at: DateTime<Utc>,
+}
+
+/// 入库清单：逐文档记录内容哈希与入库状态，保存为 JSON 文件
+///
+/// 大批量入库中途中断后，依据清单跳过已完成（[`IngestStatus::Stored`] 且内容未变化）的文档，
+/// 其余状态的文档重新入库，见 [`resume_ingestion`](crate::ingest::resume_ingestion)。
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct IngestManifest {
+    #[serde(skip)]
+    path: PathBuf,
+    /// document_id -> 记录，按 document_id 排序便于查看与 diff
+    pub documents: BTreeMap<String, ManifestEntry>,
+}
+
+impl IngestManifest {
+    /// 读取清单文件，文件不存在时返回空清单（之后保存到该路径）
+    pub fn load(path: impl AsRef<Path>) -> Result<Self> {
+        let path = path.as_ref();
+        let mut manifest = if path.exists() {
+            let content = std::fs::read_to_string(path)
+                .with_context(|| format!("无法读取入库清单 {}", path.display()))?;
+            serde_json::from_str::<Self>(&content)
+                .with_context(|| format!("入库清单格式错误 {}", path.display()))?
+        } else {
+            Self::default()
+        };
+        manifest.path = path.to_path_buf();
+        Ok(manifest)
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    /// 写回清单文件：先写临时文件再重命名，中断时不会留下半个文件
+    pub fn save(&self) -> Result<()> {
+        let tmp = self.path.with_extension("json.tmp");
+        std::fs::write(&tmp, serde_json::to_string_pretty(self)?)
+            .with_context(|| format!("无法写入入库清单 {}", tmp.display()))?;
+        std::fs::rename(&tmp, &self.path)
+            .with_context(|| format!("无法写入入库清单 {}", self.path.display()))?;
+        Ok(())
+    }
+
+    pub fn get(&self, document_id: &str) -> Option<&ManifestEntry> {
+        self.documents.get(document_id)
+    }
+
+    /// 文档已按相同内容入库完成
+    pub fn is_completed(&self, document_id: &str, content_hash: &str) -> bool {
+        self.get(document_id)
+            .is_some_and(|entry| entry.status == IngestStatus::Stored && entry.content_hash == content_hash)
+    }
+
+    /// 更新文档状态，`Failed` 之外的状态会清除之前的错误信息
+    pub fn mark(&mut self, document_id: &str, content_hash: &str, status: IngestStatus, error: Option<String>) {
+        self.documents.insert(document_id.to_string(), ManifestEntry {
+            content_hash: content_hash.to_string(),
+            status,
+            error: error.filter(|_| status == IngestStatus::Failed),
+            updated_at: Utc::now(),
+        });
+    }
+
+    /// 处于 `status` 的文档数
+    pub fn count(&self, status: IngestStatus) -> usize {
+        self.documents.values().filter(|entry| entry.status == status).count()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_manifest_roundtrip() -> Result<()> {
+        let path = std::env::temp_dir().join(format!("rag-manifest-{}.json", std::process::id()));
+        let _ = std::fs::remove_file(&path);
+
+        let mut manifest = IngestManifest::load(&path)?;
+        assert!(manifest.documents.is_empty());
+        manifest.mark("a.md", "h1", IngestStatus::Stored, None);
+        manifest.mark("b.md", "h2", IngestStatus::Failed, Some("timeout".to_string()));
+        manifest.mark("c.md", "h3", IngestStatus::Pending, Some("ignored".to_string()));
+        manifest.save()?;
+
+        let manifest = IngestManifest::load(&path)?;
+        assert!(manifest.is_completed("a.md", "h1"));
+        assert!(!manifest.is_completed("a.md", "changed"));
+        assert!(!manifest.is_completed("b.md", "h2"));
+        assert_eq!(manifest.get("b.md").unwrap().error.as_deref(), Some("timeout"));
+        assert_eq!(manifest.get("c.md").unwrap().error, None);
+        assert_eq!(manifest.count(IngestStatus::Failed), 1);
+        assert!(std::fs::read_to_string(&path)?.contains("\"status\": \"failed\""));
+
+        std::fs::remove_file(&path)?;
+        Ok(())
+    }
+}
@@ -0,0 +1,70 @@
+use anyhow::{Result, bail};
+
+use crate::client::{EmbeddingClient, cosine_similarity};
+
+/// 嵌入两段文本并返回二者的余弦相似度，取值 [-1, 1]
+///
+/// 两段文本在同一次请求中按文档方式嵌入（[`EmbeddingClient::embed`]），适合快速试验与测试；
+/// 批量比较时应自行批量嵌入后计算，避免逐对请求。服务返回的两个向量长度不一致时报错。
+///
+/// ```no_run
+/// use rag_embeddings::{client::qwen::QwenEmbeddingClient, util::text_similarity};
+///
+/// # async fn run() -> anyhow::Result<()> {
+/// let client = QwenEmbeddingClient::for_text(std::env::var("DASHSCOPE_API_KEY")?, "text-embedding-v3".to_string());
+/// let score = text_similarity(&client, "所有权规则", "Rust 的 ownership 规则").await?;
+/// println!("相似度: {:.3}", score);
+/// # Ok(())
+/// # }
+/// ```
+pub async fn text_similarity<C: EmbeddingClient + ?Sized>(client: &C, a: &str, b: &str) -> Result<f32> {
+    let vectors = client.embed(vec![a.to_string(), b.to_string()]).await?;
+    let [va, vb] = vectors.as_slice() else {
+        bail!("embedding 服务返回了 {} 个向量，应为 2 个", vectors.len());
+    };
+    Ok(cosine_similarity(va, vb)?)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use async_trait::async_trait;
+    use crate::client::EmbeddingResult;
+
+    /// 按文本中 'a' / 'b' 的出现次数生成二维向量
+    struct CountingClient;
+
+    /// 按文本长度生成向量，长度不同的文本得到维度不一致的向量
+    struct RaggedClient;
+
+    #[async_trait]
+    impl EmbeddingClient for RaggedClient {
+        async fn embed(&self, texts: Vec<String>) -> EmbeddingResult<Vec<Vec<f32>>> {
+            Ok(texts.iter().map(|t| vec![1.0; t.len()]).collect())
+        }
+
+        fn dimension(&self) -> usize {
+            2
+        }
+    }
+
+    #[async_trait]
+    impl EmbeddingClient for CountingClient {
+        async fn embed(&self, texts: Vec<String>) -> EmbeddingResult<Vec<Vec<f32>>> {
+            Ok(texts.iter().map(|t| vec![t.matches('a').count() as f32, t.matches('b').count() as f32]).collect())
+        }
+
+        fn dimension(&self) -> usize {
+            2
+        }
+    }
+
+    #[tokio::test]
+    async fn test_text_similarity() -> Result<()> {
+        assert!((text_similarity(&CountingClient, "aa", "a").await? - 1.0).abs() < 1e-6);
+        assert!(text_similarity(&CountingClient, "a", "b").await?.abs() < 1e-6);
+        assert!((text_similarity(&CountingClient, "ab", "a").await? - std::f32::consts::FRAC_1_SQRT_2).abs() < 1e-6);
+        assert!(text_similarity(&RaggedClient, "ab", "a").await.is_err());
+        Ok(())
+    }
+}
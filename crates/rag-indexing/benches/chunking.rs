@@ -0,0 +1,114 @@
+//! 分块器、tokenizer 与目录解析的基准测试
+//!
+//! 运行：`cargo bench -p rag-indexing`
+//!
+//! FAQ 分块器逐句计数 token，其输入规模保持较小，以便整组基准在数分钟内完成。
+
+use criterion::{BenchmarkId, Criterion, Throughput, black_box, criterion_group, criterion_main};
+
+use std::path::PathBuf;
+
+use rag_indexing::faq::{FAQChunker, FAQEntry};
+use rag_indexing::loader::{load_any, load_parallel};
+use rag_indexing::recursive_splitting::RecursiveChunker;
+use rag_indexing::tiktoken::count_tokens;
+
+const MODEL: &str = "gpt-4o";
+
+const PARAGRAPH: &str = "Rust 的所有权系统保证了内存安全，每个值都有唯一的所有者。\
+当所有者离开作用域时，值会被自动释放，无需垃圾回收。\
+The borrow checker enforces these rules at compile time, so most memory errors never reach production. \
+借用分为不可变借用与可变借用，同一时刻只能存在一个可变借用。";
+
+/// 由重复段落拼成的大文档，段落间以空行分隔
+fn large_document(paragraphs: usize) -> String {
+    (0..paragraphs)
+        .map(|i| format!("第 {} 段。{}", i, PARAGRAPH))
+        .collect::<Vec<_>>()
+        .join("\n\n")
+}
+
+/// 大规模 FAQ 集合，每隔若干条插入一个超长答案以覆盖拆分路径
+fn large_faq_set(entries: usize) -> Vec<FAQEntry> {
+    (0..entries)
+        .map(|i| FAQEntry {
+            category: format!("分类{}", i % 10),
+            q: format!("问题 {}：如何理解所有权与借用？", i),
+            a: if i % 7 == 0 { PARAGRAPH.repeat(8) } else { PARAGRAPH.to_string() },
+            tags: vec![],
+        })
+        .collect()
+}
+
+fn bench_count_tokens(c: &mut Criterion) {
+    let mut group = c.benchmark_group("count_tokens");
+    for paragraphs in [1, 100] {
+        let text = large_document(paragraphs);
+        // 预热 BPE 缓存，避免首次加载编码器计入结果
+        let tokens = count_tokens(&text, MODEL);
+        group.throughput(Throughput::Elements(tokens as u64));
+        group.bench_with_input(BenchmarkId::from_parameter(paragraphs), &text, |b, text| {
+            b.iter(|| count_tokens(black_box(text), MODEL))
+        });
+    }
+    group.finish();
+}
+
+fn bench_recursive_chunker(c: &mut Criterion) {
+    let mut group = c.benchmark_group("recursive_chunker");
+    group.sample_size(10);
+    let chunker = RecursiveChunker::new(256, MODEL);
+    let text = large_document(500);
+    let chunks = chunker.chunk(vec![(0, text.clone())]).len();
+    group.throughput(Throughput::Elements(chunks as u64));
+    group.bench_function("chunk_large_document", |b| {
+        b.iter(|| chunker.chunk(black_box(vec![(0, text.clone())])))
+    });
+    group.finish();
+}
+
+fn bench_faq_chunker(c: &mut Criterion) {
+    let mut group = c.benchmark_group("faq_chunker");
+    group.sample_size(10);
+    let chunker = FAQChunker::new(200, 1, MODEL.to_string());
+    let entries = large_faq_set(5);
+    let chunks = chunker.chunk_by_qa(entries.clone()).len();
+    group.throughput(Throughput::Elements(chunks as u64));
+    group.bench_function("chunk_by_qa", |b| {
+        b.iter(|| chunker.chunk_by_qa(black_box(entries.clone())))
+    });
+    group.finish();
+}
+
+/// 在临时目录写入 `count` 个 markdown 文件，返回 (路径, document_id)
+fn markdown_files(count: usize) -> Vec<(PathBuf, String)> {
+    let dir = std::env::temp_dir().join(format!("rag-bench-parse-{}", std::process::id()));
+    std::fs::create_dir_all(&dir).unwrap();
+    (0..count)
+        .map(|i| {
+            let path = dir.join(format!("doc-{}.md", i));
+            let sections: String = (0..10)
+                .map(|s| format!("## 第 {} 节\n\n{}\n\n", s, large_document(3)))
+                .collect();
+            std::fs::write(&path, format!("# 文档 {}\n\n{}", i, sections)).unwrap();
+            (path, format!("doc-{}", i))
+        })
+        .collect()
+}
+
+fn bench_parse_directory(c: &mut Criterion) {
+    let mut group = c.benchmark_group("parse_directory");
+    group.sample_size(10);
+    let files = markdown_files(32);
+    group.throughput(Throughput::Elements(files.len() as u64));
+    group.bench_function("sequential", |b| {
+        b.iter(|| files.iter().map(|(path, _)| load_any(path).unwrap()).collect::<Vec<_>>())
+    });
+    group.bench_function("parallel", |b| {
+        b.iter(|| load_parallel(black_box(&files)))
+    });
+    group.finish();
+}
+
+criterion_group!(benches, bench_count_tokens, bench_recursive_chunker, bench_faq_chunker, bench_parse_directory);
+criterion_main!(benches);
@@ -0,0 +1,22 @@
+#![no_main]
+
+//! 将任意字节（按 UTF-8 有损解码）作为 markdown 交给 `MarkdownParser::parse`：
+//! 解析不应 panic 或报错，得到的树须通过 `NodeTree::validate`，叶子的 `source_range` 须落在原文的字符边界上。
+
+use libfuzzer_sys::fuzz_target;
+use rag_indexing::tree_structrue::markdown_bulid::MarkdownParser;
+
+fuzz_target!(|data: &[u8]| {
+    let content = String::from_utf8_lossy(data);
+    let tree = MarkdownParser::new("fuzz".to_string(), None)
+        .with_source_ranges(true)
+        .parse(&content)
+        .expect("解析失败");
+    tree.validate().expect("树结构不一致");
+
+    for leaf in tree.leaf_nodes() {
+        if let Some((start, end)) = leaf.metadata.source_range {
+            assert!(content.get(start..end).is_some(), "source_range {}..{} 越界或不在字符边界上", start, end);
+        }
+    }
+});
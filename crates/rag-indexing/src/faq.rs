@@ -227,18 +227,45 @@ impl fmt::Display for FAQChunk {
     }
 }
 
+/// 解析 Q/A 时的行状态
+enum ParseState {
+    /// 等待下一个 Q 行
+    Idle,
+    /// 已读到 Q 行，等待 A 行
+    Question(String),
+    /// 正在累积答案，`lines` 中的空串表示段落分隔
+    Answer { q: String, lines: Vec<String> },
+}
+
 impl FAQEntry {
+    /// 从 markdown 中解析 FAQ 条目
+    ///
+    /// `## ` 标题为分类，`- Q..: ` 行为问题，其后的 `A..: ` 行开始答案；答案累积后续所有行
+    /// （空行保留为段落分隔），直到下一个 Q 行、任意标题或分隔线 `---` 为止。
     pub fn parse_from_markdown(markdown: &str) -> Vec<FAQEntry> {
         let mut entries = Vec::new();
         let mut current_category = "General".to_string();
-        let mut pending_q: Option<String> = None;
+        let mut state = ParseState::Idle;
+
+        let mut finish = |state: ParseState, category: &str| {
+            if let ParseState::Answer { q, lines } = state {
+                entries.push(FAQEntry {
+                    category: category.to_string(),
+                    q,
+                    a: join_answer_lines(&lines),
+                    tags: vec![],
+                });
+            }
+        };
 
         // 按行处理
         for line in markdown.lines() {
             let trimmed = line.trim();
 
-            // 1.分类标题
+            // 1. 分类标题
             if trimmed.starts_with("## ") && !trimmed.starts_with("###") {
+                finish(std::mem::replace(&mut state, ParseState::Idle), &current_category);
+
                 let after_hash = trimmed.trim_start_matches("## ").trim();
                 let category_clean = after_hash
                     .split(|c: char| c == '、' || c == '.')
@@ -251,46 +278,79 @@ impl FAQEntry {
                 } else {
                     category_clean
                 };
+                continue;
             }
 
-            // 匹配 Q 行
-            // 2. 匹配 Q 行
-            if trimmed.starts_with("- Q") && trimmed.contains(": ") {
-                let q_text = trimmed
-                    .splitn(2, ':')
-                    .nth(1)
-                    .map(|s| s.trim().to_string())
-                    .unwrap_or_default();
+            // 2. 其他标题或分隔线结束当前条目
+            if trimmed.starts_with('#') || is_thematic_break(trimmed) {
+                finish(std::mem::replace(&mut state, ParseState::Idle), &current_category);
+                continue;
+            }
 
-                pending_q = Some(q_text);
+            // 3. Q 行开始新条目
+            if trimmed.starts_with("- Q") && trimmed.contains(": ") {
+                finish(std::mem::replace(&mut state, ParseState::Idle), &current_category);
+                state = ParseState::Question(text_after_colon(trimmed));
                 continue;
             }
 
-            // 3. 匹配 A 行（上一行是 Q）
-            if let Some(q) = pending_q.take() {
-                if trimmed.starts_with("A") && trimmed.contains(": ") {
-                    let a_text = trimmed
-                        .splitn(2, ':')
-                        .nth(1)
-                        .map(|s| s.trim().to_string())
-                        .unwrap_or_default();
-
-                    entries.push(FAQEntry {
-                        category: current_category.clone(),
-                        q,
-                        a: a_text,
-                        tags: vec![],
-                    });
-                } else {
-                    pending_q = None;
+            state = match state {
+                ParseState::Idle => ParseState::Idle,
+                // 4. A 行（上一非空行是 Q），否则丢弃该问题
+                ParseState::Question(q) => {
+                    if trimmed.is_empty() {
+                        ParseState::Question(q)
+                    } else if trimmed.starts_with("A") && trimmed.contains(": ") {
+                        ParseState::Answer { q, lines: vec![text_after_colon(trimmed)] }
+                    } else {
+                        ParseState::Idle
+                    }
                 }
-            }
+                // 5. 答案续行
+                ParseState::Answer { q, mut lines } => {
+                    lines.push(trimmed.to_string());
+                    ParseState::Answer { q, lines }
+                }
+            };
         }
+        finish(state, &current_category);
 
         entries
     }
 }
 
+fn text_after_colon(line: &str) -> String {
+    line.split_once(':')
+        .map(|(_, text)| text.trim().to_string())
+        .unwrap_or_default()
+}
+
+/// markdown 分隔线：至少三个相同的 `-` / `*` / `_`，可夹空格
+fn is_thematic_break(line: &str) -> bool {
+    let marks: Vec<char> = line.chars().filter(|c| !c.is_whitespace()).collect();
+    marks.len() >= 3 && ['-', '*', '_'].iter().any(|m| marks.iter().all(|c| c == m))
+}
+
+/// 合并答案行：段内换行保留为 `\n`，连续空行压缩为一个段落分隔 `\n\n`
+fn join_answer_lines(lines: &[String]) -> String {
+    let mut paragraphs: Vec<Vec<&str>> = vec![vec![]];
+    for line in lines {
+        if line.is_empty() {
+            if !paragraphs.last().unwrap().is_empty() {
+                paragraphs.push(vec![]);
+            }
+        } else {
+            paragraphs.last_mut().unwrap().push(line);
+        }
+    }
+    paragraphs
+        .iter()
+        .filter(|p| !p.is_empty())
+        .map(|p| p.join("\n"))
+        .collect::<Vec<_>>()
+        .join("\n\n")
+}
+
 #[cfg(test)]
 mod tests { 
     use super::*;
@@ -315,4 +375,42 @@ mod tests {
         }
     }
 
+    #[test]
+    fn test_parse_multi_paragraph_answer() {
+        let markdown = "\
+## 一、账户
+- Q1: 如何重置密码？
+  A1: 打开设置页面，
+  点击“忘记密码”。
+
+  随后按邮件中的链接设置新密码。
+
+  - 链接 24 小时内有效
+- Q2: 可以修改用户名吗？
+  A2: 不可以。
+
+---
+说明文字不属于任何答案。
+
+## 二、计费
+- Q3: 支持哪些支付方式？
+  A3: 信用卡与支付宝。
+";
+        let entries = FAQEntry::parse_from_markdown(markdown);
+        assert_eq!(entries.len(), 3);
+
+        assert_eq!(entries[0].category, "账户");
+        assert_eq!(entries[0].q, "如何重置密码？");
+        assert_eq!(
+            entries[0].a,
+            "打开设置页面，\n点击“忘记密码”。\n\n随后按邮件中的链接设置新密码。\n\n- 链接 24 小时内有效"
+        );
+
+        // 分隔线结束答案
+        assert_eq!(entries[1].a, "不可以。");
+
+        assert_eq!(entries[2].category, "计费");
+        assert_eq!(entries[2].a, "信用卡与支付宝。");
+    }
+
 }
\ No newline at end of file
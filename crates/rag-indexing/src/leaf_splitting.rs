@@ -0,0 +1,173 @@
+use anyhow::Result;
+
+use crate::recursive_splitting::RecursiveChunker;
+use crate::tree_structrue::{Node, NodeId, NodeTree};
+
+/// 超过 token 上限的叶子的切分策略，见 [`split_oversized_leaves`]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct SplitPolicy {
+    /// 叶子的最大 token 数
+    pub max_tokens: usize,
+    /// 表格与代码块保持完整（默认开启）：超限时仍作为一个叶子输出并标记 `oversized`，不会在行中间断开
+    pub atomic_blocks: bool,
+}
+
+impl SplitPolicy {
+    pub fn new(max_tokens: usize) -> Self {
+        Self { max_tokens, atomic_blocks: true }
+    }
+
+    pub fn with_atomic_blocks(mut self, atomic: bool) -> Self {
+        self.atomic_blocks = atomic;
+        self
+    }
+
+    /// 该类型的叶子是否不可切分：图片与文档摘要总是保持完整，表格与代码块取决于 `atomic_blocks`
+    fn is_atomic(&self, block_type: &str) -> bool {
+        match block_type {
+            "image" | "summary" => true,
+            "table" | "code" => self.atomic_blocks,
+            _ => false,
+        }
+    }
+}
+
+/// [`split_oversized_leaves`] 的结果
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct SplitReport {
+    /// 被切分的叶子数
+    pub split: usize,
+    /// 超限但保持完整、标记为 `oversized` 的叶子数
+    pub oversized: usize,
+}
+
+/// 将 token 数超过 `policy.max_tokens` 的叶子用 `chunker` 切分，原位替换为同一父节点下的多个叶子
+///
+/// 新叶子的层级路径追加 `part_{i}`，`source_range` 按切分位置换算。不可切分的叶子（见 [`SplitPolicy::atomic_blocks`]）
+/// 保持原样，标记 `metadata.oversized` 并打印警告。切分后重新写入叶子的 `order`。
+pub fn split_oversized_leaves(tree: &mut NodeTree, policy: &SplitPolicy, chunker: &RecursiveChunker) -> Result<SplitReport> {
+    let targets: Vec<NodeId> = tree.leaf_nodes_in_order()
+        .into_iter()
+        .filter(|leaf| leaf.metadata.chunk_size.is_some_and(|tokens| tokens > policy.max_tokens))
+        .map(|leaf| leaf.id)
+        .collect();
+
+    let mut report = SplitReport::default();
+    for leaf_id in targets {
+        let Some(leaf) = tree.nodes.get(&leaf_id).and_then(|n| n.as_leaf()) else { continue };
+        let chunks = if policy.is_atomic(leaf.block_type()) {
+            Vec::new()
+        } else {
+            chunker.chunk(vec![(0, leaf.text.clone())])
+        };
+        if chunks.len() <= 1 {
+            println!(
+                "警告: {} 叶子有 {} 个 token，超过上限 {}，保持完整输出 ({})",
+                leaf.block_type(),
+                leaf.metadata.chunk_size.unwrap_or_default(),
+                policy.max_tokens,
+                leaf.metadata.hierarchy.join(" > ")
+            );
+            if let Some(node) = tree.nodes.get_mut(&leaf_id) {
+                node.metadata_mut().oversized = true;
+            }
+            report.oversized += 1;
+            continue;
+        }
+
+        let parent_id = tree.nodes[&leaf_id].parent_id().unwrap_or(tree.root);
+        let meta = &leaf.metadata;
+        // 去掉 new_leaf 追加的 chunk_{index}_{size} 标签
+        let base_hierarchy = &meta.hierarchy[..meta.hierarchy.len().saturating_sub(1)];
+        let chunk_index = meta.chunk_index.unwrap_or(0);
+
+        let parts: Vec<Node> = chunks.iter()
+            .enumerate()
+            .map(|(i, chunk)| {
+                let mut hierarchy = base_hierarchy.to_vec();
+                hierarchy.push(format!("part_{}", i));
+                let tokens = chunk.metadata.get("token_count").and_then(|t| t.parse().ok()).unwrap_or(0);
+
+                let mut node = Node::new_leaf(
+                    parent_id,
+                    chunk.content.clone(),
+                    tokens,
+                    chunk_index,
+                    hierarchy,
+                    meta.document_id.clone(),
+                    None,
+                    None,
+                    None,
+                    meta.file_name.clone(),
+                );
+                let node_meta = node.metadata_mut();
+                node_meta.source_range = meta.source_range
+                    .filter(|(start, end)| end - start == leaf.text.len())
+                    .map(|(start, _)| (start + chunk.char_range.0, start + chunk.char_range.1));
+                node_meta.file_path = meta.file_path.clone();
+                node
+            })
+            .collect();
+
+        tree.replace_leaf(leaf_id, parts)?;
+        report.split += 1;
+    }
+
+    if report.split > 0 {
+        tree.assign_leaf_order();
+    }
+    Ok(report)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::tree_structrue::markdown_bulid::MarkdownParser;
+
+    #[test]
+    fn test_split_oversized_leaves() -> Result<()> {
+        let rows: String = (0..40).map(|i| format!("| 第{}行 | 数据{} |\n", i, i)).collect();
+        let doc = format!(
+            "# 报告\n\n{}\n\n| 列 | 值 |\n|---|---|\n{}\n短句。\n",
+            "所有权规则很重要。".repeat(40),
+            rows
+        );
+        let parser = MarkdownParser::new("doc-001".to_string(), None)
+            .with_token_model("gpt-4o")
+            .with_source_ranges(true);
+
+        let mut tree = parser.parse(&doc)?;
+        let leaves_before = tree.leaf_nodes().count();
+        let chunker = RecursiveChunker::new(100, "gpt-4o");
+        let report = split_oversized_leaves(&mut tree, &SplitPolicy::new(100), &chunker)?;
+        assert_eq!(report, SplitReport { split: 1, oversized: 1 });
+
+        // 段落被切开，表格保持完整并标记 oversized
+        let leaves = tree.leaf_nodes_in_order();
+        assert!(leaves.len() > leaves_before);
+        let table = leaves.iter().find(|l| l.block_type() == "table").unwrap();
+        assert!(table.metadata.oversized);
+        assert!(table.text.contains("第39行"));
+        let parts: Vec<_> = leaves.iter().filter(|l| l.metadata.hierarchy.iter().any(|h| h.starts_with("part_"))).collect();
+        assert!(parts.len() > 1);
+        assert!(parts.iter().all(|l| l.metadata.chunk_size.unwrap() <= 100 && !l.metadata.oversized));
+        for part in &parts {
+            let (start, end) = part.metadata.source_range.unwrap();
+            assert_eq!(&doc[start..end], part.text);
+        }
+        assert!(leaves.iter().enumerate().all(|(i, l)| l.metadata.order == Some(i as i64)));
+
+        // 关闭后表格也会被切分
+        let mut tree = parser.parse(&doc)?;
+        let report = split_oversized_leaves(&mut tree, &SplitPolicy::new(100).with_atomic_blocks(false), &chunker)?;
+        assert_eq!(report, SplitReport { split: 2, oversized: 0 });
+
+        // 解析器配置策略后在建树时切分，行号按切分后的范围计算
+        let tree = parser.with_split_policy(SplitPolicy::new(100)).parse(&doc)?;
+        let leaves = tree.leaf_nodes_in_order();
+        assert!(leaves.iter().any(|l| l.metadata.oversized));
+        assert!(leaves.iter().filter(|l| !l.metadata.oversized).all(|l| l.metadata.chunk_size.unwrap() <= 100));
+        assert!(leaves.iter().all(|l| l.metadata.line_start.is_some()));
+        Ok(())
+    }
+}
@@ -1,4 +1,10 @@
+pub mod leaf_splitting;
+pub mod loader;
+pub mod normalize;
+pub mod pdf;
 pub mod recursive_splitting;
+pub mod sentence_window;
+pub mod slug;
 pub mod tiktoken;
 pub mod faq;
 
@@ -0,0 +1,232 @@
+use std::path::{Path, PathBuf};
+
+use anyhow::{Context, Result};
+use rayon::prelude::*;
+
+use crate::recursive_splitting::RecursiveChunker;
+use crate::tiktoken::count_tokens;
+use crate::tree_structrue::markdown_bulid::{DEFAULT_TOKEN_MODEL, MarkdownParser};
+use crate::tree_structrue::{Node, NodeTree};
+
+/// 纯文本分块的默认 token 上限
+pub const DEFAULT_PLAINTEXT_MAX_TOKENS: usize = 512;
+
+/// [`PlainTextLoader`] 写入根节点的分块方式，见 [`NodeTree::chunker`]
+pub const PLAIN_TEXT_CHUNKER: &str = "plain_text";
+
+/// 文档加载器：读取文件并解析为 NodeTree
+pub trait DocumentLoader: Send + Sync {
+    /// 读取并解析文件，使用给定的 document_id，文件名写入 `file_name`，路径与行号写入 `file_path` / `line_start` / `line_end`
+    fn load_as(&self, path: &Path, document_id: &str) -> Result<NodeTree>;
+
+    /// 读取并解析文件，以文件路径作为 document_id
+    fn load(&self, path: &Path) -> Result<NodeTree> {
+        self.load_as(path, &path.to_string_lossy())
+    }
+}
+
+fn read_file(path: &Path) -> Result<(String, Option<String>)> {
+    let content = std::fs::read_to_string(path)
+        .with_context(|| format!("读取文件失败: {}", path.display()))?;
+    let file_name = path.file_name().map(|n| n.to_string_lossy().to_string());
+    Ok((content, file_name))
+}
+
+/// markdown 加载器，基于 [`MarkdownParser`]
+#[derive(Debug, Clone)]
+pub struct MarkdownLoader {
+    token_model: String,
+}
+
+impl Default for MarkdownLoader {
+    fn default() -> Self {
+        Self { token_model: DEFAULT_TOKEN_MODEL.to_string() }
+    }
+}
+
+impl MarkdownLoader {
+    pub fn with_token_model(mut self, model: impl Into<String>) -> Self {
+        self.token_model = model.into();
+        self
+    }
+}
+
+impl DocumentLoader for MarkdownLoader {
+    fn load_as(&self, path: &Path, document_id: &str) -> Result<NodeTree> {
+        let (content, file_name) = read_file(path)?;
+        let mut tree = MarkdownParser::new(document_id.to_string(), file_name)
+            .with_token_model(&self.token_model)
+            .with_source_ranges(true)
+            .parse(&content)?;
+        tree.assign_provenance(Some(&path.to_string_lossy()), &content);
+        Ok(tree)
+    }
+}
+
+/// 纯文本加载器：用 [`RecursiveChunker`] 分块，所有叶子直接挂在根节点下
+#[derive(Debug, Clone)]
+pub struct PlainTextLoader {
+    chunker: RecursiveChunker,
+    token_model: String,
+}
+
+impl Default for PlainTextLoader {
+    fn default() -> Self {
+        Self::new(DEFAULT_PLAINTEXT_MAX_TOKENS, DEFAULT_TOKEN_MODEL)
+    }
+}
+
+impl PlainTextLoader {
+    pub fn new(max_tokens: usize, token_model: &str) -> Self {
+        Self {
+            chunker: RecursiveChunker::new(max_tokens, token_model),
+            token_model: token_model.to_string(),
+        }
+    }
+
+    /// 将文本分块为 NodeTree，叶子的 `source_range` 为其在原文中的字节范围
+    pub fn parse(&self, document_id: &str, file_name: Option<String>, content: &str) -> Result<NodeTree> {
+        let mut tree = NodeTree::new(Node::new_root(document_id.to_string(), file_name.clone()));
+        tree.set_token_model(&self.token_model);
+        tree.set_chunker(PLAIN_TEXT_CHUNKER, Some(self.chunker.max_tokens()));
+        let root = tree.root;
+
+        for chunk in self.chunker.chunk(vec![(0, content.to_string())]) {
+            let text = chunk.content.trim();
+            if text.is_empty() {
+                continue;
+            }
+            let mut leaf = Node::new_leaf(
+                root,
+                text.to_string(),
+                count_tokens(text, &self.token_model),
+                chunk.chunk_index,
+                vec!["Root".to_string()],
+                document_id.to_string(),
+                None,
+                None,
+                None,
+                file_name.clone(),
+            );
+            // 叶子文本去掉了首尾空白，范围随之收缩，保证 `content[start..end] == text`
+            let (start, end) = chunk.char_range;
+            let leading = chunk.content.len() - chunk.content.trim_start().len();
+            let trailing = chunk.content.len() - chunk.content.trim_end().len();
+            leaf.metadata_mut().source_range = Some((start + leading, end - trailing));
+            tree.add_node(leaf)?;
+        }
+
+        tree.assign_leaf_order();
+        Ok(tree)
+    }
+}
+
+impl DocumentLoader for PlainTextLoader {
+    fn load_as(&self, path: &Path, document_id: &str) -> Result<NodeTree> {
+        let (content, file_name) = read_file(path)?;
+        let mut tree = self.parse(document_id, file_name, &content)?;
+        tree.assign_provenance(Some(&path.to_string_lossy()), &content);
+        Ok(tree)
+    }
+}
+
+/// 按扩展名选择默认加载器，不支持的类型返回 None
+///
+/// - `md` / `markdown`：[`MarkdownLoader`]
+/// - `txt` / `text`：[`PlainTextLoader`]
+///
+/// PDF 暂无到 NodeTree 的转换，不在支持范围内；文档信息可用 [`crate::pdf::PdfMetadata`] 读取。
+pub fn loader_for(path: &Path) -> Option<Box<dyn DocumentLoader>> {
+    let extension = path.extension()?.to_str()?.to_ascii_lowercase();
+    match extension.as_str() {
+        "md" | "markdown" => Some(Box::new(MarkdownLoader::default())),
+        "txt" | "text" => Some(Box::new(PlainTextLoader::default())),
+        _ => None,
+    }
+}
+
+/// 按扩展名选择加载器加载文件，以文件路径作为 document_id
+pub fn load_any(path: &Path) -> Result<NodeTree> {
+    loader_for(path)
+        .with_context(|| format!("不支持的文件类型: {}", path.display()))?
+        .load(path)
+}
+
+/// 在 rayon 线程池中并行加载多个文件，`files` 为 (路径, document_id)
+///
+/// 返回结果与输入一一对应；单个文件失败只体现在对应位置，不影响其他文件。
+pub fn load_parallel(files: &[(PathBuf, String)]) -> Vec<Result<NodeTree>> {
+    files.par_iter()
+        .map(|(path, document_id)| {
+            loader_for(path)
+                .with_context(|| format!("不支持的文件类型: {}", path.display()))?
+                .load_as(path, document_id)
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::tree_structrue::markdown_bulid::MARKDOWN_CHUNKER;
+    use std::fs;
+
+    #[test]
+    fn test_load_any() -> Result<()> {
+        let dir = std::env::temp_dir().join(format!("rag-loader-{}", std::process::id()));
+        fs::create_dir_all(&dir)?;
+        let text = "第一段文本。\n\n第二段文本。";
+        fs::write(dir.join("a.md"), "# 标题\n\n正文第一行\n正文第二行\n")?;
+        fs::write(dir.join("b.TXT"), text)?;
+        fs::write(dir.join("c.pdf"), "%PDF-1.4")?;
+
+        let markdown = load_any(&dir.join("a.md"))?;
+        assert_eq!(markdown.document_title(), Some("标题"));
+        let leaf = markdown.leaf_nodes().next().unwrap();
+        assert_eq!((leaf.metadata.line_start, leaf.metadata.line_end), (Some(3), Some(4)));
+        assert_eq!(leaf.metadata.file_path, Some(dir.join("a.md").to_string_lossy().to_string()));
+
+        let plain = load_any(&dir.join("b.TXT"))?;
+        let leaves = plain.leaf_nodes_in_order();
+        assert!(!leaves.is_empty());
+        assert!(leaves.iter().all(|l| l.metadata.file_name.as_deref() == Some("b.TXT")));
+        for leaf in &leaves {
+            let (start, end) = leaf.metadata.source_range.unwrap();
+            assert_eq!(text[start..end], leaf.text);
+        }
+        assert_eq!(leaves[0].metadata.line_start, Some(1));
+        assert_eq!(markdown.chunker(), Some(MARKDOWN_CHUNKER));
+        assert_eq!((plain.chunker(), plain.max_tokens()), (Some(PLAIN_TEXT_CHUNKER), Some(DEFAULT_PLAINTEXT_MAX_TOKENS)));
+
+        assert!(load_any(&dir.join("c.pdf")).is_err());
+        assert!(loader_for(&dir.join("noext")).is_none());
+
+        let results = load_parallel(&[
+            (dir.join("a.md"), "a".to_string()),
+            (dir.join("c.pdf"), "c".to_string()),
+            (dir.join("missing.md"), "missing".to_string()),
+            (dir.join("b.TXT"), "b".to_string()),
+        ]);
+        assert_eq!(results.len(), 4);
+        assert_eq!(results[0].as_ref().unwrap().document_title(), Some("标题"));
+        assert!(results[1].is_err() && results[2].is_err());
+        assert!(results[3].as_ref().unwrap().leaf_nodes().all(|l| l.metadata.document_id == "b"));
+
+        fs::remove_dir_all(&dir)?;
+        Ok(())
+    }
+
+    #[test]
+    fn test_parse_trims_source_range() -> Result<()> {
+        // 无句末标点的长句按字符硬切，切分点落在空格处，分块首尾带空白
+        let content = "alpha beta gamma delta epsilon zeta eta theta iota kappa lambda mu nu xi omicron pi rho sigma";
+        let tree = PlainTextLoader::new(5, DEFAULT_TOKEN_MODEL).parse("doc", None, content)?;
+        let leaves = tree.leaf_nodes_in_order();
+        assert!(leaves.len() > 1);
+        for leaf in leaves {
+            let (start, end) = leaf.metadata.source_range.unwrap();
+            assert_eq!(&content[start..end], leaf.text);
+        }
+        Ok(())
+    }
+}
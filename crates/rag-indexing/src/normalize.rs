@@ -0,0 +1,86 @@
+use serde::{Deserialize, Serialize};
+use unicode_normalization::UnicodeNormalization;
+
+use crate::tiktoken::count_tokens;
+
+/// 文本规范化：统一全角 / 半角、多余空白等差异，使同一内容得到相同的 embedding
+///
+/// 默认开启 NFKC 与空白折叠，不转小写（大小写可能影响语义）。
+///
+/// 配置的规范化只作用于发送给 embedding 服务的文本（经 `NormalizingClient`），不影响其他环节：
+/// 解析器按原文计算 token 数，缓存以客户端收到的文本为键，去重哈希使用与配置无关的固定规范化
+/// （见 `rag_embeddings::dedup::normalize_for_dedup`）。
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct TextNormalizer {
+    /// NFKC 规范化：全角字母数字与标点转半角、兼容字符分解（如 "ｆｉ" → "fi"）
+    pub nfkc: bool,
+    /// 连续空白折叠为一个空格，并去除首尾空白
+    pub collapse_whitespace: bool,
+    /// 转为小写
+    pub lowercase: bool,
+}
+
+impl Default for TextNormalizer {
+    fn default() -> Self {
+        Self { nfkc: true, collapse_whitespace: true, lowercase: false }
+    }
+}
+
+impl TextNormalizer {
+    pub fn with_nfkc(mut self, enabled: bool) -> Self {
+        self.nfkc = enabled;
+        self
+    }
+
+    pub fn with_collapse_whitespace(mut self, enabled: bool) -> Self {
+        self.collapse_whitespace = enabled;
+        self
+    }
+
+    pub fn with_lowercase(mut self, enabled: bool) -> Self {
+        self.lowercase = enabled;
+        self
+    }
+
+    pub fn normalize(&self, text: &str) -> String {
+        let mut result: String = if self.nfkc { text.nfkc().collect() } else { text.to_string() };
+        if self.collapse_whitespace {
+            result = result.split_whitespace().collect::<Vec<_>>().join(" ");
+        }
+        if self.lowercase {
+            result = result.to_lowercase();
+        }
+        result
+    }
+
+    /// 规范化后计算 token 数，即实际发送给 embedding 服务的 token 数；解析器记录的 token 数仍按原文计算
+    pub fn count_tokens(&self, text: &str, model: &str) -> usize {
+        count_tokens(&self.normalize(text), model)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_normalize() {
+        let normalizer = TextNormalizer::default();
+        assert_eq!(normalizer.normalize("  Ｒｕｓｔ　１．８５ \n\t发布！ "), "Rust 1.85 发布!");
+
+        let lower = normalizer.with_lowercase(true);
+        assert_eq!(lower.normalize("ＡＢＣ Def"), "abc def");
+
+        let raw = TextNormalizer::default().with_nfkc(false).with_collapse_whitespace(false);
+        assert_eq!(raw.normalize(" Ａ  b "), " Ａ  b ");
+
+        assert_eq!(
+            normalizer.count_tokens("ｈｅｌｌｏ　ｗｏｒｌｄ", "gpt-4o"),
+            normalizer.count_tokens("hello world", "gpt-4o"),
+        );
+
+        let config: TextNormalizer = serde_json::from_str(r#"{"lowercase": true}"#).unwrap();
+        assert_eq!(config, TextNormalizer::default().with_lowercase(true));
+    }
+}
@@ -0,0 +1,131 @@
+use std::path::Path;
+
+use anyhow::{Context, Result};
+use lopdf::{Document, decode_text_string};
+use serde::{Deserialize, Serialize};
+
+use crate::tree_structrue::{Node, NodeTree};
+
+/// PDF 文档信息字典（trailer 中的 `/Info`）里的元数据及页数
+///
+/// 目前只读取信息字典，不解析 XMP；正文到 NodeTree 的转换仍不支持，见 [`crate::loader::loader_for`]。
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct PdfMetadata {
+    pub title: Option<String>,
+    pub author: Option<String>,
+    /// 创建时间，保留 PDF 日期格式，如 `D:20240101120000+08'00'`
+    pub creation_date: Option<String>,
+    /// 生成 PDF 的软件
+    pub producer: Option<String>,
+    pub page_count: usize,
+}
+
+impl PdfMetadata {
+    /// 读取 PDF 文件的元数据
+    pub fn from_path(path: &Path) -> Result<Self> {
+        let document = Document::load(path)
+            .with_context(|| format!("无法解析 PDF: {}", path.display()))?;
+        Ok(Self::from_document(&document))
+    }
+
+    /// 从内存中的 PDF 读取元数据
+    pub fn from_bytes(bytes: &[u8]) -> Result<Self> {
+        let document = Document::load_mem(bytes).context("无法解析 PDF")?;
+        Ok(Self::from_document(&document))
+    }
+
+    /// 没有信息字典或字段无法解码时对应字段为 `None`
+    fn from_document(document: &Document) -> Self {
+        let info = document.trailer.get(b"Info").ok()
+            .and_then(|info| document.dereference(info).ok())
+            .and_then(|(_, info)| info.as_dict().ok());
+        let field = |key: &[u8]| {
+            let value = info?.get(key).ok()?;
+            let (_, value) = document.dereference(value).ok()?;
+            let text = decode_text_string(value).ok()?;
+            let text = text.trim_start_matches('\u{feff}').trim();
+            (!text.is_empty()).then(|| text.to_string())
+        };
+
+        Self {
+            title: field(b"Title"),
+            author: field(b"Author"),
+            creation_date: field(b"CreationDate"),
+            producer: field(b"Producer"),
+            page_count: document.get_pages().len(),
+        }
+    }
+
+    /// 将标题与作者写入树的根节点，PDF 中缺失的字段保留原值
+    pub fn apply_to(&self, tree: &mut NodeTree) {
+        if let Some(Node::Root(root)) = tree.nodes.get_mut(&tree.root) {
+            if self.title.is_some() {
+                root.title = self.title.clone();
+            }
+            if self.author.is_some() {
+                root.author = self.author.clone();
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use lopdf::{Object, dictionary, text_string};
+
+    fn sample_pdf(info: Option<lopdf::Dictionary>) -> Result<Vec<u8>> {
+        let mut document = Document::with_version("1.5");
+        let pages_id = document.new_object_id();
+        let page_ids: Vec<Object> = (0..2)
+            .map(|_| document.add_object(dictionary! { "Type" => "Page", "Parent" => pages_id }).into())
+            .collect();
+        document.objects.insert(pages_id, Object::Dictionary(dictionary! {
+            "Type" => "Pages",
+            "Kids" => page_ids,
+            "Count" => 2,
+        }));
+        let catalog_id = document.add_object(dictionary! { "Type" => "Catalog", "Pages" => pages_id });
+        document.trailer.set("Root", catalog_id);
+        if let Some(info) = info {
+            let info_id = document.add_object(info);
+            document.trailer.set("Info", info_id);
+        }
+
+        let mut bytes = Vec::new();
+        document.save_to(&mut bytes)?;
+        Ok(bytes)
+    }
+
+    #[test]
+    fn test_pdf_metadata() -> Result<()> {
+        let bytes = sample_pdf(Some(dictionary! {
+            "Title" => text_string("季度报告"),
+            "Author" => Object::string_literal("Alice"),
+            "Producer" => Object::string_literal("LibreOffice"),
+            "CreationDate" => Object::string_literal("D:20240101120000Z"),
+        }))?;
+        let metadata = PdfMetadata::from_bytes(&bytes)?;
+        assert_eq!(metadata, PdfMetadata {
+            title: Some("季度报告".to_string()),
+            author: Some("Alice".to_string()),
+            creation_date: Some("D:20240101120000Z".to_string()),
+            producer: Some("LibreOffice".to_string()),
+            page_count: 2,
+        });
+
+        let mut tree = NodeTree::new(Node::new_root("doc-001".to_string(), Some("report.pdf".to_string())));
+        metadata.apply_to(&mut tree);
+        assert_eq!(tree.document_title(), Some("季度报告"));
+        assert_eq!(tree.document_author(), Some("Alice"));
+
+        // 没有信息字典时只有页数
+        let metadata = PdfMetadata::from_bytes(&sample_pdf(None)?)?;
+        assert_eq!(metadata, PdfMetadata { page_count: 2, ..Default::default() });
+        metadata.apply_to(&mut tree);
+        assert_eq!(tree.document_title(), Some("季度报告"));
+
+        assert!(PdfMetadata::from_bytes(b"not a pdf").is_err());
+        Ok(())
+    }
+}
@@ -1,15 +1,19 @@
+use jieba_rs::Jieba;
 use once_cell::sync::Lazy;
 use regex::Regex;
 use std::collections::HashMap;
 use std::fmt;
 use tiktoken_rs::CoreBPE;
 
+use crate::tiktoken::bpe_for_model;
+
 
 #[derive(Debug, Clone)]
 pub struct TextChunk {
     pub content: String,
     pub page_number: usize,
     pub chunk_index: usize,
+    /// 在全文中的字节范围 [start, end)，见 [`RecursiveChunker::chunk`]
     pub char_range: (usize, usize),
     pub metadata: HashMap<String, String>,
 }
@@ -31,12 +35,34 @@ impl fmt::Debug for RecursiveChunker {
     }
 }
 
+/// 文本中 `$$...$$` 与 `$...$` 公式的字节范围
+fn math_spans(text: &str) -> Vec<(usize, usize)> {
+    static MATH: Lazy<Regex> =
+        Lazy::new(|| Regex::new(r"\$\$[\s\S]+?\$\$|\$[^$\n]+?\$").unwrap());
+    MATH.find_iter(text).map(|m| (m.start(), m.end())).collect()
+}
+
+/// 分词器加载词典开销较大，全局共享一个
+static JIEBA: Lazy<Jieba> = Lazy::new(Jieba::new);
+
+/// jieba 分词得到的词起点（字节偏移），不含 0
+///
+/// 中文没有空格，在这些位置断开不会把一个词切成两半
+fn word_boundaries(text: &str) -> Vec<usize> {
+    JIEBA.cut(text, true)
+        .into_iter()
+        .scan(0, |offset, word| {
+            *offset += word.len();
+            Some(*offset)
+        })
+        .filter(|&offset| offset < text.len())
+        .collect()
+}
+
 impl RecursiveChunker {
-    /// 创建分块器
+    /// 创建分块器，`model` 经 [`crate::tiktoken::resolve_model`] 标准化
     pub fn new(max_tokens: usize, model: &str) -> Self {
-        let key = Self::normalize_model(model);
-        let bpe = tiktoken_rs::get_bpe_from_model(&key)
-            .expect(&format!("无法为模型 {} 创建 tokenizer（标准化后: {}）", model, key));
+        let bpe = bpe_for_model(model).unwrap_or_else(|e| panic!("{}", e));
 
         Self {
             max_tokens,
@@ -45,40 +71,53 @@ impl RecursiveChunker {
         }
     }
 
+    pub fn max_tokens(&self) -> usize {
+        self.max_tokens
+    }
+
     /// 递归分块主函数
+    ///
+    /// 每个 chunk 的 `char_range` 是其在所有页文本依次拼接后的全文中的字节范围，
+    /// 总是落在 UTF-8 字符边界上，且 `content` 与该范围内的原文完全一致。
     pub fn chunk(&self, text_with_pages: Vec<(usize, String)>) -> Vec<TextChunk> {
         let mut chunks = Vec::new();
-        let mut global_offset = 0;
+        let mut page_offset = 0;
         let mut chunk_index = 0;
 
         for (page, page_text) in text_with_pages {
             let paragraphs = self.split_paragraphs(&page_text);
 
             for para in paragraphs {
-                let para_len = para.len();
-                if self.token_count(&para) <= self.max_tokens {
+                let para_offset = page_offset + Self::offset_in(&page_text, para);
+                if self.token_count(para) <= self.max_tokens {
                     // 小段落直接成块
                     chunks.push(self.make_chunk(
-                        &para,
+                        para,
                         page,
-                        global_offset,
+                        para_offset,
                         chunk_index,
                     ));
                     chunk_index += 1;
-                    global_offset += para_len + 1;
                 } else {
                     // 递归切分
-                    let subchunks = self.recursive_split(&para, page, global_offset, &mut chunk_index);
+                    let subchunks = self.recursive_split(para, page, para_offset, &mut chunk_index);
                     chunks.extend(subchunks);
-                    global_offset += para_len + 1;
                 }
             }
+            page_offset += page_text.len();
         }
 
         chunks
     }
 
+    /// `part` 在 `base` 中的字节偏移（`part` 必须是 `base` 的子切片）
+    fn offset_in(base: &str, part: &str) -> usize {
+        part.as_ptr() as usize - base.as_ptr() as usize
+    }
+
     /// 递归切分大段落
+    ///
+    /// 相邻句子合并为原文中连续的一段（保留句间标点），不再重新拼接
     fn recursive_split(
         &self,
         text: &str,
@@ -87,82 +126,71 @@ impl RecursiveChunker {
         chunk_index: &mut usize,
     ) -> Vec<TextChunk> {
         let mut chunks = Vec::new();
-        let mut buffer = String::new();
-        let mut current_offset = start_offset;
+        // 当前缓冲在 text 中的字节范围
+        let mut buffer: Option<(usize, usize)> = None;
 
         // 按句子切分
         let sentences = self.split_sentences(text);
 
-        for sentence in sentences {
-            let sent = sentence.trim();
-            if sent.is_empty() { continue; }
+        for sent in sentences {
+            let sent_start = Self::offset_in(text, sent);
+            let sent_end = sent_start + sent.len();
 
-            let new_buffer = if buffer.is_empty() {
-                sent.to_string()
-            } else {
-                format!("{} {}", buffer, sent)
-            };
+            let merged_start = buffer.map_or(sent_start, |(start, _)| start);
 
             // 检查 token 数
-            if self.token_count(&new_buffer) <= self.max_tokens {
-                buffer = new_buffer;
+            if self.token_count(&text[merged_start..sent_end]) <= self.max_tokens {
+                buffer = Some((merged_start, sent_end));
+                continue;
+            }
+
+            // 提交当前 buffer
+            if let Some((start, end)) = buffer.take() {
+                chunks.push(self.make_chunk(&text[start..end], page, start_offset + start, *chunk_index));
+                *chunk_index += 1;
+            }
+            // 新句子单独成块（如果太长，按字符硬切）
+            if self.token_count(sent) <= self.max_tokens {
+                buffer = Some((sent_start, sent_end));
             } else {
-                // 提交当前 buffer
-                if !buffer.is_empty() {
-                    chunks.push(self.make_chunk(&buffer, page, current_offset, *chunk_index));
-                    *chunk_index += 1;
-                    current_offset += buffer.len() + 1;
-                }
-                // 新句子单独成块（如果太长，再递归）
-                if self.token_count(sent) <= self.max_tokens {
-                    chunks.push(self.make_chunk(sent, page, current_offset, *chunk_index));
-                    *chunk_index += 1;
-                    current_offset += sent.len() + 1;
-                    buffer.clear();
-                } else {
-                    // 极端长句：按字符硬切
-                    let hard_chunks = self.hard_split(sent, page, current_offset, chunk_index);
-                    chunks.extend(hard_chunks.clone());
-                    let total_len: usize = hard_chunks.iter().map(|c| c.content.len() + 1).sum();
-                    current_offset += total_len;
-                    *chunk_index += hard_chunks.len();
-                    buffer.clear();
-                }
+                chunks.extend(self.hard_split(sent, page, start_offset + sent_start, chunk_index));
             }
         }
 
         // 最后一块
-        if !buffer.is_empty() {
-            chunks.push(self.make_chunk(&buffer, page, current_offset, *chunk_index));
+        if let Some((start, end)) = buffer {
+            chunks.push(self.make_chunk(&text[start..end], page, start_offset + start, *chunk_index));
             *chunk_index += 1;
         }
 
         chunks
     }
 
-    /// 按段落切分（空行分隔）
-    fn split_paragraphs(&self, text: &str) -> Vec<String> {
+    /// 按段落切分（空行分隔），返回原文的子切片
+    fn split_paragraphs<'a>(&self, text: &'a str) -> Vec<&'a str> {
         text.split("\n\n")
-            .map(|s| s.trim().to_string())
+            .map(|s| s.trim())
             .filter(|s| !s.is_empty())
             .collect()
     }
 
-    /// 按句子切分（中英文）
+    /// 按句子切分（中英文），每个句子保留句末标点，去掉首尾空白
     fn split_sentences<'a>(&self, text: &'a str) -> Vec<&'a str> {
         static CN_SENT: Lazy<Regex> = 
             Lazy::new(|| Regex::new(r"[。！？\n]+").unwrap());
         static EN_SENT: Lazy<Regex> = 
             Lazy::new(|| Regex::new(r"[.!?\n]+").unwrap());
 
+        // 公式内部的标点不作为句子边界
+        let math = math_spans(text);
+        let outside_math = |mat: &regex::Match| !math.iter().any(|&(s, e)| mat.start() >= s && mat.start() < e);
+
         let mut sentences = Vec::new();
         let mut start = 0;
 
         // 优先中文标点
-        for mat in CN_SENT.find_iter(text) {
-            if mat.start() > start {
-                sentences.push(text[start..mat.start()].trim());
-            }
+        for mat in CN_SENT.find_iter(text).filter(outside_math) {
+            sentences.push(text[start..mat.end()].trim());
             start = mat.end();
         }
         if start < text.len() {
@@ -173,10 +201,8 @@ impl RecursiveChunker {
         if sentences.len() <= 1 {
             sentences.clear();
             start = 0;
-            for mat in EN_SENT.find_iter(text) {
-                if mat.start() > start {
-                    sentences.push(text[start..mat.start()].trim());
-                }
+            for mat in EN_SENT.find_iter(text).filter(outside_math) {
+                sentences.push(text[start..mat.end()].trim());
                 start = mat.end();
             }
             if start < text.len() {
@@ -191,7 +217,9 @@ impl RecursiveChunker {
             .collect()
     }
 
-    /// 极端长句：按字符硬切
+    /// 极端长句：按字符硬切，切分点均取自 `char_indices`，保证落在字符边界上
+    ///
+    /// 断开位置依次优先：空格或标点、jieba 词边界（避免切断中文词语）、第 300 个字符
     fn hard_split(
         &self,
         text: &str,
@@ -200,24 +228,32 @@ impl RecursiveChunker {
         chunk_index: &mut usize,
     ) -> Vec<TextChunk> {
         let mut chunks = Vec::new();
-        let chars: Vec<char> = text.chars().collect();
+        // 每个字符的 (字节起点, 字符)，末尾追加文本长度作为哨兵
+        let chars: Vec<(usize, char)> = text.char_indices().collect();
+        let byte_at = |i: usize| chars.get(i).map_or(text.len(), |&(b, _)| b);
+        let boundaries = word_boundaries(text);
         let mut i = 0;
-        let mut current_offset = start_offset;
 
         while i < chars.len() {
-            let mut end = i + 500; // 每次最多 500 字符
-            if end > chars.len() { end = chars.len(); }
+            let limit = (i + 500).min(chars.len()); // 每次最多 500 字符
+            let mut end = limit;
 
             // 尽量在空格或标点处断开
-            while end > i && !Self::is_good_break(chars[end - 1]) {
+            while end > i && !Self::is_good_break(chars[end - 1].1) {
                 end -= 1;
             }
-            if end == i { end = i + 300; } // 强制断开
+            // 其次在词边界处断开
+            if end == i && limit < chars.len() {
+                let (start_byte, limit_byte) = (byte_at(i), byte_at(limit));
+                if let Some(&boundary) = boundaries.iter().rev().find(|&&b| b > start_byte && b <= limit_byte) {
+                    end = chars.partition_point(|&(b, _)| b < boundary);
+                }
+            }
+            if end == i { end = (i + 300).min(chars.len()); } // 强制断开
 
-            let slice = chars[i..end].iter().collect::<String>();
-            chunks.push(self.make_chunk(&slice, page, current_offset, *chunk_index));
+            let (start_byte, end_byte) = (byte_at(i), byte_at(end));
+            chunks.push(self.make_chunk(&text[start_byte..end_byte], page, start_offset + start_byte, *chunk_index));
             *chunk_index += 1;
-            current_offset += slice.len() + 1;
             i = end;
         }
 
@@ -246,20 +282,6 @@ impl RecursiveChunker {
     fn token_count(&self, text: &str) -> usize {
         self.bpe.encode_with_special_tokens(text).len()
     }
-
-    /// 标准化模型名（支持别名）
-    fn normalize_model(model: &str) -> String {
-        match model.trim().to_lowercase().as_str() {
-            "gpt-4o" | "gpt-4" | "gpt-4-turbo" | "gpt-4o-mini" => "gpt-4o".to_string(),
-            "gpt-3.5" | "gpt-3.5-turbo" | "chatgpt" => "gpt-3.5-turbo".to_string(),
-            "text-embedding-3-small" | "embedding-small" => "text-embedding-3-small".to_string(),
-            "text-embedding-3-large" | "embedding-large" => "text-embedding-3-large".to_string(),
-            "text-embedding-ada-002" | "ada" => "text-embedding-ada-002".to_string(),
-            // Qwen 系列（使用 cl100k_base 编码，与 GPT-4 兼容）
-            "qwen" | "qwen-max" | "qwen-plus" | "qwen-turbo" | "qwen-7b" | "qwen-14b" | "qwen-72b" => "gpt-4o".to_string(),
-            _ => model.to_string(),
-        }
-    }
 }
 
 #[cfg(test)]
@@ -292,4 +314,86 @@ mod tests {
         }
         Ok(())
     }
-}
\ No newline at end of file
+
+    #[test]
+    fn test_math_not_split() {
+        let chunker = RecursiveChunker::new(16, "gpt-4o");
+        let text = "The ratio is $p = 0.5! x$ here. Next sentence follows. $$a. b? c$$ done.";
+        let sentences = chunker.split_sentences(text);
+        assert!(sentences.contains(&"The ratio is $p = 0.5! x$ here."));
+        assert!(sentences.contains(&"$$a. b? c$$ done."));
+    }
+
+    #[test]
+    fn test_sentences_keep_punctuation() {
+        let chunker = RecursiveChunker::new(16, "gpt-4o");
+        let normalize = |s: &str| s.split_whitespace().collect::<String>();
+
+        let text = "句子一。句子二！！\n句子三？ 句子四";
+        let sentences = chunker.split_sentences(text);
+        assert_eq!(sentences, vec!["句子一。", "句子二！！", "句子三？", "句子四"]);
+        assert_eq!(normalize(&sentences.concat()), normalize(text));
+
+        let text = "First one. Second one!  Third one? tail";
+        let sentences = chunker.split_sentences(text);
+        assert_eq!(sentences, vec!["First one.", "Second one!", "Third one?", "tail"]);
+        assert_eq!(sentences.join(" "), "First one. Second one! Third one? tail");
+
+        // 单句成块时同样保留句末标点
+        let chunks = RecursiveChunker::new(4, "gpt-4o").chunk(vec![(1, "所有权规则很重要。借用检查器负责验证。".to_string())]);
+        assert!(chunks.iter().any(|c| c.content.ends_with('。')));
+    }
+
+    proptest::proptest! {
+        #![proptest_config(proptest::prelude::ProptestConfig::with_cases(48))]
+
+        #[test]
+        fn prop_ranges_match_source(
+            pages in proptest::collection::vec("[a-z中文字符。，！ .\\n]{0,200}", 1..3),
+        ) {
+            // 构建 tokenizer 开销较大，所有用例共享一个分块器
+            static CHUNKER: Lazy<RecursiveChunker> = Lazy::new(|| RecursiveChunker::new(8, "gpt-4o"));
+            let chunker = &*CHUNKER;
+            let source: String = pages.concat();
+            let chunks = chunker.chunk(pages.into_iter().enumerate().collect());
+
+            let mut last_end = 0;
+            for chunk in &chunks {
+                let (start, end) = chunk.char_range;
+                proptest::prop_assert!(source.is_char_boundary(start) && source.is_char_boundary(end));
+                proptest::prop_assert_eq!(&source[start..end], chunk.content.as_str());
+                proptest::prop_assert!(start >= last_end);
+                last_end = end;
+            }
+        }
+    }
+
+    #[test]
+    fn test_hard_split_cjk() {
+        let chunker = RecursiveChunker::new(8, "gpt-4o");
+        let text = "中".repeat(1200);
+        let chunks = chunker.chunk(vec![(1, text.clone())]);
+
+        // 没有空格或标点，逐字成词，在 500 字符处的词边界断开
+        assert_eq!(chunks.len(), 3);
+        assert_eq!(chunks[0].char_range, (0, 500 * 3));
+        for chunk in &chunks {
+            assert_eq!(&text[chunk.char_range.0..chunk.char_range.1], chunk.content);
+        }
+    }
+
+    #[test]
+    fn test_hard_split_keeps_cjk_words() {
+        let chunker = RecursiveChunker::new(8, "gpt-4o");
+        // 按 300 字符强制断开会把“长江大桥”切成“长江 | 大桥”
+        let text = format!("在{}", "南京市长江大桥".repeat(80));
+        let chunks = chunker.chunk(vec![(1, text.clone())]);
+        assert!(chunks.len() > 1);
+
+        let boundaries = word_boundaries(&text);
+        for chunk in &chunks[1..] {
+            assert!(boundaries.contains(&chunk.char_range.0), "切分点落在词语内部: {}", chunk.content);
+            assert!(chunk.content.starts_with("南京市") || chunk.content.starts_with("长江大桥"));
+        }
+    }
+}
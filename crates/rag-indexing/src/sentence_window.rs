@@ -0,0 +1,113 @@
+use anyhow::Result;
+
+use crate::recursive_splitting::RecursiveChunker;
+use crate::tree_structrue::{Node, NodeId, NodeTree};
+
+/// 将文本叶子拆分为句子级叶子（sentence-window 检索）
+///
+/// 每个段落叶子按 `chunker` 的 token 上限切成若干句子块，原位替换为同一父节点下的多个叶子；
+/// 新叶子的 `metadata.window` 保存原段落全文，层级路径追加 `sent_{i}`。
+/// 图片、表格、代码块、公式、文档摘要及只有一句的段落保持不变，拆分后重新写入叶子的 `order`。返回被拆分的段落数。
+pub fn split_into_sentence_windows(tree: &mut NodeTree, chunker: &RecursiveChunker) -> Result<usize> {
+    let targets: Vec<NodeId> = tree.leaf_nodes_in_order()
+        .into_iter()
+        .filter(|leaf| {
+            leaf.block_type() == "text"
+                && leaf.metadata.image_path.is_none()
+                && leaf.metadata.window.is_none()
+        })
+        .map(|leaf| leaf.id)
+        .collect();
+
+    let mut split = 0;
+    for leaf_id in targets {
+        let Some(leaf) = tree.nodes.get(&leaf_id).and_then(|n| n.as_leaf()) else { continue };
+        let chunks = chunker.chunk(vec![(0, leaf.text.clone())]);
+        if chunks.len() <= 1 {
+            continue;
+        }
+
+        let parent_id = tree.nodes[&leaf_id].parent_id().unwrap_or(tree.root);
+        let meta = &leaf.metadata;
+        // 去掉 new_leaf 追加的 chunk_{index}_{size} 标签
+        let base_hierarchy = &meta.hierarchy[..meta.hierarchy.len().saturating_sub(1)];
+        let chunk_index = meta.chunk_index.unwrap_or(0);
+
+        let sentences: Vec<Node> = chunks.iter()
+            .enumerate()
+            .map(|(i, chunk)| {
+                let mut hierarchy = base_hierarchy.to_vec();
+                hierarchy.push(format!("sent_{}", i));
+                let tokens = chunk.metadata.get("token_count").and_then(|t| t.parse().ok()).unwrap_or(0);
+
+                let mut node = Node::new_leaf(
+                    parent_id,
+                    chunk.content.clone(),
+                    tokens,
+                    chunk_index,
+                    hierarchy,
+                    meta.document_id.clone(),
+                    None,
+                    None,
+                    None,
+                    meta.file_name.clone(),
+                );
+                let node_meta = node.metadata_mut();
+                node_meta.window = Some(leaf.text.clone());
+                node_meta.source_range = meta.source_range
+                    .filter(|(start, end)| end - start == leaf.text.len())
+                    .map(|(start, _)| (start + chunk.char_range.0, start + chunk.char_range.1));
+                // 没有原文无法细化行号，沿用所属段落的行范围
+                node_meta.file_path = meta.file_path.clone();
+                node_meta.line_start = meta.line_start;
+                node_meta.line_end = meta.line_end;
+                node
+            })
+            .collect();
+
+        tree.replace_leaf(leaf_id, sentences)?;
+        split += 1;
+    }
+
+    if split > 0 {
+        tree.assign_leaf_order();
+    }
+    Ok(split)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::tree_structrue::markdown_bulid::MarkdownParser;
+
+    const DOC: &str = "# 所有权\n\n每个值都有唯一的所有者。所有者离开作用域时值被释放。值可以被移动或借用。\n\n```rust\nlet a = 1. + 2.;\n```\n\n短句。\n";
+
+    #[test]
+    fn test_split_into_sentence_windows() -> Result<()> {
+        let mut tree = MarkdownParser::new("doc-001".to_string(), None).parse(DOC)?;
+        let leaves_before = tree.leaf_nodes().count();
+
+        let split = split_into_sentence_windows(&mut tree, &RecursiveChunker::new(12, "gpt-4o"))?;
+        assert_eq!(split, 1);
+
+        let leaves = tree.leaf_nodes_in_order();
+        assert_eq!(leaves.len(), leaves_before + 2);
+        let paragraph = "每个值都有唯一的所有者。所有者离开作用域时值被释放。值可以被移动或借用。";
+        let windows: Vec<_> = leaves.iter().filter(|l| l.metadata.window.as_deref() == Some(paragraph)).collect();
+        assert_eq!(windows.len(), 3);
+        assert_eq!(windows[0].text, "每个值都有唯一的所有者。");
+        assert!(windows[1].metadata.hierarchy.contains(&"sent_1".to_string()));
+
+        // 句子叶子按原顺序挂在同一章节下，且 prev/next 链完整
+        let section = tree.nodes[&windows[0].id].parent_id().unwrap();
+        let children = tree.nodes[&section].children();
+        assert_eq!(children.len(), 5);
+        for pair in children.windows(2) {
+            assert_eq!(tree.nodes[&pair[0]].next_id(), Some(pair[1]));
+            assert_eq!(tree.nodes[&pair[1]].prev_id(), Some(pair[0]));
+        }
+        assert!(leaves.iter().any(|l| l.text.starts_with("let a") && l.metadata.window.is_none()));
+        assert!(leaves.iter().enumerate().all(|(i, l)| l.metadata.order == Some(i as i64)));
+        Ok(())
+    }
+}
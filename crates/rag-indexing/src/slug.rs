@@ -0,0 +1,80 @@
+use std::collections::HashMap;
+use std::fmt::Write;
+
+/// 由标题生成 URL 安全的锚点，规则与 GitHub 渲染 markdown 时的标题锚点一致：
+/// 转小写，去掉字母、数字、`-`、`_` 与空格以外的字符，空格替换为 `-`。
+///
+/// 中文等非 ASCII 字符保留原字的 UTF-8 百分号编码，浏览器中与 `#安装指南` 等价，
+/// 如 `"安装 指南"` -> `"%E5%AE%89%E8%A3%85-%E6%8C%87%E5%8D%97"`。标题只含标点时返回 `"section"`。
+pub fn slugify(title: &str) -> String {
+    let mut slug = String::new();
+    for c in title.trim().chars().flat_map(char::to_lowercase) {
+        if c == ' ' {
+            slug.push('-');
+        } else if c.is_ascii_alphanumeric() || c == '-' || c == '_' {
+            slug.push(c);
+        } else if c.is_alphanumeric() {
+            let mut buf = [0u8; 4];
+            for byte in c.encode_utf8(&mut buf).bytes() {
+                let _ = write!(slug, "%{:02X}", byte);
+            }
+        }
+    }
+
+    if slug.is_empty() {
+        "section".to_string()
+    } else {
+        slug
+    }
+}
+
+/// 为同一文档中的标题生成不重复的锚点：重复标题依次追加 `-1`、`-2`……
+#[derive(Debug, Clone, Default)]
+pub struct Slugger {
+    occurrences: HashMap<String, usize>,
+}
+
+impl Slugger {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// 按文档顺序调用，返回标题的锚点
+    pub fn slug(&mut self, title: &str) -> String {
+        let base = slugify(title);
+        let mut slug = base.clone();
+        // 追加后缀后仍可能与已有标题（如 "安装-1"）冲突，继续递增
+        while self.occurrences.contains_key(&slug) {
+            let count = self.occurrences.entry(base.clone()).or_default();
+            *count += 1;
+            slug = format!("{}-{}", base, count);
+        }
+        self.occurrences.insert(slug.clone(), 0);
+        slug
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_slugify() {
+        assert_eq!(slugify("Getting Started"), "getting-started");
+        assert_eq!(slugify("What's new in v2.0?"), "whats-new-in-v20");
+        assert_eq!(slugify("  snake_case & kebab-case "), "snake_case--kebab-case");
+        assert_eq!(slugify("安装 指南"), "%E5%AE%89%E8%A3%85-%E6%8C%87%E5%8D%97");
+        assert_eq!(slugify("第 1 章：Rust"), "%E7%AC%AC-1-%E7%AB%A0rust");
+        assert_eq!(slugify("!!!"), "section");
+    }
+
+    #[test]
+    fn test_slugger() {
+        let mut slugger = Slugger::new();
+        assert_eq!(slugger.slug("Usage"), "usage");
+        assert_eq!(slugger.slug("Usage"), "usage-1");
+        assert_eq!(slugger.slug("Usage 1"), "usage-1-1");
+        assert_eq!(slugger.slug("usage"), "usage-2");
+        assert_eq!(slugger.slug("Install"), "install");
+    }
+}
@@ -1,57 +1,113 @@
+use anyhow::{Result, anyhow};
 use tiktoken_rs::{get_bpe_from_model, CoreBPE};
 use once_cell::sync::Lazy;
 use std::collections::HashMap;
+use std::sync::{Arc, RwLock};
 
-/// 全局缓存：模型名 → BPE 编码器（线程安全、高性能）
-static BPE_CACHE: Lazy<std::sync::Mutex<HashMap<String, CoreBPE>>> = Lazy::new(|| {
+/// 默认的模型别名 → tokenizer 映射
+///
+/// 右侧为 tiktoken 可识别的模型名（如 "gpt-4o"）或编码名（如 "cl100k_base"），
+/// 运行时可通过 [`register_model_alias`] 追加或覆盖。
+pub const DEFAULT_MODEL_ALIASES: &[(&str, &str)] = &[
+    // GPT-4 系列
+    ("gpt-4", "gpt-4o"),
+    ("gpt-4-turbo", "gpt-4o"),
+    ("gpt-4o", "gpt-4o"),
+    ("gpt-4o-mini", "gpt-4o"),
+    // GPT-3.5 系列
+    ("gpt-3.5", "gpt-3.5-turbo"),
+    ("gpt-3.5-turbo", "gpt-3.5-turbo"),
+    ("chatgpt", "gpt-3.5-turbo"),
+    // 嵌入模型
+    ("text-embedding-3-small", "text-embedding-3-small"),
+    ("embedding-small", "text-embedding-3-small"),
+    ("text-embedding-3-large", "text-embedding-3-large"),
+    ("embedding-large", "text-embedding-3-large"),
+    ("text-embedding-ada-002", "text-embedding-ada-002"),
+    ("ada", "text-embedding-ada-002"),
+    // Qwen 系列（无公开的 tiktoken 编码，借用 GPT-4o 的编码近似计数）
+    ("qwen", "gpt-4o"),
+    ("qwen-max", "gpt-4o"),
+    ("qwen-plus", "gpt-4o"),
+    ("qwen-turbo", "gpt-4o"),
+    ("qwen-7b", "gpt-4o"),
+    ("qwen-14b", "gpt-4o"),
+    ("qwen-72b", "gpt-4o"),
+];
+
+/// 全局别名表：小写别名 → tokenizer 模型名或编码名
+static MODEL_ALIASES: Lazy<RwLock<HashMap<String, String>>> = Lazy::new(|| {
+    RwLock::new(DEFAULT_MODEL_ALIASES.iter()
+        .map(|(alias, base)| (alias.to_string(), base.to_string()))
+        .collect())
+});
+
+/// 全局缓存：模型名 → BPE 编码器（线程安全、高性能），取出时只克隆 Arc，编码在锁外进行
+static BPE_CACHE: Lazy<std::sync::Mutex<HashMap<String, Arc<CoreBPE>>>> = Lazy::new(|| {
     std::sync::Mutex::new(HashMap::new())
 });
 
+/// 注册模型别名，`base` 为 tiktoken 可识别的模型名或编码名（`o200k_base` / `cl100k_base` / `p50k_base` / `r50k_base`）
+///
+/// 别名不区分大小写，已存在的别名会被覆盖。
+pub fn register_model_alias(alias: &str, base: &str) {
+    MODEL_ALIASES.write().unwrap()
+        .insert(alias.trim().to_lowercase(), base.to_string());
+}
+
+/// 标准化模型名：命中别名表时返回对应的 tokenizer 名，否则原样返回
+pub fn resolve_model(model: &str) -> String {
+    MODEL_ALIASES.read().unwrap()
+        .get(&model.trim().to_lowercase())
+        .cloned()
+        .unwrap_or_else(|| model.to_string())
+}
+
+/// 按模型名（经别名表标准化）创建 BPE 编码器
+pub fn bpe_for_model(model: &str) -> Result<CoreBPE> {
+    let key = resolve_model(model);
+    let bpe = match key.as_str() {
+        "o200k_base" => tiktoken_rs::o200k_base(),
+        "cl100k_base" => tiktoken_rs::cl100k_base(),
+        "p50k_base" => tiktoken_rs::p50k_base(),
+        "r50k_base" => tiktoken_rs::r50k_base(),
+        _ => get_bpe_from_model(&key),
+    };
+    bpe.map_err(|e| anyhow!("无法为模型 {} 创建 tokenizer（标准化后: {}）: {}", model, key, e))
+}
+
+/// 从全局缓存取出模型对应的 BPE 编码器，未缓存时创建；模型无法识别时报错
+pub fn cached_bpe(model: &str) -> Result<Arc<CoreBPE>> {
+    let model_key = resolve_model(model);
+    let mut cache = BPE_CACHE.lock().unwrap();
+    if let Some(bpe) = cache.get(&model_key) {
+        return Ok(bpe.clone());
+    }
+    let bpe = Arc::new(bpe_for_model(model)?);
+    cache.insert(model_key, bpe.clone());
+    Ok(bpe)
+}
+
 /// 计算文本的 token 数量
 /// 
 /// # 参数
 /// - `text`: 输入文本
-/// - `model`: 模型名，如 "gpt-4o", "gpt-3.5-turbo", "text-embedding-3-small", "qwen-max"
+/// - `model`: 模型名，如 "gpt-4o", "gpt-3.5-turbo", "text-embedding-3-small", "qwen-max"，
+///   经 [`resolve_model`] 标准化
 /// 
 /// # 返回
 /// `usize` token 数量
+///
+/// # Panics
+/// 模型无法识别时 panic；模型来自外部配置时先用 [`cached_bpe`] 校验
 pub fn count_tokens(text: &str, model: &str) -> usize {
-    // 标准化模型名
-    let model_key = normalize_model_name(model);
-
     // 获取或创建 BPE 编码器
-    let bpe = {
-        let mut cache = BPE_CACHE.lock().unwrap();
-        cache.entry(model_key.clone())
-            .or_insert_with(|| {
-                get_bpe_from_model(&model_key)
-                    .expect(&format!("无法为模型 {} 创建 tokenizer（标准化后: {}）", model, model_key))
-            })
-            .clone()
-    };
+    let bpe = cached_bpe(model).unwrap_or_else(|e| panic!("{}", e));
 
     // 编码并计数
     bpe.encode_with_special_tokens(text).len()
 }
 
-/// 标准化模型名（支持别名）
-fn normalize_model_name(model: &str) -> String {
-    match model.trim().to_lowercase().as_str() {
-        // GPT-4 系列
-        "gpt-4" | "gpt-4-turbo" | "gpt-4o" | "gpt-4o-mini" => "gpt-4o".to_string(),
-        // GPT-3.5 系列
-        "gpt-3.5" | "gpt-3.5-turbo" | "chatgpt" => "gpt-3.5-turbo".to_string(),
-        // 嵌入模型
-        "text-embedding-3-small" | "embedding-small" => "text-embedding-3-small".to_string(),
-        "text-embedding-3-large" | "embedding-large" => "text-embedding-3-large".to_string(),
-        "text-embedding-ada-002" | "ada" => "text-embedding-ada-002".to_string(),
-        // Qwen 系列（使用 cl100k_base 编码，与 GPT-4 兼容）
-        "qwen" | "qwen-max" | "qwen-plus" | "qwen-turbo" | "qwen-7b" | "qwen-14b" | "qwen-72b" => "gpt-4o".to_string(),
-        // 默认
-        _ => model.to_string(),
-    }
-}
-
 #[cfg(test)]
 mod tests {
     use super::*;  
@@ -73,4 +129,18 @@ mod tests {
             assert!(tokens > 0);
         }
     }
+
+    #[test]
+    fn test_register_model_alias() {
+        assert_eq!(resolve_model(" ADA "), "text-embedding-ada-002");
+        assert_eq!(resolve_model("unknown-model"), "unknown-model");
+
+        register_model_alias("My-Embedder", "cl100k_base");
+        assert_eq!(resolve_model("my-embedder"), "cl100k_base");
+        assert_eq!(
+            count_tokens("hello world", "my-embedder"),
+            count_tokens("hello world", "text-embedding-ada-002"),
+        );
+        assert!(bpe_for_model("no-such-model").is_err());
+    }
 }
\ No newline at end of file
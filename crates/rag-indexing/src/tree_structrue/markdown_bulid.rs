@@ -1,22 +1,122 @@
-use crate::tree_structrue::{Node, NodeId, NodeTree};
+use crate::leaf_splitting::{SplitPolicy, split_oversized_leaves};
+use crate::recursive_splitting::RecursiveChunker;
+use crate::tiktoken::{cached_bpe, count_tokens};
+use crate::tree_structrue::{LeafNode, Node, NodeId, NodeTree};
 use pulldown_cmark::{Parser, Options, Event, Tag};
 use anyhow::Result;
 use std::fmt;
-
+use std::ops::Range;
+
+
+/// 没有内容的标题（子树中没有正文）的处理方式，见 [`MarkdownParser::with_empty_headings`]
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum EmptyHeadings {
+    /// 保留所有标题节点
+    #[default]
+    Keep,
+    /// 删除子树中没有叶子的标题节点，见 [`NodeTree::prune_empty_sections`]
+    Prune,
+    /// 先删除，再将只有一个子标题、没有直接内容的标题并入子标题，见 [`NodeTree::merge_single_child_sections`]
+    Merge,
+}
 
 pub struct MarkdownParser {
     document_id: String,
     file_name: Option<String>,
+    /// 是否在叶子节点上记录原文字节范围
+    keep_source_ranges: bool,
+    /// 计算 chunk_size（token 数）所用的模型
+    token_model: String,
+    /// 超长叶子的切分策略，未设置时不切分
+    split_policy: Option<SplitPolicy>,
+    /// 没有内容的标题的处理方式
+    empty_headings: EmptyHeadings,
 }
 
+/// 计算 chunk_size 的默认模型
+pub const DEFAULT_TOKEN_MODEL: &str = "qwen";
+
+/// [`MarkdownParser`] 写入根节点的分块方式，见 [`NodeTree::chunker`]
+pub const MARKDOWN_CHUNKER: &str = "markdown";
+
 impl MarkdownParser {
     pub fn new(document_id: String, file_name: Option<String>) -> Self {
-        Self { document_id, file_name }
+        Self { document_id, file_name, keep_source_ranges: false, token_model: DEFAULT_TOKEN_MODEL.to_string(), split_policy: None, empty_headings: EmptyHeadings::Keep }
+    }
+
+    /// 设置计算叶子 token 数所用的模型，无法识别的模型在 [`parse`](Self::parse) 时报错
+    pub fn with_token_model(mut self, model: impl Into<String>) -> Self {
+        self.token_model = model.into();
+        self
+    }
+
+    /// 在叶子的 `metadata.source_range` 中记录其在原始 markdown 中的字节范围，便于回溯高亮，
+    /// 并据此写入 `line_start` / `line_end`
+    pub fn with_source_ranges(mut self, keep: bool) -> Self {
+        self.keep_source_ranges = keep;
+        self
+    }
+
+    /// 按 token 上限切分超长叶子，表格与代码块是否保持完整见 [`SplitPolicy::atomic_blocks`]
+    pub fn with_split_policy(mut self, policy: SplitPolicy) -> Self {
+        self.split_policy = Some(policy);
+        self
+    }
+
+    /// 连续标题之间没有正文时（常见于大纲）如何处理空章节，默认保留
+    pub fn with_empty_headings(mut self, empty_headings: EmptyHeadings) -> Self {
+        self.empty_headings = empty_headings;
+        self
+    }
+
+    /// 按配置为叶子节点附加原文范围
+    fn with_range(&self, mut leaf: Node, range: Option<(usize, usize)>) -> Node {
+        if self.keep_source_ranges {
+            leaf.metadata_mut().source_range = range;
+        }
+        leaf
+    }
+
+    /// 将段落缓冲区中的文本输出为叶子（空白则跳过），并清空缓冲区与范围
+    fn flush_paragraph(
+        &self,
+        tree: &mut NodeTree,
+        buffer: &mut String,
+        range: &mut Option<(usize, usize)>,
+        parent_id: NodeId,
+        hierarchy: &[String],
+        chunk_index: &mut usize,
+    ) -> Result<()> {
+        let text = buffer.trim().to_string();
+        let range = range.take();
+        buffer.clear();
+        if text.is_empty() {
+            return Ok(());
+        }
+
+        let leaf = Node::new_leaf(
+            parent_id,
+            text.clone(),
+            count_tokens(&text, &self.token_model),
+            *chunk_index,
+            hierarchy.to_vec(),
+            self.document_id.clone(),
+            None,
+            None,
+            None,
+            self.file_name.clone(),
+        );
+        tree.add_node(self.with_range(leaf, range))?;
+        *chunk_index += 1;
+        Ok(())
     }
 
     pub fn parse(&self, content: &str) -> Result<NodeTree> {
+        // 先解析 tokenizer：模型无法识别时直接报错，之后的 count_tokens 都命中缓存
+        cached_bpe(&self.token_model)?;
+
         let options = Options::all();
-        let parser = Parser::new_ext(content, options);
+        let parser = Parser::new_ext(content, options).into_offset_iter();
 
         let mut tree = NodeTree::new(Node::new_root(
             self.document_id.clone(),
@@ -25,8 +125,9 @@ impl MarkdownParser {
 
         let root_id = tree.root;
 
-        // 标题栈：(node_id, hierarchy_vec)
-        let mut heading_stack: Vec<(NodeId, Vec<String>)> = vec![(root_id, vec!["Root".to_string()])];
+        // 标题栈：(level, node_id, hierarchy_vec)，根节点 level 为 0
+        // 按实际级别出栈而非栈深度，跳级标题（如 h1 后直接 h3）仍挂到最近的上级标题下
+        let mut heading_stack: Vec<(u32, NodeId, Vec<String>)> = vec![(0, root_id, vec!["Root".to_string()])];
         let mut current_parent_id = root_id;
         let mut current_hierarchy = vec!["Root".to_string()];
 
@@ -34,6 +135,8 @@ impl MarkdownParser {
         let mut in_code_block = false;
         let mut in_table = false;
         let mut in_image = false;
+        // 脚注定义：(标签, 已收集的定义文本)
+        let mut footnote: Option<(String, String)> = None;
 
         // 缓冲区
         let mut table_header: Option<Vec<String>> = None;
@@ -43,8 +146,19 @@ impl MarkdownParser {
         let mut paragraph_buffer = String::new();
 
         let mut image_alt = String::new();
+        let mut image_title = String::new();
         let mut image_path = String::new();
 
+        // 原文范围：段落由缓冲区内所有文本的范围合并而成，其余块取开始标签的范围
+        let mut paragraph_range: Option<(usize, usize)> = None;
+        let mut block_range: Option<(usize, usize)> = None;
+        let extend = |acc: &mut Option<(usize, usize)>, r: &Range<usize>| {
+            *acc = Some(match *acc {
+                Some((start, end)) => (start.min(r.start), end.max(r.end)),
+                None => (r.start, r.end),
+            });
+        };
+
         // 待处理的标题
         struct PendingHeading {
             level: u32,
@@ -57,22 +171,23 @@ impl MarkdownParser {
         // 全局 chunk 计数
         let mut chunk_index = 0;
 
-        for event in parser {
+        for (event, range) in parser {
             match event {
                 // === 开始标签 ===
                 Event::Start(tag) => {
                     match tag {
                         Tag::Heading { level, .. } => {
-                            // 弹出超出当前级别的栈顶
-                            while heading_stack.len() > level as usize {
+                            // 弹出级别不低于当前标题的栈顶
+                            let level = level as u32;
+                            while heading_stack.last().is_some_and(|(l, ..)| *l >= level) {
                                 heading_stack.pop();
                             }
 
-                            let (parent_id, parent_hier) = heading_stack.last().cloned()
-                                .unwrap_or((root_id, vec!["Root".to_string()]));
+                            let (_, parent_id, parent_hier) = heading_stack.last().cloned()
+                                .unwrap_or((0, root_id, vec!["Root".to_string()]));
 
                             pending_heading = Some(PendingHeading {
-                                level: level as u32,
+                                level,
                                 text: String::new(),
                                 _parent_id: parent_id,
                                 _parent_hierarchy: parent_hier,
@@ -82,18 +197,45 @@ impl MarkdownParser {
                         Tag::CodeBlock(_) => {
                             in_code_block = true;
                             code_buffer.clear();
+                            block_range = Some((range.start, range.end));
                         }
 
                         Tag::Table(_) => {
                             in_table = true;
+                            block_range = Some((range.start, range.end));
                             table_header = None;
                             table_buffer.clear();
                             current_row.clear();
                         }
 
+                        // 脚注定义收集为独立叶子，其中的段落不单独输出
+                        Tag::FootnoteDefinition(label) => {
+                            self.flush_paragraph(
+                                &mut tree,
+                                &mut paragraph_buffer,
+                                &mut paragraph_range,
+                                current_parent_id,
+                                &current_hierarchy,
+                                &mut chunk_index,
+                            )?;
+                            footnote = Some((label.to_string(), String::new()));
+                            block_range = Some((range.start, range.end));
+                        }
+
                         Tag::Image { dest_url, title, .. } => {
+                            // 同一段落中图片之前的文本先作为独立叶子输出，保持原文顺序
+                            self.flush_paragraph(
+                                &mut tree,
+                                &mut paragraph_buffer,
+                                &mut paragraph_range,
+                                current_parent_id,
+                                &current_hierarchy,
+                                &mut chunk_index,
+                            )?;
                             in_image = true;
-                            image_alt = title.to_string();
+                            block_range = Some((range.start, range.end));
+                            image_alt.clear();
+                            image_title = title.to_string();
                             image_path = dest_url.to_string();
                         }
 
@@ -113,12 +255,12 @@ impl MarkdownParser {
                                 let title_str = title.to_string();
 
                                 // 确保栈深度正确
-                                while heading_stack.len() > heading.level as usize {
+                                while heading_stack.last().is_some_and(|(l, ..)| *l >= heading.level) {
                                     heading_stack.pop();
                                 }
 
-                                let (parent_id, parent_hier) = heading_stack.last().cloned()
-                                    .unwrap_or((root_id, vec!["Root".to_string()]));
+                                let (_, parent_id, parent_hier) = heading_stack.last().cloned()
+                                    .unwrap_or((0, root_id, vec!["Root".to_string()]));
 
                                 let mut new_hier = parent_hier.clone();
                                 new_hier.push(title_str.clone());
@@ -133,7 +275,7 @@ impl MarkdownParser {
                                 tree.add_node(intermediate)?;
 
                                 // 入栈
-                                heading_stack.push((new_id, new_hier.clone()));
+                                heading_stack.push((heading.level, new_id, new_hier.clone()));
 
                                 // 更新当前上下文
                                 current_parent_id = new_id;
@@ -141,44 +283,96 @@ impl MarkdownParser {
                             }
                         }
 
-                        pulldown_cmark::TagEnd::Paragraph => {
-                            if !paragraph_buffer.trim().is_empty() {
-                                let text = paragraph_buffer.trim().to_string();
+                        pulldown_cmark::TagEnd::Paragraph if footnote.is_some() => {
+                            if let Some((_, text)) = &mut footnote {
+                                if !text.is_empty() {
+                                    text.push('\n');
+                                }
+                                text.push_str(paragraph_buffer.trim());
+                            }
+                            paragraph_buffer.clear();
+                            paragraph_range = None;
+                        }
+
+                        pulldown_cmark::TagEnd::FootnoteDefinition => {
+                            if let Some((label, mut text)) = footnote.take() {
+                                // 没有以段落结束的定义内容也一并收入
+                                if !paragraph_buffer.trim().is_empty() {
+                                    text.push('\n');
+                                    text.push_str(paragraph_buffer.trim());
+                                }
+                                paragraph_buffer.clear();
+                                paragraph_range = None;
+
+                                let markdown = format!("[^{}]: {}", label, text.trim());
+                                let mut footnote_hier = current_hierarchy.clone();
+                                footnote_hier.push(format!("footnote_{}", label));
+
                                 let leaf = Node::new_leaf(
                                     current_parent_id,
-                                    text.clone(),
-                                    text.len(),
+                                    markdown.clone(),
+                                    count_tokens(&markdown, &self.token_model),
                                     chunk_index,
-                                    current_hierarchy.clone(),
+                                    footnote_hier,
                                     self.document_id.clone(),
                                     None,
                                     None,
                                     None,
                                     self.file_name.clone(),
                                 );
-                                tree.add_node(leaf)?;
+                                tree.add_node(self.with_range(leaf, block_range.take()))?;
                                 chunk_index += 1;
                             }
-                            paragraph_buffer.clear();
+                        }
+
+                        // 定义列表的每个 "术语: 定义" 输出为一个叶子
+                        pulldown_cmark::TagEnd::DefinitionListTitle => {
+                            paragraph_buffer.truncate(paragraph_buffer.trim_end().len());
+                            paragraph_buffer.push_str(": ");
+                        }
+
+                        pulldown_cmark::TagEnd::DefinitionListDefinition => {
+                            self.flush_paragraph(
+                                &mut tree,
+                                &mut paragraph_buffer,
+                                &mut paragraph_range,
+                                current_parent_id,
+                                &current_hierarchy,
+                                &mut chunk_index,
+                            )?;
+                        }
+
+                        pulldown_cmark::TagEnd::Paragraph => {
+                            self.flush_paragraph(
+                                &mut tree,
+                                &mut paragraph_buffer,
+                                &mut paragraph_range,
+                                current_parent_id,
+                                &current_hierarchy,
+                                &mut chunk_index,
+                            )?;
                         }
 
                         pulldown_cmark::TagEnd::CodeBlock => {
                             if in_code_block {
                                 let text = code_buffer.trim_end().to_string();
                                 if !text.is_empty() {
+                                    let mut code_hier = current_hierarchy.clone();
+                                    code_hier.push(format!("code_{}", chunk_index));
+
                                     let leaf = Node::new_leaf(
                                         current_parent_id,
                                         text.clone(),
-                                        text.len(),
+                                        count_tokens(&text, &self.token_model),
                                         chunk_index,
-                                        current_hierarchy.clone(),
+                                        code_hier,
                                         self.document_id.clone(),
                                         None,
                                         None,
                                         None,
                                         self.file_name.clone(),
                                     );
-                                    tree.add_node(leaf)?;
+                                    tree.add_node(self.with_range(leaf, block_range.take()))?;
                                     chunk_index += 1;
                                 }
                                 in_code_block = false;
@@ -218,7 +412,7 @@ impl MarkdownParser {
                                     let leaf = Node::new_leaf(
                                         current_parent_id,
                                         markdown.clone(),
-                                        markdown.len(),
+                                        count_tokens(&markdown, &self.token_model),
                                         chunk_index,
                                         table_hier,
                                         self.document_id.clone(),
@@ -227,7 +421,7 @@ impl MarkdownParser {
                                         None,
                                         self.file_name.clone(),
                                     );
-                                    tree.add_node(leaf)?;
+                                    tree.add_node(self.with_range(leaf, block_range.take()))?;
                                     chunk_index += 1;
                                 }
 
@@ -239,7 +433,11 @@ impl MarkdownParser {
 
                         pulldown_cmark::TagEnd::Image => {
                             if in_image {
-                                let markdown = format!("![{}]({})", image_alt, image_path);
+                                let markdown = if image_title.is_empty() {
+                                    format!("![{}]({})", image_alt, image_path)
+                                } else {
+                                    format!("![{}]({} \"{}\")", image_alt, image_path, image_title)
+                                };
                                 let mut img_hier = current_hierarchy.clone();
                                 img_hier.push(format!("img_{}", chunk_index));
 
@@ -248,7 +446,7 @@ impl MarkdownParser {
                                 let leaf = Node::new_leaf(
                                     current_parent_id,
                                     markdown.clone(),
-                                    markdown.len(),
+                                    count_tokens(&markdown, &self.token_model),
                                     chunk_index,
                                     img_hier,
                                     self.document_id.clone(),
@@ -257,13 +455,17 @@ impl MarkdownParser {
                                     Some(image_id),
                                     self.file_name.clone(),
                                 );
+                                let mut leaf = self.with_range(leaf, block_range.take());
+                                if !image_title.is_empty() {
+                                    leaf.metadata_mut().image_title = Some(image_title.clone());
+                                }
                                 tree.add_node(leaf)?;
                                 chunk_index += 1;
 
                                 in_image = false;
                                 image_alt.clear();
+                                image_title.clear();
                                 image_path.clear();
-                                paragraph_buffer.clear();
                             }
                         }
 
@@ -287,12 +489,83 @@ impl MarkdownParser {
                     } else if !s.trim().is_empty() {
                         paragraph_buffer.push_str(s);
                         paragraph_buffer.push(' ');
+                        extend(&mut paragraph_range, &range);
+                    }
+                }
+
+                Event::InlineMath(text) => {
+                    let math = format!("${}$", text);
+                    if let Some(heading) = &mut pending_heading {
+                        heading.text.push_str(&math);
+                    } else if in_table {
+                        current_row.push(math);
+                    } else if in_image {
+                        image_alt.push_str(&math);
+                    } else {
+                        // 文本片段后追加的分隔空格与原文空白合并为一个，公式后的空白由后续文本自带
+                        paragraph_buffer.truncate(paragraph_buffer.trim_end().len());
+                        if !paragraph_buffer.is_empty() {
+                            paragraph_buffer.push(' ');
+                        }
+                        paragraph_buffer.push_str(&math);
+                        extend(&mut paragraph_range, &range);
+                    }
+                }
+
+                // 行间公式独立成叶，前面的段落文本先输出
+                Event::DisplayMath(text) => {
+                    self.flush_paragraph(
+                        &mut tree,
+                        &mut paragraph_buffer,
+                        &mut paragraph_range,
+                        current_parent_id,
+                        &current_hierarchy,
+                        &mut chunk_index,
+                    )?;
+
+                    let formula = text.trim();
+                    if !formula.is_empty() {
+                        let markdown = format!("$${}$$", formula);
+                        let mut math_hier = current_hierarchy.clone();
+                        math_hier.push(format!("math_{}", chunk_index));
+
+                        let leaf = Node::new_leaf(
+                            current_parent_id,
+                            markdown.clone(),
+                            count_tokens(&markdown, &self.token_model),
+                            chunk_index,
+                            math_hier,
+                            self.document_id.clone(),
+                            None,
+                            None,
+                            None,
+                            self.file_name.clone(),
+                        );
+                        tree.add_node(self.with_range(leaf, Some((range.start, range.end))))?;
+                        chunk_index += 1;
                     }
                 }
 
                 Event::Code(text) => {
                     if pending_heading.is_none() && !in_code_block {
                         paragraph_buffer.push_str(&format!("`{}` ", text));
+                        extend(&mut paragraph_range, &range);
+                    }
+                }
+
+                // 脚注引用以 `[^label]` 保留在原位
+                Event::FootnoteReference(label) => {
+                    let marker = format!("[^{}]", label);
+                    if let Some(heading) = &mut pending_heading {
+                        heading.text.push_str(&marker);
+                    } else if in_table {
+                        if let Some(cell) = current_row.last_mut() {
+                            cell.push_str(&marker);
+                        }
+                    } else if !in_code_block && !in_image {
+                        paragraph_buffer.truncate(paragraph_buffer.trim_end().len());
+                        paragraph_buffer.push_str(&marker);
+                        extend(&mut paragraph_range, &range);
                     }
                 }
 
@@ -307,31 +580,127 @@ impl MarkdownParser {
         }
 
         // 处理最后未结束的段落
-        if !paragraph_buffer.trim().is_empty() {
-            let text = paragraph_buffer.trim().to_string();
-            let leaf = Node::new_leaf(
-                current_parent_id,
-                text.clone(),
-                text.len(),
-                chunk_index,
-                current_hierarchy.clone(),
-                self.document_id.clone(),
-                None,
-                None,
-                None,
-                self.file_name.clone(),
-            );
-            tree.add_node(leaf)?;
+        self.flush_paragraph(
+            &mut tree,
+            &mut paragraph_buffer,
+            &mut paragraph_range,
+            current_parent_id,
+            &current_hierarchy,
+            &mut chunk_index,
+        )?;
+
+        tree.extract_document_title();
+        tree.set_token_model(&self.token_model);
+        tree.set_chunker(MARKDOWN_CHUNKER, self.split_policy.as_ref().map(|policy| policy.max_tokens));
+        match self.empty_headings {
+            EmptyHeadings::Keep => {}
+            EmptyHeadings::Prune => {
+                tree.prune_empty_sections();
+            }
+            EmptyHeadings::Merge => {
+                tree.prune_empty_sections();
+                tree.merge_single_child_sections();
+            }
+        }
+        tree.assign_leaf_order();
+        if let Some(policy) = &self.split_policy {
+            let chunker = RecursiveChunker::new(policy.max_tokens, &self.token_model);
+            split_oversized_leaves(&mut tree, policy, &chunker)?;
+        }
+        tree.assign_slugs();
+        if self.keep_source_ranges {
+            tree.assign_provenance(None, content);
         }
-
         Ok(tree)
     }
 }
 
+/// 叶子文本展示时保留的最大字符数
+pub const SNIPPET_MAX_CHARS: usize = 500;
+
+/// 截取文本前 `max_chars` 个字符（超出时追加 "..."），并移除换行符以便在一行显示
+pub fn snippet(text: &str, max_chars: usize) -> String {
+    let display_text = if text.chars().count() > max_chars {
+        let truncated: String = text.chars().take(max_chars).collect();
+        format!("{}...", truncated)
+    } else {
+        text.to_string()
+    };
+    display_text.replace('\n', " ").replace('\r', "")
+}
+
+/// 打印节点与文档树时的展示选项
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct DisplayOptions {
+    /// 叶子文本预览的最大字符数
+    pub preview_chars: usize,
+    /// 以 token 数代替叶子文本预览，适合打印大文档的结构
+    pub token_count: bool,
+}
+
+impl Default for DisplayOptions {
+    fn default() -> Self {
+        Self { preview_chars: SNIPPET_MAX_CHARS, token_count: false }
+    }
+}
+
+impl DisplayOptions {
+    pub fn with_preview_chars(mut self, preview_chars: usize) -> Self {
+        self.preview_chars = preview_chars;
+        self
+    }
+
+    pub fn with_token_count(mut self, enabled: bool) -> Self {
+        self.token_count = enabled;
+        self
+    }
+
+    /// 叶子的展示内容：文本预览，或 token 数与字符数
+    fn leaf_preview(&self, leaf: &LeafNode) -> String {
+        if self.token_count {
+            let tokens = leaf.metadata.chunk_size
+                .unwrap_or_else(|| count_tokens(&leaf.text, DEFAULT_TOKEN_MODEL));
+            format!("({} tokens, {} 字符)", tokens, leaf.text.chars().count())
+        } else {
+            snippet(&leaf.text, self.preview_chars)
+        }
+    }
+}
+
+/// 按 [`DisplayOptions`] 展示单个节点，见 [`Node::display_with`]
+pub struct NodeDisplay<'a> {
+    node: &'a Node,
+    options: DisplayOptions,
+}
+
+/// 按 [`DisplayOptions`] 分层展示文档树，见 [`NodeTree::display_with`]
+pub struct TreeDisplay<'a> {
+    tree: &'a NodeTree,
+    options: DisplayOptions,
+}
+
+impl Node {
+    pub fn display_with(&self, options: DisplayOptions) -> NodeDisplay<'_> {
+        NodeDisplay { node: self, options }
+    }
+}
+
+impl NodeTree {
+    pub fn display_with(&self, options: DisplayOptions) -> TreeDisplay<'_> {
+        TreeDisplay { tree: self, options }
+    }
+}
+
 // 添加 Display trait 的实现
 impl fmt::Display for Node {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
-        match self {
+        self.display_with(DisplayOptions::default()).fmt(f)
+    }
+}
+
+impl fmt::Display for NodeDisplay<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self.node {
             Node::Root(root) => {
                 write!(f, "📁 ROOT: {} (文件: {:?})", 
                     root.document_id, 
@@ -346,16 +715,7 @@ impl fmt::Display for Node {
                 }
             }
             Node::Leaf(leaf) => {
-                // 截取文本前500个字符显示
-                let display_text = if leaf.text.chars().count() > 500 {
-                    let truncated: String = leaf.text.chars().take(500).collect();
-                    format!("{}...", truncated)
-                } else {
-                    leaf.text.clone()
-                };
-                // 移除换行符以便在一行显示
-                let clean_text = display_text.replace('\n', " ").replace('\r', "");
-                write!(f, "📄 {}", clean_text)
+                write!(f, "📄 {}", self.options.leaf_preview(leaf))
             }
         }
     }
@@ -364,22 +724,29 @@ impl fmt::Display for Node {
 // 为 NodeTree 实现 Display trait，实现分层打印
 impl fmt::Display for NodeTree {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        self.display_with(DisplayOptions::default()).fmt(f)
+    }
+}
+
+impl fmt::Display for TreeDisplay<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let tree = self.tree;
         writeln!(f, "🌳 文档树结构:")?;
         writeln!(f, "{}", "=".repeat(60))?;
         
         // 从根节点开始递归打印
-        self.print_node_recursive(f, self.root, 0)?;
+        tree.print_node_recursive(f, tree.root, 0, &self.options)?;
         
         writeln!(f, "{}", "=".repeat(60))?;
         writeln!(f, "📊 统计信息:")?;
-        writeln!(f, "   总节点数: {}", self.nodes.len())?;
+        writeln!(f, "   总节点数: {}", tree.nodes.len())?;
         
         // 统计各类节点数量
         let mut root_count = 0;
         let mut intermediate_count = 0;
         let mut leaf_count = 0;
         
-        for node in self.nodes.values() {
+        for node in tree.nodes.values() {
             match node {
                 Node::Root(_) => root_count += 1,
                 Node::Intermediate(_) => intermediate_count += 1,
@@ -397,7 +764,7 @@ impl fmt::Display for NodeTree {
 
 // 递归打印节点
 impl NodeTree {
-    fn print_node_recursive(&self, f: &mut fmt::Formatter, node_id: NodeId, depth: usize) -> fmt::Result {
+    fn print_node_recursive(&self, f: &mut fmt::Formatter, node_id: NodeId, depth: usize, options: &DisplayOptions) -> fmt::Result {
         if let Some(node) = self.nodes.get(&node_id) {
             // 打印缩进
             let indent = "  ".repeat(depth);
@@ -409,7 +776,8 @@ impl NodeTree {
                         Some(name) => format!(" [{}]", name),
                         None => "".to_string(),
                     };
-                    ("🌳", format!("ROOT{}{}", root.document_id, file_info))
+                    let title = root.title.as_ref().map(|t| format!(" 《{}》", t)).unwrap_or_default();
+                    ("🌳", format!("ROOT{}{}{}", root.document_id, file_info, title))
                 }
                 Node::Intermediate(inter) => {
                     let title = inter.title.as_deref().unwrap_or("(未命名)");
@@ -437,15 +805,7 @@ impl NodeTree {
                             format!("{} [{}] {}", chunk_info, alt, path)
                         }
                     } else {
-                        let display_text = if leaf.text.chars().count() > 500 {
-                            let truncated: String = leaf.text.chars().take(500).collect();
-                            format!("{}...", truncated)
-                        } else {
-                            leaf.text.clone()
-                        };
-
-                        let clean_text = display_text.replace('\n', " ").replace('\r', "");
-                        format!("{} {}", chunk_info, clean_text)
+                        format!("{} {}", chunk_info, options.leaf_preview(leaf))
                     };
 
                     (icon, content)
@@ -458,7 +818,7 @@ impl NodeTree {
             // 如果有子节点，递归打印
             if !node.children().is_empty() {
                 for &child_id in node.children() {
-                    self.print_node_recursive(f, child_id, depth + 1)?;
+                    self.print_node_recursive(f, child_id, depth + 1, options)?;
                 }
             }
         }
@@ -563,4 +923,251 @@ print("hello world")
         Ok(())
     }
 
+    #[test]
+    fn test_source_ranges() -> Result<()> {
+        let parser = MarkdownParser::new("doc-003".to_string(), None).with_source_ranges(true);
+        let tree = parser.parse(TEST_MARKDOWN)?;
+
+        for leaf in tree.leaf_nodes() {
+            let (start, end) = leaf.metadata.source_range.expect("leaf should carry a source range");
+            let source = &TEST_MARKDOWN[start..end];
+
+            // 表格叶子是重新拼接的 markdown，比较单元格内容；其余比较开头的文本片段
+            let probe: String = if leaf.metadata.hierarchy.iter().any(|h| h.starts_with("table_")) {
+                "OpenAI发布GPT-3".to_string()
+            } else if leaf.metadata.image_path.is_some() {
+                leaf.metadata.image_path.clone().unwrap()
+            } else {
+                leaf.text.chars().take_while(|c| !c.is_whitespace()).take(10).collect()
+            };
+            assert!(source.contains(&probe), "source {:?} should contain {:?}", source, probe);
+        }
+
+        let without = MarkdownParser::new("doc-003".to_string(), None).parse(TEST_MARKDOWN)?;
+        assert!(without.leaf_nodes().all(|leaf| leaf.metadata.source_range.is_none()));
+        Ok(())
+    }
+
+    #[test]
+    fn test_chunk_size_is_token_count() -> Result<()> {
+        let tree = MarkdownParser::new("doc-005".to_string(), None)
+            .with_token_model("gpt-4o")
+            .parse("# 标题\n\nRust 是一门系统编程语言。\n")?;
+
+        let leaf = tree.leaf_nodes().next().unwrap();
+        let tokens = count_tokens(&leaf.text, "gpt-4o");
+        assert_eq!(leaf.metadata.chunk_size, Some(tokens));
+        assert_eq!(leaf.metadata.char_len, Some(leaf.text.chars().count()));
+        assert_ne!(leaf.metadata.chunk_size, Some(leaf.text.len()));
+        assert!(leaf.metadata.hierarchy.last().unwrap().ends_with(&format!("_{}", tokens)));
+        assert_eq!(tree.token_model(), Some("gpt-4o"));
+        Ok(())
+    }
+
+    #[test]
+    fn test_unknown_token_model() {
+        let result = MarkdownParser::new("doc-006".to_string(), None)
+            .with_token_model("no-such-model")
+            .parse("# 标题\n\n正文\n");
+        let err = result.expect_err("unknown token model should be rejected");
+        assert!(err.to_string().contains("no-such-model"), "{}", err);
+    }
+
+    #[test]
+    fn test_image_alt_and_title() -> Result<()> {
+        let parser = MarkdownParser::new("doc-004".to_string(), None);
+        let tree = parser.parse("# 图片\n\n![real alt](images/a.png \"the title\")\n\n![only alt](images/b.png)\n")?;
+
+        let mut images: Vec<_> = tree.leaf_nodes().filter(|l| l.metadata.image_path.is_some()).collect();
+        images.sort_by_key(|l| l.metadata.image_path.clone());
+        assert_eq!(images.len(), 2);
+
+        assert_eq!(images[0].metadata.image_alt.as_deref(), Some("real alt"));
+        assert_eq!(images[0].metadata.image_title.as_deref(), Some("the title"));
+        assert_eq!(images[0].text, "![real alt](images/a.png \"the title\")");
+
+        assert_eq!(images[1].metadata.image_alt.as_deref(), Some("only alt"));
+        assert_eq!(images[1].metadata.image_title, None);
+        Ok(())
+    }
+
+    #[test]
+    fn test_math_blocks() -> Result<()> {
+        let markdown = "# 公式\n\n质能方程 $E = mc^2$ 描述能量。\n\n$$\n\\sum_{i=1}^{n} i = \\frac{n(n+1)}{2}\n$$\n\n结尾段落。\n";
+        let tree = MarkdownParser::new("doc-008".to_string(), None)
+            .with_source_ranges(true)
+            .parse(markdown)?;
+
+        let leaves = tree.leaf_nodes_in_order();
+        let texts: Vec<&str> = leaves.iter().map(|l| l.text.as_str()).collect();
+        assert_eq!(texts, vec![
+            "质能方程 $E = mc^2$ 描述能量。",
+            "$$\\sum_{i=1}^{n} i = \\frac{n(n+1)}{2}$$",
+            "结尾段落。",
+        ]);
+        let types: Vec<&str> = leaves.iter().map(|l| l.block_type()).collect();
+        assert_eq!(types, vec!["text", "math", "text"]);
+
+        let (start, end) = leaves[1].metadata.source_range.unwrap();
+        assert!(markdown[start..end].contains("\\frac{n(n+1)}{2}"));
+        Ok(())
+    }
+
+    #[test]
+    fn test_chunk_index_across_leaf_types() -> Result<()> {
+        let markdown = "# 混合\n\n第一段。\n\n![图](a.png)\n\n| 列 | 值 |\n|---|---|\n| a | 1 |\n\n```rust\nfn main() {}\n```\n\n第二段。\n";
+        let tree = MarkdownParser::new("doc-010".to_string(), None).parse(markdown)?;
+
+        let leaves = tree.leaf_nodes_in_order();
+        let types: Vec<&str> = leaves.iter().map(|l| l.block_type()).collect();
+        assert_eq!(types, vec!["text", "image", "table", "code", "text"]);
+        let indices: Vec<Option<usize>> = leaves.iter().map(|l| l.metadata.chunk_index).collect();
+        assert_eq!(indices, (0..5).map(Some).collect::<Vec<_>>());
+        assert!(tree.nodes.values().filter(|n| n.as_leaf().is_none()).all(|n| n.metadata().chunk_index.is_none()));
+        Ok(())
+    }
+
+    proptest::proptest! {
+        #![proptest_config(proptest::prelude::ProptestConfig::with_cases(64))]
+
+        // 与 fuzz/fuzz_targets/markdown_parser.rs 相同的检查，用常见语法片段的随机组合在常规测试中覆盖
+        #[test]
+        fn prop_parse_yields_valid_tree(
+            content in r#"(#{1,4} |\n|\n\n|\|---|\| a |```|~~~|!\[a\]\(b "t"\)|\[\^1\]|\[\^1\]: |\$\$?|- |> |: |\*\*?|<div>|===|中文|text| ){0,40}"#,
+        ) {
+            let tree = MarkdownParser::new("doc-prop".to_string(), None)
+                .with_source_ranges(true)
+                .parse(&content)
+                .unwrap();
+            proptest::prop_assert!(tree.validate().is_ok(), "{:?}", tree.validate());
+            for leaf in tree.leaf_nodes() {
+                if let Some((start, end)) = leaf.metadata.source_range {
+                    proptest::prop_assert!(content.get(start..end).is_some());
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn test_empty_headings() -> Result<()> {
+        let markdown = "# 大纲\n\n## 第一部分\n\n### 1.1\n\n### 1.2\n\n## 第二部分\n\n### 2.1\n\n正文。\n\n# 附录\n";
+        let parser = MarkdownParser::new("doc-011".to_string(), None);
+        let titles = |tree: &NodeTree| -> Vec<String> {
+            let mut titles: Vec<String> = tree.nodes.values().filter_map(|n| n.title().map(str::to_string)).collect();
+            titles.sort();
+            titles
+        };
+
+        let tree = parser.parse(markdown)?;
+        assert_eq!(titles(&tree).len(), 7);
+
+        let tree = MarkdownParser::new("doc-011".to_string(), None)
+            .with_empty_headings(EmptyHeadings::Prune)
+            .parse(markdown)?;
+        tree.validate()?;
+        assert_eq!(titles(&tree), vec!["2.1", "大纲", "第二部分"]);
+
+        let tree = MarkdownParser::new("doc-011".to_string(), None)
+            .with_empty_headings(EmptyHeadings::Merge)
+            .parse(markdown)?;
+        tree.validate()?;
+        assert_eq!(titles(&tree), vec!["大纲 / 第二部分 / 2.1"]);
+        assert_eq!(tree.document_title(), Some("大纲"));
+        let leaf = tree.leaf_nodes().next().unwrap();
+        assert_eq!(leaf.text, "正文。");
+        assert_eq!(tree.nodes[&leaf.id].parent_id().and_then(|id| tree.nodes[&id].parent_id()), Some(tree.root));
+        assert_eq!(leaf.metadata.hierarchy[..4], ["Root", "大纲", "第二部分", "2.1"]);
+        Ok(())
+    }
+
+    #[test]
+    fn test_inline_image_keeps_paragraph_text() -> Result<()> {
+        let tree = MarkdownParser::new("doc-007".to_string(), None)
+            .with_source_ranges(true)
+            .parse("# 图文
+
+some text ![img](p) more text
+")?;
+
+        let leaves = tree.leaf_nodes_in_order();
+        let texts: Vec<&str> = leaves.iter().map(|l| l.text.as_str()).collect();
+        assert_eq!(texts, vec!["some text", "![img](p)", "more text"]);
+        assert_eq!(leaves[1].metadata.image_path.as_deref(), Some("p"));
+        assert!(leaves.iter().all(|l| l.metadata.source_range.is_some()));
+        Ok(())
+    }
+
+    #[test]
+    fn test_heading_level_skips() -> Result<()> {
+        let markdown = "### 前言\n\n前言正文。\n\n# 一\n\n### 一.1\n\n跳级正文。\n\n### 一.2\n\n同级正文。\n\n## 一.3\n\n二级正文。\n\n二\n==\n\n#### 二.1\n\n深层正文。\n";
+        let tree = MarkdownParser::new("doc-006".to_string(), None).parse(markdown)?;
+
+        let headings = |text: &str| -> Vec<String> {
+            let leaf = tree.leaf_nodes().find(|l| l.text == text).unwrap();
+            leaf.metadata.hierarchy.iter().filter(|h| !h.starts_with("chunk_")).cloned().collect()
+        };
+        assert_eq!(headings("前言正文。"), vec!["Root", "前言"]);
+        assert_eq!(headings("跳级正文。"), vec!["Root", "一", "一.1"]);
+        assert_eq!(headings("同级正文。"), vec!["Root", "一", "一.2"]);
+        assert_eq!(headings("二级正文。"), vec!["Root", "一", "一.3"]);
+        assert_eq!(headings("深层正文。"), vec!["Root", "二", "二.1"]);
+        Ok(())
+    }
+
+    #[test]
+    fn test_footnotes_and_definition_lists() -> Result<()> {
+        let markdown = "# 所有权\n\n借用检查器[^borrow]在编译期验证引用。\n\n[^borrow]: 见 Rust 参考手册。\n\nRust\n: 一门系统编程语言\n";
+        let tree = MarkdownParser::new("doc-008".to_string(), None).with_source_ranges(true).parse(markdown)?;
+        let leaves = tree.leaf_nodes_in_order();
+        let texts: Vec<&str> = leaves.iter().map(|l| l.text.as_str()).collect();
+
+        assert!(texts.contains(&"借用检查器[^borrow]在编译期验证引用。"));
+        let footnote = leaves.iter().find(|l| l.block_type() == "footnote").unwrap();
+        assert_eq!(footnote.text, "[^borrow]: 见 Rust 参考手册。");
+        assert!(footnote.metadata.hierarchy.contains(&"footnote_borrow".to_string()));
+        assert!(footnote.metadata.line_start.is_some());
+        // 脚注中的段落不重复输出为普通叶子
+        assert_eq!(texts.iter().filter(|t| t.contains("参考手册")).count(), 1);
+        assert!(texts.contains(&"Rust: 一门系统编程语言"));
+        Ok(())
+    }
+
+    #[test]
+    fn test_heading_slugs() -> Result<()> {
+        let markdown = "# Guide\n\n## Install\n\n安装正文。\n\n## Install\n\n重复标题正文。\n\n# 使用 说明\n\n使用正文。\n";
+        let tree = MarkdownParser::new("doc-007".to_string(), None).parse(markdown)?;
+
+        let slugs = |text: &str| {
+            let leaf = tree.leaf_nodes().find(|l| l.text == text).unwrap();
+            (leaf.metadata.slug.clone(), leaf.metadata.slug_path.clone())
+        };
+        assert_eq!(slugs("安装正文。"), (Some("install".to_string()), vec!["guide".to_string(), "install".to_string()]));
+        assert_eq!(slugs("重复标题正文。").0.as_deref(), Some("install-1"));
+        assert_eq!(slugs("使用正文。").0.as_deref(), Some("%E4%BD%BF%E7%94%A8-%E8%AF%B4%E6%98%8E"));
+
+        let headings: Vec<_> = tree.nodes.values().filter_map(|n| n.title().map(|_| n.metadata().slug.clone())).collect();
+        assert!(headings.iter().all(|slug| slug.is_some()));
+        assert_eq!(tree.nodes[&tree.root].metadata().slug, None);
+        Ok(())
+    }
+
+
+    #[test]
+    fn test_display_options() -> Result<()> {
+        let text = "长".repeat(600);
+        let tree = MarkdownParser::new("doc-006".to_string(), None).parse(&format!("# 标题\n\n{}\n", text))?;
+        let leaf = tree.nodes.values().find(|n| n.as_leaf().is_some()).unwrap();
+
+        // 默认保留 500 个字符
+        assert_eq!(leaf.to_string(), format!("📄 {}...", "长".repeat(SNIPPET_MAX_CHARS)));
+        let short = DisplayOptions::default().with_preview_chars(10);
+        assert_eq!(leaf.display_with(short).to_string(), format!("📄 {}...", "长".repeat(10)));
+        assert!(tree.display_with(short).to_string().contains(&format!(" {}...\n", "长".repeat(10))));
+
+        let tokens = leaf.as_leaf().unwrap().metadata.chunk_size.unwrap();
+        let counted = tree.display_with(DisplayOptions::default().with_token_count(true)).to_string();
+        assert!(counted.contains(&format!("({} tokens, 600 字符)", tokens)));
+        assert!(!counted.contains("长长"));
+        Ok(())
+    }
 }
@@ -1,10 +1,19 @@
 pub mod markdown_bulid;
 
 use anyhow::{Result, anyhow};
+use serde::de::{self, Deserializer, MapAccess, Visitor};
 use serde::{Deserialize, Serialize};
 use std::collections::HashMap;
+use std::fmt;
 use uuid::Uuid;
 
+use crate::slug::Slugger;
+use crate::tiktoken::count_tokens;
+use crate::tree_structrue::markdown_bulid::DEFAULT_TOKEN_MODEL;
+
+/// 文档摘要叶子在 hierarchy 中的标签
+pub const SUMMARY_LABEL: &str = "summary";
+
 pub type NodeId = Uuid;
 pub type ParentId = Option<NodeId>;
 pub type ChildrenIds = Vec<NodeId>;
@@ -31,12 +40,56 @@ pub struct NodeMetadata {
     pub document_id: String,
     pub hierarchy: Vec<String>,
     pub node_type: NodeType,
+    /// 叶子文本的 token 数（按解析器配置的模型经 tiktoken 计算）
     pub chunk_size: Option<usize>,
+    /// 叶子文本的字符数
+    pub char_len: Option<usize>,
     pub file_name: Option<String>,
     
+    /// 图片替代文本，即 `![alt](path)` 中的 alt
     pub image_alt: Option<String>,
+    /// 图片标题，即 `![alt](path "title")` 中的 title
+    pub image_title: Option<String>,
     pub image_path: Option<String>,
     pub image_id: Option<String>,
+
+    /// 叶子在原始 markdown 中的字节范围 [start, end)
+    pub source_range: Option<(usize, usize)>,
+
+    /// 源文件路径（加载器传入的路径），与 `line_start` / `line_end` 一起用于引用跳转
+    #[serde(default)]
+    pub file_path: Option<String>,
+    /// 叶子在源文件中的起始行号（从 1 开始），由 [`NodeTree::assign_provenance`] 根据 `source_range` 计算
+    #[serde(default)]
+    pub line_start: Option<usize>,
+    /// 叶子在源文件中的结束行号（含）
+    #[serde(default)]
+    pub line_end: Option<usize>,
+
+    /// 叶子超过切分上限但按策略保持完整（如大表格、长代码块），见 [`crate::leaf_splitting::SplitPolicy`]
+    #[serde(default)]
+    pub oversized: bool,
+
+    /// 标题节点的锚点（GitHub 风格，见 [`crate::slug::slugify`]），文档内唯一；叶子为所在最近一级标题的锚点，
+    /// 与文件名拼接即可链接到渲染后的章节，如 `guide.html#安装`。由 [`NodeTree::assign_slugs`] 写入
+    #[serde(default)]
+    pub slug: Option<String>,
+    /// 从顶层标题到该节点（含）所经过的各级标题锚点
+    #[serde(default)]
+    pub slug_path: Vec<String>,
+
+    /// 叶子在文档内的全局序号，段落、代码、表格、图片、公式等各类叶子共用一个计数器；
+    /// 切分出的子叶子沿用原叶子的序号
+    #[serde(default)]
+    pub chunk_index: Option<usize>,
+
+    /// 叶子在文档中的阅读顺序（从 0 开始），由 [`NodeTree::assign_leaf_order`] 按树结构写入，
+    /// 用于将检索结果重新排回原文顺序
+    #[serde(default)]
+    pub order: Option<i64>,
+
+    /// 句子窗口：句子级叶子所属段落的完整文本，检索命中后返回该窗口用于生成
+    pub window: Option<String>,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -50,6 +103,21 @@ pub enum Node {
 pub struct RootNode {
     pub id: NodeId,
     pub document_id: String,
+    /// 文档标题，解析后取自第一个顶层标题
+    #[serde(default)]
+    pub title: Option<String>,
+    /// 文档作者，取自源文件自带的元数据（如 PDF 信息字典）
+    #[serde(default)]
+    pub author: Option<String>,
+    /// 计算叶子 token 数（`chunk_size`）所用的 tiktoken 模型，由解析器 / 加载器写入
+    #[serde(default)]
+    pub token_model: Option<String>,
+    /// 生成叶子的分块方式（如 `"markdown"`、`"plain_text"`），由解析器 / 加载器写入
+    #[serde(default)]
+    pub chunker: Option<String>,
+    /// 分块时的 token 上限，未按 token 切分时为 `None`
+    #[serde(default)]
+    pub max_tokens: Option<usize>,
     pub relationships: HashMap<NodeRelationship, Vec<NodeId>>,
     pub metadata: NodeMetadata,
 }
@@ -80,16 +148,33 @@ impl Node {
         Node::Root(RootNode {
             id,
             document_id: document_id.clone(),
+            title: None,
+            author: None,
+            token_model: None,
+            chunker: None,
+            max_tokens: None,
             relationships,
             metadata: NodeMetadata {
                 document_id,
                 hierarchy: vec!["Root".to_string()],
                 node_type: NodeType::Root,
                 chunk_size: None,
+                char_len: None,
                 file_name,
                 image_alt: None,
+                image_title: None,
                 image_path: None,
                 image_id: None,
+                source_range: None,
+                order: None,
+                window: None,
+                file_path: None,
+                line_start: None,
+                line_end: None,
+                oversized: false,
+                slug: None,
+                slug_path: Vec::new(),
+                chunk_index: None,
             },
         })
     }
@@ -113,10 +198,22 @@ impl Node {
                 hierarchy,
                 node_type: NodeType::Intermediate,
                 chunk_size: None,
+                char_len: None,
                 file_name: None,
                 image_alt: None,
+                image_title: None,
                 image_path: None,
                 image_id: None,
+                source_range: None,
+                order: None,
+                window: None,
+                file_path: None,
+                line_start: None,
+                line_end: None,
+                oversized: false,
+                slug: None,
+                slug_path: Vec::new(),
+                chunk_index: None,
             },
         })
     }
@@ -137,6 +234,7 @@ impl Node {
         let mut relationships = HashMap::new();
         relationships.insert(NodeRelationship::Parent, vec![parent_id]);
 
+        let char_len = text.chars().count();
         let mut hier = hierarchy;
         hier.push(format!("chunk_{}_{}", chunk_index, chunk_size));
 
@@ -150,10 +248,22 @@ impl Node {
                 hierarchy: hier,
                 node_type: NodeType::Leaf,
                 chunk_size: Some(chunk_size),
+                char_len: Some(char_len),
                 file_name,
                 image_alt,
+                image_title: None,
                 image_path,
                 image_id,
+                source_range: None,
+                order: None,
+                window: None,
+                file_path: None,
+                line_start: None,
+                line_end: None,
+                oversized: false,
+                slug: None,
+                slug_path: Vec::new(),
+                chunk_index: Some(chunk_index),
             },
         })
     }
@@ -273,12 +383,61 @@ impl Node {
     }
 }
 
+/// 独立成叶的块级内容在层级路径中的标签前缀及对应的内容类型
+const BLOCK_LABELS: [(&str, &str); 6] = [
+    ("img_", "image"),
+    ("table_", "table"),
+    ("code_", "code"),
+    ("math_", "math"),
+    ("footnote_", "footnote"),
+    (SUMMARY_LABEL, "summary"),
+];
+
+impl LeafNode {
+    /// 叶子的内容类型，由层级标签推断：`image` / `table` / `code` / `math` / `footnote` / `summary`，其余为 `text`
+    pub fn block_type(&self) -> &'static str {
+        self.metadata.hierarchy.iter()
+            .find_map(|label| BLOCK_LABELS.iter().find(|(prefix, _)| label.starts_with(prefix)))
+            .map_or("text", |(_, block_type)| block_type)
+    }
+}
+
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct NodeTree {
+    /// 反序列化时重复的 id 报错，见 [`deserialize_nodes`]
+    #[serde(deserialize_with = "deserialize_nodes")]
     pub nodes: HashMap<NodeId, Node>,
     pub root: NodeId,
 }
 
+/// 反序列化 `nodes`：重复的 id 或与节点自身 id 不一致的键直接报错，而不是静默覆盖
+fn deserialize_nodes<'de, D: Deserializer<'de>>(deserializer: D) -> Result<HashMap<NodeId, Node>, D::Error> {
+    struct NodesVisitor;
+
+    impl<'de> Visitor<'de> for NodesVisitor {
+        type Value = HashMap<NodeId, Node>;
+
+        fn expecting(&self, f: &mut fmt::Formatter) -> fmt::Result {
+            f.write_str("a map from node id to node")
+        }
+
+        fn visit_map<A: MapAccess<'de>>(self, mut map: A) -> Result<Self::Value, A::Error> {
+            let mut nodes = HashMap::with_capacity(map.size_hint().unwrap_or(0));
+            while let Some((id, node)) = map.next_entry::<NodeId, Node>()? {
+                if node.id() != id {
+                    return Err(de::Error::custom(format!("Node key {} does not match node id {}", id, node.id())));
+                }
+                if nodes.insert(id, node).is_some() {
+                    return Err(de::Error::custom(format!("Duplicate node id {}", id)));
+                }
+            }
+            Ok(nodes)
+        }
+    }
+
+    deserializer.deserialize_map(NodesVisitor)
+}
+
 impl NodeTree {
     pub fn new(root: Node) -> Self {
         let root_id = root.id();
@@ -287,8 +446,17 @@ impl NodeTree {
         Self { nodes, root: root_id }
     }
 
-    /// 添加节点，自动维护双向关系 + prev/next
+    /// id 已存在时报错，避免覆盖已有节点导致其子节点成为孤儿
+    fn ensure_new_id(&self, id: NodeId) -> Result<()> {
+        if self.nodes.contains_key(&id) {
+            return Err(anyhow!("Duplicate node id {}", id));
+        }
+        Ok(())
+    }
+
+    /// 添加节点，自动维护双向关系 + prev/next；id 已存在时报错且不修改树
     pub fn add_node(&mut self, mut child_node: Node) -> Result<()> {
+        self.ensure_new_id(child_node.id())?;
         let parent_id = child_node.parent_id()
             .ok_or_else(|| anyhow!("Node must have a parent"))?;
 
@@ -311,10 +479,380 @@ impl NodeTree {
         Ok(())
     }
 
+    /// 将另一棵树（如并行解析的文档片段）合并到 `under` 节点下
+    ///
+    /// `other` 根节点的子节点被重新挂到 `under` 下，其余节点保持原有 id 与关系，
+    /// 拼接处的 prev/next 重新链接。存在 id 冲突时报错且不修改 `self`。
+    pub fn merge(&mut self, other: NodeTree, under: NodeId) -> Result<()> {
+        if !self.nodes.contains_key(&under) {
+            return Err(anyhow!("Merge target node {} not found", under));
+        }
+        for &id in other.nodes.keys().filter(|&&id| id != other.root) {
+            self.ensure_new_id(id).map_err(|_| anyhow!("Node id collision while merging: {}", id))?;
+        }
+
+        let NodeTree { mut nodes, root } = other;
+        let other_root = nodes.remove(&root)
+            .ok_or_else(|| anyhow!("Root node {} not found in merged tree", root))?;
+        let new_children = other_root.children().to_vec();
+
+        // 1. 重新设置父节点
+        for child_id in &new_children {
+            if let Some(child) = nodes.get_mut(child_id) {
+                child.relationships_mut().insert(NodeRelationship::Parent, vec![under]);
+            }
+        }
+
+        // 2. 拼接处维护 prev/next
+        let last_existing = self.nodes.get(&under).and_then(|n| n.children().last().copied());
+        if let (Some(last_id), Some(&first_id)) = (last_existing, new_children.first()) {
+            if let Some(last) = self.nodes.get_mut(&last_id) {
+                last.set_next(Some(first_id));
+            }
+            if let Some(first) = nodes.get_mut(&first_id) {
+                first.set_previous(Some(last_id));
+            }
+        }
+
+        // 3. 挂载并插入
+        if let Some(target) = self.nodes.get_mut(&under) {
+            target.children_mut().extend(new_children);
+        }
+        self.nodes.extend(nodes);
+        self.assign_leaf_order();
+        Ok(())
+    }
+
+    /// 用 `replacements`（须为挂在同一父节点下的叶子）原位替换叶子 `leaf_id`，维护父子关系与 prev/next
+    pub fn replace_leaf(&mut self, leaf_id: NodeId, mut replacements: Vec<Node>) -> Result<()> {
+        let old = self.nodes.get(&leaf_id)
+            .filter(|n| n.is_leaf())
+            .ok_or_else(|| anyhow!("Leaf node with id {} not found", leaf_id))?;
+        let parent_id = old.parent_id()
+            .ok_or_else(|| anyhow!("Leaf node {} has no parent", leaf_id))?;
+        let (prev, next) = (old.prev_id(), old.next_id());
+        if replacements.is_empty() {
+            return Err(anyhow!("Replacement list for {} is empty", leaf_id));
+        }
+        if replacements.iter().any(|n| !n.is_leaf() || n.parent_id() != Some(parent_id)) {
+            return Err(anyhow!("Replacements must be leaves under parent {}", parent_id));
+        }
+        for node in replacements.iter().filter(|n| n.id() != leaf_id) {
+            self.ensure_new_id(node.id())?;
+        }
+
+        // 1. 新叶子之间及与两侧邻居的 prev/next
+        let ids: Vec<NodeId> = replacements.iter().map(|n| n.id()).collect();
+        for (i, node) in replacements.iter_mut().enumerate() {
+            node.set_previous(if i == 0 { prev } else { Some(ids[i - 1]) });
+            node.set_next(ids.get(i + 1).copied().or(next));
+        }
+        if let Some(prev_node) = prev.and_then(|id| self.nodes.get_mut(&id)) {
+            prev_node.set_next(ids.first().copied());
+        }
+        if let Some(next_node) = next.and_then(|id| self.nodes.get_mut(&id)) {
+            next_node.set_previous(ids.last().copied());
+        }
+
+        // 2. 父节点中原位替换
+        if let Some(parent) = self.nodes.get_mut(&parent_id) {
+            let children = parent.children_mut();
+            if let Some(pos) = children.iter().position(|&id| id == leaf_id) {
+                children.splice(pos..=pos, ids.iter().copied());
+            }
+        }
+
+        // 3. 替换节点
+        self.nodes.remove(&leaf_id);
+        self.nodes.extend(replacements.into_iter().map(|n| (n.id(), n)));
+        Ok(())
+    }
+
+    /// 文档标题（见 [`extract_document_title`](Self::extract_document_title)）
+    pub fn document_title(&self) -> Option<&str> {
+        match self.nodes.get(&self.root) {
+            Some(Node::Root(root)) => root.title.as_deref(),
+            _ => None,
+        }
+    }
+
+    /// 文档作者，见 [`RootNode::author`]
+    pub fn document_author(&self) -> Option<&str> {
+        match self.nodes.get(&self.root) {
+            Some(Node::Root(root)) => root.author.as_deref(),
+            _ => None,
+        }
+    }
+
+    /// 叶子 token 数所用的模型，见 [`RootNode::token_model`]
+    pub fn token_model(&self) -> Option<&str> {
+        match self.nodes.get(&self.root) {
+            Some(Node::Root(root)) => root.token_model.as_deref(),
+            _ => None,
+        }
+    }
+
+    pub fn set_token_model(&mut self, model: impl Into<String>) {
+        if let Some(Node::Root(root)) = self.nodes.get_mut(&self.root) {
+            root.token_model = Some(model.into());
+        }
+    }
+
+    /// 生成叶子的分块方式，见 [`RootNode::chunker`]
+    pub fn chunker(&self) -> Option<&str> {
+        match self.nodes.get(&self.root) {
+            Some(Node::Root(root)) => root.chunker.as_deref(),
+            _ => None,
+        }
+    }
+
+    /// 分块时的 token 上限，见 [`RootNode::max_tokens`]
+    pub fn max_tokens(&self) -> Option<usize> {
+        match self.nodes.get(&self.root) {
+            Some(Node::Root(root)) => root.max_tokens,
+            _ => None,
+        }
+    }
+
+    pub fn set_chunker(&mut self, chunker: impl Into<String>, max_tokens: Option<usize>) {
+        if let Some(Node::Root(root)) = self.nodes.get_mut(&self.root) {
+            root.chunker = Some(chunker.into());
+            root.max_tokens = max_tokens;
+        }
+    }
+
+    /// 以第一个顶层标题作为文档标题写入根节点，返回该标题
+    pub fn extract_document_title(&mut self) -> Option<String> {
+        let title = self.nodes.get(&self.root)?
+            .children()
+            .iter()
+            .filter_map(|id| self.nodes.get(id))
+            .find_map(|node| node.title())
+            .map(str::to_string);
+
+        if let Some(Node::Root(root)) = self.nodes.get_mut(&self.root) {
+            root.title = title.clone();
+        }
+        title
+    }
+
+    /// 根节点下的文档摘要叶子
+    pub fn summary_leaf(&self) -> Option<&LeafNode> {
+        self.nodes.get(&self.root)?
+            .children()
+            .iter()
+            .filter_map(|id| self.nodes.get(id)?.as_leaf())
+            .find(|leaf| leaf.metadata.hierarchy.get(1).is_some_and(|h| h == SUMMARY_LABEL))
+    }
+
+    /// 在根节点下挂载（或替换）文档摘要叶子，位于所有子节点之前，返回其 id
+    ///
+    /// 摘要叶子的 hierarchy 为 `["Root", "summary", "chunk_0_{tokens}"]`，作为文档级表示参与检索。
+    pub fn attach_summary(&mut self, summary: String) -> Result<NodeId> {
+        let chunk_size = count_tokens(&summary, self.token_model().unwrap_or(DEFAULT_TOKEN_MODEL));
+        if let Some(id) = self.summary_leaf().map(|leaf| leaf.id) {
+            let leaf = self.nodes.get_mut(&id).and_then(Node::as_leaf_mut)
+                .ok_or_else(|| anyhow!("Summary leaf {} not found", id))?;
+            leaf.metadata.chunk_size = Some(chunk_size);
+            leaf.metadata.char_len = Some(summary.chars().count());
+            leaf.metadata.hierarchy = vec!["Root".to_string(), SUMMARY_LABEL.to_string(), format!("chunk_0_{}", chunk_size)];
+            leaf.text = summary;
+            leaf.embedding = None;
+            return Ok(id);
+        }
+
+        let root = self.nodes.get(&self.root)
+            .ok_or_else(|| anyhow!("Root node {} not found", self.root))?;
+        let first = root.children().first().copied();
+        let mut leaf = Node::new_leaf(
+            self.root,
+            summary,
+            chunk_size,
+            0,
+            vec!["Root".to_string(), SUMMARY_LABEL.to_string()],
+            root.metadata().document_id.clone(),
+            None,
+            None,
+            None,
+            root.metadata().file_name.clone(),
+        );
+        let id = leaf.id();
+
+        leaf.set_next(first);
+        if let Some(first_node) = first.and_then(|f| self.nodes.get_mut(&f)) {
+            first_node.set_previous(Some(id));
+        }
+        if let Some(root) = self.nodes.get_mut(&self.root) {
+            root.children_mut().insert(0, id);
+        }
+        self.nodes.insert(id, leaf);
+        self.assign_leaf_order();
+        Ok(id)
+    }
+
+    /// 按子节点列表的顺序重写 `parent_id` 下各子节点的 prev/next
+    fn relink_children(&mut self, parent_id: NodeId) {
+        let children = self.nodes.get(&parent_id).map(|n| n.children().to_vec()).unwrap_or_default();
+        for (i, id) in children.iter().enumerate() {
+            if let Some(node) = self.nodes.get_mut(id) {
+                node.set_previous(i.checked_sub(1).map(|prev| children[prev]));
+                node.set_next(children.get(i + 1).copied());
+            }
+        }
+    }
+
+    /// 子树中是否有叶子
+    fn has_leaf_descendant(&self, id: NodeId) -> bool {
+        let mut stack = vec![id];
+        while let Some(id) = stack.pop() {
+            let Some(node) = self.nodes.get(&id) else { continue };
+            if node.is_leaf() {
+                return true;
+            }
+            stack.extend(node.children());
+        }
+        false
+    }
+
+    /// 删除子树中没有叶子的中间节点（如大纲中只有标题的章节），返回删除的节点数
+    pub fn prune_empty_sections(&mut self) -> usize {
+        let empty: Vec<NodeId> = self.nodes.values()
+            .filter(|node| matches!(node, Node::Intermediate(_)))
+            .map(|node| node.id())
+            .filter(|&id| !self.has_leaf_descendant(id))
+            .collect();
+        if empty.is_empty() {
+            return 0;
+        }
+
+        let mut parents = Vec::new();
+        for id in &empty {
+            if let Some(node) = self.nodes.remove(id)
+                && let Some(parent_id) = node.parent_id()
+                && let Some(parent) = self.nodes.get_mut(&parent_id)
+            {
+                parent.children_mut().retain(|child| child != id);
+                parents.push(parent_id);
+            }
+        }
+        for parent_id in parents {
+            self.relink_children(parent_id);
+        }
+        empty.len()
+    }
+
+    /// 将没有直接内容、只有一个子章节的中间节点并入该子章节，返回合并的次数
+    ///
+    /// 子章节替代父节点的位置，标题改为 `"父标题 / 子标题"`；连续多级的空标题逐级合并。
+    /// 叶子的 `hierarchy` 保持不变。
+    pub fn merge_single_child_sections(&mut self) -> usize {
+        let mut merged = 0;
+        loop {
+            let candidate = self.nodes.values().find_map(|node| match node {
+                Node::Intermediate(inter) => match node.children() {
+                    [child] if matches!(self.nodes.get(child), Some(Node::Intermediate(_))) => {
+                        Some((inter.id, *child, node.parent_id()?))
+                    }
+                    _ => None,
+                },
+                _ => None,
+            });
+            let Some((parent_id, child_id, grandparent_id)) = candidate else { break };
+
+            let Some(Node::Intermediate(parent)) = self.nodes.remove(&parent_id) else { break };
+            if let Some(Node::Intermediate(child)) = self.nodes.get_mut(&child_id) {
+                child.title = match (parent.title, child.title.take()) {
+                    (Some(outer), Some(inner)) => Some(format!("{} / {}", outer, inner)),
+                    (outer, inner) => outer.or(inner),
+                };
+                child.relationships.insert(NodeRelationship::Parent, vec![grandparent_id]);
+            }
+            if let Some(grandparent) = self.nodes.get_mut(&grandparent_id) {
+                for id in grandparent.children_mut().iter_mut().filter(|id| **id == parent_id) {
+                    *id = child_id;
+                }
+            }
+            self.relink_children(grandparent_id);
+            merged += 1;
+        }
+        merged
+    }
+
     pub fn leaf_nodes(&self) -> impl Iterator<Item = &LeafNode> {
         self.nodes.values().filter_map(|node| node.as_leaf())
     }
 
+    /// 按文档顺序（深度优先）返回叶子节点
+    pub fn leaf_nodes_in_order(&self) -> Vec<&LeafNode> {
+        let mut leaves = Vec::new();
+        let mut stack = vec![self.root];
+        while let Some(id) = stack.pop() {
+            let Some(node) = self.nodes.get(&id) else { continue };
+            if let Some(leaf) = node.as_leaf() {
+                leaves.push(leaf);
+            }
+            stack.extend(node.children().iter().rev());
+        }
+        leaves
+    }
+
+    /// 按 [`leaf_nodes_in_order`](Self::leaf_nodes_in_order) 的顺序为所有叶子写入 `metadata.order`
+    ///
+    /// 解析器在建树完成后调用；`merge`、`attach_summary` 会自动重排，
+    /// 直接调用 `add_node` / `replace_leaf` 修改结构后需手动调用。
+    pub fn assign_leaf_order(&mut self) {
+        let ids: Vec<NodeId> = self.leaf_nodes_in_order().iter().map(|leaf| leaf.id).collect();
+        for (order, id) in ids.into_iter().enumerate() {
+            if let Some(node) = self.nodes.get_mut(&id) {
+                node.metadata_mut().order = Some(order as i64);
+            }
+        }
+    }
+
+    /// 写入来源信息：所有节点的 `file_path`，以及有 `source_range` 的叶子在 `source` 中的行号范围
+    ///
+    /// `source` 须为解析时的原文，`source_range` 为其中的字节偏移。
+    pub fn assign_provenance(&mut self, file_path: Option<&str>, source: &str) {
+        let newlines: Vec<usize> = source.match_indices('\n').map(|(i, _)| i).collect();
+        // 偏移之前的换行数 + 1 即为行号
+        let line_at = |offset: usize| newlines.partition_point(|&n| n < offset) + 1;
+
+        for node in self.nodes.values_mut() {
+            let metadata = node.metadata_mut();
+            if file_path.is_some() {
+                metadata.file_path = file_path.map(str::to_string);
+            }
+            if let Some((start, end)) = metadata.source_range {
+                metadata.line_start = Some(line_at(start));
+                // 结尾的换行不计入下一行
+                metadata.line_end = Some(line_at(end.saturating_sub(1).max(start)));
+            }
+        }
+    }
+
+    /// 按文档顺序为标题节点生成锚点，重复标题追加数字后缀；叶子继承最近一级标题的锚点与锚点路径
+    ///
+    /// 解析器在建树完成后调用；修改结构后（如 `merge`）可再次调用以重新生成。
+    pub fn assign_slugs(&mut self) {
+        let mut slugger = Slugger::new();
+        // (节点, 父节点的锚点路径)
+        let mut stack = vec![(self.root, Vec::new())];
+        while let Some((id, parent_path)) = stack.pop() {
+            let Some(node) = self.nodes.get_mut(&id) else { continue };
+            let mut path: Vec<String> = parent_path;
+            if let Node::Intermediate(inter) = node
+                && let Some(title) = inter.title.as_deref()
+            {
+                path.push(slugger.slug(title));
+            }
+
+            let metadata = node.metadata_mut();
+            metadata.slug = path.last().cloned();
+            metadata.slug_path = path.clone();
+            stack.extend(node.children().iter().rev().map(|&child| (child, path.clone())));
+        }
+    }
+
     // 获取节点的路径
     pub fn get_ancestors(&self, mut node_id: NodeId) -> Vec<&Node> {
         let mut path = Vec::new();
@@ -332,6 +870,65 @@ impl NodeTree {
         path
     }
 
+    /// 检查树结构的一致性，返回发现的第一个问题：
+    ///
+    /// - 根节点存在、类型为根且没有父节点；其余节点都能从根节点到达，且只出现在一个父节点的子节点列表中
+    /// - 子节点的父节点指向所在的父节点，兄弟之间的 prev/next 与子节点列表顺序一致
+    /// - 叶子没有子节点，`source_range` 的起点不大于终点
+    pub fn validate(&self) -> Result<()> {
+        match self.nodes.get(&self.root) {
+            Some(Node::Root(root)) if root.id == self.root => {}
+            Some(_) => return Err(anyhow!("Root node {} is not a root", self.root)),
+            None => return Err(anyhow!("Root node {} not found", self.root)),
+        }
+        if self.nodes[&self.root].parent_id().is_some() {
+            return Err(anyhow!("Root node {} has a parent", self.root));
+        }
+
+        let mut visited = std::collections::HashSet::from([self.root]);
+        let mut stack = vec![self.root];
+        while let Some(id) = stack.pop() {
+            let node = &self.nodes[&id];
+            if node.is_leaf() && !node.children().is_empty() {
+                return Err(anyhow!("Leaf node {} has children", id));
+            }
+            if let Some((start, end)) = node.metadata().source_range
+                && start > end
+            {
+                return Err(anyhow!("Node {} has an invalid source range {}..{}", id, start, end));
+            }
+
+            let children = node.children();
+            for (i, &child_id) in children.iter().enumerate() {
+                let child = self.nodes.get(&child_id)
+                    .ok_or_else(|| anyhow!("Child node {} of {} not found", child_id, id))?;
+                if child.id() != child_id {
+                    return Err(anyhow!("Node stored under {} has id {}", child_id, child.id()));
+                }
+                if matches!(child, Node::Root(_)) {
+                    return Err(anyhow!("Root node {} appears as a child of {}", child_id, id));
+                }
+                if child.parent_id() != Some(id) {
+                    return Err(anyhow!("Node {} is listed under {} but its parent is {:?}", child_id, id, child.parent_id()));
+                }
+                let prev = i.checked_sub(1).map(|p| children[p]);
+                let next = children.get(i + 1).copied();
+                if child.prev_id() != prev || child.next_id() != next {
+                    return Err(anyhow!("Sibling links of node {} do not match the children of {}", child_id, id));
+                }
+                if !visited.insert(child_id) {
+                    return Err(anyhow!("Node {} is reachable more than once", child_id));
+                }
+                stack.push(child_id);
+            }
+        }
+
+        if visited.len() != self.nodes.len() {
+            return Err(anyhow!("{} node(s) are not reachable from the root", self.nodes.len() - visited.len()));
+        }
+        Ok(())
+    }
+
     pub fn set_leaf_embedding(&mut self, leaf_id: NodeId, embedding: Vec<f32>) -> Result<()> {
         if let Some(Node::Leaf(leaf)) = self.nodes.get_mut(&leaf_id) {
             leaf.embedding = Some(embedding);
@@ -342,3 +939,127 @@ impl NodeTree {
     }
 }
 
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::tree_structrue::markdown_bulid::MarkdownParser;
+
+    fn section(title: &str, text: &str) -> Result<NodeTree> {
+        MarkdownParser::new("doc-001".to_string(), None).parse(&format!("# {}\n\n{}\n", title, text))
+    }
+
+    #[test]
+    fn test_merge() -> Result<()> {
+        let mut tree = section("第一章", "第一章内容")?;
+        let other = section("第二章", "第二章内容")?;
+        let other_len = other.nodes.len();
+        let len = tree.nodes.len();
+
+        let root = tree.root;
+        tree.merge(other, root)?;
+        assert_eq!(tree.nodes.len(), len + other_len - 1);
+
+        let chapters: Vec<NodeId> = tree.nodes[&root].children().to_vec();
+        assert_eq!(chapters.len(), 2);
+        let (first, second) = (&tree.nodes[&chapters[0]], &tree.nodes[&chapters[1]]);
+        assert_eq!(first.title(), Some("第一章"));
+        assert_eq!(second.title(), Some("第二章"));
+        assert_eq!(first.next_id(), Some(second.id()));
+        assert_eq!(second.prev_id(), Some(first.id()));
+        assert_eq!(second.parent_id(), Some(root));
+
+        // 合并进来的叶子仍挂在原来的章节下
+        let leaf = second.children()[0];
+        assert_eq!(tree.nodes[&leaf].parent_id(), Some(second.id()));
+        assert_eq!(tree.leaf_nodes().count(), 2);
+        Ok(())
+    }
+
+    #[test]
+    fn test_validate() -> Result<()> {
+        let tree = section("第一章", "第一段\n\n第二段")?;
+        tree.validate()?;
+        let chapter = tree.nodes[&tree.root].children()[0];
+        let leaves = tree.nodes[&chapter].children().to_vec();
+
+        // 子节点列表中丢失的节点无法从根节点到达
+        let mut broken = tree.clone();
+        broken.nodes.get_mut(&chapter).unwrap().children_mut().retain(|&id| id != leaves[1]);
+        broken.nodes.get_mut(&leaves[0]).unwrap().set_next(None);
+        assert!(broken.validate().unwrap_err().to_string().contains("not reachable"));
+
+        // prev/next 与子节点顺序不一致
+        let mut broken = tree.clone();
+        broken.nodes.get_mut(&leaves[1]).unwrap().set_previous(None);
+        assert!(broken.validate().is_err());
+
+        // 父节点指针错误
+        let mut broken = tree.clone();
+        broken.nodes.get_mut(&leaves[0]).unwrap().relationships_mut().insert(NodeRelationship::Parent, vec![tree.root]);
+        assert!(broken.validate().is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn test_merge_rejects_id_collision() -> Result<()> {
+        let mut tree = section("第一章", "内容")?;
+        let copy = tree.clone();
+        let len = tree.nodes.len();
+
+        let root = tree.root;
+        assert!(tree.merge(copy, root).is_err());
+        assert_eq!(tree.nodes.len(), len);
+        Ok(())
+    }
+
+    #[test]
+    fn test_add_node_rejects_duplicate_id() -> Result<()> {
+        let mut tree = section("第一章", "内容")?;
+        let leaf_id = tree.leaf_nodes().next().unwrap().id;
+        let leaf = tree.nodes[&leaf_id].clone();
+        let parent_id = leaf.parent_id().unwrap();
+        let children = tree.nodes[&parent_id].children().to_vec();
+
+        let err = tree.add_node(leaf.clone()).unwrap_err();
+        assert_eq!(err.to_string(), format!("Duplicate node id {}", leaf_id));
+        assert_eq!(tree.nodes[&parent_id].children(), children.as_slice());
+
+        // 反序列化时重复的键同样报错
+        let node_json = serde_json::to_string(&leaf)?;
+        let json = serde_json::to_string(&tree)?.replacen(
+            "\"nodes\":{",
+            &format!("\"nodes\":{{\"{}\":{},", leaf_id, node_json),
+            1,
+        );
+        let err = serde_json::from_str::<NodeTree>(&json).unwrap_err();
+        assert!(err.to_string().contains("Duplicate node id"));
+        assert!(serde_json::from_str::<NodeTree>(&serde_json::to_string(&tree)?).is_ok());
+        Ok(())
+    }
+
+    #[test]
+    fn test_document_title_and_summary() -> Result<()> {
+        let mut tree = MarkdownParser::new("doc-001".to_string(), None)
+            .parse("前言段落。\n\n# 报告标题\n\n正文。\n\n# 附录\n\n附录正文。\n")?;
+        assert_eq!(tree.document_title(), Some("报告标题"));
+
+        let id = tree.attach_summary("这是一份报告。".to_string())?;
+        let order: Vec<&str> = tree.leaf_nodes_in_order().iter().map(|l| l.text.as_str()).collect();
+        assert_eq!(order, vec!["这是一份报告。", "前言段落。", "正文。", "附录正文。"]);
+        // 阅读顺序随摘要插入重新编号
+        let orders: Vec<Option<i64>> = tree.leaf_nodes_in_order().iter().map(|l| l.metadata.order).collect();
+        assert_eq!(orders, vec![Some(0), Some(1), Some(2), Some(3)]);
+
+        let first = tree.nodes[&tree.root].children()[1];
+        assert_eq!(tree.nodes[&id].next_id(), Some(first));
+        assert_eq!(tree.nodes[&first].prev_id(), Some(id));
+
+        // 再次挂载替换原摘要
+        assert_eq!(tree.attach_summary("新摘要。".to_string())?, id);
+        let summary = tree.summary_leaf().unwrap();
+        assert_eq!(summary.text, "新摘要。");
+        assert_eq!(summary.metadata.hierarchy[1], SUMMARY_LABEL);
+        assert_eq!(tree.leaf_nodes().count(), 4);
+        Ok(())
+    }
+}
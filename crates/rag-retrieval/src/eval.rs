@@ -0,0 +1,151 @@
+use std::fs::OpenOptions;
+use std::io::{BufWriter, Write};
+use std::path::Path;
+use std::sync::Mutex;
+
+use anyhow::{Result, anyhow};
+use chrono::{DateTime, Utc};
+use rag_embeddings::{client::EmbeddingClient, database::{SearchResult, VectorStore}};
+use serde::{Deserialize, Serialize};
+use serde_json::Value as JsonValue;
+
+use crate::Retriever;
+
+/// 单条检索结果的评估记录
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct LoggedHit {
+    pub rank: usize,
+    pub id: String,
+    pub score: f32,
+    pub document_id: Option<String>,
+    pub hierarchy: Vec<String>,
+}
+
+/// 一次检索的评估记录（JSON lines 中的一行）
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct RetrievalLogEntry {
+    pub timestamp: DateTime<Utc>,
+    pub query: String,
+    pub top_k: usize,
+    pub elapsed_ms: f64,
+    pub results: Vec<LoggedHit>,
+}
+
+impl RetrievalLogEntry {
+    pub fn new(query: &str, top_k: usize, elapsed_ms: f64, hits: &[SearchResult]) -> Self {
+        let results = hits.iter()
+            .map(|SearchResult { rank, score, record }| LoggedHit {
+                rank: *rank,
+                id: record.id.clone(),
+                score: *score,
+                document_id: record.metadata.get("document_id")
+                    .and_then(|v| v.as_str())
+                    .map(|s| s.to_string()),
+                hierarchy: record.metadata.get("hierarchy")
+                    .and_then(|v| serde_json::from_value(v.clone()).ok())
+                    .unwrap_or_default(),
+            })
+            .collect();
+
+        Self {
+            timestamp: Utc::now(),
+            query: query.to_string(),
+            top_k,
+            elapsed_ms,
+            results,
+        }
+    }
+}
+
+/// 检索评估日志，每次检索写入一行 JSON，便于离线计算 recall@k 等指标
+pub struct EvalLog {
+    writer: Mutex<Box<dyn Write + Send>>,
+}
+
+impl EvalLog {
+    /// 以追加模式写入文件
+    pub fn create(path: impl AsRef<Path>) -> Result<Self> {
+        let file = OpenOptions::new().create(true).append(true).open(path)?;
+        Ok(Self::from_writer(BufWriter::new(file)))
+    }
+
+    pub fn from_writer(writer: impl Write + Send + 'static) -> Self {
+        Self { writer: Mutex::new(Box::new(writer)) }
+    }
+
+    pub fn record(&self, entry: &RetrievalLogEntry) -> Result<()> {
+        let line = serde_json::to_string(entry)?;
+        let mut writer = self.writer.lock().map_err(|_| anyhow!("eval log writer poisoned"))?;
+        writeln!(writer, "{}", line)?;
+        writer.flush()?;
+        Ok(())
+    }
+}
+
+/// 重排序训练样本：(query, chunk, label)，label 留空待人工标注
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct RerankExample {
+    pub query: String,
+    pub rank: usize,
+    pub score: f32,
+    pub id: String,
+    pub text: String,
+    pub metadata: JsonValue,
+    /// 相关性标注，导出时为 null
+    pub label: Option<u8>,
+}
+
+/// 对每个查询检索 `top_k` 个候选，按 JSON lines 写出重排序训练数据，返回写出的样本数
+pub async fn export_rerank_dataset<S: VectorStore, C: EmbeddingClient>(
+    retriever: &Retriever<S, C>,
+    queries: &[String],
+    top_k: usize,
+    mut writer: impl Write,
+) -> Result<usize> {
+    let mut count = 0;
+    for query in queries {
+        for SearchResult { rank, score, record } in retriever.retrieve(query, top_k).await? {
+            let example = RerankExample {
+                query: query.clone(),
+                rank,
+                score,
+                id: record.id,
+                text: record.text.unwrap_or_default(),
+                metadata: record.metadata,
+                label: None,
+            };
+            writeln!(writer, "{}", serde_json::to_string(&example)?)?;
+            count += 1;
+        }
+    }
+    writer.flush()?;
+    Ok(count)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_support::{FakeStore, KeywordClient, record};
+
+    #[tokio::test]
+    async fn test_export_rerank_dataset() -> Result<()> {
+        let store = FakeStore(vec![record("rust", vec![1.0, 0.0]), record("python", vec![0.0, 1.0])]);
+        let retriever = Retriever::new(store, KeywordClient);
+
+        let mut out = Vec::new();
+        let queries = vec!["rust 所有权".to_string(), "python".to_string()];
+        let count = export_rerank_dataset(&retriever, &queries, 2, &mut out).await?;
+        assert_eq!(count, 4);
+
+        let examples: Vec<RerankExample> = String::from_utf8(out)?
+            .lines()
+            .map(serde_json::from_str)
+            .collect::<Result<_, _>>()?;
+        assert_eq!(examples[0].query, "rust 所有权");
+        assert_eq!((examples[0].rank, examples[0].id.as_str()), (1, "rust"));
+        assert_eq!(examples[0].metadata["document_id"], "doc-001");
+        assert!(examples.iter().all(|e| e.label.is_none()));
+        assert_eq!(examples[2].id, "python");
+        Ok(())
+    }
+}
@@ -0,0 +1,113 @@
+use std::fmt;
+
+use rag_embeddings::database::VectorRecord;
+use rag_indexing::tree_structrue::markdown_bulid::{SNIPPET_MAX_CHARS, snippet};
+
+/// 检索结果的展示类型，渲染为一行紧凑的结果：图标、文件名、章节路径、摘要与分数
+pub struct SearchHit<'a> {
+    pub record: &'a VectorRecord,
+    pub score: Option<f32>,
+    pub max_chars: usize,
+}
+
+impl<'a> SearchHit<'a> {
+    pub fn new(record: &'a VectorRecord) -> Self {
+        Self {
+            record,
+            score: None,
+            max_chars: SNIPPET_MAX_CHARS,
+        }
+    }
+
+    pub fn with_score(mut self, score: f32) -> Self {
+        self.score = Some(score);
+        self
+    }
+
+    pub fn with_max_chars(mut self, max_chars: usize) -> Self {
+        self.max_chars = max_chars;
+        self
+    }
+
+    fn meta_str(&self, key: &str) -> Option<&str> {
+        self.record.metadata.get(key).and_then(|v| v.as_str())
+    }
+}
+
+impl fmt::Display for SearchHit<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let metadata = &self.record.metadata;
+        let is_image = metadata.get("is_image").and_then(|v| v.as_bool()).unwrap_or(false);
+        let icon = if is_image { "🖼️" } else { "📄" };
+
+        let source = self.meta_str("file_name").or(self.meta_str("document_id")).unwrap_or("未知文档");
+        let path = metadata.get("hierarchy")
+            .and_then(|v| v.as_array())
+            .map(|parts| parts.iter().filter_map(|p| p.as_str()).collect::<Vec<_>>().join(" > "))
+            .unwrap_or_default();
+
+        let content = if is_image {
+            let alt = self.meta_str("image_alt").unwrap_or("无描述");
+            let image_path = self.meta_str("image_path").unwrap_or("未知路径");
+            format!("[{}] {}", alt, image_path)
+        } else {
+            snippet(self.record.text.as_deref().unwrap_or_default(), self.max_chars)
+        };
+
+        write!(f, "{} [{}] {} — {}", icon, source, path, content)?;
+        if let Some(score) = self.score {
+            write!(f, " ({:.3})", score)?;
+        }
+        Ok(())
+    }
+}
+
+/// 将单条记录渲染为一行展示文本
+pub fn format_hit(record: &VectorRecord) -> String {
+    SearchHit::new(record).to_string()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn record(metadata: serde_json::Value, text: &str) -> VectorRecord {
+        VectorRecord {
+            id: "1".to_string(),
+            embedding: vec![],
+            metadata,
+            text: Some(text.to_string()),
+            createat: None,
+            updateat: None,
+        }
+    }
+
+    #[test]
+    fn test_format_text_hit() {
+        let r = record(serde_json::json!({
+            "document_id": "doc-001",
+            "file_name": "rust.md",
+            "hierarchy": ["Rust", "所有权"],
+            "is_image": false,
+        }), "每个值都有\n唯一的所有者。");
+
+        assert_eq!(format_hit(&r), "📄 [rust.md] Rust > 所有权 — 每个值都有 唯一的所有者。");
+        assert_eq!(
+            SearchHit::new(&r).with_score(0.8765).with_max_chars(5).to_string(),
+            "📄 [rust.md] Rust > 所有权 — 每个值都有... (0.877)"
+        );
+    }
+
+    #[test]
+    fn test_format_image_hit() {
+        let r = record(serde_json::json!({
+            "document_id": "doc-001",
+            "hierarchy": ["架构"],
+            "is_image": true,
+            "image_alt": "架构图",
+            "image_path": "images/arch.png",
+        }), "![架构图](images/arch.png)");
+
+        assert_eq!(format_hit(&r), "🖼️ [doc-001] 架构 — [架构图] images/arch.png");
+    }
+}
@@ -0,0 +1,9 @@
+pub mod eval;
+pub mod hit;
+pub mod retriever;
+
+pub use hit::{SearchHit, format_hit};
+pub use retriever::Retriever;
+
+#[cfg(test)]
+mod test_support;
@@ -0,0 +1,373 @@
+use std::collections::HashSet;
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+use anyhow::{Result, bail};
+use chrono::{DateTime, Utc};
+use rag_embeddings::{client::EmbeddingClient, database::{SearchQuery, SearchResult, TextSearchStore, VectorStore}};
+
+use crate::eval::{EvalLog, RetrievalLogEntry};
+
+/// 时间衰减在综合得分中的默认权重
+pub const DEFAULT_RECENCY_WEIGHT: f32 = 0.3;
+
+/// 按时间重排时，先按相似度取 `top_k * RECENCY_CANDIDATE_FACTOR` 条候选
+pub const RECENCY_CANDIDATE_FACTOR: usize = 3;
+
+/// 检索器：将查询文本嵌入后在向量库中检索
+pub struct Retriever<S: VectorStore, C: EmbeddingClient> {
+    search: TextSearchStore<S, C>,
+    /// 文档级 embedding 所在的向量库，用于两阶段检索
+    document_store: Option<S>,
+    eval_log: Option<Arc<EvalLog>>,
+    sentence_window: bool,
+    recency_weight: f32,
+}
+
+impl<S: VectorStore, C: EmbeddingClient> Retriever<S, C> {
+    pub fn new(store: S, embedding_client: C) -> Self {
+        Self {
+            search: TextSearchStore::new(store, embedding_client),
+            document_store: None,
+            eval_log: None,
+            sentence_window: false,
+            recency_weight: DEFAULT_RECENCY_WEIGHT,
+        }
+    }
+
+    /// 开启评估日志：记录每次检索的查询、结果 (id, score, document_id, hierarchy) 与耗时
+    pub fn with_eval_log(mut self, eval_log: Arc<EvalLog>) -> Self {
+        self.eval_log = Some(eval_log);
+        self
+    }
+
+    /// 设置文档级 embedding 所在的向量库（见 `rag_embeddings::embedding::build_document_embeddings`），
+    /// 开启 [`retrieve_in_documents`](Self::retrieve_in_documents)
+    pub fn with_document_store(mut self, document_store: S) -> Self {
+        self.document_store = Some(document_store);
+        self
+    }
+
+    /// 开启后命中句子级叶子时返回其所属段落（`metadata.window`），见 [`expand_sentence_windows`]
+    pub fn with_sentence_window(mut self, enabled: bool) -> Self {
+        self.sentence_window = enabled;
+        self
+    }
+
+    /// 时间衰减在 [`retrieve_with_recency`](Self::retrieve_with_recency) 综合得分中的权重，取值 [0, 1]
+    pub fn with_recency_weight(mut self, weight: f32) -> Self {
+        self.recency_weight = weight.clamp(0.0, 1.0);
+        self
+    }
+
+    pub fn store(&self) -> &S {
+        self.search.store()
+    }
+
+    pub fn embedding_client(&self) -> &C {
+        self.search.embedding_client()
+    }
+
+    /// 检索与查询最相似的 `top_k` 条记录
+    pub async fn retrieve(&self, query: &str, top_k: usize) -> Result<Vec<SearchResult>> {
+        let start = Instant::now();
+
+        let hits = self.search.search_by_text(query, top_k).await?;
+        Ok(self.finish(query, top_k, start, hits))
+    }
+
+    /// 检索并按更新时间重排：相似度与 `updateat` 的指数衰减（半衰期 `half_life`）加权，见 [`rerank_by_recency`]
+    ///
+    /// 先取 `top_k * RECENCY_CANDIDATE_FACTOR` 条候选，重排后保留前 `top_k` 条。
+    pub async fn retrieve_with_recency(&self, query: &str, top_k: usize, half_life: Duration) -> Result<Vec<SearchResult>> {
+        let start = Instant::now();
+
+        let candidates = self.search.search_by_text(query, top_k * RECENCY_CANDIDATE_FACTOR).await?;
+        let mut hits = rerank_by_recency(candidates, half_life, self.recency_weight, Utc::now());
+        hits.truncate(top_k);
+        Ok(self.finish(query, top_k, start, hits))
+    }
+
+    /// 自适应条数检索：先取 `max_k` 条候选，再在相邻得分的最大相对降幅处截断，见 [`cut_at_score_gap`]
+    ///
+    /// 降幅不足 `min_gap` 时说明候选得分没有明显断层，全部保留。
+    pub async fn retrieve_adaptive(&self, query: &str, max_k: usize, min_gap: f32) -> Result<Vec<SearchResult>> {
+        let start = Instant::now();
+
+        let candidates = self.search.search_by_text(query, max_k).await?;
+        let hits = cut_at_score_gap(candidates, min_gap);
+        Ok(self.finish(query, max_k, start, hits))
+    }
+
+    /// 两阶段检索：先在文档向量中取最相似的 `top_documents` 个文档，再只在这些文档的叶子中检索 `top_k` 条
+    ///
+    /// 需先通过 [`with_document_store`](Self::with_document_store) 设置文档向量库。
+    pub async fn retrieve_in_documents(&self, query: &str, top_documents: usize, top_k: usize) -> Result<Vec<SearchResult>> {
+        let Some(document_store) = &self.document_store else {
+            bail!("未设置文档向量库，请先调用 Retriever::with_document_store");
+        };
+        let start = Instant::now();
+
+        let embedding = self.search.embed_query(query).await?;
+        let documents = document_store.search(&embedding, top_documents).await?;
+        let document_ids: Vec<&str> = documents.iter()
+            .filter_map(|hit| hit.record.metadata.get("document_id").and_then(|id| id.as_str()))
+            .collect();
+        if document_ids.is_empty() {
+            return Ok(self.finish(query, top_k, start, Vec::new()));
+        }
+
+        let search = document_ids.into_iter()
+            .fold(SearchQuery::new(embedding).top_k(top_k), |search, id| search.filter_document(id));
+        let hits = self.store().search_with(&search).await?;
+        Ok(self.finish(query, top_k, start, hits))
+    }
+
+    /// 句子窗口展开与评估日志
+    ///
+    /// 评估日志只用于离线分析，写入失败时打印警告并照常返回检索结果。
+    fn finish(&self, query: &str, top_k: usize, start: Instant, mut hits: Vec<SearchResult>) -> Vec<SearchResult> {
+        if self.sentence_window {
+            hits = expand_sentence_windows(hits);
+        }
+
+        if let Some(eval_log) = &self.eval_log {
+            let elapsed_ms = start.elapsed().as_secs_f64() * 1000.0;
+            if let Err(e) = eval_log.record(&RetrievalLogEntry::new(query, top_k, elapsed_ms, &hits)) {
+                println!("警告: 写入评估日志失败: {:#}", e);
+            }
+        }
+
+        hits
+    }
+}
+
+/// 按时间衰减重排：`score' = (1 - weight) * score + weight * 0.5^(age / half_life)`，按新得分降序
+///
+/// `age` 为 `now - updateat`（未来时间按 0 计）；没有 `updateat` 的记录视为无限久远，衰减项为 0。
+pub fn rerank_by_recency(
+    mut hits: Vec<SearchResult>,
+    half_life: Duration,
+    weight: f32,
+    now: DateTime<Utc>,
+) -> Vec<SearchResult> {
+    let half_life = half_life.as_secs_f64().max(f64::EPSILON);
+    for hit in &mut hits {
+        let decay = hit.record.updateat.map_or(0.0, |updated| {
+            let age = (now - updated).num_milliseconds().max(0) as f64 / 1000.0;
+            0.5f64.powf(age / half_life) as f32
+        });
+        hit.score = (1.0 - weight) * hit.score + weight * decay;
+    }
+    SearchResult::rerank(&mut hits);
+    hits
+}
+
+/// 在相邻得分的最大相对降幅 `(s[i] - s[i+1]) / s[i]` 处截断（"拐点"），只保留拐点之前的结果
+///
+/// 输入按分数降序；最大降幅小于 `min_gap` 时原样返回。
+pub fn cut_at_score_gap(mut hits: Vec<SearchResult>, min_gap: f32) -> Vec<SearchResult> {
+    let knee = hits.windows(2)
+        .enumerate()
+        .filter(|(_, pair)| pair[0].score > 0.0)
+        .map(|(i, pair)| (i + 1, (pair[0].score - pair[1].score) / pair[0].score))
+        .max_by(|a, b| a.1.total_cmp(&b.1));
+    if let Some((cut, gap)) = knee
+        && gap >= min_gap
+    {
+        hits.truncate(cut);
+    }
+    hits
+}
+
+/// 将句子级命中的 text 替换为其所属段落窗口；同一窗口的多个句子只保留得分最高的一条，名次随之重排
+pub fn expand_sentence_windows(hits: Vec<SearchResult>) -> Vec<SearchResult> {
+    let mut seen = HashSet::new();
+    let expanded = hits.into_iter()
+        .filter_map(|SearchResult { mut record, score, .. }| {
+            let Some(window) = record.metadata.get("window").and_then(|w| w.as_str()).map(str::to_string) else {
+                return Some((record, score));
+            };
+            let document_id = record.metadata.get("document_id").and_then(|d| d.as_str()).unwrap_or_default();
+            // 输入按分数降序，首次出现的即为最高分
+            if !seen.insert((document_id.to_string(), window.clone())) {
+                return None;
+            }
+            record.text = Some(window);
+            Some((record, score))
+        });
+    SearchResult::ranked(expanded)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_support::{FakeStore, KeywordClient, record};
+    use rag_embeddings::database::VectorRecord;
+    use std::io::Write;
+    use std::sync::Mutex;
+
+    #[derive(Clone, Default)]
+    struct SharedBuf(Arc<Mutex<Vec<u8>>>);
+
+    impl Write for SharedBuf {
+        fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+            self.0.lock().unwrap().write(buf)
+        }
+        fn flush(&mut self) -> std::io::Result<()> { Ok(()) }
+    }
+
+    #[tokio::test]
+    async fn test_retrieve_with_eval_log() -> Result<()> {
+        let store = FakeStore(vec![
+            record("rust", vec![1.0, 0.0]),
+            record("python", vec![0.0, 1.0]),
+            record("mixed", vec![0.6, 0.8]),
+        ]);
+        let buf = SharedBuf::default();
+        let retriever = Retriever::new(store, KeywordClient)
+            .with_eval_log(Arc::new(EvalLog::from_writer(buf.clone())));
+
+        let hits = retriever.retrieve("why rust", 2).await?;
+        assert_eq!(hits.iter().map(|h| h.record.id.as_str()).collect::<Vec<_>>(), vec!["rust", "mixed"]);
+        retriever.retrieve("why python", 1).await?;
+
+        let log = String::from_utf8(buf.0.lock().unwrap().clone())?;
+        let entries: Vec<RetrievalLogEntry> = log.lines()
+            .map(serde_json::from_str)
+            .collect::<Result<_, _>>()?;
+        assert_eq!(entries.len(), 2);
+        assert_eq!(entries[0].query, "why rust");
+        assert_eq!(entries[0].results.len(), 2);
+        assert_eq!(entries[0].results[1].rank, 2);
+        assert_eq!(entries[0].results[1].id, "mixed");
+        assert_eq!(entries[0].results[0].document_id.as_deref(), Some("doc-001"));
+        assert_eq!(entries[0].results[0].hierarchy, vec!["Root", "rust"]);
+        assert_eq!(entries[1].results[0].id, "python");
+        Ok(())
+    }
+
+    /// 总是写入失败的日志输出
+    struct BrokenWriter;
+
+    impl Write for BrokenWriter {
+        fn write(&mut self, _buf: &[u8]) -> std::io::Result<usize> {
+            Err(std::io::Error::other("disk full"))
+        }
+        fn flush(&mut self) -> std::io::Result<()> { Ok(()) }
+    }
+
+    #[tokio::test]
+    async fn test_eval_log_failure_keeps_results() -> Result<()> {
+        let store = FakeStore(vec![record("rust", vec![1.0, 0.0])]);
+        let retriever = Retriever::new(store, KeywordClient)
+            .with_eval_log(Arc::new(EvalLog::from_writer(BrokenWriter)));
+
+        let hits = retriever.retrieve("why rust", 1).await?;
+        assert_eq!(hits[0].record.id, "rust");
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_retrieve_with_recency() -> Result<()> {
+        let now = Utc::now();
+        let dated = |id: &str, days: i64| VectorRecord {
+            updateat: Some(now - chrono::Duration::days(days)),
+            ..record(id, vec![1.0, 0.0])
+        };
+        let store = FakeStore(vec![
+            dated("old", 60),
+            dated("new", 1),
+            record("undated", vec![1.0, 0.0]),
+            record("python", vec![0.0, 1.0]),
+        ]);
+        let retriever = Retriever::new(store, KeywordClient);
+
+        // 相似度相同，较新的排在前面
+        let hits = retriever.retrieve_with_recency("rust", 3, Duration::from_secs(7 * 24 * 3600)).await?;
+        let ids: Vec<&str> = hits.iter().map(|h| h.record.id.as_str()).collect();
+        assert_eq!(ids, vec!["new", "old", "undated"]);
+        assert_eq!(hits[0].rank, 1);
+        assert!(hits[0].score > 0.9 && hits[0].score <= 1.0);
+        assert!((hits[2].score - 0.7).abs() < 1e-6);
+
+        // 权重为 0 时退化为纯相似度
+        let raw = SearchResult::ranked(vec![(dated("old", 60), 0.9), (dated("new", 1), 0.8)]);
+        let hits = rerank_by_recency(raw, Duration::from_secs(3600), 0.0, now);
+        assert_eq!(hits[0].record.id, "old");
+        assert_eq!(hits[0].score, 0.9);
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_sentence_window() -> Result<()> {
+        let window = "所有权。借用。";
+        let sentence = |id: &str, embedding: Vec<f32>, text: &str| VectorRecord {
+            text: Some(text.to_string()),
+            metadata: serde_json::json!({ "document_id": "doc-001", "window": window }),
+            ..record(id, embedding)
+        };
+        let store = FakeStore(vec![
+            sentence("rust-1", vec![1.0, 0.0], "所有权"),
+            sentence("rust-2", vec![0.9, 0.1], "借用"),
+            record("python", vec![0.5, 0.5]),
+        ]);
+
+        let retriever = Retriever::new(store, KeywordClient).with_sentence_window(true);
+        let hits = retriever.retrieve("rust", 3).await?;
+        assert_eq!(hits.len(), 2);
+        assert_eq!(hits[0].record.id, "rust-1");
+        assert_eq!(hits[0].record.text.as_deref(), Some(window));
+        assert_eq!(hits[1].record.text.as_deref(), Some("python"));
+        assert_eq!(hits[1].rank, 2);
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_retrieve_adaptive() -> Result<()> {
+        let store = FakeStore(vec![
+            record("rust-1", vec![1.0, 0.0]),
+            record("rust-2", vec![0.95, 0.05]),
+            record("python-1", vec![0.1, 0.9]),
+            record("python-2", vec![0.0, 1.0]),
+        ]);
+        let retriever = Retriever::new(store, KeywordClient);
+
+        let hits = retriever.retrieve_adaptive("rust", 4, 0.2).await?;
+        assert_eq!(hits.iter().map(|h| h.record.id.as_str()).collect::<Vec<_>>(), vec!["rust-1", "rust-2"]);
+
+        // 降幅不足 min_gap 时全部保留
+        assert_eq!(retriever.retrieve_adaptive("rust", 4, 0.99).await?.len(), 4);
+
+        let flat = SearchResult::ranked(vec![(record("a", vec![1.0]), 0.9), (record("b", vec![1.0]), 0.85)]);
+        assert_eq!(cut_at_score_gap(flat, 0.1).len(), 2);
+        assert!(cut_at_score_gap(Vec::new(), 0.1).is_empty());
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_retrieve_in_documents() -> Result<()> {
+        let in_document = |id: &str, document_id: &str, embedding: Vec<f32>| VectorRecord {
+            metadata: serde_json::json!({ "document_id": document_id }),
+            ..record(id, embedding)
+        };
+        let leaves = FakeStore(vec![
+            in_document("rust-a", "doc-a", vec![1.0, 0.0]),
+            in_document("rust-b", "doc-b", vec![0.9, 0.1]),
+            in_document("python-a", "doc-a", vec![0.0, 1.0]),
+        ]);
+        let documents = FakeStore(vec![
+            in_document("doc-a", "doc-a", vec![1.0, 0.0]),
+            in_document("doc-b", "doc-b", vec![0.0, 1.0]),
+        ]);
+
+        let retriever = Retriever::new(leaves, KeywordClient);
+        assert!(retriever.retrieve_in_documents("rust", 1, 2).await.is_err());
+
+        let retriever = retriever.with_document_store(documents);
+        let hits = retriever.retrieve_in_documents("rust", 1, 2).await?;
+        assert_eq!(hits.iter().map(|h| h.record.id.as_str()).collect::<Vec<_>>(), vec!["rust-a"]);
+        assert_eq!(retriever.retrieve_in_documents("rust", 2, 2).await?.len(), 2);
+        Ok(())
+    }
+}
@@ -0,0 +1,54 @@
+//! 测试用的嵌入客户端与内存向量库
+
+use anyhow::Result;
+use async_trait::async_trait;
+use rag_embeddings::{client::{EmbeddingClient, EmbeddingResult, l2_norm}, database::{DistanceMetric, SearchResult, VectorRecord, VectorStore}};
+
+/// 按关键字给出固定向量的嵌入客户端
+pub struct KeywordClient;
+
+#[async_trait]
+impl EmbeddingClient for KeywordClient {
+    async fn embed(&self, texts: Vec<String>) -> EmbeddingResult<Vec<Vec<f32>>> {
+        Ok(texts.iter()
+            .map(|t| if t.contains("rust") { vec![1.0, 0.0] } else { vec![0.0, 1.0] })
+            .collect())
+    }
+
+    fn dimension(&self) -> usize {
+        2
+    }
+}
+
+pub struct FakeStore(pub Vec<VectorRecord>);
+
+#[async_trait]
+impl VectorStore for FakeStore {
+    async fn add_vectors(&self, _vectors: Vec<VectorRecord>) -> Result<()> { Ok(()) }
+    async fn upsert_vectors(&self, _vectors: Vec<VectorRecord>) -> Result<()> { Ok(()) }
+    async fn delete_vector(&self, _ids: Vec<String>) -> Result<()> { Ok(()) }
+
+    async fn search(&self, query: &[f32], top_k: usize) -> Result<Vec<SearchResult>> {
+        let mut hits: Vec<(VectorRecord, f32)> = self.0.iter()
+            .map(|r| {
+                let dot: f32 = r.embedding.iter().zip(query).map(|(a, b)| a * b).sum();
+                let cosine = dot / (l2_norm(&r.embedding) * l2_norm(query));
+                (r.clone(), DistanceMetric::Cosine.normalize(1.0 - cosine))
+            })
+            .collect();
+        hits.sort_by(|a, b| b.1.total_cmp(&a.1));
+        hits.truncate(top_k);
+        Ok(SearchResult::ranked(hits))
+    }
+}
+
+pub fn record(id: &str, embedding: Vec<f32>) -> VectorRecord {
+    VectorRecord {
+        id: id.to_string(),
+        embedding,
+        metadata: serde_json::json!({ "document_id": "doc-001", "hierarchy": ["Root", id] }),
+        text: Some(id.to_string()),
+        createat: None,
+        updateat: None,
+    }
+}
@@ -1 +1,3 @@
-pub mod llm;
\ No newline at end of file
+pub mod llm;
+pub mod pipeline;
+pub mod summary;
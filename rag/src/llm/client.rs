@@ -1,11 +1,211 @@
-use async_openai::types::ChatCompletionRequestMessage;
+use std::time::Duration;
+
+use async_openai::error::OpenAIError;
+use async_openai::types::{ChatCompletionRequestMessage, ChatCompletionRequestSystemMessageArgs, ChatCompletionRequestUserMessageArgs};
 use async_trait::async_trait;
-use anyhow::Result;
+use futures::stream::{self, BoxStream, StreamExt};
+use tokio_util::sync::CancellationToken;
+
+#[derive(Debug, Clone, thiserror::Error)]
+pub enum LlmError {
+    #[error("Network error: {0}")]
+    Network(String),
+    /// 服务端返回的其他错误，`code` 为错误码（无错误码时为 HTTP 状态码）
+    #[error("API error [{code}]: {message}")]
+    Api { code: String, message: String },
+    /// 被限流或额度 / 余额不足，`retry_after` 取自 Retry-After 响应头
+    #[error("Rate limited: {message}")]
+    RateLimited { retry_after: Option<Duration>, message: String },
+    #[error("Invalid response: {0}")]
+    InvalidResponse(String),
+    /// API key 无效或无权访问模型
+    #[error("Authentication failed: {0}")]
+    Auth(String),
+    /// 请求参数无法构建（如消息内容非法）
+    #[error("Invalid request: {0}")]
+    InvalidRequest(String),
+    /// 请求被 [`CancellationToken`] 取消
+    #[error("Request cancelled")]
+    Cancelled,
+}
+
+impl LlmError {
+    /// 网络错误与限流可重试；鉴权失败、请求非法与取消重试无意义
+    pub fn is_retryable(&self) -> bool {
+        matches!(self, Self::Network(_) | Self::RateLimited { .. })
+    }
+}
+
+impl From<OpenAIError> for LlmError {
+    fn from(e: OpenAIError) -> Self {
+        Self::InvalidRequest(e.to_string())
+    }
+}
+
+pub type LlmResult<T> = Result<T, LlmError>;
+
+/// [`LlmClient::validate`] 发送的探测消息
+pub const VALIDATION_PROBE: &str = "ping";
+
+/// 只含探测消息的对话，预检时使用
+pub(crate) fn probe_messages() -> LlmResult<Vec<ChatCompletionRequestMessage>> {
+    Ok(vec![ChatCompletionRequestMessage::User(
+        ChatCompletionRequestUserMessageArgs::default()
+            .content(VALIDATION_PROBE)
+            .build()?
+    )])
+}
+
+/// 单次调用的生成参数，未设置的字段沿用客户端默认值
+#[derive(Debug, Clone, Default)]
+pub struct GenParams {
+    pub temperature: Option<f32>,
+    pub max_tokens: Option<u32>,
+    pub top_p: Option<f32>,
+    /// 随机种子，相同种子与参数下生成结果可复现（服务端尽力保证）
+    pub seed: Option<i64>,
+    /// 停止序列，生成遇到其中任一序列即结束（OpenAI 兼容接口最多 4 个）
+    pub stop: Option<Vec<String>>,
+    /// 覆盖消息中的 system 提示词
+    pub system: Option<String>,
+}
+
+impl GenParams {
+    pub fn with_temperature(mut self, temperature: f32) -> Self {
+        self.temperature = Some(temperature);
+        self
+    }
+
+    pub fn with_max_tokens(mut self, max_tokens: u32) -> Self {
+        self.max_tokens = Some(max_tokens);
+        self
+    }
+
+    pub fn with_top_p(mut self, top_p: f32) -> Self {
+        self.top_p = Some(top_p);
+        self
+    }
+
+    pub fn with_seed(mut self, seed: i64) -> Self {
+        self.seed = Some(seed);
+        self
+    }
+
+    pub fn with_stop(mut self, stop: Vec<String>) -> Self {
+        self.stop = Some(stop);
+        self
+    }
+
+    pub fn with_system(mut self, system: impl Into<String>) -> Self {
+        self.system = Some(system.into());
+        self
+    }
+
+    /// 未设置的字段取 `fallback` 中的值
+    pub fn or(self, fallback: &GenParams) -> GenParams {
+        GenParams {
+            temperature: self.temperature.or(fallback.temperature),
+            max_tokens: self.max_tokens.or(fallback.max_tokens),
+            top_p: self.top_p.or(fallback.top_p),
+            seed: self.seed.or(fallback.seed),
+            stop: self.stop.or_else(|| fallback.stop.clone()),
+            system: self.system.or_else(|| fallback.system.clone()),
+        }
+    }
+
+    /// 若设置了 system，则移除原有 system 消息并将其插入到最前面
+    pub fn apply_system(&self, messages: Vec<ChatCompletionRequestMessage>) -> LlmResult<Vec<ChatCompletionRequestMessage>> {
+        let Some(system) = &self.system else {
+            return Ok(messages);
+        };
+
+        let mut result = vec![ChatCompletionRequestMessage::System(
+            ChatCompletionRequestSystemMessageArgs::default()
+                .content(system.as_str())
+                .build()?
+        )];
+        result.extend(messages.into_iter()
+            .filter(|m| !matches!(m, ChatCompletionRequestMessage::System(_))));
+        Ok(result)
+    }
+}
 
 #[async_trait]
 pub trait LlmClient: Send + Sync {
-    async fn chat(&self, messages: Vec<ChatCompletionRequestMessage>) -> Result<String>;
+    async fn chat(&self, messages: Vec<ChatCompletionRequestMessage>) -> LlmResult<String>;
+
+    async fn generate(&self, messages: Vec<ChatCompletionRequestMessage>) -> LlmResult<String>;
+
+    /// 使用单次调用的生成参数覆盖客户端默认值
+    async fn chat_with_params(&self, messages: Vec<ChatCompletionRequestMessage>, params: &GenParams) -> LlmResult<String>;
+
+    /// 启动前的预检：发送一条只生成 1 个 token 的探测请求，确认 API key 有效且模型可用
+    async fn validate(&self) -> LlmResult<()> {
+        self.chat_with_params(probe_messages()?, &GenParams::default().with_max_tokens(1)).await?;
+        Ok(())
+    }
+
+    /// 可取消的 [`chat_with_params`](Self::chat_with_params)：token 被取消时丢弃进行中的请求并返回 [`LlmError::Cancelled`]
+    async fn chat_cancellable(
+        &self,
+        messages: Vec<ChatCompletionRequestMessage>,
+        params: &GenParams,
+        cancel: &CancellationToken,
+    ) -> LlmResult<String> {
+        tokio::select! {
+            biased;
+            _ = cancel.cancelled() => Err(LlmError::Cancelled),
+            result = self.chat_with_params(messages, params) => result,
+        }
+    }
+
+    /// 流式生成：逐段返回回复文本（增量），拼接后即完整回复
+    ///
+    /// 默认实现等待 [`chat_with_params`](Self::chat_with_params) 完成后一次性返回整段回复，
+    /// 支持流式接口的客户端应覆盖此方法。
+    async fn chat_stream(
+        &self,
+        messages: Vec<ChatCompletionRequestMessage>,
+        params: &GenParams,
+    ) -> LlmResult<BoxStream<'static, LlmResult<String>>> {
+        let text = self.chat_with_params(messages, params).await?;
+        Ok(stream::once(async move { Ok(text) }).boxed())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// 永不返回的 LLM
+    struct HangingLlm;
+
+    #[async_trait]
+    impl LlmClient for HangingLlm {
+        async fn chat(&self, messages: Vec<ChatCompletionRequestMessage>) -> LlmResult<String> {
+            self.chat_with_params(messages, &GenParams::default()).await
+        }
+
+        async fn generate(&self, messages: Vec<ChatCompletionRequestMessage>) -> LlmResult<String> {
+            self.chat(messages).await
+        }
+
+        async fn chat_with_params(&self, _messages: Vec<ChatCompletionRequestMessage>, _params: &GenParams) -> LlmResult<String> {
+            std::future::pending().await
+        }
+    }
 
-    async fn generate(&self, messages: Vec<ChatCompletionRequestMessage>) -> Result<String>;
+    #[tokio::test]
+    async fn test_chat_cancellable() {
+        let cancel = CancellationToken::new();
+        let trigger = cancel.clone();
+        tokio::spawn(async move {
+            tokio::time::sleep(std::time::Duration::from_millis(10)).await;
+            trigger.cancel();
+        });
 
-}
\ No newline at end of file
+        let err = HangingLlm.chat_cancellable(vec![], &GenParams::default(), &cancel).await.unwrap_err();
+        assert!(matches!(err, LlmError::Cancelled));
+        assert!(!err.is_retryable());
+    }
+}
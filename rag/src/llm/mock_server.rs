@@ -0,0 +1,36 @@
+//! 测试用的本地 DashScope 聊天接口模拟服务（基于 wiremock），LLM 客户端测试无需 API key 与网络
+
+use serde_json::json;
+use wiremock::ResponseTemplate;
+
+/// OpenAI 兼容模式的聊天回复
+pub(crate) fn chat_response(content: &str) -> ResponseTemplate {
+    ResponseTemplate::new(200).set_body_json(json!({
+        "id": "chatcmpl-test",
+        "object": "chat.completion",
+        "model": "qwen-max",
+        "choices": [{
+            "index": 0,
+            "message": { "role": "assistant", "content": content },
+            "finish_reason": "stop",
+        }],
+    }))
+}
+
+/// 流式回复：每段增量一个 `data:` 事件，以 `[DONE]` 结束
+pub(crate) fn stream_response(deltas: &[&str]) -> ResponseTemplate {
+    let mut body = String::new();
+    for delta in deltas {
+        let event = json!({ "choices": [{ "index": 0, "delta": { "content": delta } }] });
+        body.push_str(&format!("data: {}\n\n", event));
+    }
+    body.push_str("data: [DONE]\n\n");
+    ResponseTemplate::new(200).set_body_raw(body, "text/event-stream")
+}
+
+/// DashScope 兼容模式的错误响应
+pub(crate) fn error_response(status: u16, code: &str, message: &str) -> ResponseTemplate {
+    ResponseTemplate::new(status).set_body_json(json!({
+        "error": { "code": code, "message": message, "type": code },
+    }))
+}
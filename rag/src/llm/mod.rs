@@ -1,5 +1,7 @@
 pub mod client;
+#[cfg(test)]
+pub(crate) mod mock_server;
 pub mod tongyi;
 
-pub use client::LlmClient;
-pub use tongyi::TongyiClient;
\ No newline at end of file
+pub use client::{GenParams, LlmClient, LlmError, LlmResult};
+pub use tongyi::TongyiClient;
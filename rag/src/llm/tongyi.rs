@@ -1,9 +1,180 @@
-use anyhow::{anyhow, Result};
-use async_openai::types::{ChatCompletionRequestMessage, CreateChatCompletionRequestArgs};
+use std::collections::VecDeque;
+use std::time::Duration;
+
+use async_openai::types::{ChatCompletionRequestMessage, CreateChatCompletionRequest, CreateChatCompletionRequestArgs, Stop};
 use async_trait::async_trait;
 use dotenv::dotenv;
+use futures::stream::{self, BoxStream, Stream, StreamExt};
+use reqwest::StatusCode;
+use serde_json::Value;
+
+use crate::llm::{GenParams, LlmClient, LlmError, LlmResult, client::probe_messages};
+
+/// 默认最大重试次数
+pub const DEFAULT_MAX_RETRIES: u32 = 3;
+
+/// 默认首次重试等待时间，之后每次翻倍
+pub const DEFAULT_RETRY_BASE_DELAY: Duration = Duration::from_millis(500);
+
+/// 限流（429）与服务端错误（5xx）可重试
+fn is_retryable(status: StatusCode) -> bool {
+    status == StatusCode::TOO_MANY_REQUESTS || status.is_server_error()
+}
+
+/// 第 `attempt` 次重试（从 0 开始）前的等待时间：base * 2^attempt
+fn backoff_delay(base: Duration, attempt: u32) -> Duration {
+    base.saturating_mul(2u32.saturating_pow(attempt))
+}
 
-use crate::llm::LlmClient;
+/// 错误响应中的错误码：兼容模式为 `error.code`，原生格式为 `code`
+fn error_code(value: &Value) -> Option<String> {
+    value["error"]["code"].as_str()
+        .or_else(|| value["code"].as_str())
+        .map(str::to_string)
+}
+
+/// 按状态码与错误码将失败的响应归类为 [`LlmError`]
+fn classify_error(status: StatusCode, body: &str, retry_after: Option<Duration>) -> LlmError {
+    let lower = body.to_lowercase();
+    if status == StatusCode::TOO_MANY_REQUESTS || ["throttling", "quota", "arrearage"].iter().any(|k| lower.contains(k)) {
+        LlmError::RateLimited { retry_after, message: format!("{} - {}", status, body) }
+    } else if status == StatusCode::UNAUTHORIZED
+        || status == StatusCode::FORBIDDEN
+        || ["invalidapikey", "invalid_api_key", "accessdenied"].iter().any(|k| lower.contains(k))
+    {
+        LlmError::Auth(format!("{} - {}", status, body))
+    } else {
+        let code = serde_json::from_str::<Value>(body).ok()
+            .and_then(|value| error_code(&value))
+            .unwrap_or_else(|| status.as_u16().to_string());
+        let message = if body.is_empty() { status.to_string() } else { body.to_string() };
+        LlmError::Api { code, message }
+    }
+}
+
+/// 预检失败的说明：在 [`classify_error`] 的基础上补充限流 / 额度不足、鉴权失败与模型不可用的处理建议
+fn preflight_error(model: &str, status: StatusCode, body: &str) -> LlmError {
+    match classify_error(status, body, None) {
+        LlmError::RateLimited { retry_after, message } => LlmError::RateLimited {
+            retry_after,
+            message: format!("DashScope 调用被限流或额度不足（模型 {}），请稍后重试或检查账户余额: {}", model, message),
+        },
+        LlmError::Auth(message) => {
+            LlmError::Auth(format!("DashScope API key 无效或无权访问模型 {}，请检查 DASHSCOPE_API_KEY: {}", model, message))
+        }
+        LlmError::Api { code, .. }
+            if status == StatusCode::NOT_FOUND || ["model_not_found", "modelnotfound"].iter().any(|k| code.to_lowercase().contains(k)) =>
+        {
+            LlmError::Api { code, message: format!("模型 {} 不存在或不可用: {} - {}", model, status, body) }
+        }
+        error => error,
+    }
+}
+
+/// 从响应中提取回复文本，兼容 OpenAI 兼容模式（`choices[].message.content`，字符串或分段数组）
+/// 与 DashScope 原生格式（`output.text` / `output.choices[].message.content`）
+fn extract_content(value: &Value) -> Option<String> {
+    let message_content = |choices: &Value| -> Option<String> {
+        let content = &choices.as_array()?.first()?["message"]["content"];
+        match content {
+            Value::String(text) => Some(text.clone()),
+            Value::Array(parts) => Some(parts.iter().filter_map(|p| p["text"].as_str()).collect()),
+            _ => None,
+        }
+    };
+
+    message_content(&value["choices"])
+        .or_else(|| value["output"]["text"].as_str().map(str::to_string))
+        .or_else(|| message_content(&value["output"]["choices"]))
+}
+
+/// SSE 事件流的解析状态：按行切分 `data:` 事件，提取增量文本
+struct SseState<S> {
+    inner: S,
+    buffer: Vec<u8>,
+    pending: VecDeque<String>,
+    done: bool,
+}
+
+impl<S> SseState<S> {
+    /// 处理缓冲区中所有完整的行，增量文本放入 `pending`，遇到 `[DONE]` 结束
+    fn drain_lines(&mut self) -> LlmResult<()> {
+        while let Some(pos) = self.buffer.iter().position(|&b| b == b'\n') {
+            let line: Vec<u8> = self.buffer.drain(..=pos).collect();
+            let line = String::from_utf8_lossy(&line);
+            let Some(data) = line.trim().strip_prefix("data:").map(str::trim) else { continue };
+            if data == "[DONE]" {
+                self.done = true;
+                return Ok(());
+            }
+
+            let event: Value = serde_json::from_str(data)
+                .map_err(|e| LlmError::InvalidResponse(format!("流式响应不是合法的 JSON ({}): {}", e, data)))?;
+            if let Some(error) = event.get("error") {
+                return Err(LlmError::Api {
+                    code: error_code(&event).unwrap_or_else(|| "stream_error".to_string()),
+                    message: format!("流式响应返回错误: {}", error),
+                });
+            }
+            if let Some(delta) = extract_delta(&event).filter(|d| !d.is_empty()) {
+                self.pending.push_back(delta);
+            }
+        }
+        Ok(())
+    }
+}
+
+/// 从流式事件中提取增量文本（`choices[].delta.content`，兼容原生格式的 `output.text`）
+fn extract_delta(event: &Value) -> Option<String> {
+    event["choices"].as_array()
+        .and_then(|choices| choices.first())
+        .and_then(|choice| choice["delta"]["content"].as_str())
+        .or_else(|| event["output"]["text"].as_str())
+        .map(str::to_string)
+}
+
+/// 将 SSE 字节流转换为增量文本流，出错后流结束
+fn sse_deltas<S, B, E>(inner: S) -> BoxStream<'static, LlmResult<String>>
+where
+    S: Stream<Item = Result<B, E>> + Send + Unpin + 'static,
+    B: AsRef<[u8]>,
+    E: std::fmt::Display,
+{
+    let state = SseState { inner, buffer: Vec::new(), pending: VecDeque::new(), done: false };
+    stream::unfold(state, |mut state| async move {
+        loop {
+            if let Some(delta) = state.pending.pop_front() {
+                return Some((Ok(delta), state));
+            }
+            if state.done {
+                return None;
+            }
+            match state.inner.next().await {
+                Some(Ok(chunk)) => {
+                    state.buffer.extend_from_slice(chunk.as_ref());
+                    if let Err(e) = state.drain_lines() {
+                        state.done = true;
+                        state.pending.clear();
+                        return Some((Err(e), state));
+                    }
+                }
+                Some(Err(e)) => {
+                    state.done = true;
+                    return Some((Err(LlmError::Network(e.to_string())), state));
+                }
+                None => {
+                    // 末尾没有换行的最后一行
+                    state.buffer.push(b'\n');
+                    state.done = true;
+                    if let Err(e) = state.drain_lines() {
+                        return Some((Err(e), state));
+                    }
+                }
+            }
+        }
+    })
+    .boxed()
+}
 
 pub struct TongyiClient {
     pub api_key: String,
@@ -11,7 +182,15 @@ pub struct TongyiClient {
     pub model: String,
     pub max_tokens: Option<u32>,
     pub temperature: Option<f32>,
+    pub top_p: Option<f32>,
+    /// 随机种子，固定后相同输入的生成结果可复现，便于测试
+    pub seed: Option<i64>,
+    /// 停止序列
+    pub stop: Option<Vec<String>>,
     pub client: reqwest::Client,
+    /// 429 / 5xx / 网络错误的最大重试次数
+    pub max_retries: u32,
+    pub retry_base_delay: Duration,
 }
 
 
@@ -26,7 +205,12 @@ impl TongyiClient {
             model: "qwen-max".to_string(),
             max_tokens: Some(10000),
             temperature: Some(0.7),
+            top_p: None,
+            seed: None,
+            stop: None,
             client: reqwest::Client::new(),
+            max_retries: DEFAULT_MAX_RETRIES,
+            retry_base_delay: DEFAULT_RETRY_BASE_DELAY,
         }
     }
 
@@ -44,6 +228,101 @@ impl TongyiClient {
         self.max_tokens = Some(max_tokens);
         self
     }
+
+    pub fn with_top_p(mut self, top_p: f32) -> Self {
+        self.top_p = Some(top_p);
+        self
+    }
+
+    pub fn with_seed(mut self, seed: i64) -> Self {
+        self.seed = Some(seed);
+        self
+    }
+
+    /// 空列表表示不设置停止序列
+    pub fn with_stop(mut self, stop: Vec<String>) -> Self {
+        self.stop = Some(stop);
+        self
+    }
+
+    /// 使用外部构建的 HTTP 客户端（如配置了代理、TLS 或超时），可与 embedding 等其他客户端共享连接池
+    pub fn with_http_client(mut self, client: reqwest::Client) -> Self {
+        self.client = client;
+        self
+    }
+
+    pub fn with_max_retries(mut self, max_retries: u32) -> Self {
+        self.max_retries = max_retries;
+        self
+    }
+
+    pub fn with_retry_base_delay(mut self, delay: Duration) -> Self {
+        self.retry_base_delay = delay;
+        self
+    }
+
+    /// 构建聊天请求，单次调用的参数优先于客户端默认值
+    fn build_request(&self, messages: Vec<ChatCompletionRequestMessage>, params: &GenParams, stream: bool) -> LlmResult<CreateChatCompletionRequest> {
+        let mut args = CreateChatCompletionRequestArgs::default();
+        args.model(self.model.clone())
+            .messages(params.apply_system(messages)?)
+            .max_tokens(params.max_tokens.or(self.max_tokens).unwrap_or(10000))
+            .temperature(params.temperature.or(self.temperature).unwrap_or(0.7))
+            .stream(stream);
+        if let Some(top_p) = params.top_p.or(self.top_p) {
+            args.top_p(top_p);
+        }
+        if let Some(seed) = params.seed.or(self.seed) {
+            args.seed(seed);
+        }
+        if let Some(stop) = params.stop.as_ref().or(self.stop.as_ref()).filter(|s| !s.is_empty()) {
+            args.stop(Stop::StringArray(stop.clone()));
+        }
+        Ok(args.build()?)
+    }
+
+    /// 发送请求，对 429 / 5xx / 网络错误按指数退避重试（优先使用 Retry-After 响应头），返回成功的响应
+    async fn send_with_retry<T: serde::Serialize + Sync>(&self, url: &str, body: &T) -> LlmResult<reqwest::Response> {
+        let mut attempt = 0;
+        loop {
+            let result = self.client
+                .post(url)
+                .header("Authorization", format!("Bearer {}", self.api_key))
+                .header("Content-Type", "application/json")
+                .json(body)
+                .send()
+                .await;
+
+            let (error, retry_after) = match result {
+                Ok(response) if response.status().is_success() => return Ok(response),
+                Ok(response) => {
+                    let status = response.status();
+                    let retry_after = response.headers()
+                        .get(reqwest::header::RETRY_AFTER)
+                        .and_then(|v| v.to_str().ok())
+                        .and_then(|v| v.parse::<u64>().ok())
+                        .map(Duration::from_secs);
+                    let error_text = response.text().await.unwrap_or_default();
+                    let error = classify_error(status, &error_text, retry_after);
+                    if !is_retryable(status) {
+                        return Err(error);
+                    }
+                    (error, retry_after)
+                }
+                Err(e) if e.is_timeout() || e.is_connect() => (LlmError::Network(format!("网络请求错误: {}", e)), None),
+                Err(e) => return Err(LlmError::Network(e.to_string())),
+            };
+
+            if attempt >= self.max_retries {
+                println!("重试 {} 次后仍失败: {}", self.max_retries, error);
+                return Err(error);
+            }
+            let delay = retry_after.unwrap_or_else(|| backoff_delay(self.retry_base_delay, attempt));
+            println!("请求失败，{:?} 后重试 ({}/{}): {}", delay, attempt + 1, self.max_retries, error);
+            tokio::time::sleep(delay).await;
+            attempt += 1;
+        }
+    }
 }
 
 impl Default for TongyiClient {
@@ -54,49 +333,275 @@ impl Default for TongyiClient {
 
 #[async_trait]
 impl LlmClient for TongyiClient {
-    async fn chat(&self, messages: Vec<ChatCompletionRequestMessage>) -> Result<String> {
-        // 构建请求参数
-        let request = CreateChatCompletionRequestArgs::default()
-            .model(self.model.clone())
-            .messages(messages)
-            .max_tokens(self.max_tokens.unwrap_or(10000))
-            .temperature(self.temperature.unwrap_or(0.7))
-            .build()?;
-
-        // 发送请求
+    async fn chat(&self, messages: Vec<ChatCompletionRequestMessage>) -> LlmResult<String> {
+        self.chat_with_params(messages, &GenParams::default()).await
+    }
+
+    async fn generate(&self, messages: Vec<ChatCompletionRequestMessage>) -> LlmResult<String> {
+        // generate方法可以复用chat方法
+        self.chat(messages).await
+    }
+
+    async fn chat_with_params(&self, messages: Vec<ChatCompletionRequestMessage>, params: &GenParams) -> LlmResult<String> {
+        let request = self.build_request(messages, params, false)?;
+
+        // 发送请求（429 / 5xx 自动重试）
         let url = format!("{}/chat/completions", self.base_url);
+        let response_text = self.send_with_retry(&url, &request).await?
+            .text()
+            .await
+            .map_err(|e| LlmError::Network(e.to_string()))?;
+
+        // 解析响应
+        let response_json: Value = serde_json::from_str(&response_text)
+            .map_err(|e| LlmError::InvalidResponse(format!("响应不是合法的 JSON ({}): {}", e, response_text)))?;
+
+        if let Some(content) = extract_content(&response_json) {
+            return Ok(content);
+        }
+
+        Err(LlmError::InvalidResponse(format!("无法从响应中提取消息内容: {}", response_text)))
+    }
+
+    /// 发送一次探测请求（不重试），按状态码与错误码给出鉴权、额度或模型问题的说明
+    async fn validate(&self) -> LlmResult<()> {
+        let request = self.build_request(probe_messages()?, &GenParams::default().with_max_tokens(1), false)?;
         let response = self.client
-            .post(&url)
+            .post(format!("{}/chat/completions", self.base_url))
             .header("Authorization", format!("Bearer {}", self.api_key))
             .header("Content-Type", "application/json")
             .json(&request)
             .send()
-            .await?;
+            .await
+            .map_err(|e| LlmError::Network(format!("无法连接 {}: {}", self.base_url, e)))?;
 
-        // 检查响应状态
-        if !response.status().is_success() {
-            let status = response.status();
-            let error_text = response.text().await.unwrap_or_default();
-            return Err(anyhow!("API请求失败: {} - {}", status, error_text));
+        let status = response.status();
+        if !status.is_success() {
+            let body = response.text().await.unwrap_or_default();
+            return Err(preflight_error(&self.model, status, &body));
         }
+        println!("✅ LLM 服务可用: {}", self.model);
+        Ok(())
+    }
 
-        // 解析响应
-        let response_text = response.text().await?;
-        let response_json: serde_json::Value = serde_json::from_str(&response_text)?;
+    /// 以 SSE 方式请求（`stream: true`），仅建立连接阶段按重试策略重试
+    async fn chat_stream(
+        &self,
+        messages: Vec<ChatCompletionRequestMessage>,
+        params: &GenParams,
+    ) -> LlmResult<BoxStream<'static, LlmResult<String>>> {
+        let request = self.build_request(messages, params, true)?;
+        let url = format!("{}/chat/completions", self.base_url);
+        let response = self.send_with_retry(&url, &request).await?;
+        Ok(sse_deltas(response.bytes_stream().boxed()))
+    }
+}
 
-        // 提取返回的消息内容
-        if let Some(choices) = response_json["choices"].as_array()
-            && let Some(first_choice) = choices.first()
-            && let Some(content) = first_choice["message"]["content"].as_str()
-        {
-            return Ok(content.to_string());
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::llm::client::VALIDATION_PROBE;
+    use crate::llm::mock_server::{chat_response, error_response, stream_response};
+    use anyhow::Result;
+    use futures::TryStreamExt;
+    use wiremock::matchers::{header, method, path};
+    use wiremock::{Mock, MockServer};
+
+    #[test]
+    fn test_extract_content() {
+        let compatible = serde_json::json!({ "choices": [{ "message": { "role": "assistant", "content": "你好" } }] });
+        assert_eq!(extract_content(&compatible).as_deref(), Some("你好"));
+
+        let parts = serde_json::json!({ "choices": [{ "message": { "content": [{ "text": "你" }, { "text": "好" }] } }] });
+        assert_eq!(extract_content(&parts).as_deref(), Some("你好"));
+
+        let native = serde_json::json!({ "output": { "text": "你好", "finish_reason": "stop" } });
+        assert_eq!(extract_content(&native).as_deref(), Some("你好"));
+
+        let native_message = serde_json::json!({ "output": { "choices": [{ "message": { "content": "你好" } }] } });
+        assert_eq!(extract_content(&native_message).as_deref(), Some("你好"));
+
+        assert_eq!(extract_content(&serde_json::json!({ "choices": [] })), None);
+        assert_eq!(extract_content(&serde_json::json!({ "output": {} })), None);
+    }
+
+    fn client() -> TongyiClient {
+        TongyiClient {
+            api_key: "test".to_string(),
+            base_url: "http://localhost".to_string(),
+            model: "qwen-max".to_string(),
+            max_tokens: None,
+            temperature: None,
+            top_p: None,
+            seed: None,
+            stop: None,
+            client: reqwest::Client::new(),
+            max_retries: 0,
+            retry_base_delay: DEFAULT_RETRY_BASE_DELAY,
         }
+    }
+
+    #[test]
+    fn test_build_request() -> Result<()> {
+        let body = |client: &TongyiClient, params: &GenParams| -> Result<Value> {
+            Ok(serde_json::to_value(client.build_request(probe_messages()?, params, false)?)?)
+        };
+
+        let request = body(&client(), &GenParams::default())?;
+        assert!(request.get("top_p").is_none() && request.get("seed").is_none() && request.get("stop").is_none());
+
+        let client = client().with_top_p(0.8).with_seed(42).with_stop(vec!["\n\n".to_string()]);
+        let request = body(&client, &GenParams::default())?;
+        assert_eq!(request["seed"], 42);
+        assert!((request["top_p"].as_f64().unwrap() - 0.8).abs() < 1e-6);
+        assert_eq!(request["stop"], serde_json::json!(["\n\n"]));
 
-        Err(anyhow!("无法从响应中提取消息内容: {}", response_text))
+        // 单次调用的参数优先，空的停止序列不发送
+        let request = body(&client, &GenParams::default().with_seed(7).with_stop(Vec::new()))?;
+        assert_eq!(request["seed"], 7);
+        assert!(request.get("stop").is_none());
+        Ok(())
     }
 
-    async fn generate(&self, messages: Vec<ChatCompletionRequestMessage>) -> Result<String> {
-        // generate方法可以复用chat方法
-        self.chat(messages).await
+    #[test]
+    fn test_classify_error() {
+        let retry_after = Some(Duration::from_secs(3));
+        assert!(matches!(
+            classify_error(StatusCode::TOO_MANY_REQUESTS, "", retry_after),
+            LlmError::RateLimited { retry_after: Some(d), .. } if d == Duration::from_secs(3)
+        ));
+        assert!(matches!(classify_error(StatusCode::BAD_REQUEST, r#"{"code":"Arrearage"}"#, None), LlmError::RateLimited { .. }));
+        assert!(matches!(classify_error(StatusCode::UNAUTHORIZED, "", None), LlmError::Auth(_)));
+        assert!(matches!(classify_error(StatusCode::BAD_REQUEST, r#"{"code":"InvalidApiKey"}"#, None), LlmError::Auth(_)));
+        assert!(matches!(
+            classify_error(StatusCode::BAD_REQUEST, r#"{"error":{"code":"invalid_parameter_error","message":"bad"}}"#, None),
+            LlmError::Api { code, .. } if code == "invalid_parameter_error"
+        ));
+        assert!(matches!(classify_error(StatusCode::BAD_GATEWAY, "oops", None), LlmError::Api { code, .. } if code == "502"));
+        assert!(classify_error(StatusCode::TOO_MANY_REQUESTS, "", None).is_retryable());
+        assert!(!classify_error(StatusCode::UNAUTHORIZED, "", None).is_retryable());
+    }
+
+    #[test]
+    fn test_preflight_error() {
+        let message = |status, body| preflight_error("qwen-max", status, body).to_string();
+        assert!(message(StatusCode::UNAUTHORIZED, r#"{"error":{"code":"invalid_api_key"}}"#).contains("API key 无效"));
+        assert!(message(StatusCode::BAD_REQUEST, r#"{"code":"InvalidApiKey"}"#).contains("API key 无效"));
+        assert!(message(StatusCode::TOO_MANY_REQUESTS, "").contains("额度不足"));
+        assert!(message(StatusCode::BAD_REQUEST, r#"{"code":"Arrearage"}"#).contains("额度不足"));
+        assert!(message(StatusCode::NOT_FOUND, r#"{"error":{"code":"model_not_found"}}"#).contains("模型 qwen-max 不存在"));
+        assert!(matches!(preflight_error("qwen-max", StatusCode::BAD_REQUEST, "bad"), LlmError::Api { code, message } if code == "400" && message == "bad"));
+    }
+
+    #[test]
+    fn test_retry_policy() {
+        assert!(is_retryable(StatusCode::TOO_MANY_REQUESTS));
+        assert!(is_retryable(StatusCode::SERVICE_UNAVAILABLE));
+        assert!(!is_retryable(StatusCode::BAD_REQUEST));
+        assert!(!is_retryable(StatusCode::UNAUTHORIZED));
+
+        let base = Duration::from_millis(500);
+        assert_eq!(backoff_delay(base, 0), Duration::from_millis(500));
+        assert_eq!(backoff_delay(base, 3), Duration::from_secs(4));
+    }
+
+    fn mock_client(server: &MockServer) -> TongyiClient {
+        TongyiClient { base_url: server.uri(), ..client() }
+            .with_max_retries(2)
+            .with_retry_base_delay(Duration::from_millis(1))
+    }
+
+    #[tokio::test]
+    async fn test_chat_with_mock_server() -> Result<()> {
+        let server = MockServer::start().await;
+        Mock::given(method("POST"))
+            .and(path("/chat/completions"))
+            .and(header("Authorization", "Bearer test"))
+            .respond_with(chat_response("你好"))
+            .expect(1)
+            .mount(&server)
+            .await;
+
+        let client = mock_client(&server);
+        let reply = client.chat_with_params(probe_messages()?, &GenParams::default().with_max_tokens(8)).await?;
+        assert_eq!(reply, "你好");
+
+        let requests = server.received_requests().await.unwrap_or_default();
+        let body: Value = requests[0].body_json()?;
+        assert_eq!(body["model"], "qwen-max");
+        assert_eq!(body["max_tokens"], 8);
+        assert_eq!(body["messages"][0]["content"], VALIDATION_PROBE);
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_retry_with_mock_server() -> Result<()> {
+        let server = MockServer::start().await;
+        Mock::given(method("POST"))
+            .respond_with(error_response(503, "ServiceUnavailable", "busy"))
+            .up_to_n_times(2)
+            .mount(&server)
+            .await;
+        Mock::given(method("POST"))
+            .respond_with(chat_response("ok"))
+            .mount(&server)
+            .await;
+
+        // 两次 503 后成功
+        let client = mock_client(&server);
+        assert_eq!(client.chat(probe_messages()?).await?, "ok");
+        assert_eq!(server.received_requests().await.unwrap_or_default().len(), 3);
+
+        // 鉴权失败不重试
+        server.reset().await;
+        Mock::given(method("POST"))
+            .respond_with(error_response(401, "invalid_api_key", "Incorrect API key provided."))
+            .expect(1)
+            .mount(&server)
+            .await;
+        assert!(matches!(client.chat(probe_messages()?).await, Err(LlmError::Auth(_))));
+        Ok(())
     }
-}
\ No newline at end of file
+
+    #[tokio::test]
+    async fn test_chat_stream_with_mock_server() -> Result<()> {
+        let server = MockServer::start().await;
+        Mock::given(method("POST"))
+            .and(path("/chat/completions"))
+            .respond_with(stream_response(&["所有权", "规则"]))
+            .mount(&server)
+            .await;
+
+        let client = mock_client(&server);
+        let deltas: Vec<String> = client.chat_stream(probe_messages()?, &GenParams::default()).await?
+            .try_collect()
+            .await?;
+        assert_eq!(deltas, vec!["所有权", "规则"]);
+
+        let body: Value = server.received_requests().await.unwrap_or_default()[0].body_json()?;
+        assert_eq!(body["stream"], true);
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_sse_deltas() {
+        // 事件跨 chunk 切分，含空增量、注释行与结束标记
+        let chunks: Vec<std::result::Result<&'static [u8], std::io::Error>> = vec![
+            Ok(b"data: {\"choices\":[{\"delta\":{\"role\":\"assistant\",\"content\":\"\"}}]}\n\n"),
+            Ok(b": keep-alive\n\ndata: {\"choices\":[{\"delta\":{\"content\":\"\xe4\xbd"),
+            Ok(b"\xa0\"}}]}\n\ndata: {\"choices\":[{\"delta\":{\"content\":\"\xe5\xa5\xbd\"}}]}\n\n"),
+            Ok(b"data: [DONE]\n\ndata: {\"choices\":[{\"delta\":{\"content\":\"ignored\"}}]}\n\n"),
+        ];
+        let deltas: Vec<String> = sse_deltas(stream::iter(chunks))
+            .map(|d| d.unwrap())
+            .collect()
+            .await;
+        assert_eq!(deltas, vec!["你", "好"]);
+
+        let error: Vec<std::result::Result<&'static [u8], std::io::Error>> =
+            vec![Ok(b"data: {\"error\":{\"message\":\"quota\"}}\n\n")];
+        let results: Vec<LlmResult<String>> = sse_deltas(stream::iter(error)).collect().await;
+        assert_eq!(results.len(), 1);
+        assert!(matches!(results[0], Err(LlmError::Api { .. })));
+    }
+}
@@ -13,6 +13,9 @@ async fn main() -> Result<()> {
 
     println!("🤖 通义千问聊天测试\n");
 
+    // 预检 API key 与模型
+    client.validate().await?;
+
     let messages = vec![
         ChatCompletionRequestMessage::System(
             ChatCompletionRequestSystemMessageArgs::default()
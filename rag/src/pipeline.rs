@@ -0,0 +1,661 @@
+use anyhow::Result;
+use futures::future::try_join_all;
+use futures::stream::{self, Stream, StreamExt, TryStreamExt};
+use async_openai::types::{ChatCompletionRequestMessage, ChatCompletionRequestSystemMessageArgs, ChatCompletionRequestUserMessageArgs};
+use rag_embeddings::{client::EmbeddingClient, database::{SearchResult, VectorStore}};
+use rag_retrieval::Retriever;
+
+use crate::llm::{GenParams, LlmClient};
+
+/// 默认的 system 提示词
+pub const DEFAULT_SYSTEM_PROMPT: &str = "你是一个知识库问答助手。请仅根据提供的参考资料回答问题，资料中没有的信息请如实说明。";
+
+/// 没有相关资料时返回的默认回答，见 [`RagPipeline::with_no_answer_message`]
+pub const DEFAULT_NO_ANSWER_MESSAGE: &str = "抱歉，知识库中没有找到与该问题相关的资料，无法根据现有资料回答。";
+
+/// 上下文压缩时每个片段摘要的默认 token 上限
+pub const DEFAULT_SUMMARY_MAX_TOKENS: u32 = 256;
+
+/// 上下文压缩时摘要调用使用的 system 提示词
+const SUMMARY_SYSTEM_PROMPT: &str = "你负责压缩检索到的参考资料。请只保留与问题相关的事实，简洁地概括，不要添加资料中没有的信息；若资料与问题无关，回答“无关”。";
+
+/// 翻译查询时使用的 system 提示词
+const TRANSLATE_SYSTEM_PROMPT: &str = "你是翻译助手。请把用户给出的问题翻译成指定语言，只输出译文，不要回答问题，也不要添加解释。";
+
+/// 常见语言代码与提示词中使用的语言名称
+const LANGUAGE_NAMES: &[(&str, &str)] = &[
+    ("zh", "中文"),
+    ("zh-cn", "简体中文"),
+    ("zh-tw", "繁体中文"),
+    ("en", "英文"),
+    ("ja", "日文"),
+    ("ko", "韩文"),
+    ("fr", "法文"),
+    ("de", "德文"),
+    ("es", "西班牙文"),
+    ("ru", "俄文"),
+];
+
+/// 将语言代码（如 `en`、`zh-CN`）转换为提示词中的语言名称，未知的代码或名称原样返回
+///
+/// 空字符串返回 `None`，表示不指定语言。
+pub fn language_name(language: &str) -> Option<String> {
+    let language = language.trim();
+    if language.is_empty() {
+        return None;
+    }
+    let code = language.to_lowercase().replace('_', "-");
+    let name = LANGUAGE_NAMES.iter()
+        .find(|(c, _)| *c == code)
+        .map(|(_, name)| name.to_string())
+        .unwrap_or_else(|| language.to_string());
+    Some(name)
+}
+
+/// 流式回答中的事件
+#[derive(Debug, Clone)]
+pub enum AnswerEvent {
+    /// 生成所依据的检索结果，总是第一个事件，便于界面先渲染引用
+    Sources(Vec<SearchResult>),
+    /// 回答的增量文本
+    Delta(String),
+}
+
+/// 检索增强生成流程：检索相关片段 -> 拼接上下文 -> 调用 LLM 生成回答
+pub struct RagPipeline<L: LlmClient, S: VectorStore, C: EmbeddingClient> {
+    llm: L,
+    retriever: Retriever<S, C>,
+    system_prompt: String,
+    params: GenParams,
+    compress_context: bool,
+    summary_max_tokens: u32,
+    answer_language: Option<String>,
+    query_language: Option<String>,
+    min_score: Option<f32>,
+    no_answer_message: String,
+}
+
+impl<L: LlmClient, S: VectorStore, C: EmbeddingClient> RagPipeline<L, S, C> {
+    pub fn new(llm: L, retriever: Retriever<S, C>) -> Self {
+        Self {
+            llm,
+            retriever,
+            system_prompt: DEFAULT_SYSTEM_PROMPT.to_string(),
+            params: GenParams::default(),
+            compress_context: false,
+            summary_max_tokens: DEFAULT_SUMMARY_MAX_TOKENS,
+            answer_language: None,
+            query_language: None,
+            min_score: None,
+            no_answer_message: DEFAULT_NO_ANSWER_MESSAGE.to_string(),
+        }
+    }
+
+    /// 开启上下文压缩：生成前先用 LLM 针对问题逐个摘要检索片段（map），再用摘要生成回答（reduce）
+    ///
+    /// 每个片段多一次 LLM 调用，换取在最终 prompt 中容纳更多相关资料。
+    pub fn with_compress_context(mut self, enabled: bool) -> Self {
+        self.compress_context = enabled;
+        self
+    }
+
+    /// 每个片段摘要的 token 上限
+    pub fn with_summary_max_tokens(mut self, max_tokens: u32) -> Self {
+        self.summary_max_tokens = max_tokens;
+        self
+    }
+
+    /// 无论问题与参考资料使用何种语言，都用指定语言回答，如 `"en"` 或 `"英文"`，见 [`language_name`]
+    ///
+    /// 模型无法用该语言准确作答时，提示词要求其改用参考资料的语言并加以说明。
+    pub fn with_answer_language(mut self, language: &str) -> Self {
+        self.answer_language = language_name(language);
+        self
+    }
+
+    /// 跨语言检索：检索前先用 LLM 将问题翻译为语料的语言，原问题与译文分别检索后合并结果
+    ///
+    /// 翻译失败或译文为空时只用原问题检索。
+    pub fn with_query_translation(mut self, corpus_language: &str) -> Self {
+        self.query_language = language_name(corpus_language);
+        self
+    }
+
+    /// 相关度阈值：得分低于 `min_score` 的检索结果不放入上下文，见 [`with_no_answer_message`](Self::with_no_answer_message)
+    pub fn with_min_score(mut self, min_score: f32) -> Self {
+        self.min_score = Some(min_score);
+        self
+    }
+
+    /// 没有相关资料（检索结果为空或得分都低于 [`min_score`](Self::with_min_score)）时直接返回的回答
+    ///
+    /// 此时不调用 LLM，避免模型脱离知识库凭自身记忆作答；流式回答中 [`AnswerEvent::Sources`] 为空。
+    pub fn with_no_answer_message(mut self, message: impl Into<String>) -> Self {
+        self.no_answer_message = message.into();
+        self
+    }
+
+    pub fn with_system_prompt(mut self, system_prompt: impl Into<String>) -> Self {
+        self.system_prompt = system_prompt.into();
+        self
+    }
+
+    /// 流程级默认生成参数，未设置的字段沿用 LLM 客户端默认值
+    pub fn with_params(mut self, params: GenParams) -> Self {
+        self.params = params;
+        self
+    }
+
+    pub fn retriever(&self) -> &Retriever<S, C> {
+        &self.retriever
+    }
+
+    /// 使用流程默认参数回答问题
+    pub async fn answer(&self, question: &str, top_k: usize) -> Result<String> {
+        self.answer_with(question, top_k, GenParams::default()).await
+    }
+
+    /// 使用单次调用的参数回答问题，优先级：`params` > 流程默认参数 > 客户端默认值
+    pub async fn answer_with(&self, question: &str, top_k: usize, params: GenParams) -> Result<String> {
+        let hits = self.retrieve_context(question, top_k).await?;
+        if hits.is_empty() {
+            return Ok(self.no_answer_message.clone());
+        }
+        let params = params.or(&self.params);
+        Ok(self.llm.chat_with_params(self.build_messages(question, &hits)?, &params).await?)
+    }
+
+    /// 流式回答：检索完成后逐段返回 LLM 生成的回答文本，使用流程默认参数
+    pub fn answer_stream<'a>(&'a self, question: &'a str, top_k: usize) -> impl Stream<Item = Result<String>> + 'a {
+        self.answer_events(question, top_k, GenParams::default())
+            .try_filter_map(|event| async move {
+                Ok(match event {
+                    AnswerEvent::Delta(text) => Some(text),
+                    AnswerEvent::Sources(_) => None,
+                })
+            })
+    }
+
+    /// 流式回答并附带来源：先返回 [`AnswerEvent::Sources`]，再逐段返回 [`AnswerEvent::Delta`]
+    ///
+    /// 检索或建立生成请求失败时流中只有一个错误。没有相关资料时来源为空，回答为一段
+    /// [`with_no_answer_message`](Self::with_no_answer_message) 设置的文本。
+    pub fn answer_events<'a>(&'a self, question: &'a str, top_k: usize, params: GenParams) -> impl Stream<Item = Result<AnswerEvent>> + 'a {
+        let start = async move {
+            let hits = self.retrieve_context(question, top_k).await?;
+            if hits.is_empty() {
+                let deltas = stream::once(async move { Ok(self.no_answer_message.clone()) }).boxed();
+                return Ok((hits, deltas));
+            }
+            let params = params.or(&self.params);
+            let deltas = self.llm.chat_stream(self.build_messages(question, &hits)?, &params).await?;
+            Ok::<_, anyhow::Error>((hits, deltas))
+        };
+
+        stream::once(start)
+            .map(|started| match started {
+                Ok((hits, deltas)) => stream::once(async move { Ok(AnswerEvent::Sources(hits)) })
+                    .chain(deltas.map_ok(AnswerEvent::Delta).err_into())
+                    .left_stream(),
+                Err(e) => stream::once(async move { Err(e) }).right_stream(),
+            })
+            .flatten()
+    }
+}
+
+impl<L: LlmClient, S: VectorStore, C: EmbeddingClient> RagPipeline<L, S, C> {
+    /// 检索相关片段并去掉得分低于 `min_score` 的结果，开启上下文压缩时替换为摘要
+    async fn retrieve_context(&self, question: &str, top_k: usize) -> Result<Vec<SearchResult>> {
+        let mut hits = self.retriever.retrieve(question, top_k).await?;
+        if let Some(translated) = self.translate_query(question).await {
+            let translated_hits = self.retriever.retrieve(&translated, top_k).await?;
+            hits = merge_hits(hits, translated_hits, top_k);
+        }
+        if let Some(min_score) = self.min_score {
+            hits.retain(|hit| hit.score >= min_score);
+        }
+        if self.compress_context {
+            return self.compress(question, hits).await;
+        }
+        Ok(hits)
+    }
+
+    /// 将问题翻译为语料的语言；未开启跨语言检索、翻译失败或译文与原问题相同时返回 `None`
+    async fn translate_query(&self, question: &str) -> Option<String> {
+        let language = self.query_language.as_deref()?;
+        let params = GenParams::default()
+            .with_temperature(0.0)
+            .with_system(TRANSLATE_SYSTEM_PROMPT);
+        let messages = vec![ChatCompletionRequestMessage::User(
+            ChatCompletionRequestUserMessageArgs::default()
+                .content(format!("目标语言：{}\n\n问题：{}", language, question))
+                .build()
+                .ok()?
+        )];
+
+        match self.llm.chat_with_params(messages, &params).await {
+            Ok(translated) => {
+                let translated = translated.trim();
+                (!translated.is_empty() && translated != question.trim()).then(|| translated.to_string())
+            }
+            Err(e) => {
+                println!("翻译查询失败，仅使用原问题检索: {:#}", e);
+                None
+            }
+        }
+    }
+
+    fn build_messages(&self, question: &str, hits: &[SearchResult]) -> Result<Vec<ChatCompletionRequestMessage>> {
+        let mut prompt = build_prompt(question, hits);
+        // 语言要求放在用户消息中，单次调用覆盖 system 提示词时仍然生效
+        if let Some(language) = &self.answer_language {
+            prompt.push_str(&format!(
+                "\n\n请使用{}回答，无论参考资料和问题使用何种语言。如果无法用{}准确表达，请改用参考资料的语言回答并说明原因。",
+                language, language
+            ));
+        }
+        Ok(vec![
+            ChatCompletionRequestMessage::System(
+                ChatCompletionRequestSystemMessageArgs::default()
+                    .content(self.system_prompt.as_str())
+                    .build()?
+            ),
+            ChatCompletionRequestMessage::User(
+                ChatCompletionRequestUserMessageArgs::default()
+                    .content(prompt)
+                    .build()?
+            ),
+        ])
+    }
+
+    /// 并发地将每个片段替换为针对问题的摘要
+    async fn compress(&self, question: &str, hits: Vec<SearchResult>) -> Result<Vec<SearchResult>> {
+        let params = GenParams::default()
+            .with_temperature(0.0)
+            .with_max_tokens(self.summary_max_tokens)
+            .with_system(SUMMARY_SYSTEM_PROMPT);
+
+        try_join_all(hits.into_iter().map(|mut hit| {
+            let params = &params;
+            async move {
+                let prompt = format!(
+                    "问题：{}\n\n参考资料：\n{}",
+                    question,
+                    hit.record.text.as_deref().unwrap_or_default()
+                );
+                let messages = vec![ChatCompletionRequestMessage::User(
+                    ChatCompletionRequestUserMessageArgs::default()
+                        .content(prompt)
+                        .build()?
+                )];
+                let summary = self.llm.chat_with_params(messages, params).await?;
+                hit.record.text = Some(summary.trim().to_string());
+                Ok::<_, anyhow::Error>(hit)
+            }
+        }))
+        .await
+    }
+}
+
+/// 合并两组检索结果：同一记录保留较高分，按分数降序取前 `top_k` 条并重新编号
+fn merge_hits(hits: Vec<SearchResult>, extra: Vec<SearchResult>, top_k: usize) -> Vec<SearchResult> {
+    let mut merged: Vec<SearchResult> = Vec::with_capacity(hits.len() + extra.len());
+    for hit in hits.into_iter().chain(extra) {
+        match merged.iter_mut().find(|m| m.record.id == hit.record.id) {
+            Some(existing) => existing.score = existing.score.max(hit.score),
+            None => merged.push(hit),
+        }
+    }
+    SearchResult::rerank(&mut merged);
+    merged.truncate(top_k);
+    merged
+}
+
+/// 将检索结果拼接为带编号的参考资料
+fn build_prompt(question: &str, hits: &[SearchResult]) -> String {
+    let mut prompt = String::from("参考资料：\n");
+    for SearchResult { rank, record, .. } in hits {
+        let hierarchy = record.metadata.get("hierarchy")
+            .and_then(|v| v.as_array())
+            .map(|parts| parts.iter().filter_map(|p| p.as_str()).collect::<Vec<_>>().join(" > "))
+            .unwrap_or_default();
+        prompt.push_str(&format!(
+            "[{}] {}\n{}\n\n",
+            rank,
+            hierarchy,
+            record.text.as_deref().unwrap_or_default()
+        ));
+    }
+    prompt.push_str(&format!("问题：{}", question));
+    prompt
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use async_trait::async_trait;
+    use async_openai::types::{ChatCompletionRequestSystemMessageContent, ChatCompletionRequestUserMessageContent};
+    use rag_embeddings::client::EmbeddingResult;
+    use crate::llm::{LlmError, LlmResult};
+    use rag_embeddings::database::VectorRecord;
+    use std::sync::Mutex;
+
+    struct OneHotClient;
+
+    #[async_trait]
+    impl EmbeddingClient for OneHotClient {
+        async fn embed(&self, texts: Vec<String>) -> EmbeddingResult<Vec<Vec<f32>>> {
+            Ok(texts.iter().map(|_| vec![1.0, 0.0]).collect())
+        }
+
+        fn dimension(&self) -> usize {
+            2
+        }
+    }
+
+    struct SingleStore;
+
+    #[async_trait]
+    impl VectorStore for SingleStore {
+        async fn add_vectors(&self, _vectors: Vec<VectorRecord>) -> Result<()> { Ok(()) }
+        async fn upsert_vectors(&self, _vectors: Vec<VectorRecord>) -> Result<()> { Ok(()) }
+        async fn delete_vector(&self, _ids: Vec<String>) -> Result<()> { Ok(()) }
+
+        async fn search(&self, _query: &[f32], _top_k: usize) -> Result<Vec<SearchResult>> {
+            Ok(SearchResult::ranked(vec![(VectorRecord {
+                id: "1".to_string(),
+                embedding: vec![1.0, 0.0],
+                metadata: serde_json::json!({ "hierarchy": ["Rust", "所有权"] }),
+                text: Some("每个值都有唯一的所有者。".to_string()),
+                createat: None,
+                updateat: None,
+            }, 1.0)]))
+        }
+    }
+
+    /// 记录最近一次调用的 system 提示词与生成参数
+    #[derive(Default)]
+    struct RecordingLlm {
+        last: Mutex<Option<(String, GenParams)>>,
+    }
+
+    #[async_trait]
+    impl LlmClient for RecordingLlm {
+        async fn chat(&self, messages: Vec<ChatCompletionRequestMessage>) -> LlmResult<String> {
+            self.chat_with_params(messages, &GenParams::default()).await
+        }
+
+        async fn generate(&self, messages: Vec<ChatCompletionRequestMessage>) -> LlmResult<String> {
+            self.chat(messages).await
+        }
+
+        async fn chat_with_params(&self, messages: Vec<ChatCompletionRequestMessage>, params: &GenParams) -> LlmResult<String> {
+            let messages = params.apply_system(messages)?;
+            let system = match &messages[0] {
+                ChatCompletionRequestMessage::System(m) => match &m.content {
+                    ChatCompletionRequestSystemMessageContent::Text(text) => text.clone(),
+                    _ => String::new(),
+                },
+                _ => String::new(),
+            };
+            *self.last.lock().unwrap() = Some((system, params.clone()));
+            Ok("ok".to_string())
+        }
+    }
+
+    #[tokio::test]
+    async fn test_answer_with_overrides() -> Result<()> {
+        let pipeline = RagPipeline::new(RecordingLlm::default(), Retriever::new(SingleStore, OneHotClient))
+            .with_params(GenParams::default().with_temperature(0.0).with_max_tokens(512));
+
+        pipeline.answer("什么是所有权？", 3).await?;
+        let (system, params) = pipeline.llm.last.lock().unwrap().clone().unwrap();
+        assert_eq!(system, DEFAULT_SYSTEM_PROMPT);
+        assert_eq!(params.temperature, Some(0.0));
+        assert_eq!(params.max_tokens, Some(512));
+
+        pipeline.answer_with("头脑风暴一下", 3, GenParams::default().with_temperature(1.2).with_system("你是创意助手")).await?;
+        let (system, params) = pipeline.llm.last.lock().unwrap().clone().unwrap();
+        assert_eq!(system, "你是创意助手");
+        assert_eq!(params.temperature, Some(1.2));
+        assert_eq!(params.max_tokens, Some(512));
+        Ok(())
+    }
+
+    /// 摘要调用返回固定摘要，最终生成回显用户 prompt
+    struct SummarizingLlm;
+
+    #[async_trait]
+    impl LlmClient for SummarizingLlm {
+        async fn chat(&self, messages: Vec<ChatCompletionRequestMessage>) -> LlmResult<String> {
+            self.chat_with_params(messages, &GenParams::default()).await
+        }
+
+        async fn generate(&self, messages: Vec<ChatCompletionRequestMessage>) -> LlmResult<String> {
+            self.chat(messages).await
+        }
+
+        async fn chat_with_params(&self, messages: Vec<ChatCompletionRequestMessage>, params: &GenParams) -> LlmResult<String> {
+            if params.max_tokens == Some(64) {
+                assert_eq!(params.system.as_deref(), Some(SUMMARY_SYSTEM_PROMPT));
+                return Ok(" 所有权摘要 ".to_string());
+            }
+            match messages.last() {
+                Some(ChatCompletionRequestMessage::User(m)) => match &m.content {
+                    ChatCompletionRequestUserMessageContent::Text(text) => Ok(text.clone()),
+                    _ => Ok(String::new()),
+                },
+                _ => Ok(String::new()),
+            }
+        }
+    }
+
+    #[tokio::test]
+    async fn test_compress_context() -> Result<()> {
+        let pipeline = RagPipeline::new(SummarizingLlm, Retriever::new(SingleStore, OneHotClient))
+            .with_compress_context(true)
+            .with_summary_max_tokens(64);
+
+        let prompt = pipeline.answer("什么是所有权？", 3).await?;
+        assert!(prompt.contains("[1] Rust > 所有权\n所有权摘要\n"));
+        assert!(!prompt.contains("每个值都有唯一的所有者"));
+        Ok(())
+    }
+
+    /// 将回答拆成两段流式返回
+    struct StreamingLlm;
+
+    #[async_trait]
+    impl LlmClient for StreamingLlm {
+        async fn chat(&self, messages: Vec<ChatCompletionRequestMessage>) -> LlmResult<String> {
+            self.chat_with_params(messages, &GenParams::default()).await
+        }
+
+        async fn generate(&self, messages: Vec<ChatCompletionRequestMessage>) -> LlmResult<String> {
+            self.chat(messages).await
+        }
+
+        async fn chat_with_params(&self, _messages: Vec<ChatCompletionRequestMessage>, _params: &GenParams) -> LlmResult<String> {
+            Ok("所有权".to_string())
+        }
+
+        async fn chat_stream(
+            &self,
+            _messages: Vec<ChatCompletionRequestMessage>,
+            _params: &GenParams,
+        ) -> LlmResult<futures::stream::BoxStream<'static, LlmResult<String>>> {
+            Ok(stream::iter(vec![Ok("所有".to_string()), Ok("权".to_string())]).boxed())
+        }
+    }
+
+    #[tokio::test]
+    async fn test_answer_stream() -> Result<()> {
+        let pipeline = RagPipeline::new(StreamingLlm, Retriever::new(SingleStore, OneHotClient));
+
+        let events: Vec<AnswerEvent> = pipeline.answer_events("什么是所有权？", 3, GenParams::default())
+            .try_collect()
+            .await?;
+        assert_eq!(events.len(), 3);
+        match &events[0] {
+            AnswerEvent::Sources(hits) => assert_eq!(hits[0].record.id, "1"),
+            other => panic!("第一个事件应为来源: {:?}", other),
+        }
+        assert!(matches!(&events[2], AnswerEvent::Delta(text) if text == "权"));
+
+        let answer: Vec<String> = pipeline.answer_stream("什么是所有权？", 3).try_collect().await?;
+        assert_eq!(answer.concat(), "所有权");
+
+        // 未覆盖 chat_stream 的客户端一次性返回完整回答
+        let pipeline = RagPipeline::new(RecordingLlm::default(), Retriever::new(SingleStore, OneHotClient));
+        let answer: Vec<String> = pipeline.answer_stream("什么是所有权？", 3).try_collect().await?;
+        assert_eq!(answer, vec!["ok"]);
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_answer_language() -> Result<()> {
+        let pipeline = RagPipeline::new(SummarizingLlm, Retriever::new(SingleStore, OneHotClient))
+            .with_answer_language("en");
+        let prompt = pipeline.answer("什么是所有权？", 3).await?;
+        assert!(prompt.starts_with("参考资料：\n[1] Rust > 所有权"));
+        assert!(prompt.ends_with("请使用英文回答，无论参考资料和问题使用何种语言。如果无法用英文准确表达，请改用参考资料的语言回答并说明原因。"));
+
+        let pipeline = RagPipeline::new(SummarizingLlm, Retriever::new(SingleStore, OneHotClient))
+            .with_answer_language("  ");
+        assert!(pipeline.answer("什么是所有权？", 3).await?.ends_with("问题：什么是所有权？"));
+        Ok(())
+    }
+
+    #[test]
+    fn test_language_name() {
+        assert_eq!(language_name("EN").as_deref(), Some("英文"));
+        assert_eq!(language_name("zh_CN").as_deref(), Some("简体中文"));
+        assert_eq!(language_name("Klingon").as_deref(), Some("Klingon"));
+        assert_eq!(language_name(""), None);
+    }
+
+    /// 翻译调用返回固定译文，最终生成回显用户 prompt
+    struct TranslatingLlm {
+        translation: Result<String, String>,
+    }
+
+    #[async_trait]
+    impl LlmClient for TranslatingLlm {
+        async fn chat(&self, messages: Vec<ChatCompletionRequestMessage>) -> LlmResult<String> {
+            self.chat_with_params(messages, &GenParams::default()).await
+        }
+
+        async fn generate(&self, messages: Vec<ChatCompletionRequestMessage>) -> LlmResult<String> {
+            self.chat(messages).await
+        }
+
+        async fn chat_with_params(&self, messages: Vec<ChatCompletionRequestMessage>, params: &GenParams) -> LlmResult<String> {
+            if params.system.as_deref() == Some(TRANSLATE_SYSTEM_PROMPT) {
+                return self.translation.clone().map_err(LlmError::InvalidResponse);
+            }
+            SummarizingLlm.chat_with_params(messages, params).await
+        }
+    }
+
+    /// 按查询文本返回不同记录
+    struct QueryStore;
+
+    #[async_trait]
+    impl VectorStore for QueryStore {
+        async fn add_vectors(&self, _vectors: Vec<VectorRecord>) -> Result<()> { Ok(()) }
+        async fn upsert_vectors(&self, _vectors: Vec<VectorRecord>) -> Result<()> { Ok(()) }
+        async fn delete_vector(&self, _ids: Vec<String>) -> Result<()> { Ok(()) }
+
+        async fn search(&self, query: &[f32], _top_k: usize) -> Result<Vec<SearchResult>> {
+            let (id, text, score) = if query[0] > 0.5 {
+                ("en", "Every value has a single owner.", 0.6)
+            } else {
+                ("zh", "每个值都有唯一的所有者。", 0.9)
+            };
+            Ok(SearchResult::ranked(vec![(VectorRecord {
+                id: id.to_string(),
+                embedding: query.to_vec(),
+                metadata: serde_json::json!({}),
+                text: Some(text.to_string()),
+                createat: None,
+                updateat: None,
+            }, score)]))
+        }
+    }
+
+    /// ASCII 文本映射到第一维，其余映射到第二维
+    struct ScriptClient;
+
+    #[async_trait]
+    impl EmbeddingClient for ScriptClient {
+        async fn embed(&self, texts: Vec<String>) -> EmbeddingResult<Vec<Vec<f32>>> {
+            Ok(texts.iter().map(|t| if t.is_ascii() { vec![1.0, 0.0] } else { vec![0.0, 1.0] }).collect())
+        }
+
+        fn dimension(&self) -> usize {
+            2
+        }
+    }
+
+    #[tokio::test]
+    async fn test_query_translation() -> Result<()> {
+        let llm = TranslatingLlm { translation: Ok("什么是所有权？".to_string()) };
+        let pipeline = RagPipeline::new(llm, Retriever::new(QueryStore, ScriptClient))
+            .with_query_translation("zh");
+        let hits = pipeline.retrieve_context("What is ownership?", 3).await?;
+        let ids: Vec<&str> = hits.iter().map(|h| h.record.id.as_str()).collect();
+        assert_eq!(ids, vec!["zh", "en"]);
+        assert_eq!(hits[1].rank, 2);
+        assert_eq!(pipeline.retrieve_context("What is ownership?", 1).await?.len(), 1);
+
+        // 翻译失败时退回原问题检索
+        let llm = TranslatingLlm { translation: Err("unsupported language".to_string()) };
+        let pipeline = RagPipeline::new(llm, Retriever::new(QueryStore, ScriptClient))
+            .with_query_translation("tlh");
+        let hits = pipeline.retrieve_context("What is ownership?", 3).await?;
+        assert_eq!(hits.len(), 1);
+        assert_eq!(hits[0].record.id, "en");
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_no_answer_guardrail() -> Result<()> {
+        // 英文问题只命中得分 0.6 的记录，低于阈值，视为知识库外的问题
+        let pipeline = RagPipeline::new(RecordingLlm::default(), Retriever::new(QueryStore, ScriptClient))
+            .with_min_score(0.8);
+        assert_eq!(pipeline.answer("What is the capital of France?", 3).await?, DEFAULT_NO_ANSWER_MESSAGE);
+        assert!(pipeline.llm.last.lock().unwrap().is_none());
+
+        let events: Vec<AnswerEvent> = pipeline.answer_events("What is the capital of France?", 3, GenParams::default())
+            .try_collect()
+            .await?;
+        assert!(matches!(&events[0], AnswerEvent::Sources(hits) if hits.is_empty()));
+        assert!(matches!(&events[1], AnswerEvent::Delta(text) if text == DEFAULT_NO_ANSWER_MESSAGE));
+
+        // 得分达到阈值时正常生成
+        assert_eq!(pipeline.answer("什么是所有权？", 3).await?, "ok");
+        assert!(pipeline.llm.last.lock().unwrap().is_some());
+
+        let pipeline = RagPipeline::new(RecordingLlm::default(), Retriever::new(QueryStore, ScriptClient))
+            .with_min_score(0.95)
+            .with_no_answer_message("资料中没有相关内容。");
+        assert_eq!(pipeline.answer("什么是所有权？", 3).await?, "资料中没有相关内容。");
+        Ok(())
+    }
+
+    #[test]
+    fn test_build_prompt() {
+        let hits = SearchResult::ranked(vec![(VectorRecord {
+            id: "1".to_string(),
+            embedding: vec![],
+            metadata: serde_json::json!({ "hierarchy": ["Rust", "所有权"] }),
+            text: Some("每个值都有唯一的所有者。".to_string()),
+            createat: None,
+            updateat: None,
+        }, 0.9)]);
+
+        let prompt = build_prompt("什么是所有权？", &hits);
+        assert!(prompt.contains("[1] Rust > 所有权\n每个值都有唯一的所有者。"));
+        assert!(prompt.ends_with("问题：什么是所有权？"));
+    }
+}
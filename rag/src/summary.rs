@@ -0,0 +1,111 @@
+use anyhow::Result;
+use async_openai::types::{ChatCompletionRequestMessage, ChatCompletionRequestSystemMessageArgs, ChatCompletionRequestUserMessageArgs};
+use rag_indexing::tree_structrue::{NodeId, NodeTree};
+
+use crate::llm::{GenParams, LlmClient};
+
+/// 生成文档摘要时送入 LLM 的正文字符上限
+pub const DEFAULT_DOCUMENT_SUMMARY_INPUT_CHARS: usize = 8000;
+
+/// 文档摘要的默认 token 上限
+pub const DEFAULT_DOCUMENT_SUMMARY_MAX_TOKENS: u32 = 512;
+
+const DOCUMENT_SUMMARY_SYSTEM_PROMPT: &str = "你负责为知识库文档撰写摘要。请用一段话概括文档的主题、主要内容与结论，不要添加文档中没有的信息。";
+
+/// 用 LLM 生成文档摘要并挂载为根节点下的摘要叶子，返回叶子 id
+///
+/// 输入为文档标题与按顺序拼接的正文（跳过图片与已有摘要），超过 `DEFAULT_DOCUMENT_SUMMARY_INPUT_CHARS` 的部分截断。
+/// 摘要叶子随其余叶子一同入库，作为文档级表示补充分块级检索。
+pub async fn summarize_document<L: LlmClient>(llm: &L, node_tree: &mut NodeTree, params: &GenParams) -> Result<NodeId> {
+    let messages = vec![
+        ChatCompletionRequestMessage::System(
+            ChatCompletionRequestSystemMessageArgs::default()
+                .content(DOCUMENT_SUMMARY_SYSTEM_PROMPT)
+                .build()?
+        ),
+        ChatCompletionRequestMessage::User(
+            ChatCompletionRequestUserMessageArgs::default()
+                .content(build_summary_input(node_tree))
+                .build()?
+        ),
+    ];
+
+    let params = params.clone().or(&GenParams::default().with_max_tokens(DEFAULT_DOCUMENT_SUMMARY_MAX_TOKENS));
+    let summary = llm.chat_with_params(messages, &params).await?;
+    node_tree.attach_summary(summary.trim().to_string())
+}
+
+fn build_summary_input(node_tree: &NodeTree) -> String {
+    let summary_id = node_tree.summary_leaf().map(|leaf| leaf.id);
+    let body: String = node_tree.leaf_nodes_in_order()
+        .into_iter()
+        .filter(|leaf| Some(leaf.id) != summary_id && leaf.metadata.image_path.is_none())
+        .map(|leaf| leaf.text.as_str())
+        .collect::<Vec<_>>()
+        .join("\n\n")
+        .chars()
+        .take(DEFAULT_DOCUMENT_SUMMARY_INPUT_CHARS)
+        .collect();
+
+    match node_tree.document_title() {
+        Some(title) => format!("文档标题：{}\n\n{}", title, body),
+        None => body,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use async_openai::types::ChatCompletionRequestUserMessageContent;
+    use async_trait::async_trait;
+    use rag_indexing::tree_structrue::markdown_bulid::MarkdownParser;
+    use crate::llm::LlmResult;
+    use std::sync::Mutex;
+
+    /// 记录用户 prompt 并返回固定摘要
+    #[derive(Default)]
+    struct FixedSummaryLlm {
+        prompt: Mutex<String>,
+    }
+
+    #[async_trait]
+    impl LlmClient for FixedSummaryLlm {
+        async fn chat(&self, messages: Vec<ChatCompletionRequestMessage>) -> LlmResult<String> {
+            self.chat_with_params(messages, &GenParams::default()).await
+        }
+
+        async fn generate(&self, messages: Vec<ChatCompletionRequestMessage>) -> LlmResult<String> {
+            self.chat(messages).await
+        }
+
+        async fn chat_with_params(&self, messages: Vec<ChatCompletionRequestMessage>, params: &GenParams) -> LlmResult<String> {
+            assert_eq!(params.max_tokens, Some(DEFAULT_DOCUMENT_SUMMARY_MAX_TOKENS));
+            if let Some(ChatCompletionRequestMessage::User(m)) = messages.last()
+                && let ChatCompletionRequestUserMessageContent::Text(text) = &m.content
+            {
+                *self.prompt.lock().unwrap() = text.clone();
+            }
+            Ok(" 一份关于 Rust 所有权的说明。 ".to_string())
+        }
+    }
+
+    #[tokio::test]
+    async fn test_summarize_document() -> Result<()> {
+        let mut tree = MarkdownParser::new("doc-001".to_string(), None)
+            .parse("# Rust 所有权\n\n每个值都有唯一的所有者。\n\n![图](a.png)\n")?;
+        let llm = FixedSummaryLlm::default();
+
+        let id = summarize_document(&llm, &mut tree, &GenParams::default()).await?;
+        let prompt = llm.prompt.lock().unwrap().clone();
+        assert_eq!(prompt, "文档标题：Rust 所有权\n\n每个值都有唯一的所有者。");
+
+        let summary = tree.summary_leaf().unwrap();
+        assert_eq!(summary.id, id);
+        assert_eq!(summary.text, "一份关于 Rust 所有权的说明。");
+
+        // 重新生成时已有摘要不计入输入
+        summarize_document(&llm, &mut tree, &GenParams::default()).await?;
+        assert_eq!(*llm.prompt.lock().unwrap(), prompt);
+        Ok(())
+    }
+}